@@ -0,0 +1,162 @@
+//! Derive macros for [nettish](https://docs.rs/nettish)
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Index, Member, parse_macro_input, spanned::Spanned};
+
+/// Implement `nettish::NetDirty` for a struct
+///
+/// See the trait's documentation for details.
+#[proc_macro_derive(NetDirty, attributes(net_dirty))]
+pub fn derive_net_dirty(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match net_dirty(&input) {
+        Ok(x) => x.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn net_dirty(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(ref data) = input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "NetDirty can only be derived for structs",
+        ));
+    };
+
+    // Partition fields into the dirty mask, if any, and replicated fields
+    let mut mask = None;
+    let mut fields = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let mut is_mask = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("net_dirty"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("mask") {
+                    is_mask = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unrecognized net_dirty attribute"))
+                }
+            })?;
+        }
+        if is_mask {
+            if mask.is_some() {
+                return Err(syn::Error::new(field.span(), "duplicate dirty mask"));
+            }
+            mask = Some(member);
+        } else {
+            fields.push((member, field));
+        }
+    }
+    if fields.len() > 64 {
+        return Err(syn::Error::new(
+            input.span(),
+            "NetDirty supports at most 64 fields",
+        ));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let count = fields.len() as u32;
+    let indices = (0..count).collect::<Vec<_>>();
+    let members = fields.iter().map(|x| &x.0).collect::<Vec<_>>();
+    let mask_init = mask
+        .as_ref()
+        .map(|m| quote! { #m: ::nettish::FieldMask::EMPTY, });
+    let construct = match data.fields {
+        Fields::Unit => quote! { Self },
+        _ => quote! {
+            Self {
+                #(
+                    #members: if mask.contains(#indices) {
+                        ::nettish::Delta::decode_delta(&baseline.#members, r)?
+                    } else {
+                        ::core::clone::Clone::clone(&baseline.#members)
+                    },
+                )*
+                #mask_init
+            }
+        },
+    };
+
+    let setters = match mask {
+        None => quote! {},
+        Some(ref mask) => {
+            let setters = fields
+                .iter()
+                .zip(&indices)
+                .filter_map(|((member, field), i)| {
+                    let Member::Named(ref ident) = *member else {
+                        return None;
+                    };
+                    let setter = format_ident!("set_{}", ident);
+                    let ty = &field.ty;
+                    let doc = format!("Set `{ident}`, marking it dirty");
+                    Some(quote! {
+                        #[doc = #doc]
+                        pub fn #setter(&mut self, value: #ty) {
+                            self.#ident = value;
+                            self.#mask.insert(#i);
+                        }
+                    })
+                });
+            quote! {
+                impl #impl_generics #name #ty_generics #where_clause {
+                    #(#setters)*
+
+                    /// Fields set since the last call, which are then forgotten
+                    pub fn take_dirty(&mut self) -> ::nettish::FieldMask {
+                        ::core::mem::take(&mut self.#mask)
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::nettish::NetDirty for #name #ty_generics #where_clause {
+            const FIELDS: u32 = #count;
+
+            fn changed_fields(&self, baseline: &Self) -> ::nettish::FieldMask {
+                let mut mask = ::nettish::FieldMask::EMPTY;
+                #(
+                    if self.#members != baseline.#members {
+                        mask.insert(#indices);
+                    }
+                )*
+                mask
+            }
+
+            fn encode_fields(
+                &self,
+                mask: ::nettish::FieldMask,
+                baseline: &Self,
+                w: &mut ::nettish::BitWriter,
+            ) {
+                #(
+                    if mask.contains(#indices) {
+                        ::nettish::Delta::encode_delta(&self.#members, &baseline.#members, w);
+                    }
+                )*
+            }
+
+            fn decode_fields(
+                baseline: &Self,
+                mask: ::nettish::FieldMask,
+                r: &mut ::nettish::BitReader<'_>,
+            ) -> ::core::result::Result<Self, ::nettish::DecodeError> {
+                ::core::result::Result::Ok(#construct)
+            }
+        }
+
+        #setters
+    })
+}
@@ -0,0 +1,62 @@
+/* C bindings for nettish. See nettish-ffi/src/lib.rs for documentation.
+ *
+ * Times are nanoseconds on any monotonic clock consistent across all calls on a given handle.
+ * Handles must not be used concurrently from multiple threads. */
+
+#ifndef NETTISH_H
+#define NETTISH_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A byte string borrowed from a handle */
+typedef struct NettishBytes {
+    const uint8_t *data;
+    size_t len;
+} NettishBytes;
+
+/* A byte string owned by the caller, to be freed with nettish_buffer_free */
+typedef struct NettishBuffer {
+    uint8_t *data;
+    size_t len;
+} NettishBuffer;
+
+void nettish_buffer_free(NettishBuffer buffer);
+
+uint64_t nettish_throttle(uint64_t real_time_ns, uint64_t buffer_remaining_ns,
+                          uint64_t min_latency_ns, uint64_t hysteresis_ns);
+
+typedef struct NettishPredictionQueue NettishPredictionQueue;
+
+NettishPredictionQueue *nettish_prediction_queue_new(uint16_t next_sequence_number);
+void nettish_prediction_queue_free(NettishPredictionQueue *queue);
+uint16_t nettish_prediction_queue_next_sequence_number(const NettishPredictionQueue *queue);
+void nettish_prediction_queue_record(NettishPredictionQueue *queue, const uint8_t *input,
+                                     size_t len);
+void nettish_prediction_queue_reconcile(NettishPredictionQueue *queue, uint16_t sequence_number);
+size_t nettish_prediction_queue_len(const NettishPredictionQueue *queue);
+/* Invalidated by any subsequent mutation of the queue. Data is null if out of range. */
+NettishBytes nettish_prediction_queue_get(const NettishPredictionQueue *queue, size_t index);
+
+typedef struct NettishInputQueue NettishInputQueue;
+
+NettishInputQueue *nettish_input_queue_new(void);
+void nettish_input_queue_free(NettishInputQueue *queue);
+/* Returns whether an old input was dropped due to overrun */
+bool nettish_input_queue_push(NettishInputQueue *queue, size_t max, const uint8_t *input,
+                              size_t len, uint64_t now_ns);
+/* On success, `out` must be freed with nettish_buffer_free */
+bool nettish_input_queue_take(NettishInputQueue *queue, uint64_t now_ns, uint64_t delay_ns,
+                              NettishBuffer *out);
+size_t nettish_input_queue_len(const NettishInputQueue *queue);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
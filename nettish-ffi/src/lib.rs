@@ -0,0 +1,292 @@
+//! C bindings for [nettish](https://docs.rs/nettish)
+//!
+//! Declarations are in `include/nettish.h`. Types are exposed as opaque handles, created and
+//! destroyed by paired `_new` and `_free` functions, which must not be used concurrently from
+//! multiple threads. Inputs are opaque byte strings, copied on the way in. Times are nanoseconds
+//! on any monotonic clock, e.g. `std::chrono::steady_clock`, that's consistent across all calls
+//! on a given handle.
+
+use std::{ptr, slice, time::Duration};
+
+use nettish::{InputQueue, Instant, PredictionQueue};
+
+/// A byte string borrowed from a handle
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct NettishBytes {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// A byte string owned by the caller, to be freed with [`nettish_buffer_free`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct NettishBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Free a buffer returned by this library
+///
+/// # Safety
+///
+/// `buffer` must have been returned by this library, and not previously freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_buffer_free(buffer: NettishBuffer) {
+    if !buffer.data.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+    }
+}
+
+/// See `nettish::throttle`
+#[unsafe(no_mangle)]
+pub extern "C" fn nettish_throttle(
+    real_time_ns: u64,
+    buffer_remaining_ns: u64,
+    min_latency_ns: u64,
+    hysteresis_ns: u64,
+) -> u64 {
+    nettish::throttle(
+        Duration::from_nanos(real_time_ns),
+        Duration::from_nanos(buffer_remaining_ns),
+        Duration::from_nanos(min_latency_ns),
+        Duration::from_nanos(hysteresis_ns),
+    )
+    .as_nanos() as u64
+}
+
+/// Opaque handle to a `nettish::PredictionQueue`
+pub struct NettishPredictionQueue(PredictionQueue<Box<[u8]>>);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn nettish_prediction_queue_new(
+    next_sequence_number: u16,
+) -> *mut NettishPredictionQueue {
+    Box::into_raw(Box::new(NettishPredictionQueue(PredictionQueue::new(
+        next_sequence_number,
+    ))))
+}
+
+/// # Safety
+///
+/// `queue` must be null or have been returned by [`nettish_prediction_queue_new`], and not
+/// previously freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_prediction_queue_free(queue: *mut NettishPredictionQueue) {
+    if !queue.is_null() {
+        drop(unsafe { Box::from_raw(queue) });
+    }
+}
+
+/// # Safety
+///
+/// `queue` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_prediction_queue_next_sequence_number(
+    queue: *const NettishPredictionQueue,
+) -> u16 {
+    unsafe { &(*queue).0 }.next_sequence_number()
+}
+
+/// Copy `len` bytes from `input` into the queue
+///
+/// # Safety
+///
+/// `queue` must be a live handle, and `input` must be valid for reads of `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_prediction_queue_record(
+    queue: *mut NettishPredictionQueue,
+    input: *const u8,
+    len: usize,
+) {
+    let input = unsafe { bytes(input, len) };
+    unsafe { &mut (*queue).0 }.record(input.into());
+}
+
+/// # Safety
+///
+/// `queue` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_prediction_queue_reconcile(
+    queue: *mut NettishPredictionQueue,
+    sequence_number: u16,
+) {
+    unsafe { &mut (*queue).0 }.reconcile(sequence_number);
+}
+
+/// Number of inputs awaiting reconciliation
+///
+/// # Safety
+///
+/// `queue` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_prediction_queue_len(
+    queue: *const NettishPredictionQueue,
+) -> usize {
+    unsafe { &(*queue).0 }.iter().len()
+}
+
+/// The `index`th oldest input awaiting reconciliation, or null data if out of range
+///
+/// The result is invalidated by any subsequent mutation of the queue.
+///
+/// # Safety
+///
+/// `queue` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_prediction_queue_get(
+    queue: *const NettishPredictionQueue,
+    index: usize,
+) -> NettishBytes {
+    match unsafe { &(*queue).0 }.iter().nth(index) {
+        Some(input) => NettishBytes {
+            data: input.as_ptr(),
+            len: input.len(),
+        },
+        None => NettishBytes {
+            data: ptr::null(),
+            len: 0,
+        },
+    }
+}
+
+/// Opaque handle to a `nettish::InputQueue`
+pub struct NettishInputQueue {
+    queue: InputQueue<Box<[u8]>>,
+    /// The caller's time zero
+    origin: Instant,
+}
+
+impl NettishInputQueue {
+    fn instant(&self, ns: u64) -> Instant {
+        self.origin + Duration::from_nanos(ns)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn nettish_input_queue_new() -> *mut NettishInputQueue {
+    Box::into_raw(Box::new(NettishInputQueue {
+        queue: InputQueue::new(),
+        origin: Instant::now(),
+    }))
+}
+
+/// # Safety
+///
+/// `queue` must be null or have been returned by [`nettish_input_queue_new`], and not previously
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_input_queue_free(queue: *mut NettishInputQueue) {
+    if !queue.is_null() {
+        drop(unsafe { Box::from_raw(queue) });
+    }
+}
+
+/// Copy `len` bytes from `input` into the queue
+///
+/// Returns whether an old input was dropped due to overrun.
+///
+/// # Safety
+///
+/// `queue` must be a live handle, and `input` must be valid for reads of `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_input_queue_push(
+    queue: *mut NettishInputQueue,
+    max: usize,
+    input: *const u8,
+    len: usize,
+    now_ns: u64,
+) -> bool {
+    let queue = unsafe { &mut *queue };
+    let input = unsafe { bytes(input, len) };
+    let now = queue.instant(now_ns);
+    queue.queue.push(max, input.into(), now)
+}
+
+/// Obtain the input for the next simulation step, if any
+///
+/// On success, writes the input to `out`, which must then be freed with [`nettish_buffer_free`].
+///
+/// # Safety
+///
+/// `queue` must be a live handle, and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_input_queue_take(
+    queue: *mut NettishInputQueue,
+    now_ns: u64,
+    delay_ns: u64,
+    out: *mut NettishBuffer,
+) -> bool {
+    let queue = unsafe { &mut *queue };
+    let now = queue.instant(now_ns);
+    let Some(input) = queue.queue.take(now, Duration::from_nanos(delay_ns)) else {
+        return false;
+    };
+    let len = input.len();
+    let data = Box::into_raw(input).cast::<u8>();
+    unsafe { out.write(NettishBuffer { data, len }) };
+    true
+}
+
+/// # Safety
+///
+/// `queue` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nettish_input_queue_len(queue: *const NettishInputQueue) -> usize {
+    unsafe { &*queue }.queue.len()
+}
+
+/// Borrow a byte string from C, which may be null if empty
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    unsafe { slice::from_raw_parts(data, len) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prediction_queue() {
+        unsafe {
+            let queue = nettish_prediction_queue_new(0);
+            for i in 0..4u8 {
+                nettish_prediction_queue_record(queue, [i, i].as_ptr(), 2);
+            }
+            nettish_prediction_queue_record(queue, ptr::null(), 0);
+            nettish_prediction_queue_reconcile(queue, 1);
+            assert_eq!(nettish_prediction_queue_next_sequence_number(queue), 5);
+            assert_eq!(nettish_prediction_queue_len(queue), 3);
+            let input = nettish_prediction_queue_get(queue, 0);
+            assert_eq!(slice::from_raw_parts(input.data, input.len), [2, 2]);
+            assert!(nettish_prediction_queue_get(queue, 3).data.is_null());
+            nettish_prediction_queue_free(queue);
+        }
+    }
+
+    #[test]
+    fn input_queue() {
+        const MS: u64 = 1_000_000;
+        unsafe {
+            let queue = nettish_input_queue_new();
+            assert!(!nettish_input_queue_push(queue, 4, [7].as_ptr(), 1, 0));
+            let mut out = NettishBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert!(!nettish_input_queue_take(queue, 10 * MS, 20 * MS, &mut out));
+            assert!(nettish_input_queue_take(queue, 20 * MS, 20 * MS, &mut out));
+            assert_eq!(slice::from_raw_parts(out.data, out.len), [7]);
+            nettish_buffer_free(out);
+            assert_eq!(nettish_input_queue_len(queue), 0);
+            nettish_input_queue_free(queue);
+        }
+    }
+
+    #[test]
+    fn throttle() {
+        assert_eq!(nettish_throttle(10, 100, 50, 200), 10);
+    }
+}
@@ -0,0 +1,373 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{DuplicateFilter, Instant, seq};
+
+/// Tracks delivery of unreliable packets in both directions
+///
+/// Every outgoing packet is tagged with an [`AckHeader`] carrying its own sequence number and a
+/// summary of the most recent packets received from the peer. Each packet therefore acknowledges
+/// many of its predecessors, so acknowledgements are robust to loss without any additional
+/// traffic. When a header is received, newly acknowledged packets are reported as
+/// [`Delivered`](AckEvent::Delivered), and packets that have fallen out of the peer's
+/// acknowledgement window without being acknowledged are reported as [`Lost`](AckEvent::Lost).
+///
+/// This does not retransmit anything; it's up to the application to decide what, if anything,
+/// should be done in response to loss.
+#[derive(Debug, Clone)]
+pub struct AckTracker {
+    config: AckConfig,
+    /// Sequence number of the next packet to be sent
+    next_sequence: u16,
+    /// Packets sent whose fate is undetermined, ending at `next_sequence - 1`
+    sent: VecDeque<SentPacket>,
+    received: DuplicateFilter,
+    events: VecDeque<AckEvent>,
+}
+
+impl AckTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: AckConfig) -> Self {
+        Self {
+            received: DuplicateFilter::new(config.receive_window),
+            config,
+            next_sequence: 0,
+            sent: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Record the transmission of a packet of `bytes` bytes, returning the header to send with it
+    pub fn send(&mut self, bytes: usize, now: Instant) -> AckHeader {
+        while self.sent.len() >= usize::from(self.config.sent_window.max(1)) {
+            // Too old to be acknowledged by a well-behaved peer
+            let oldest = self.oldest_sent();
+            let packet = self.sent.pop_front().unwrap();
+            self.retire(oldest, packet);
+        }
+        let sequence = self.next_sequence;
+        self.next_sequence = sequence.wrapping_add(1);
+        self.sent.push_back(SentPacket {
+            sent: now,
+            bytes,
+            acked: false,
+        });
+        AckHeader {
+            sequence,
+            // Acknowledging a sequence number the peer hasn't sent yet is harmless
+            ack: self.received.latest().unwrap_or(u16::MAX),
+            ack_bits: self.ack_bits(),
+        }
+    }
+
+    /// Process the header of a packet received from the peer
+    ///
+    /// Returns `false` if the packet is a duplicate, or is too old to tell whether it might be,
+    /// in which case it should be discarded.
+    pub fn receive(&mut self, header: &AckHeader, now: Instant) -> bool {
+        if !self.received.insert(header.sequence) {
+            return false;
+        }
+
+        self.acknowledge(header.ack, now);
+        for i in 0..32 {
+            if header.ack_bits & (1 << i) != 0 {
+                self.acknowledge(header.ack.wrapping_sub(i + 1), now);
+            }
+        }
+
+        // Anything predating the peer's acknowledgement window will never be acknowledged
+        let horizon = header.ack.wrapping_sub(32);
+        while let Some(packet) = self.sent.front() {
+            let sequence = self.oldest_sent();
+            if !packet.acked && !seq::is_newer(horizon, sequence) {
+                break;
+            }
+            let packet = self.sent.pop_front().unwrap();
+            self.retire(sequence, packet);
+        }
+        true
+    }
+
+    /// Get the next delivery notification, if any
+    pub fn poll(&mut self) -> Option<AckEvent> {
+        self.events.pop_front()
+    }
+
+    /// Sequence number that will be assigned to the next packet sent
+    pub fn next_sequence(&self) -> u16 {
+        self.next_sequence
+    }
+
+    /// Number of packets sent whose fate is not yet known
+    pub fn in_flight(&self) -> usize {
+        self.sent.iter().filter(|x| !x.acked).count()
+    }
+
+    fn oldest_sent(&self) -> u16 {
+        self.next_sequence.wrapping_sub(self.sent.len() as u16)
+    }
+
+    fn acknowledge(&mut self, sequence: u16, now: Instant) {
+        let age = self.next_sequence.wrapping_sub(sequence) as usize;
+        if age == 0 || age > self.sent.len() {
+            // Not in flight
+            return;
+        }
+        let index = self.sent.len() - age;
+        let packet = &mut self.sent[index];
+        if packet.acked {
+            return;
+        }
+        packet.acked = true;
+        self.events.push_back(AckEvent::Delivered(PacketInfo {
+            sequence,
+            bytes: packet.bytes,
+            sent: packet.sent,
+            rtt: now.saturating_duration_since(packet.sent),
+        }));
+    }
+
+    /// Remove a packet from tracking, reporting it as lost if it was never acknowledged
+    fn retire(&mut self, sequence: u16, packet: SentPacket) {
+        if packet.acked {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            target: "nettish::ack",
+            sequence,
+            bytes = packet.bytes,
+            "packet lost"
+        );
+        self.events.push_back(AckEvent::Lost(LostPacket {
+            sequence,
+            bytes: packet.bytes,
+            sent: packet.sent,
+        }));
+    }
+
+    fn ack_bits(&self) -> u32 {
+        let Some(latest) = self.received.latest() else {
+            return 0;
+        };
+        let mut bits = 0;
+        for i in 0..32 {
+            if self.received.contains(latest.wrapping_sub(i + 1)) {
+                bits |= 1 << i;
+            }
+        }
+        bits
+    }
+}
+
+impl Default for AckTracker {
+    fn default() -> Self {
+        Self::with_config(AckConfig::default())
+    }
+}
+
+/// Parameters for an [`AckTracker`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AckConfig {
+    /// Number of sent packets to track before assuming they're lost
+    ///
+    /// Packets are usually resolved well before this by acknowledgements, so this only matters
+    /// when the peer stops sending. Must be at least 33 to allow the full range of
+    /// [`AckHeader::ack_bits`] to be used.
+    pub sent_window: u16,
+    /// Number of received sequence numbers to remember for duplicate detection
+    ///
+    /// Packets delayed by more than this many sequence numbers are rejected. At most 32768.
+    pub receive_window: u16,
+}
+
+impl Default for AckConfig {
+    fn default() -> Self {
+        Self {
+            sent_window: 256,
+            receive_window: 256,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct SentPacket {
+    sent: Instant,
+    bytes: usize,
+    acked: bool,
+}
+
+/// Sequencing and acknowledgement data carried by every packet
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AckHeader {
+    /// Sequence number of this packet
+    pub sequence: u16,
+    /// Most recent sequence number received from the peer
+    pub ack: u16,
+    /// Bit `n` is set if `ack - n - 1` has been received from the peer
+    pub ack_bits: u32,
+}
+
+impl AckHeader {
+    /// Size of the encoded header in bytes
+    pub const SIZE: usize = 8;
+
+    /// Encode for transmission
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0; Self::SIZE];
+        buf[0..2].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[2..4].copy_from_slice(&self.ack.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.ack_bits.to_be_bytes());
+        buf
+    }
+
+    /// Decode a header from the start of `buf`, returning `None` if it's too short
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..Self::SIZE)?;
+        Some(Self {
+            sequence: u16::from_be_bytes(buf[0..2].try_into().unwrap()),
+            ack: u16::from_be_bytes(buf[2..4].try_into().unwrap()),
+            ack_bits: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+        })
+    }
+}
+
+/// Delivery notification produced by [`AckTracker::poll`]
+#[derive(Debug, Copy, Clone)]
+pub enum AckEvent {
+    /// A packet was acknowledged by the peer
+    Delivered(PacketInfo),
+    /// A packet will never be acknowledged by the peer
+    Lost(LostPacket),
+}
+
+/// A packet that was acknowledged
+#[derive(Debug, Copy, Clone)]
+pub struct PacketInfo {
+    pub sequence: u16,
+    /// Size passed to [`AckTracker::send`]
+    pub bytes: usize,
+    /// When the packet was sent
+    pub sent: Instant,
+    /// Time between sending the packet and receiving its acknowledgement
+    ///
+    /// Includes however long the peer waited before sending a packet to carry the acknowledgement.
+    pub rtt: Duration,
+}
+
+/// A packet that was not acknowledged
+#[derive(Debug, Copy, Clone)]
+pub struct LostPacket {
+    pub sequence: u16,
+    /// Size passed to [`AckTracker::send`]
+    pub bytes: usize,
+    /// When the packet was sent
+    pub sent: Instant,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(tracker: &mut AckTracker) -> (Vec<u16>, Vec<u16>) {
+        let mut delivered = Vec::new();
+        let mut lost = Vec::new();
+        while let Some(event) = tracker.poll() {
+            match event {
+                AckEvent::Delivered(x) => delivered.push(x.sequence),
+                AckEvent::Lost(x) => lost.push(x.sequence),
+            }
+        }
+        (delivered, lost)
+    }
+
+    #[test]
+    fn smoke() {
+        let now = Instant::now();
+        let mut a = AckTracker::new();
+        let mut b = AckTracker::new();
+        let headers = (0..4).map(|_| a.send(100, now)).collect::<Vec<_>>();
+        assert!(b.receive(&headers[0], now));
+        assert!(b.receive(&headers[2], now));
+        assert!(!b.receive(&headers[2], now), "duplicate");
+        let reply = b.send(100, now);
+        assert_eq!(reply.ack, 2);
+        assert_eq!(reply.ack_bits, 0b10);
+        assert!(a.receive(&reply, now));
+        assert_eq!(events(&mut a), (vec![2, 0], vec![]));
+        assert_eq!(a.in_flight(), 2);
+    }
+
+    #[test]
+    fn loss() {
+        let now = Instant::now();
+        let mut a = AckTracker::new();
+        let mut b = AckTracker::new();
+        a.send(100, now);
+        for _ in 0..40 {
+            let header = a.send(100, now);
+            b.receive(&header, now);
+        }
+        a.receive(&b.send(100, now), now);
+        let (delivered, lost) = events(&mut a);
+        assert_eq!(delivered.len(), 33);
+        assert_eq!(
+            lost,
+            (0..8).collect::<Vec<_>>(),
+            "packets that fell outside the peer's window are lost even if received"
+        );
+        assert_eq!(a.in_flight(), 0);
+    }
+
+    #[test]
+    fn config() {
+        let now = Instant::now();
+        let mut a = AckTracker::with_config(AckConfig {
+            sent_window: 4,
+            receive_window: 2,
+        });
+        for _ in 0..6 {
+            a.send(100, now);
+        }
+        assert_eq!(events(&mut a), (vec![], vec![0, 1]));
+        assert_eq!(a.in_flight(), 4);
+
+        let mut b = AckTracker::new();
+        let headers = (0..3).map(|_| b.send(100, now)).collect::<Vec<_>>();
+        assert!(a.receive(&headers[0], now));
+        assert!(a.receive(&headers[2], now));
+        assert!(a.receive(&headers[1], now));
+        assert!(!a.receive(&headers[0], now), "outside receive window");
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        let header = AckHeader {
+            sequence: 0x1234,
+            ack: 0xfedc,
+            ack_bits: 0xdeadbeef,
+        };
+        assert_eq!(AckHeader::decode(&header.encode()), Some(header));
+        assert_eq!(AckHeader::decode(&[0; 7]), None);
+    }
+
+    #[test]
+    fn wrap() {
+        let now = Instant::now();
+        let mut a = AckTracker::new();
+        let mut b = AckTracker::new();
+        for _ in 0..70_000 {
+            let header = a.send(1, now);
+            assert!(b.receive(&header, now));
+            assert!(a.receive(&b.send(1, now), now));
+        }
+        let (delivered, lost) = events(&mut a);
+        assert_eq!(delivered.len(), 70_000);
+        assert!(lost.is_empty());
+    }
+}
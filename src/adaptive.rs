@@ -0,0 +1,191 @@
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+use crate::{TickConfig, TickRateMessage, TickTimeline};
+
+/// Configuration for a [`TickRateController`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TickRateControllerConfig {
+    /// Tickrates to choose between, from most to least preferred
+    pub levels: Vec<u32>,
+    /// Fraction of the tick interval which, if exceeded on average, causes the rate to step down
+    pub overload: f32,
+    /// Fraction of the next higher level's tick interval which, if the work would fit within it
+    /// on average, causes the rate to step up
+    ///
+    /// Should be well below `overload` to avoid oscillating between levels.
+    pub underload: f32,
+    /// Number of ticks to average over before deciding whether to change rate
+    pub window: u32,
+    /// Number of ticks ahead of the present to schedule changes, giving clients time to learn of
+    /// them
+    pub lead: u64,
+}
+
+impl Default for TickRateControllerConfig {
+    fn default() -> Self {
+        Self {
+            levels: vec![60, 30, 20],
+            overload: 0.9,
+            underload: 0.5,
+            window: 120,
+            lead: 30,
+        }
+    }
+}
+
+/// Degrades a server's tickrate gracefully when it can't keep up, and restores it when it can
+///
+/// A server that takes longer to simulate a tick than the tick interval falls further behind each
+/// tick, and catching up only makes it worse. Instead, the controller measures the time taken by
+/// each tick and steps down to the next configured level when the budget is consistently
+/// overrun, stepping back up once there's ample headroom.
+///
+/// Changes are scheduled on the server's [`TickTimeline`] a little in the future, and the
+/// resulting message should be sent to every client so that their timelines, and hence their
+/// throttles and interpolation, switch at the same tick.
+#[derive(Debug, Clone)]
+pub struct TickRateController {
+    config: TickRateControllerConfig,
+    timeline: TickTimeline,
+    /// Index into `config.levels` of the rate in effect or scheduled
+    level: usize,
+    /// Sum of tick utilizations within the current window
+    utilization: f32,
+    /// Number of ticks measured within the current window
+    samples: u32,
+    /// Tick at which the latest change takes effect, before which measurements are ignored
+    settled: u64,
+}
+
+impl TickRateController {
+    /// Start at the most preferred level
+    ///
+    /// Panics if `config.levels` is empty.
+    pub fn new(config: TickRateControllerConfig) -> Self {
+        let rate = *config.levels.first().expect("no levels configured");
+        Self {
+            config,
+            timeline: TickTimeline::new(TickConfig::new(rate)),
+            level: 0,
+            utilization: 0.0,
+            samples: 0,
+            settled: 0,
+        }
+    }
+
+    /// Account for `tick` having taken `elapsed` to simulate
+    ///
+    /// Returns a message to send to every client if the rate is to change.
+    pub fn record(&mut self, tick: u64, elapsed: Duration) -> Option<TickRateMessage> {
+        if tick < self.settled {
+            return None;
+        }
+        let interval = self.timeline.config_at(tick).interval();
+        self.utilization += elapsed.div_duration_f32(interval);
+        self.samples += 1;
+        if self.samples < self.config.window {
+            return None;
+        }
+        let mean = self.utilization / self.samples as f32;
+        self.utilization = 0.0;
+        self.samples = 0;
+
+        let current = self.config.levels[self.level];
+        let level = if mean > self.config.overload && self.level + 1 < self.config.levels.len() {
+            self.level + 1
+        } else if let Some(higher) = self.level.checked_sub(1)
+            && mean * self.config.levels[higher] as f32 / current as f32 <= self.config.underload
+        {
+            higher
+        } else {
+            return None;
+        };
+        self.level = level;
+        self.settled = tick + self.config.lead;
+        Some(
+            self.timeline
+                .change(self.settled, TickConfig::new(self.config.levels[level])),
+        )
+    }
+
+    /// The rate most recently chosen, which may not yet be in effect
+    pub fn config(&self) -> TickConfig {
+        TickConfig::new(self.config.levels[self.level])
+    }
+
+    /// The server's timeline, reflecting every change made
+    pub fn timeline(&self) -> &TickTimeline {
+        &self.timeline
+    }
+
+    /// Mutable access to the [`timeline`](Self::timeline), e.g. to forget old changes
+    pub fn timeline_mut(&mut self) -> &mut TickTimeline {
+        &mut self.timeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulate `ticks` ticks each taking `work`, returning messages for clients
+    fn run(
+        controller: &mut TickRateController,
+        tick: &mut u64,
+        ticks: u32,
+        work: Duration,
+    ) -> Vec<TickRateMessage> {
+        let mut messages = Vec::new();
+        for _ in 0..ticks {
+            messages.extend(controller.record(*tick, work));
+            *tick += 1;
+        }
+        messages
+    }
+
+    #[test]
+    fn degrade_and_recover() {
+        let config = TickRateControllerConfig::default();
+        let mut controller = TickRateController::new(config.clone());
+        let mut client = TickTimeline::new(TickConfig::new(60));
+        let mut tick = 0;
+
+        // 20ms of work can't fit in a 60Hz tick
+        let messages = run(
+            &mut controller,
+            &mut tick,
+            config.window,
+            Duration::from_millis(20),
+        );
+        assert_eq!(messages.len(), 1);
+        assert!(client.handle(&messages[0]));
+        assert_eq!(controller.config().rate, 30);
+        assert_eq!(client.config_at(tick + config.lead).rate, 30);
+
+        let messages = run(
+            &mut controller,
+            &mut tick,
+            config.window * 2,
+            Duration::from_millis(20),
+        );
+        assert!(messages.is_empty(), "stable");
+
+        // 5ms fits comfortably at 60Hz
+        let messages = run(
+            &mut controller,
+            &mut tick,
+            config.window * 2,
+            Duration::from_millis(5),
+        );
+        assert_eq!(messages.len(), 1);
+        assert!(client.handle(&messages[0]));
+        assert_eq!(controller.config().rate, 60);
+        assert_eq!(
+            client.time_of(tick + 100),
+            controller.timeline().time_of(tick + 100)
+        );
+    }
+}
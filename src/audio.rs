@@ -0,0 +1,254 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{Instant, seq, throttle};
+
+/// Configuration for an [`AudioJitterBuffer`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AudioJitterConfig {
+    /// Duration of audio carried by each packet
+    pub frame_duration: Duration,
+    /// Sample rate in which packet timestamps are expressed
+    pub sample_rate: u32,
+    /// Least audio to buffer before playback, regardless of jitter
+    pub min_delay: Duration,
+    /// Most audio to buffer before playback, regardless of jitter
+    pub max_delay: Duration,
+    /// Greatest fraction by which [`playout_rate`](AudioJitterBuffer::playout_rate) may deviate
+    /// from 1
+    ///
+    /// Small adjustments are imperceptible, particularly when resampling preserves pitch.
+    pub max_rate_adjustment: f32,
+}
+
+impl Default for AudioJitterConfig {
+    fn default() -> Self {
+        Self {
+            frame_duration: Duration::from_millis(20),
+            sample_rate: 48_000,
+            min_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(200),
+            max_rate_adjustment: 0.05,
+        }
+    }
+}
+
+/// Reorders and buffers received audio frames, e.g. for voice chat, adapting to network jitter
+///
+/// Like [`throttle`] does for simulation time, this maintains a margin of buffered audio large
+/// enough to absorb variation in packet arrival times, but no larger. The margin grows and
+/// shrinks with measured jitter, and is reached gradually by playing slightly faster or slower
+/// according to [`playout_rate`](Self::playout_rate), so that audio never skips or stalls under
+/// ordinary conditions.
+///
+/// Each frame is identified by a wrapping sequence number, incremented by one per frame, and an
+/// RTP-style timestamp in samples. Frames that never arrive are replaced by calling a packet loss
+/// concealment function when their turn comes to play.
+#[derive(Debug, Clone)]
+pub struct AudioJitterBuffer<T> {
+    config: AudioJitterConfig,
+    /// Frames received, starting from `next`
+    frames: VecDeque<Option<T>>,
+    /// Sequence number of the frame at the front of `frames`, if any frames have been received
+    next: Option<u16>,
+    /// Whether playback is paused to accumulate a margin
+    buffering: bool,
+    /// Arrival time and timestamp of the most recent frame
+    last_arrival: Option<(Instant, u32)>,
+    /// Smoothed variation in transit time, per RFC 3550
+    jitter: Duration,
+    stats: AudioJitterStats,
+}
+
+impl<T> AudioJitterBuffer<T> {
+    pub fn new(config: AudioJitterConfig) -> Self {
+        Self {
+            config,
+            frames: VecDeque::new(),
+            next: None,
+            buffering: true,
+            last_arrival: None,
+            jitter: Duration::ZERO,
+            stats: AudioJitterStats::default(),
+        }
+    }
+
+    /// Buffer a frame received at `now`
+    ///
+    /// Returns `false` if the frame arrived too late to be played, or was a duplicate.
+    pub fn push(&mut self, now: Instant, sequence: u16, timestamp: u32, frame: T) -> bool {
+        self.stats.received += 1;
+        if let Some((prev_arrival, prev_timestamp)) = self.last_arrival {
+            let arrival = now.saturating_duration_since(prev_arrival).as_secs_f64();
+            let sent = timestamp.wrapping_sub(prev_timestamp) as i32 as f64
+                / self.config.sample_rate as f64;
+            let deviation = Duration::from_secs_f64((arrival - sent).abs().min(1.0));
+            self.jitter = (self.jitter * 15 + deviation) / 16;
+        }
+        self.last_arrival = Some((now, timestamp));
+
+        let next = *self.next.get_or_insert(sequence);
+        let offset = seq::diff(sequence, next);
+        if offset < 0 {
+            self.stats.late += 1;
+            return false;
+        }
+        let offset = offset as usize;
+        if offset >= self.capacity() {
+            // Too far ahead to be a reordering; the sender probably paused or restarted
+            self.frames.clear();
+            self.next = Some(sequence);
+            self.buffering = true;
+            self.frames.push_back(Some(frame));
+            return true;
+        }
+        if self.frames.len() <= offset {
+            self.frames.resize_with(offset + 1, || None);
+        }
+        let slot = &mut self.frames[offset];
+        if slot.is_some() {
+            return false;
+        }
+        *slot = Some(frame);
+        true
+    }
+
+    /// Get the next frame to play, if any
+    ///
+    /// Should be called whenever the audio output needs another frame. A frame that was lost is
+    /// replaced with the result of `conceal`, which is passed the following frame if it has
+    /// arrived, e.g. to make use of forward error correction. `None` is returned while buffering,
+    /// in which case silence should be played.
+    pub fn pop(&mut self, conceal: impl FnOnce(Option<&T>) -> T) -> Option<T> {
+        if self.buffering {
+            if self.buffered() < self.target_delay() {
+                return None;
+            }
+            self.buffering = false;
+        }
+        let Some(frame) = self.frames.pop_front() else {
+            // Underrun; rebuild the margin
+            self.stats.underruns += 1;
+            self.buffering = true;
+            return None;
+        };
+        self.next = self.next.map(|x| x.wrapping_add(1));
+        Some(match frame {
+            Some(frame) => frame,
+            None => {
+                self.stats.concealed += 1;
+                conceal(self.frames.front().and_then(|x| x.as_ref()))
+            }
+        })
+    }
+
+    /// Rate at which to play audio relative to its nominal sample rate
+    ///
+    /// Values above 1 indicate too much audio is buffered, so it should be resampled to play
+    /// faster; below 1, it should play slower. Always within
+    /// [`max_rate_adjustment`](AudioJitterConfig::max_rate_adjustment) of 1.
+    pub fn playout_rate(&self) -> f32 {
+        if self.buffering {
+            return 1.0;
+        }
+        let frame = self.config.frame_duration;
+        let advance = throttle(
+            frame,
+            self.buffered().max(frame),
+            self.target_delay(),
+            frame,
+        );
+        let max = self.config.max_rate_adjustment;
+        advance.div_duration_f32(frame).clamp(1.0 - max, 1.0 + max)
+    }
+
+    /// Amount of audio to buffer given the measured jitter
+    pub fn target_delay(&self) -> Duration {
+        (self.config.frame_duration + self.jitter * 4)
+            .clamp(self.config.min_delay, self.config.max_delay)
+    }
+
+    /// Amount of audio buffered, including frames not yet received but followed by others that
+    /// have been
+    pub fn buffered(&self) -> Duration {
+        self.config.frame_duration * self.frames.len() as u32
+    }
+
+    /// Smoothed variation in packet transit time
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    pub fn stats(&self) -> &AudioJitterStats {
+        &self.stats
+    }
+
+    /// Maximum number of frames retained, beyond which the stream is assumed to have restarted
+    fn capacity(&self) -> usize {
+        (self
+            .config
+            .max_delay
+            .div_duration_f32(self.config.frame_duration) as usize
+            * 2)
+        .max(2)
+    }
+}
+
+/// Statistics gathered by an [`AudioJitterBuffer`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct AudioJitterStats {
+    /// Number of frames pushed
+    pub received: u64,
+    /// Number of frames that arrived too late to be played
+    pub late: u64,
+    /// Number of lost frames replaced by concealment
+    pub concealed: u64,
+    /// Number of times the buffer ran dry
+    pub underruns: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorder_and_conceal() {
+        let config = AudioJitterConfig::default();
+        let frame = config.frame_duration;
+        let mut buffer = AudioJitterBuffer::new(config);
+        let start = Instant::now();
+        let samples = |seq: u16| u32::from(seq) * 960;
+        // Frame 1 arrives after 2, and 3 is lost
+        for (i, seq) in [0, 2, 1, 4].into_iter().enumerate() {
+            assert!(buffer.push(start + frame * i as u32, seq, samples(seq), seq));
+        }
+        assert!(!buffer.push(start, 2, samples(2), 2), "duplicate");
+        let mut played = Vec::new();
+        while let Some(x) = buffer.pop(|next| next.unwrap() + 100) {
+            played.push(x);
+        }
+        assert_eq!(played, [0, 1, 2, 104, 4]);
+        assert_eq!(buffer.stats().concealed, 1);
+        assert_eq!(buffer.stats().underruns, 1);
+        assert!(!buffer.push(start, 3, samples(3), 3), "late");
+    }
+
+    #[test]
+    fn adapt() {
+        let config = AudioJitterConfig::default();
+        let frame = config.frame_duration;
+        let mut buffer = AudioJitterBuffer::new(config);
+        let start = Instant::now();
+        for seq in 0..50u16 {
+            // Alternate between early and late arrival
+            let arrival = frame * u32::from(seq) + frame * (u32::from(seq) % 2);
+            buffer.push(start + arrival, seq, u32::from(seq) * 960, ());
+        }
+        assert!(buffer.jitter() > Duration::from_millis(10));
+        assert!(buffer.target_delay() > config.min_delay);
+        assert!(buffer.pop(|_| ()).is_some());
+        assert!(buffer.playout_rate() > 1.0, "too much buffered");
+        assert!(buffer.playout_rate() <= 1.0 + config.max_rate_adjustment);
+    }
+}
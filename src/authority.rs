@@ -0,0 +1,274 @@
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+use crate::{Instant, seq};
+
+/// A message negotiating which peer may send updates for an entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthorityMessage<E, P> {
+    /// Sent to the arbiter by a peer wishing to take authority over `entity`
+    Request { entity: E },
+    /// Sent to the arbiter by the owner of `entity` to return authority to the default owner
+    Release { entity: E },
+    /// Sent by the arbiter to announce that `owner` has authority over `entity`
+    ///
+    /// `epoch` increases with every transfer, so that stale grants can be discarded.
+    Grant { entity: E, owner: P, epoch: u16 },
+    /// Sent by the arbiter to a peer whose request was refused
+    Deny { entity: E },
+}
+
+/// Rules governing transfers of authority by an [`AuthorityArbiter`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AuthorityConfig {
+    /// Minimum time a peer holds authority before another peer's request may take it
+    ///
+    /// Prevents authority from bouncing rapidly between peers contending for an entity, e.g.
+    /// two players pushing the same crate. Doesn't apply to entities held by the default owner.
+    pub min_hold: Duration,
+}
+
+impl Default for AuthorityConfig {
+    fn default() -> Self {
+        Self {
+            min_hold: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Decides which peer has authority to send updates for each entity
+///
+/// Runs on the server, or whichever peer arbitrates. Every entity is owned by exactly one peer,
+/// initially the default owner, typically the server itself. Peers request authority with
+/// [`AuthorityMessage::Request`], and the arbiter's decision should be sent to the requester and,
+/// if granted, to every peer replicating the entity. Conflicting requests are resolved in the
+/// order they reach the arbiter, subject to [`AuthorityConfig::min_hold`], and requests for
+/// [`lock`](Self::lock)ed entities are always denied.
+///
+/// The replication layer should consult [`may_update`](Self::may_update) before accepting or
+/// forwarding updates for an entity from a peer.
+#[derive(Debug, Clone)]
+pub struct AuthorityArbiter<E, P> {
+    config: AuthorityConfig,
+    default_owner: P,
+    entities: HashMap<E, Ownership<P>>,
+}
+
+impl<E: Hash + Eq + Clone, P: Eq + Clone> AuthorityArbiter<E, P> {
+    /// Construct an arbiter under which entities are owned by `default_owner` unless transferred
+    pub fn new(config: AuthorityConfig, default_owner: P) -> Self {
+        Self {
+            config,
+            default_owner,
+            entities: HashMap::new(),
+        }
+    }
+
+    /// Begin tracking `entity`, owned by the default owner
+    pub fn insert(&mut self, entity: E, now: Instant) {
+        self.entities.insert(
+            entity,
+            Ownership {
+                owner: self.default_owner.clone(),
+                epoch: 0,
+                since: now,
+                locked: false,
+            },
+        );
+    }
+
+    /// Stop tracking `entity`
+    pub fn remove(&mut self, entity: &E) {
+        self.entities.remove(entity);
+    }
+
+    /// Handle a request from `requester` for authority over `entity` received at `now`
+    ///
+    /// Returns the [`Grant`](AuthorityMessage::Grant) or [`Deny`](AuthorityMessage::Deny) to
+    /// send in response.
+    pub fn request(&mut self, entity: E, requester: P, now: Instant) -> AuthorityMessage<E, P> {
+        let Some(x) = self.entities.get_mut(&entity) else {
+            return AuthorityMessage::Deny { entity };
+        };
+        if x.owner != requester {
+            let contested = x.owner != self.default_owner
+                && now.saturating_duration_since(x.since) < self.config.min_hold;
+            if x.locked || contested {
+                return AuthorityMessage::Deny { entity };
+            }
+            x.transfer(requester, now);
+        }
+        x.grant(entity)
+    }
+
+    /// Handle `owner` relinquishing authority over `entity` at `now`
+    ///
+    /// Returns the [`Grant`](AuthorityMessage::Grant) to the default owner to broadcast, if
+    /// `owner` had authority.
+    pub fn release(
+        &mut self,
+        entity: E,
+        owner: &P,
+        now: Instant,
+    ) -> Option<AuthorityMessage<E, P>> {
+        let x = self.entities.get_mut(&entity)?;
+        if x.owner != *owner || *owner == self.default_owner {
+            return None;
+        }
+        x.transfer(self.default_owner.clone(), now);
+        Some(x.grant(entity))
+    }
+
+    /// Unconditionally give `owner` authority over `entity`, returning the grant to broadcast
+    pub fn set_owner(
+        &mut self,
+        entity: E,
+        owner: P,
+        now: Instant,
+    ) -> Option<AuthorityMessage<E, P>> {
+        let x = self.entities.get_mut(&entity)?;
+        if x.owner != owner {
+            x.transfer(owner, now);
+        }
+        Some(x.grant(entity))
+    }
+
+    /// Set whether requests for authority over `entity` are refused
+    pub fn lock(&mut self, entity: &E, locked: bool) {
+        if let Some(x) = self.entities.get_mut(entity) {
+            x.locked = locked;
+        }
+    }
+
+    /// The peer with authority over `entity`
+    pub fn owner(&self, entity: &E) -> Option<&P> {
+        Some(&self.entities.get(entity)?.owner)
+    }
+
+    /// Whether `peer` may send updates for `entity`
+    pub fn may_update(&self, entity: &E, peer: &P) -> bool {
+        self.owner(entity) == Some(peer)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Ownership<P> {
+    owner: P,
+    epoch: u16,
+    /// When `owner` took authority
+    since: Instant,
+    locked: bool,
+}
+
+impl<P: Clone> Ownership<P> {
+    fn transfer(&mut self, owner: P, now: Instant) {
+        self.owner = owner;
+        self.epoch = self.epoch.wrapping_add(1);
+        self.since = now;
+    }
+
+    fn grant<E>(&self, entity: E) -> AuthorityMessage<E, P> {
+        AuthorityMessage::Grant {
+            entity,
+            owner: self.owner.clone(),
+            epoch: self.epoch,
+        }
+    }
+}
+
+/// A peer's record of which peer has authority over each entity, as announced by an
+/// [`AuthorityArbiter`]
+///
+/// Grants may arrive out of order; those older than the latest applied are ignored.
+#[derive(Debug, Clone)]
+pub struct AuthorityView<E, P> {
+    entities: HashMap<E, (P, u16)>,
+}
+
+impl<E: Hash + Eq, P: Eq> AuthorityView<E, P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a [`Grant`](AuthorityMessage::Grant)
+    ///
+    /// Returns false if it was stale.
+    pub fn apply(&mut self, entity: E, owner: P, epoch: u16) -> bool {
+        if let Some(&(_, latest)) = self.entities.get(&entity)
+            && !seq::is_newer(epoch, latest)
+        {
+            return false;
+        }
+        self.entities.insert(entity, (owner, epoch));
+        true
+    }
+
+    /// The peer with authority over `entity`, if known
+    pub fn owner(&self, entity: &E) -> Option<&P> {
+        Some(&self.entities.get(entity)?.0)
+    }
+
+    /// Forget `entity`, e.g. when it's destroyed
+    pub fn remove(&mut self, entity: &E) {
+        self.entities.remove(entity);
+    }
+}
+
+impl<E, P> Default for AuthorityView<E, P> {
+    fn default() -> Self {
+        Self {
+            entities: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbiter() {
+        let now = Instant::now();
+        let mut arbiter = AuthorityArbiter::new(AuthorityConfig::default(), "server");
+        arbiter.insert('a', now);
+        assert!(arbiter.may_update(&'a', &"server"));
+        assert_eq!(
+            arbiter.request('a', "alice", now),
+            AuthorityMessage::Grant {
+                entity: 'a',
+                owner: "alice",
+                epoch: 1
+            }
+        );
+        assert!(arbiter.may_update(&'a', &"alice"));
+        assert_eq!(
+            arbiter.request('a', "bob", now),
+            AuthorityMessage::Deny { entity: 'a' },
+            "held too briefly"
+        );
+        let later = now + Duration::from_secs(1);
+        assert!(matches!(
+            arbiter.request('a', "bob", later),
+            AuthorityMessage::Grant { owner: "bob", .. }
+        ));
+        assert_eq!(arbiter.release('a', &"alice", later), None, "not the owner");
+        assert!(arbiter.release('a', &"bob", later).is_some());
+        assert_eq!(arbiter.owner(&'a'), Some(&"server"));
+
+        arbiter.lock(&'a', true);
+        assert_eq!(
+            arbiter.request('a', "alice", later),
+            AuthorityMessage::Deny { entity: 'a' }
+        );
+    }
+
+    #[test]
+    fn view() {
+        let mut view = AuthorityView::new();
+        assert!(view.apply('a', "alice", 1));
+        assert!(view.apply('a', "server", 3));
+        assert!(!view.apply('a', "bob", 2), "stale");
+        assert_eq!(view.owner(&'a'), Some(&"server"));
+    }
+}
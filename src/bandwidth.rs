@@ -0,0 +1,136 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{AckEvent, Instant};
+
+/// Measures throughput of a connection over a sliding window
+///
+/// Feed it every packet sent and every [`AckEvent`] from the connection's
+/// [`AckTracker`](crate::AckTracker) to learn how much data is being sent, how much of it actually
+/// arrives, and how much is lost along the way. A large gap between sent and delivered rates
+/// indicates that the link can't sustain the current send rate.
+#[derive(Debug, Clone)]
+pub struct BandwidthEstimator {
+    window: Duration,
+    sent: Samples,
+    delivered: Samples,
+    lost: Samples,
+}
+
+impl BandwidthEstimator {
+    /// Construct an estimator averaging over the most recent `window` of time
+    ///
+    /// Longer windows give more stable estimates, but respond more slowly to change.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sent: Samples::default(),
+            delivered: Samples::default(),
+            lost: Samples::default(),
+        }
+    }
+
+    /// Record the transmission of a packet
+    pub fn on_sent(&mut self, bytes: usize, now: Instant) {
+        self.sent.push(now, bytes);
+    }
+
+    /// Record the acknowledgement of a packet
+    pub fn on_delivered(&mut self, bytes: usize, now: Instant) {
+        self.delivered.push(now, bytes);
+    }
+
+    /// Record the loss of a packet
+    pub fn on_lost(&mut self, bytes: usize, now: Instant) {
+        self.lost.push(now, bytes);
+    }
+
+    /// Record a delivery notification from an [`AckTracker`](crate::AckTracker)
+    pub fn on_ack_event(&mut self, event: &AckEvent, now: Instant) {
+        match *event {
+            AckEvent::Delivered(ref packet) => self.on_delivered(packet.bytes, now),
+            AckEvent::Lost(ref packet) => self.on_lost(packet.bytes, now),
+        }
+    }
+
+    /// Compute rates over the window ending at `now`
+    pub fn estimate(&mut self, now: Instant) -> Bandwidth {
+        let secs = self.window.as_secs_f64();
+        Bandwidth {
+            sent: self.sent.total(now, self.window) as f64 / secs,
+            delivered: self.delivered.total(now, self.window) as f64 / secs,
+            lost: self.lost.total(now, self.window) as f64 / secs,
+        }
+    }
+}
+
+/// Throughput measured by a [`BandwidthEstimator`], in bytes per second
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bandwidth {
+    /// Rate at which data was transmitted
+    pub sent: f64,
+    /// Rate at which transmitted data was acknowledged
+    pub delivered: f64,
+    /// Rate at which transmitted data was lost
+    pub lost: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Samples {
+    samples: VecDeque<(Instant, usize)>,
+    /// Sum of the sizes in `samples`
+    total: usize,
+}
+
+impl Samples {
+    fn push(&mut self, now: Instant, bytes: usize) {
+        self.samples.push_back((now, bytes));
+        self.total += bytes;
+    }
+
+    /// Total bytes recorded within `window` of `now`, discarding anything older
+    fn total(&mut self, now: Instant, window: Duration) -> usize {
+        while let Some(&(time, bytes)) = self.samples.front() {
+            if now.saturating_duration_since(time) < window {
+                break;
+            }
+            self.samples.pop_front();
+            self.total -= bytes;
+        }
+        self.total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let start = Instant::now();
+        let mut est = BandwidthEstimator::new(Duration::from_secs(1));
+        for i in 0..10 {
+            let now = start + Duration::from_millis(100) * i;
+            est.on_sent(1000, now);
+            if i % 2 == 0 {
+                est.on_delivered(1000, now);
+            } else {
+                est.on_lost(1000, now);
+            }
+        }
+        let rates = est.estimate(start + Duration::from_millis(950));
+        assert_eq!(rates.sent, 10_000.0);
+        assert_eq!(rates.delivered, 5_000.0);
+        assert_eq!(rates.lost, 5_000.0);
+
+        let rates = est.estimate(start + Duration::from_millis(1050));
+        assert_eq!(rates.sent, 9_000.0, "old samples leave the window");
+        assert_eq!(rates.delivered, 4_000.0);
+        assert_eq!(rates.lost, 5_000.0);
+
+        assert_eq!(
+            est.estimate(start + Duration::from_secs(10)),
+            Bandwidth::default()
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{AckEvent, seq};
+
+/// Tracks which states each client has confirmed receiving, for use as delta baselines
+///
+/// Delta encoding is only safe against a baseline the receiver is known to have. Each time state
+/// for some entities is sent to a client, record which tick it was from and which packet carried
+/// it; when the client's [`AckTracker`](crate::AckTracker) reports the packet delivered, that
+/// tick becomes the newest confirmed baseline for those entities. Lost packets are simply
+/// forgotten.
+///
+/// To delta-encode whole snapshots rather than individual entities, use `()` as the entity type.
+#[derive(Debug, Clone)]
+pub struct BaselineTracker<C, E> {
+    clients: HashMap<C, Client<E>>,
+}
+
+impl<C: Hash + Eq, E: Hash + Eq + Clone> BaselineTracker<C, E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the packet `sequence` sent to `client` carried the state of `entities` as of
+    /// `tick`
+    pub fn on_sent(
+        &mut self,
+        client: C,
+        sequence: u16,
+        tick: u16,
+        entities: impl IntoIterator<Item = E>,
+    ) {
+        self.clients
+            .entry(client)
+            .or_default()
+            .in_flight
+            .insert(sequence, (tick, entities.into_iter().collect()));
+    }
+
+    /// Process a delivery notification from `client`'s [`AckTracker`](crate::AckTracker)
+    pub fn on_ack_event(&mut self, client: &C, event: &AckEvent) {
+        let Some(state) = self.clients.get_mut(client) else {
+            return;
+        };
+        match *event {
+            AckEvent::Delivered(ref packet) => {
+                let Some((tick, entities)) = state.in_flight.remove(&packet.sequence) else {
+                    return;
+                };
+                for entity in entities {
+                    let baseline = state.acked.entry(entity).or_insert(tick);
+                    if seq::is_newer(tick, *baseline) {
+                        *baseline = tick;
+                    }
+                }
+            }
+            AckEvent::Lost(ref packet) => {
+                state.in_flight.remove(&packet.sequence);
+            }
+        }
+    }
+
+    /// Most recent tick of `entity`'s state that `client` is known to have received
+    pub fn baseline(&self, client: &C, entity: &E) -> Option<u16> {
+        self.clients.get(client)?.acked.get(entity).copied()
+    }
+
+    /// Forget baselines older than `max_age` ticks before `tick`
+    ///
+    /// Call regularly with `max_age` no greater than the number of states retained for use as
+    /// baselines, so that baselines are never selected after they've been discarded. Since ticks
+    /// wrap, `max_age` must also be less than 32768.
+    pub fn expire(&mut self, tick: u16, max_age: u16) {
+        let fresh = |baseline: u16| (0..=max_age.into()).contains(&seq::diff(tick, baseline));
+        for state in self.clients.values_mut() {
+            state.acked.retain(|_, &mut baseline| fresh(baseline));
+            state
+                .in_flight
+                .retain(|_, &mut (baseline, _)| fresh(baseline));
+        }
+    }
+
+    /// Forget everything known about `client`, e.g. on disconnect
+    pub fn remove_client(&mut self, client: &C) {
+        self.clients.remove(client);
+    }
+
+    /// Forget all baselines for `entity`, e.g. when it's destroyed
+    pub fn remove_entity(&mut self, entity: &E) {
+        for state in self.clients.values_mut() {
+            state.acked.remove(entity);
+            for (_, entities) in state.in_flight.values_mut() {
+                entities.retain(|x| x != entity);
+            }
+        }
+    }
+}
+
+impl<C, E> Default for BaselineTracker<C, E> {
+    fn default() -> Self {
+        Self {
+            clients: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Client<E> {
+    /// Tick and entities carried by each packet in flight
+    in_flight: HashMap<u16, (u16, Vec<E>)>,
+    /// Most recent acknowledged tick for each entity
+    acked: HashMap<E, u16>,
+}
+
+impl<E> Default for Client<E> {
+    fn default() -> Self {
+        Self {
+            in_flight: HashMap::new(),
+            acked: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LostPacket, PacketInfo};
+    use std::time::Duration;
+
+    use crate::Instant;
+
+    fn delivered(sequence: u16) -> AckEvent {
+        AckEvent::Delivered(PacketInfo {
+            sequence,
+            bytes: 0,
+            sent: Instant::now(),
+            rtt: Duration::ZERO,
+        })
+    }
+
+    fn lost(sequence: u16) -> AckEvent {
+        AckEvent::Lost(LostPacket {
+            sequence,
+            bytes: 0,
+            sent: Instant::now(),
+        })
+    }
+
+    #[test]
+    fn smoke() {
+        let mut tracker = BaselineTracker::new();
+        tracker.on_sent("alice", 0, 10, ['a', 'b']);
+        tracker.on_sent("alice", 1, 11, ['a']);
+        tracker.on_sent("alice", 2, 12, ['b']);
+        tracker.on_sent("bob", 0, 10, ['a']);
+        assert_eq!(tracker.baseline(&"alice", &'a'), None);
+
+        tracker.on_ack_event(&"alice", &delivered(1));
+        tracker.on_ack_event(&"alice", &delivered(0));
+        tracker.on_ack_event(&"alice", &lost(2));
+        assert_eq!(tracker.baseline(&"alice", &'a'), Some(11), "newest wins");
+        assert_eq!(tracker.baseline(&"alice", &'b'), Some(10));
+        assert_eq!(tracker.baseline(&"bob", &'a'), None, "clients are separate");
+
+        tracker.expire(20, 9);
+        assert_eq!(tracker.baseline(&"alice", &'a'), Some(11));
+        assert_eq!(tracker.baseline(&"alice", &'b'), None);
+
+        tracker.remove_entity(&'a');
+        assert_eq!(tracker.baseline(&"alice", &'a'), None);
+        tracker.on_ack_event(&"bob", &delivered(0));
+        assert_eq!(tracker.baseline(&"bob", &'a'), None);
+    }
+}
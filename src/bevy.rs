@@ -0,0 +1,319 @@
+//! Integration with the [Bevy](https://bevy.org) game engine
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use bevy_app::{App, First, FixedUpdate, Plugin};
+use bevy_ecs::prelude::*;
+use bevy_time::{Real, Time, TimeSystems, Virtual};
+
+use crate::{InputQueue, Instant, PredictionQueue, throttle};
+
+/// System sets in which the plugins' systems run
+#[derive(SystemSet, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NettishSystems {
+    /// Adjusts the speed of [`Time<Virtual>`] according to the [`TimeController`], in [`First`]
+    Throttle,
+    /// Applies [`ServerAck`]s to the [`Prediction`], in [`FixedUpdate`]
+    Reconcile,
+    /// Records the [`LocalInput`] for the current step in the [`Prediction`], in [`FixedUpdate`]
+    /// after [`Reconcile`](Self::Reconcile)
+    Record,
+    /// Selects each client's input for the current step in [`ServerInputs`], in [`FixedUpdate`]
+    TakeInputs,
+}
+
+/// Client-side time control and input prediction for inputs of type `I`
+///
+/// Inserts the [`TimeController`], [`Prediction<I>`](Prediction), and
+/// [`LocalInput<I>`](LocalInput) resources, and handles [`ServerAck`] messages. Each fixed step,
+/// the [`LocalInput`] is recorded, and should be sent to the server along with the rest of the
+/// [`Prediction`]. After reconciliation, game systems should re-simulate the remaining predicted
+/// inputs from the latest server state.
+pub struct ClientPlugin<I>(PhantomData<fn() -> I>);
+
+impl<I> Default for ClientPlugin<I> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<I: Default + Clone + Send + Sync + 'static> Plugin for ClientPlugin<I> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeController>()
+            .insert_resource(Prediction::<I>(PredictionQueue::new(0)))
+            .init_resource::<LocalInput<I>>()
+            .add_message::<ServerAck>()
+            .configure_sets(First, NettishSystems::Throttle.before(TimeSystems))
+            .configure_sets(
+                FixedUpdate,
+                NettishSystems::Reconcile.before(NettishSystems::Record),
+            )
+            .add_systems(
+                First,
+                (
+                    throttle_time.in_set(NettishSystems::Throttle),
+                    consume_buffer.after(TimeSystems),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    reconcile::<I>.in_set(NettishSystems::Reconcile),
+                    record_input::<I>.in_set(NettishSystems::Record),
+                ),
+            );
+    }
+}
+
+/// Controls the flow of [`Time<Virtual>`] to keep a margin of server data buffered
+///
+/// See [`throttle`] for details.
+#[derive(Resource, Debug, Copy, Clone)]
+pub struct TimeController {
+    /// Amount the simulation can progress without running out of data from the server
+    ///
+    /// Should be increased as data arrives; decreased automatically as virtual time passes.
+    pub buffer_remaining: Duration,
+    pub min_latency: Duration,
+    pub hysteresis: Duration,
+}
+
+impl Default for TimeController {
+    fn default() -> Self {
+        Self {
+            buffer_remaining: Duration::ZERO,
+            min_latency: Duration::from_millis(50),
+            hysteresis: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Inputs awaiting acknowledgement by the server
+#[derive(Resource, Debug, Clone)]
+pub struct Prediction<I>(pub PredictionQueue<I>);
+
+impl<I> Deref for Prediction<I> {
+    type Target = PredictionQueue<I>;
+    fn deref(&self) -> &PredictionQueue<I> {
+        &self.0
+    }
+}
+
+impl<I> DerefMut for Prediction<I> {
+    fn deref_mut(&mut self) -> &mut PredictionQueue<I> {
+        &mut self.0
+    }
+}
+
+/// The local player's input, recorded once per fixed step
+#[derive(Resource, Debug, Clone, Default)]
+pub struct LocalInput<I>(pub I);
+
+/// Notification that the server has incorporated inputs up to and including `sequence`
+#[derive(Message, Debug, Copy, Clone)]
+pub struct ServerAck {
+    pub sequence: u16,
+}
+
+fn throttle_time(
+    controller: Res<TimeController>,
+    real: Res<Time<Real>>,
+    mut virt: ResMut<Time<Virtual>>,
+    #[cfg(feature = "tracing")] mut regime: Local<Option<crate::ThrottleRegime>>,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        let current = crate::ThrottleRegime::new(
+            controller.buffer_remaining,
+            controller.min_latency,
+            controller.hysteresis,
+        );
+        if *regime != Some(current) {
+            tracing::debug!(
+                target: "nettish::throttle",
+                regime = ?current,
+                previous = ?*regime,
+                buffer_remaining = ?controller.buffer_remaining,
+                "throttle regime changed"
+            );
+            *regime = Some(current);
+        }
+    }
+    // Real time hasn't been updated for this frame yet, so assume it'll be similar to the last
+    let real_delta = real.delta();
+    if real_delta.is_zero() {
+        return;
+    }
+    let advance = throttle(
+        real_delta,
+        controller.buffer_remaining,
+        controller.min_latency,
+        controller.hysteresis,
+    );
+    virt.set_relative_speed_f64(advance.as_secs_f64() / real_delta.as_secs_f64());
+}
+
+fn consume_buffer(mut controller: ResMut<TimeController>, virt: Res<Time<Virtual>>) {
+    controller.buffer_remaining = controller.buffer_remaining.saturating_sub(virt.delta());
+}
+
+fn reconcile<I: Send + Sync + 'static>(
+    mut acks: MessageReader<ServerAck>,
+    mut prediction: ResMut<Prediction<I>>,
+) {
+    for ack in acks.read() {
+        prediction.reconcile(ack.sequence);
+    }
+}
+
+fn record_input<I: Clone + Send + Sync + 'static>(
+    input: Res<LocalInput<I>>,
+    mut prediction: ResMut<Prediction<I>>,
+) {
+    prediction.record(input.0.clone());
+}
+
+/// Server-side input buffering for clients identified by `C` sending inputs of type `I`
+///
+/// Inserts the [`ServerInputs<C, I>`](ServerInputs) resource, and selects each client's input
+/// for every fixed step in [`NettishSystems::TakeInputs`]. Game systems consuming inputs should
+/// run after that set.
+pub struct ServerPlugin<C, I> {
+    max: usize,
+    delay: Duration,
+    _marker: PhantomData<fn() -> (C, I)>,
+}
+
+impl<C, I> ServerPlugin<C, I> {
+    /// Buffer up to `max` inputs per client, each of which may arrive up to `delay` late
+    ///
+    /// See [`InputQueue`] for details.
+    pub fn new(max: usize, delay: Duration) -> Self {
+        Self {
+            max,
+            delay,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Hash + Eq + Clone + Send + Sync + 'static, I: Send + Sync + 'static> Plugin
+    for ServerPlugin<C, I>
+{
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ServerInputs::<C, I> {
+            queues: HashMap::new(),
+            current: HashMap::new(),
+            max: self.max,
+            delay: self.delay,
+        })
+        .add_systems(
+            FixedUpdate,
+            take_inputs::<C, I>.in_set(NettishSystems::TakeInputs),
+        );
+    }
+}
+
+/// Inputs received from each client
+#[derive(Resource)]
+pub struct ServerInputs<C, I> {
+    queues: HashMap<C, InputQueue<I>>,
+    /// Inputs for the current step
+    current: HashMap<C, I>,
+    max: usize,
+    delay: Duration,
+}
+
+impl<C: Hash + Eq + Clone, I> ServerInputs<C, I> {
+    /// Enqueue an input from `client` received at `now`
+    ///
+    /// Returns whether an old input was dropped due to overrun.
+    pub fn push(&mut self, client: C, input: I, now: Instant) -> bool {
+        self.queues
+            .entry(client)
+            .or_default()
+            .push(self.max, input, now)
+    }
+
+    /// The input from `client` for the current step, if available
+    pub fn get(&self, client: &C) -> Option<&I> {
+        self.current.get(client)
+    }
+
+    /// Forget `client`, e.g. on disconnect
+    pub fn remove(&mut self, client: &C) {
+        self.queues.remove(client);
+        self.current.remove(client);
+    }
+
+    fn take(&mut self, now: Instant) {
+        self.current.clear();
+        for (client, queue) in &mut self.queues {
+            if let Some(input) = queue.take(now, self.delay) {
+                self.current.insert(client.clone(), input);
+            }
+        }
+    }
+}
+
+fn take_inputs<C: Hash + Eq + Clone + Send + Sync + 'static, I: Send + Sync + 'static>(
+    mut inputs: ResMut<ServerInputs<C, I>>,
+    real: Res<Time<Real>>,
+) {
+    // Bevy's `Instant` is `std`'s or `web-time`'s depending on its features, so convert through
+    // the time elapsed since
+    let now = Instant::now();
+    inputs.take(real.last_update().map_or(now, |x| now - x.elapsed()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_time::{TimePlugin, TimeUpdateStrategy};
+
+    #[test]
+    fn client() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, ClientPlugin::<u32>::default()))
+            .insert_resource(TimeUpdateStrategy::FixedTimesteps(1));
+        app.world_mut().resource_mut::<LocalInput<u32>>().0 = 7;
+        for _ in 0..4 {
+            // Keep the buffer within the window where time flows normally
+            app.world_mut()
+                .resource_mut::<TimeController>()
+                .buffer_remaining = Duration::from_millis(75);
+            app.update();
+        }
+        let steps = app.world().resource::<Prediction<u32>>().iter().count();
+        assert!(steps > 0);
+        app.world_mut().write_message(ServerAck {
+            sequence: steps as u16 - 2,
+        });
+        app.update();
+        let prediction = app.world().resource::<Prediction<u32>>();
+        assert!(prediction.iter().all(|&x| x == 7));
+        assert!(prediction.iter().count() <= 2);
+    }
+
+    #[test]
+    fn server() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, ServerPlugin::<u8, u32>::new(4, Duration::ZERO)))
+            .insert_resource(TimeUpdateStrategy::FixedTimesteps(1));
+        app.update();
+        let mut inputs = app.world_mut().resource_mut::<ServerInputs<u8, u32>>();
+        inputs.push(0, 1, Instant::now());
+        inputs.push(0, 2, Instant::now());
+        app.update();
+        assert_eq!(
+            app.world().resource::<ServerInputs<u8, u32>>().get(&0),
+            Some(&1)
+        );
+    }
+}
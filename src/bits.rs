@@ -0,0 +1,204 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Packs values into a byte buffer at bit granularity
+///
+/// Game state is dominated by values that need far fewer bits than their in-memory
+/// representation: booleans, small enums, counters with known bounds, and quantized reals.
+/// Writing them with exactly as many bits as they need can shrink packets severalfold. Values are
+/// packed least significant bit first; read them back in the same order with a [`BitReader`].
+#[derive(Debug, Clone, Default)]
+pub struct BitWriter {
+    buf: Vec<u8>,
+    /// Total number of bits written
+    len: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the low `bits` bits of `value`
+    ///
+    /// Panics if `bits` exceeds 64. Higher bits of `value` are ignored.
+    pub fn write_bits(&mut self, value: u64, bits: u32) {
+        assert!(bits <= 64, "at most 64 bits can be written at once");
+        let mut value = value;
+        let mut remaining = bits;
+        while remaining > 0 {
+            let offset = (self.len % 8) as u32;
+            if offset == 0 {
+                self.buf.push(0);
+            }
+            let n = Ord::min(8 - offset, remaining);
+            let chunk = (value & ((1 << n) - 1)) as u8;
+            *self.buf.last_mut().unwrap() |= chunk << offset;
+            value = value.checked_shr(n).unwrap_or(0);
+            remaining -= n;
+            self.len += n as usize;
+        }
+    }
+
+    /// Write a single bit
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(value.into(), 1);
+    }
+
+    /// Pad with zero bits up to the next byte boundary
+    pub fn align(&mut self) {
+        self.len = self.buf.len() * 8;
+    }
+
+    /// Write `bytes` verbatim, starting at the next byte boundary
+    ///
+    /// The length is not recorded; it must be known to the reader in advance or written
+    /// separately.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.align();
+        self.buf.extend_from_slice(bytes);
+        self.len = self.buf.len() * 8;
+    }
+
+    /// Number of bits written so far
+    pub fn bit_len(&self) -> usize {
+        self.len
+    }
+
+    /// Data written so far, with the final byte zero-padded if necessary
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Obtain the packed data, with the final byte zero-padded if necessary
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Unpacks values written by a [`BitWriter`]
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    buf: &'a [u8],
+    /// Number of bits read so far
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Read a `bits`-bit unsigned integer
+    ///
+    /// Panics if `bits` exceeds 64.
+    pub fn read_bits(&mut self, bits: u32) -> Result<u64, DecodeError> {
+        assert!(bits <= 64, "at most 64 bits can be read at once");
+        if self.remaining_bits() < bits as usize {
+            return Err(DecodeError::Truncated);
+        }
+        let mut value = 0u64;
+        let mut read = 0;
+        while read < bits {
+            let offset = (self.pos % 8) as u32;
+            let n = Ord::min(8 - offset, bits - read);
+            let chunk = (self.buf[self.pos / 8] >> offset) & ((1u16 << n) - 1) as u8;
+            value |= u64::from(chunk) << read;
+            read += n;
+            self.pos += n as usize;
+        }
+        Ok(value)
+    }
+
+    /// Read a single bit
+    pub fn read_bool(&mut self) -> Result<bool, DecodeError> {
+        Ok(self.read_bits(1)? != 0)
+    }
+
+    /// Skip to the next byte boundary
+    pub fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(8);
+    }
+
+    /// Read `len` bytes, starting at the next byte boundary
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let start = self.pos.div_ceil(8);
+        let bytes = self
+            .buf
+            .get(start..start.saturating_add(len))
+            .ok_or(DecodeError::Truncated)?;
+        self.pos = (start + len) * 8;
+        Ok(bytes)
+    }
+
+    /// Number of bits that remain to be read, including any final padding
+    pub fn remaining_bits(&self) -> usize {
+        (self.buf.len() * 8).saturating_sub(self.pos)
+    }
+}
+
+/// Reasons data could not be decoded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data ended prematurely
+    Truncated,
+    /// An encoded integer was too large for its type
+    Overflow,
+    /// Data was encoded relative to a baseline that isn't available
+    UnknownBaseline,
+    /// The data is not validly encoded
+    Malformed,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DecodeError::Truncated => f.write_str("unexpected end of data"),
+            DecodeError::Overflow => f.write_str("integer overflow"),
+            DecodeError::UnknownBaseline => f.write_str("unknown delta baseline"),
+            DecodeError::Malformed => f.write_str("malformed data"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut w = BitWriter::new();
+        w.write_bool(true);
+        w.write_bits(0b101, 3);
+        w.write_bits(u64::MAX, 64);
+        w.write_bits(0x1234, 13);
+        w.write_bytes(b"hello");
+        w.write_bits(0, 0);
+        w.write_bits(0x7f, 7);
+        assert_eq!(w.bit_len(), 88 + 40 + 7, "bytes are aligned");
+        let buf = w.finish();
+        assert_eq!(buf.len(), 17);
+
+        let mut r = BitReader::new(&buf);
+        assert_eq!(r.read_bool(), Ok(true));
+        assert_eq!(r.read_bits(3), Ok(0b101));
+        assert_eq!(r.read_bits(64), Ok(u64::MAX));
+        assert_eq!(r.read_bits(13), Ok(0x1234 & 0x1fff));
+        assert_eq!(r.read_bytes(5), Ok(&b"hello"[..]));
+        assert_eq!(r.read_bits(0), Ok(0));
+        assert_eq!(r.read_bits(7), Ok(0x7f));
+        assert_eq!(r.remaining_bits(), 1);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut r = BitReader::new(&[0xff]);
+        assert_eq!(r.read_bits(9), Err(DecodeError::Truncated));
+        assert_eq!(r.read_bits(3), Ok(0b111), "failed reads consume nothing");
+        assert_eq!(r.read_bytes(1), Err(DecodeError::Truncated));
+        let mut r = BitReader::new(&[]);
+        assert_eq!(r.read_bool(), Err(DecodeError::Truncated));
+    }
+}
@@ -0,0 +1,162 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{BitReader, BitWriter, DecodeError, varint_len};
+
+impl BitWriter {
+    /// Write a set of flags, such as which fields or entities changed since a baseline
+    ///
+    /// The length is not written; the reader must already know it. Whichever of three encodings
+    /// is smallest is selected automatically, at the cost of a 2-bit header:
+    ///
+    /// - A plain bitmap, best for dense sets with no particular structure
+    /// - The distances between set flags, best for sparse sets
+    /// - The lengths of runs of equal flags, best for sets dominated by long runs
+    pub fn write_bitset(&mut self, flags: &[bool]) {
+        let set = || flags.iter().enumerate().filter(|x| *x.1).map(|x| x.0);
+        let bitmap_bits = flags.len();
+        let sparse_bits = varint_bits(set().count()) + gaps(set()).map(varint_bits).sum::<usize>();
+        let runs_bits = 1 + runs(flags).map(varint_bits).sum::<usize>();
+
+        if bitmap_bits <= sparse_bits && bitmap_bits <= runs_bits {
+            self.write_bits(BITMAP, 2);
+            for &flag in flags {
+                self.write_bool(flag);
+            }
+        } else if sparse_bits <= runs_bits {
+            self.write_bits(SPARSE, 2);
+            self.write_varint(set().count() as u64);
+            for gap in gaps(set()) {
+                self.write_varint(gap as u64);
+            }
+        } else {
+            self.write_bits(RUNS, 2);
+            self.write_bool(flags.first().copied().unwrap_or(false));
+            for run in runs(flags) {
+                self.write_varint(run as u64);
+            }
+        }
+    }
+}
+
+impl BitReader<'_> {
+    /// Read `len` flags written with [`BitWriter::write_bitset`]
+    pub fn read_bitset(&mut self, len: usize) -> Result<Vec<bool>, DecodeError> {
+        let mut flags = vec![false; len];
+        match self.read_bits(2)? {
+            BITMAP => {
+                for flag in &mut flags {
+                    *flag = self.read_bool()?;
+                }
+            }
+            SPARSE => {
+                let count = self.read_varint()?;
+                if count > len as u64 {
+                    return Err(DecodeError::Malformed);
+                }
+                let mut next = 0usize;
+                for _ in 0..count {
+                    let index = usize::try_from(self.read_varint()?)
+                        .ok()
+                        .and_then(|gap| next.checked_add(gap))
+                        .filter(|&i| i < len)
+                        .ok_or(DecodeError::Malformed)?;
+                    flags[index] = true;
+                    next = index + 1;
+                }
+            }
+            RUNS => {
+                let mut value = self.read_bool()?;
+                let mut start = 0;
+                while start < len {
+                    let end = usize::try_from(self.read_varint()?)
+                        .ok()
+                        .and_then(|run| start.checked_add(run)?.checked_add(1))
+                        .filter(|&end| end <= len)
+                        .ok_or(DecodeError::Malformed)?;
+                    flags[start..end].fill(value);
+                    value = !value;
+                    start = end;
+                }
+            }
+            _ => return Err(DecodeError::Malformed),
+        }
+        Ok(flags)
+    }
+}
+
+const BITMAP: u64 = 0;
+const SPARSE: u64 = 1;
+const RUNS: u64 = 2;
+
+fn varint_bits(x: usize) -> usize {
+    8 * varint_len(x as u64)
+}
+
+/// Number of unset flags preceding each set flag
+fn gaps(set: impl Iterator<Item = usize>) -> impl Iterator<Item = usize> {
+    let mut next = 0;
+    set.map(move |i| {
+        let gap = i - next;
+        next = i + 1;
+        gap
+    })
+}
+
+/// Length, minus one, of each run of equal flags
+fn runs(flags: &[bool]) -> impl Iterator<Item = usize> + '_ {
+    flags
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| w[0] != w[1])
+        .map(|(i, _)| i + 1)
+        .chain((!flags.is_empty()).then_some(flags.len()))
+        .scan(0, |start, end| {
+            let run = end - *start - 1;
+            *start = end;
+            Some(run)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut sparse = vec![false; 1000];
+        sparse[3] = true;
+        sparse[900] = true;
+        let mut runs = vec![true; 1000];
+        runs[100..700].fill(false);
+        let dense = (0..1000).map(|i| i % 3 == 0).collect::<Vec<_>>();
+        for (flags, max_bits) in [
+            (Vec::new(), 2),
+            (vec![true], 3),
+            (sparse, 2 + 4 * 8),
+            (runs, 2 + 1 + 3 * 16),
+            (dense, 2 + 1000),
+        ] {
+            let mut w = BitWriter::new();
+            w.write_bitset(&flags);
+            w.write_bool(true);
+            assert!(w.bit_len() <= max_bits + 1, "{} bits", w.bit_len());
+            let buf = w.finish();
+            let mut r = BitReader::new(&buf);
+            assert_eq!(r.read_bitset(flags.len()).unwrap(), flags);
+            assert_eq!(r.read_bool(), Ok(true));
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let mut flags = [false; 100];
+        flags[99] = true;
+        let mut w = BitWriter::new();
+        w.write_bitset(&flags);
+        let buf = w.finish();
+        assert_eq!(
+            BitReader::new(&buf).read_bitset(5),
+            Err(DecodeError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Use of [`renet`] connections as a [`Transport`]
+//!
+//! Applications that already manage connections with another library need only expose one of its
+//! unreliable channels as a [`Transport`] to use the rest of this crate on top of it. The adapters
+//! here borrow a renet endpoint for the duration of a frame, leaving it owned and updated by the
+//! application as usual; others can be bridged the same way.
+
+use std::{fmt, time::Duration};
+
+use renet::{ChannelConfig, ClientId, RenetClient, RenetServer, SendType};
+
+use crate::{Reliability, Transport, transport::DEFAULT_MAX_DATAGRAM_SIZE};
+
+/// Configuration of a renet channel delivering messages with `reliability`
+///
+/// Only [`Reliability::Reliable`] data benefits from renet's own retransmission and ordering.
+/// [`Reliability::Eventual`] data is repaired by the crate resending the latest value, so a
+/// channel carrying it is unreliable too, avoiding head-of-line blocking.
+pub fn renet_channel_config(channel_id: u8, reliability: Reliability) -> ChannelConfig {
+    ChannelConfig {
+        channel_id,
+        max_memory_usage_bytes: 5 * 1024 * 1024,
+        send_type: match reliability {
+            Reliability::Unreliable | Reliability::Eventual => SendType::Unreliable,
+            Reliability::Reliable => SendType::ReliableOrdered {
+                resend_time: Duration::from_millis(300),
+            },
+        },
+    }
+}
+
+/// [`Transport`] over a channel of a [`RenetClient`]
+///
+/// Messages sent on the channel are received from the server's end of the same channel, which
+/// should be unreliable for messages to behave like datagrams. Datagrams that don't fit in the
+/// channel's memory budget are dropped, as they might be by a congested network.
+#[derive(Debug)]
+pub struct RenetClientTransport<'a> {
+    client: &'a mut RenetClient,
+    channel: u8,
+}
+
+impl<'a> RenetClientTransport<'a> {
+    pub fn new(client: &'a mut RenetClient, channel: u8) -> Self {
+        Self { client, channel }
+    }
+}
+
+impl Transport for RenetClientTransport<'_> {
+    type Error = RenetError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), RenetError> {
+        if self.client.is_disconnected() {
+            return Err(RenetError::Disconnected);
+        }
+        if self.client.can_send_message(self.channel, datagram.len()) {
+            self.client.send_message(self.channel, datagram.to_vec());
+        }
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, RenetError> {
+        match self.client.receive_message(self.channel) {
+            Some(message) => Ok(Some(message.into())),
+            None if self.client.is_disconnected() => Err(RenetError::Disconnected),
+            None => Ok(None),
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        DEFAULT_MAX_DATAGRAM_SIZE
+    }
+}
+
+/// [`Transport`] over a channel to one client of a [`RenetServer`]
+///
+/// Counterpart to [`RenetClientTransport`].
+#[derive(Debug)]
+pub struct RenetServerTransport<'a> {
+    server: &'a mut RenetServer,
+    client: ClientId,
+    channel: u8,
+}
+
+impl<'a> RenetServerTransport<'a> {
+    pub fn new(server: &'a mut RenetServer, client: ClientId, channel: u8) -> Self {
+        Self {
+            server,
+            client,
+            channel,
+        }
+    }
+}
+
+impl Transport for RenetServerTransport<'_> {
+    type Error = RenetError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), RenetError> {
+        if !self.server.is_connected(self.client) {
+            return Err(RenetError::Disconnected);
+        }
+        if self
+            .server
+            .can_send_message(self.client, self.channel, datagram.len())
+        {
+            self.server
+                .send_message(self.client, self.channel, datagram.to_vec());
+        }
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, RenetError> {
+        match self.server.receive_message(self.client, self.channel) {
+            Some(message) => Ok(Some(message.into())),
+            None if !self.server.is_connected(self.client) => Err(RenetError::Disconnected),
+            None => Ok(None),
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        DEFAULT_MAX_DATAGRAM_SIZE
+    }
+}
+
+/// Errors produced by renet transports
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenetError {
+    /// The peer is no longer connected
+    Disconnected,
+}
+
+impl fmt::Display for RenetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RenetError::Disconnected => f.write_str("disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for RenetError {}
+
+#[cfg(test)]
+mod tests {
+    use renet::ConnectionConfig;
+
+    use super::*;
+
+    #[test]
+    fn exchange() {
+        let channels = vec![renet_channel_config(0, Reliability::Unreliable)];
+        let mut server = RenetServer::new(ConnectionConfig {
+            server_channels_config: channels.clone(),
+            client_channels_config: channels,
+            ..ConnectionConfig::default()
+        });
+        let mut client = server.new_local_client(7);
+        RenetClientTransport::new(&mut client, 0)
+            .send(&[1, 2])
+            .unwrap();
+        RenetServerTransport::new(&mut server, 7, 0)
+            .send(&[3])
+            .unwrap();
+        server.process_local_client(7, &mut client).unwrap();
+
+        let mut transport = RenetServerTransport::new(&mut server, 7, 0);
+        assert_eq!(transport.try_recv().unwrap(), Some(vec![1, 2]));
+        assert_eq!(transport.try_recv().unwrap(), None);
+        let mut transport = RenetClientTransport::new(&mut client, 0);
+        assert_eq!(transport.try_recv().unwrap(), Some(vec![3]));
+
+        server.disconnect_local_client(7, &mut client);
+        let mut transport = RenetServerTransport::new(&mut server, 7, 0);
+        assert_eq!(transport.send(&[4]), Err(RenetError::Disconnected));
+    }
+}
@@ -0,0 +1,338 @@
+use std::time::Duration;
+
+use crate::{Instant, TokenBucket};
+
+/// Sends a large blob reliably in the background without starving realtime traffic
+///
+/// Map downloads, asset manifests, and late-joiner snapshots are too large to send in one packet
+/// and too large to send all at once without disrupting realtime traffic on the same link. The
+/// blob is split into fixed-size chunks which are released at a bounded rate, leaving the rest of
+/// the link's capacity for other channels, and resent if not acknowledged in time.
+///
+/// Transfers are resumable: if a connection is interrupted, the [`BulkReceiver`]'s
+/// [`received_chunks`](BulkReceiver::received_chunks) can be passed to
+/// [`resume`](Self::resume) on a fresh sender to skip data that already arrived.
+#[derive(Debug, Clone)]
+pub struct BulkSender {
+    data: Vec<u8>,
+    config: BulkConfig,
+    chunks: Vec<ChunkState>,
+    /// Index from which to begin searching for a chunk to send
+    cursor: usize,
+    acked: usize,
+    rate: TokenBucket,
+}
+
+impl BulkSender {
+    pub fn new(data: Vec<u8>, mut config: BulkConfig, now: Instant) -> Self {
+        config.chunk_size = config.chunk_size.max(1);
+        let chunks = data.len().div_ceil(config.chunk_size).max(1);
+        Self {
+            data,
+            rate: TokenBucket::new(config.rate, config.burst, now),
+            config,
+            chunks: vec![ChunkState::Pending; chunks],
+            cursor: 0,
+            acked: 0,
+        }
+    }
+
+    /// Skip sending chunks that the receiver already has
+    pub fn resume(&mut self, received: impl IntoIterator<Item = u32>) {
+        for index in received {
+            self.on_ack(index);
+        }
+    }
+
+    /// Get the next chunk to transmit, if the rate limit allows
+    ///
+    /// Each returned chunk is assumed to have been sent at `now`, and will be returned again
+    /// after `retransmit_timeout` if it isn't acknowledged.
+    pub fn poll_transmit(&mut self, now: Instant) -> Option<Chunk<'_>> {
+        let n = self.chunks.len();
+        let index = (0..n)
+            .map(|i| (self.cursor + i) % n)
+            .find(|&i| match self.chunks[i] {
+                ChunkState::Pending => true,
+                ChunkState::InFlight(sent) => {
+                    now.saturating_duration_since(sent) >= self.config.retransmit_timeout
+                }
+                ChunkState::Acked => false,
+            })?;
+        let range = self.range(index);
+        if !self.rate.try_consume(range.len() as u64, now) {
+            return None;
+        }
+        self.chunks[index] = ChunkState::InFlight(now);
+        self.cursor = (index + 1) % n;
+        Some(Chunk {
+            index: index as u32,
+            data: &self.data[range],
+        })
+    }
+
+    /// Amount of time after `now` until [`poll_transmit`](Self::poll_transmit) may next yield a
+    /// chunk, assuming one is ready to send
+    pub fn delay(&mut self, now: Instant) -> Duration {
+        self.rate
+            .delay(self.config.chunk_size as u64, now)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Record that the receiver has chunk `index`
+    pub fn on_ack(&mut self, index: u32) -> TransferProgress {
+        if let Some(state) = self.chunks.get_mut(index as usize)
+            && *state != ChunkState::Acked
+        {
+            *state = ChunkState::Acked;
+            self.acked += 1;
+        }
+        self.progress()
+    }
+
+    /// Fraction of the transfer acknowledged so far
+    pub fn progress(&self) -> TransferProgress {
+        let done = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| **x == ChunkState::Acked)
+            .map(|(i, _)| self.range(i).len() as u64)
+            .sum();
+        TransferProgress {
+            done,
+            total: self.data.len() as u64,
+        }
+    }
+
+    /// Whether every chunk has been acknowledged
+    pub fn is_complete(&self) -> bool {
+        self.acked == self.chunks.len()
+    }
+
+    /// Total number of chunks in the transfer
+    pub fn chunk_count(&self) -> u32 {
+        self.chunks.len() as u32
+    }
+
+    fn range(&self, index: usize) -> std::ops::Range<usize> {
+        let start = index * self.config.chunk_size;
+        start..Ord::min(start + self.config.chunk_size, self.data.len())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ChunkState {
+    Pending,
+    InFlight(Instant),
+    Acked,
+}
+
+/// Parameters for a [`BulkSender`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct BulkConfig {
+    /// Maximum number of bytes per chunk, treated as 1 if zero
+    ///
+    /// Should leave room for headers within the path's maximum datagram size.
+    pub chunk_size: usize,
+    /// Bytes per second the transfer may use
+    pub rate: u64,
+    /// Bytes the transfer may send in a single burst; must be at least `chunk_size`
+    pub burst: u64,
+    /// How long to wait for a chunk to be acknowledged before sending it again
+    pub retransmit_timeout: Duration,
+}
+
+impl Default for BulkConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1024,
+            rate: 64 * 1024,
+            burst: 4 * 1024,
+            retransmit_timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A piece of a bulk transfer to be transmitted
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    /// Position of this chunk in the transfer
+    pub index: u32,
+    pub data: &'a [u8],
+}
+
+/// Reassembles a blob sent by a [`BulkSender`]
+#[derive(Debug, Clone)]
+pub struct BulkReceiver {
+    data: Vec<u8>,
+    chunk_size: usize,
+    received: Vec<bool>,
+    done: u64,
+}
+
+impl BulkReceiver {
+    /// Prepare to receive `len` bytes split into chunks of `chunk_size`
+    ///
+    /// Returns `None` if `chunk_size` is zero or `len` exceeds `max_len`. Both are typically
+    /// announced by the peer, so `max_len` bounds the memory a misbehaving peer can cause to be
+    /// allocated.
+    pub fn new(len: usize, chunk_size: usize, max_len: usize) -> Option<Self> {
+        if chunk_size == 0 || len > max_len {
+            return None;
+        }
+        Some(Self {
+            data: vec![0; len],
+            chunk_size,
+            received: vec![false; len.div_ceil(chunk_size).max(1)],
+            done: 0,
+        })
+    }
+
+    /// Store a received chunk
+    ///
+    /// Returns `false` if the chunk is a duplicate or inconsistent with the expected size. In
+    /// either case, it should still be acknowledged, since resending it won't help.
+    pub fn insert(&mut self, index: u32, data: &[u8]) -> bool {
+        let index = index as usize;
+        let start = index.saturating_mul(self.chunk_size);
+        let end = Ord::min(start.saturating_add(self.chunk_size), self.data.len());
+        if index >= self.received.len() || data.len() != end - start || self.received[index] {
+            return false;
+        }
+        self.data[start..end].copy_from_slice(data);
+        self.received[index] = true;
+        self.done += data.len() as u64;
+        true
+    }
+
+    /// Fraction of the transfer received so far
+    pub fn progress(&self) -> TransferProgress {
+        TransferProgress {
+            done: self.done,
+            total: self.data.len() as u64,
+        }
+    }
+
+    /// Whether every chunk has been received
+    pub fn is_complete(&self) -> bool {
+        self.received.iter().all(|&x| x)
+    }
+
+    /// Indices of chunks received so far, for resuming an interrupted transfer
+    pub fn received_chunks(&self) -> impl Iterator<Item = u32> + '_ {
+        self.received
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| **x)
+            .map(|(i, _)| i as u32)
+    }
+
+    /// Obtain the reassembled data if the transfer is complete
+    pub fn finish(self) -> Result<Vec<u8>, Self> {
+        if !self.is_complete() {
+            return Err(self);
+        }
+        Ok(self.data)
+    }
+}
+
+/// Amount of a bulk transfer completed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Bytes transferred
+    pub done: u64,
+    /// Total size of the transfer in bytes
+    pub total: u64,
+}
+
+impl TransferProgress {
+    /// Fraction of the transfer completed, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.done as f64 / self.total as f64) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> BulkConfig {
+        BulkConfig {
+            chunk_size: 4,
+            rate: 400,
+            burst: 8,
+            retransmit_timeout: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn smoke() {
+        let data = (0..10).collect::<Vec<u8>>();
+        let start = Instant::now();
+        let mut sender = BulkSender::new(data.clone(), config(), start);
+        let mut receiver = BulkReceiver::new(data.len(), 4, 1024).unwrap();
+        assert_eq!(sender.chunk_count(), 3);
+
+        let chunk = sender.poll_transmit(start).unwrap();
+        assert_eq!(chunk.index, 0);
+        assert!(receiver.insert(chunk.index, chunk.data));
+        assert_eq!(sender.on_ack(0), receiver.progress());
+        // Lose chunk 1
+        assert_eq!(sender.poll_transmit(start).unwrap().index, 1);
+        assert_eq!(sender.poll_transmit(start), None, "rate limited");
+
+        let now = start + Duration::from_millis(10);
+        let chunk = sender.poll_transmit(now).unwrap();
+        assert_eq!(chunk.index, 2);
+        assert_eq!(chunk.data, &[8, 9]);
+        assert!(receiver.insert(chunk.index, chunk.data));
+        sender.on_ack(2);
+        assert_eq!(sender.progress().done, 6);
+
+        let now = start + Duration::from_millis(100);
+        let chunk = sender.poll_transmit(now).unwrap();
+        assert_eq!(chunk.index, 1, "retransmitted");
+        assert!(receiver.insert(chunk.index, chunk.data));
+        assert!(!receiver.insert(chunk.index, &[0; 4]), "duplicate");
+        assert_eq!(sender.on_ack(1).fraction(), 1.0);
+        assert!(sender.is_complete());
+        assert_eq!(sender.poll_transmit(now), None);
+        assert_eq!(receiver.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn resume() {
+        let data = (0..16).collect::<Vec<u8>>();
+        let start = Instant::now();
+        let mut receiver = BulkReceiver::new(data.len(), 4, 1024).unwrap();
+        assert!(receiver.insert(1, &data[4..8]));
+        assert!(receiver.insert(3, &data[12..16]));
+        assert!(!receiver.insert(4, &[]), "out of bounds");
+        let receiver = receiver.finish().unwrap_err();
+
+        let mut sender = BulkSender::new(data, config(), start);
+        sender.resume(receiver.received_chunks());
+        assert_eq!(sender.progress().fraction(), 0.5);
+        assert_eq!(sender.poll_transmit(start).unwrap().index, 0);
+        assert_eq!(sender.poll_transmit(start).unwrap().index, 2);
+    }
+
+    #[test]
+    fn invalid() {
+        let config = BulkConfig {
+            chunk_size: 0,
+            ..config()
+        };
+        let sender = BulkSender::new(vec![0; 3], config, Instant::now());
+        assert_eq!(sender.chunk_count(), 3);
+
+        assert!(BulkReceiver::new(16, 0, 1024).is_none(), "zero chunk size");
+        assert!(BulkReceiver::new(2048, 4, 1024).is_none(), "too large");
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// What a single client has been told about the replicated world
+///
+/// Tracks which entities the client knows about, which are awaiting creation or destruction on
+/// it, and the tick at which each entity's state was last sent. Creation and destruction should
+/// be delivered with e.g. a [`LifecycleSender`](crate::LifecycleSender), and reported here once
+/// acknowledged. Last-sent ticks are suitable for driving a
+/// [`PriorityAccumulator`](crate::PriorityAccumulator) or choosing delta baselines, so that
+/// unchanged entities aren't resent constantly.
+///
+/// Use one cache per client.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "E: serde::Deserialize<'de> + Hash + Eq"))
+)]
+pub struct ReplicationCache<E> {
+    entities: HashMap<E, Entry>,
+}
+
+impl<E: Hash + Eq + Clone> ReplicationCache<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Synchronize with the set of entities currently `relevant` to the client
+    ///
+    /// Newly relevant entities become pending creation, and entities no longer relevant become
+    /// pending destruction.
+    pub fn update(&mut self, relevant: impl IntoIterator<Item = E>) {
+        let relevant = relevant.into_iter().collect::<HashSet<_>>();
+        for (entity, entry) in &mut self.entities {
+            if !relevant.contains(entity) {
+                entry.state = CacheState::Destroying;
+            }
+        }
+        for entity in relevant {
+            let entry = self.entities.entry(entity).or_insert(Entry {
+                state: CacheState::Creating,
+                last_sent: None,
+            });
+            if entry.state == CacheState::Destroying {
+                // The client might have already destroyed it
+                entry.state = CacheState::Creating;
+                entry.last_sent = None;
+            }
+        }
+    }
+
+    /// Record that the client acknowledged the creation of `entity`
+    pub fn created(&mut self, entity: &E) {
+        if let Some(entry) = self.entities.get_mut(entity)
+            && entry.state == CacheState::Creating
+        {
+            entry.state = CacheState::Known;
+        }
+    }
+
+    /// Record that the client acknowledged the destruction of `entity`, forgetting it
+    pub fn destroyed(&mut self, entity: &E) {
+        if self
+            .entities
+            .get(entity)
+            .is_some_and(|x| x.state == CacheState::Destroying)
+        {
+            self.entities.remove(entity);
+        }
+    }
+
+    /// Record that `entity`'s state as of `tick` was sent to the client
+    pub fn mark_sent(&mut self, entity: &E, tick: u64) {
+        if let Some(entry) = self.entities.get_mut(entity) {
+            entry.last_sent = Some(tick);
+        }
+    }
+
+    /// Tick of the most recent state of `entity` sent to the client, if any
+    pub fn last_sent(&self, entity: &E) -> Option<u64> {
+        self.entities.get(entity)?.last_sent
+    }
+
+    /// The client's view of `entity`, if it's relevant or awaiting destruction
+    pub fn state(&self, entity: &E) -> Option<CacheState> {
+        Some(self.entities.get(entity)?.state)
+    }
+
+    /// Whether the client is known to have created `entity`, so updates may be sent
+    pub fn is_known(&self, entity: &E) -> bool {
+        self.state(entity) == Some(CacheState::Known)
+    }
+
+    /// Entities that must be created on the client before updates can be sent
+    pub fn pending_creates(&self) -> impl Iterator<Item = &E> {
+        self.in_state(CacheState::Creating)
+    }
+
+    /// Entities that must be destroyed on the client
+    pub fn pending_destroys(&self) -> impl Iterator<Item = &E> {
+        self.in_state(CacheState::Destroying)
+    }
+
+    /// Entities the client is known to have
+    pub fn known(&self) -> impl Iterator<Item = &E> {
+        self.in_state(CacheState::Known)
+    }
+
+    fn in_state(&self, state: CacheState) -> impl Iterator<Item = &E> {
+        self.entities
+            .iter()
+            .filter(move |x| x.1.state == state)
+            .map(|x| x.0)
+    }
+}
+
+impl<E> Default for ReplicationCache<E> {
+    fn default() -> Self {
+        Self {
+            entities: HashMap::new(),
+        }
+    }
+}
+
+/// A client's view of an entity, according to a [`ReplicationCache`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CacheState {
+    /// Creation has not yet been acknowledged
+    Creating,
+    /// The client has the entity
+    Known,
+    /// Destruction has not yet been acknowledged
+    Destroying,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Entry {
+    state: CacheState,
+    last_sent: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut cache = ReplicationCache::new();
+        cache.update(['a', 'b']);
+        let mut creates = cache.pending_creates().copied().collect::<Vec<_>>();
+        creates.sort_unstable();
+        assert_eq!(creates, ['a', 'b']);
+        cache.created(&'a');
+        assert!(cache.is_known(&'a'));
+        assert!(!cache.is_known(&'b'));
+        cache.mark_sent(&'a', 7);
+        assert_eq!(cache.last_sent(&'a'), Some(7));
+
+        cache.update(['b']);
+        assert_eq!(cache.pending_destroys().collect::<Vec<_>>(), [&'a']);
+        cache.update(['a', 'b']);
+        assert_eq!(cache.state(&'a'), Some(CacheState::Creating));
+        assert_eq!(cache.last_sent(&'a'), None, "must be resent in full");
+
+        cache.update([]);
+        cache.destroyed(&'a');
+        assert_eq!(cache.state(&'a'), None);
+        assert_eq!(cache.state(&'b'), Some(CacheState::Destroying));
+    }
+}
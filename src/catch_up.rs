@@ -0,0 +1,127 @@
+use core::time::Duration;
+
+/// Configuration for [`CatchUp`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CatchUpConfig {
+    /// Most ticks to run in a single frame
+    ///
+    /// Spreads recovery from a hitch over several frames, so that a long hitch doesn't cause a
+    /// correspondingly long frame which itself must be caught up on.
+    pub max_ticks_per_frame: u32,
+    /// Most simulation time the server may fall behind
+    ///
+    /// Time beyond this is dropped, so the simulation permanently lags wall-clock time by that
+    /// much. Clients' [`throttle`](crate::throttle)s absorb the discontinuity by slowing down as
+    /// their buffers run low.
+    pub max_debt: Duration,
+}
+
+impl Default for CatchUpConfig {
+    fn default() -> Self {
+        Self {
+            max_ticks_per_frame: 4,
+            max_debt: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Decides how many ticks an authoritative server should run each frame to keep pace with real
+/// time
+///
+/// The server-side counterpart to [`throttle`](crate::throttle): after a hitch, such as a
+/// garbage collection pause or a slow disk access, the server owes ticks for the time lost. Small
+/// debts are repaid gradually by running extra ticks each frame, while debts too large to repay
+/// without further degrading service are forgiven by dropping simulation time.
+#[derive(Debug, Clone)]
+pub struct CatchUp {
+    config: CatchUpConfig,
+    tick_interval: Duration,
+    /// Real time elapsed but not yet simulated
+    debt: Duration,
+    /// Total real time that will never be simulated
+    dropped: Duration,
+}
+
+impl CatchUp {
+    pub fn new(config: CatchUpConfig, tick_interval: Duration) -> Self {
+        Self {
+            config,
+            tick_interval,
+            debt: Duration::ZERO,
+            dropped: Duration::ZERO,
+        }
+    }
+
+    /// Account for `elapsed` real time passing, returning the number of ticks to run now
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.debt += elapsed;
+        if let Some(excess) = self.debt.checked_sub(self.config.max_debt)
+            && !excess.is_zero()
+        {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(target: "nettish::catch_up", dropped = ?excess, "simulation time dropped");
+            self.dropped += excess;
+            self.debt = self.config.max_debt;
+        }
+        let interval = self.tick_interval.max(Duration::from_nanos(1));
+        let due = (self.debt.as_nanos() / interval.as_nanos()).min(u32::MAX.into()) as u32;
+        let ticks = due.min(self.config.max_ticks_per_frame);
+        self.debt -= interval * ticks;
+        ticks
+    }
+
+    /// [`advance`](Self::advance) by `elapsed` seconds
+    ///
+    /// Panics if `elapsed` is negative or not finite.
+    pub fn advance_secs_f64(&mut self, elapsed: f64) -> u32 {
+        self.advance(Duration::from_secs_f64(elapsed))
+    }
+
+    /// Change the tick interval, e.g. when the tickrate changes
+    pub fn set_tick_interval(&mut self, tick_interval: Duration) {
+        self.tick_interval = tick_interval;
+    }
+
+    /// Real time elapsed but not yet simulated, including any partial tick
+    pub fn debt(&self) -> Duration {
+        self.debt
+    }
+
+    /// [`debt`](Self::debt) in seconds
+    pub fn debt_secs_f64(&self) -> f64 {
+        self.debt.as_secs_f64()
+    }
+
+    /// Total real time that was dropped rather than simulated
+    pub fn dropped(&self) -> Duration {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catch_up() {
+        let ms = Duration::from_millis;
+        let mut catch_up = CatchUp::new(CatchUpConfig::default(), ms(10));
+        assert_eq!(catch_up.advance(ms(15)), 1);
+        assert_eq!(catch_up.debt(), ms(5));
+        assert_eq!(catch_up.advance(ms(5)), 1);
+
+        // Hitch of 100ms is repaid over several frames
+        assert_eq!(catch_up.advance(ms(100)), 4);
+        assert_eq!(catch_up.advance(ms(10)), 4);
+        assert_eq!(catch_up.advance(ms(10)), 4);
+        assert_eq!(catch_up.advance(ms(10)), 1);
+        assert_eq!(catch_up.dropped(), Duration::ZERO);
+
+        // Hitch of 1s is mostly forgiven
+        catch_up.advance(ms(1000));
+        assert_eq!(catch_up.dropped(), ms(750));
+        assert_eq!(catch_up.debt(), ms(210));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{PredictionQueue, seq};
+
+/// Advances a client's simulation by one time step
+///
+/// Implemented for closures taking the same arguments as [`step`](Self::step).
+pub trait PredictionStep<State, Input> {
+    /// Apply `input` to `state`, exactly as the server would
+    fn step(&mut self, state: &mut State, input: &Input);
+}
+
+impl<State, Input, F: FnMut(&mut State, &Input)> PredictionStep<State, Input> for F {
+    fn step(&mut self, state: &mut State, input: &Input) {
+        self(state, input)
+    }
+}
+
+/// Client-side prediction of locally controlled state
+///
+/// Waiting a round trip for the server to respond to each input makes controls feel sluggish, so
+/// the client applies inputs to its own copy of the state immediately. When the server reports
+/// the authoritative state resulting from some input, that state replaces the prediction, and
+/// every input the server hasn't yet incorporated is replayed on top of it. Mispredictions, e.g.
+/// due to interactions with other players, are thereby corrected without losing recent inputs.
+///
+/// Each time step, pass the local input to [`predict`](Self::predict) and send the inputs in
+/// [`queue`](Self::queue) to the server. Pass states received from the server, along with the
+/// sequence number of the latest input they incorporate, to
+/// [`on_server_state`](Self::on_server_state). Present [`current`](Self::current).
+#[derive(Debug, Clone)]
+pub struct ClientPrediction<State, Input, F> {
+    queue: PredictionQueue<Input>,
+    step: F,
+    /// Latest state received from the server
+    authoritative: State,
+    /// `authoritative` with every unacknowledged input applied
+    predicted: State,
+    /// Sequence number of the latest input incorporated into `authoritative`, if any
+    acknowledged: Option<u16>,
+}
+
+impl<State: Clone, Input, F: PredictionStep<State, Input>> ClientPrediction<State, Input, F> {
+    /// Begin predicting from `state`, using `step` to advance it
+    ///
+    /// `next_sequence_number` is passed to [`PredictionQueue::new`].
+    pub fn new(state: State, next_sequence_number: u16, step: F) -> Self {
+        Self {
+            queue: PredictionQueue::new(next_sequence_number),
+            step,
+            predicted: state.clone(),
+            authoritative: state,
+            acknowledged: None,
+        }
+    }
+
+    /// Record `input` for transmission and apply it to the predicted state
+    ///
+    /// Returns the sequence number the server should report once it has incorporated `input`.
+    /// Should be called exactly once per simulation time step.
+    pub fn predict(&mut self, input: Input) -> u16 {
+        let sequence = self.queue.next_sequence_number();
+        self.step.step(&mut self.predicted, &input);
+        self.queue.record(input);
+        sequence
+    }
+
+    /// Replace the prediction with `state` from the server, which incorporates inputs up to and
+    /// including `sequence`, then replay later inputs
+    ///
+    /// Returns `false`, ignoring `state`, if it's older than a state already received, e.g.
+    /// because it was reordered in transit.
+    pub fn on_server_state(&mut self, sequence: u16, state: State) -> bool {
+        if let Some(acknowledged) = self.acknowledged
+            && !seq::is_newer(sequence, acknowledged)
+        {
+            return false;
+        }
+        self.acknowledged = Some(sequence);
+        self.queue.reconcile(sequence);
+        self.authoritative = state;
+        self.predicted.clone_from(&self.authoritative);
+        for input in &self.queue {
+            self.step.step(&mut self.predicted, input);
+        }
+        true
+    }
+
+    /// The predicted state, reflecting every input passed to [`predict`](Self::predict)
+    pub fn current(&self) -> &State {
+        &self.predicted
+    }
+
+    /// The latest state received from the server
+    pub fn authoritative(&self) -> &State {
+        &self.authoritative
+    }
+
+    /// Inputs not yet incorporated into the [`authoritative`](Self::authoritative) state, which
+    /// should be sent to the server
+    pub fn queue(&self) -> &PredictionQueue<Input> {
+        &self.queue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let mut prediction = ClientPrediction::new(0, 0, |x: &mut i32, input: &i32| *x += input);
+        assert_eq!(prediction.predict(1), 0);
+        assert_eq!(prediction.predict(2), 1);
+        assert_eq!(prediction.predict(3), 2);
+        assert_eq!(*prediction.current(), 6);
+
+        // Confirmed
+        assert!(prediction.on_server_state(0, 1));
+        assert_eq!(*prediction.current(), 6);
+        assert_eq!(prediction.queue().iter().count(), 2);
+
+        // Mispredicted
+        assert!(prediction.on_server_state(1, 10));
+        assert_eq!(*prediction.current(), 13);
+        assert_eq!(*prediction.authoritative(), 10);
+
+        // Reordered
+        assert!(!prediction.on_server_state(0, 1));
+        assert_eq!(*prediction.current(), 13);
+    }
+}
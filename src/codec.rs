@@ -0,0 +1,148 @@
+use crate::{BitReader, BitWriter, DecodeError, Delta};
+
+/// Converts messages of type `T` to and from bytes
+///
+/// Lets game types be passed to channels without committing to any particular serialization
+/// format. Adapters for common formats are provided behind feature flags; implement this
+/// directly for anything else.
+pub trait Codec<T> {
+    type Error;
+
+    /// Append the encoding of `value` to `out`
+    fn encode(&mut self, value: &T, out: &mut Vec<u8>) -> Result<(), Self::Error>;
+    /// Decode a value from the entirety of `data`
+    fn decode(&mut self, data: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// Encodes messages with their [`Delta`] implementation, without reference to any baseline
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BitCodec;
+
+impl<T: Delta> Codec<T> for BitCodec {
+    type Error = DecodeError;
+
+    fn encode(&mut self, value: &T, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let mut w = BitWriter::new();
+        value.encode(&mut w);
+        out.extend_from_slice(&w.finish());
+        Ok(())
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<T, DecodeError> {
+        T::decode(&mut BitReader::new(data))
+    }
+}
+
+/// Encodes `serde`-compatible messages with `bincode`'s standard configuration
+#[cfg(feature = "bincode")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for Bincode {
+    type Error = BincodeError;
+
+    fn encode(&mut self, value: &T, out: &mut Vec<u8>) -> Result<(), BincodeError> {
+        bincode::serde::encode_into_std_write(value, out, bincode::config::standard())
+            .map_err(BincodeError::Encode)?;
+        Ok(())
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<T, BincodeError> {
+        bincode::serde::decode_from_slice(data, bincode::config::standard())
+            .map(|(value, _)| value)
+            .map_err(BincodeError::Decode)
+    }
+}
+
+/// Errors produced by [`Bincode`]
+#[cfg(feature = "bincode")]
+#[derive(Debug)]
+pub enum BincodeError {
+    Encode(bincode::error::EncodeError),
+    Decode(bincode::error::DecodeError),
+}
+
+#[cfg(feature = "bincode")]
+impl std::fmt::Display for BincodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            BincodeError::Encode(ref e) => write!(f, "encoding failed: {e}"),
+            BincodeError::Decode(ref e) => write!(f, "decoding failed: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl std::error::Error for BincodeError {}
+
+/// Encodes `serde`-compatible messages with `postcard`
+///
+/// Postcard's varint-based format is typically more compact than bincode's.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for Postcard {
+    type Error = postcard::Error;
+
+    fn encode(&mut self, value: &T, out: &mut Vec<u8>) -> Result<(), postcard::Error> {
+        out.extend_from_slice(&postcard::to_stdvec(value)?);
+        Ok(())
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<T, postcard::Error> {
+        postcard::from_bytes(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<T: PartialEq + std::fmt::Debug, C: Codec<T>>(mut codec: C, value: T)
+    where
+        C::Error: std::fmt::Debug,
+    {
+        let mut buf = vec![0xff];
+        codec.encode(&value, &mut buf).unwrap();
+        assert_eq!(buf[0], 0xff, "appends");
+        assert_eq!(codec.decode(&buf[1..]).unwrap(), value);
+    }
+
+    #[test]
+    fn bits() {
+        roundtrip(BitCodec, 0x1234_5678u32);
+        roundtrip(BitCodec, true);
+    }
+
+    #[cfg(any(feature = "bincode", feature = "postcard"))]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Message {
+        id: u32,
+        name: String,
+        position: [f32; 3],
+    }
+
+    #[cfg(any(feature = "bincode", feature = "postcard"))]
+    fn message() -> Message {
+        Message {
+            id: 42,
+            name: "goblin".into(),
+            position: [1.0, 2.0, 3.0],
+        }
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode() {
+        roundtrip(Bincode, message());
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard() {
+        roundtrip(Postcard, message());
+    }
+}
@@ -0,0 +1,220 @@
+use std::io;
+
+use crate::{DecodeError, read_varint, write_varint};
+
+/// General-purpose compression algorithm used by a [`Compressor`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    /// Very fast, with modest compression ratios
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// Slower, with better compression ratios, especially given a dictionary
+    ///
+    /// `level` ranges from 1 to 22; low levels are usually appropriate for realtime use.
+    #[cfg(feature = "zstd")]
+    Zstd { level: i32 },
+}
+
+/// Compresses packets or bulk transfers, such as full snapshots sent to late joiners
+///
+/// Each output begins with a one-byte header identifying how it was compressed, so compressed
+/// and uncompressed messages can be freely mixed. Small messages rarely benefit from
+/// compression; write them with [`store`](Self::store) instead. Decode with a [`Decompressor`].
+pub struct Compressor {
+    compression: Compression,
+    #[cfg(feature = "zstd")]
+    zstd: Option<zstd::bulk::Compressor<'static>>,
+}
+
+impl Compressor {
+    /// Fails if `compression`'s parameters are rejected by the underlying implementation
+    pub fn new(compression: Compression) -> io::Result<Self> {
+        Ok(Self {
+            compression,
+            #[cfg(feature = "zstd")]
+            zstd: match compression {
+                Compression::Zstd { level } => Some(zstd::bulk::Compressor::new(level)?),
+                #[allow(unreachable_patterns)]
+                _ => None,
+            },
+        })
+    }
+
+    /// Compress with zstd using a dictionary, e.g. from [`train_dictionary`]
+    ///
+    /// Dictionaries substantially improve compression of small messages with common structure.
+    /// The receiver must use the same dictionary.
+    #[cfg(feature = "zstd")]
+    pub fn with_dictionary(level: i32, dictionary: &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            compression: Compression::Zstd { level },
+            zstd: Some(zstd::bulk::Compressor::with_dictionary(level, dictionary)?),
+        })
+    }
+
+    /// Append a compressed representation of `data` to `out`
+    ///
+    /// Falls back to [`store`](Self::store) if compression doesn't reduce the size.
+    pub fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        let start = out.len();
+        if self.try_compress(data, out).is_err() || out.len() - start > data.len() {
+            out.truncate(start);
+            Self::store(data, out);
+        }
+    }
+
+    fn try_compress(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        let compressed = match self.compression {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => lz4_flex::block::compress(data),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { .. } => self
+                .zstd
+                .as_mut()
+                .ok_or(io::ErrorKind::InvalidInput)?
+                .compress(data)?,
+        };
+        out.push(self.compression.tag());
+        write_varint(out, data.len() as u64);
+        out.extend_from_slice(&compressed);
+        Ok(())
+    }
+
+    /// Append `data` to `out` without compression, for reading by a [`Decompressor`]
+    pub fn store(data: &[u8], out: &mut Vec<u8>) {
+        out.push(STORED);
+        out.extend_from_slice(data);
+    }
+}
+
+/// Decodes messages written by a [`Compressor`]
+pub struct Decompressor {
+    max_len: usize,
+    #[cfg(feature = "zstd")]
+    zstd: zstd::bulk::Decompressor<'static>,
+}
+
+impl Decompressor {
+    /// Construct a decompressor that rejects messages larger than `max_len` when decompressed
+    ///
+    /// Bounding the size protects against maliciously crafted messages consuming excessive
+    /// memory.
+    pub fn new(max_len: usize) -> io::Result<Self> {
+        Ok(Self {
+            max_len,
+            #[cfg(feature = "zstd")]
+            zstd: zstd::bulk::Decompressor::new()?,
+        })
+    }
+
+    /// Construct a decompressor for messages compressed with a zstd dictionary
+    #[cfg(feature = "zstd")]
+    pub fn with_dictionary(max_len: usize, dictionary: &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            max_len,
+            zstd: zstd::bulk::Decompressor::with_dictionary(dictionary)?,
+        })
+    }
+
+    /// Decode `data`
+    pub fn decompress(&mut self, data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let (&tag, mut data) = data.split_first().ok_or(DecodeError::Truncated)?;
+        if tag == STORED {
+            return Ok(data.to_vec());
+        }
+        let len = read_varint(&mut data)?;
+        if len > self.max_len as u64 {
+            return Err(DecodeError::Overflow);
+        }
+        let len = len as usize;
+        let result = match tag {
+            #[cfg(feature = "lz4")]
+            LZ4 => lz4_flex::block::decompress(data, len).map_err(|_| DecodeError::Malformed)?,
+            #[cfg(feature = "zstd")]
+            ZSTD => self
+                .zstd
+                .decompress(data, len)
+                .map_err(|_| DecodeError::Malformed)?,
+            _ => return Err(DecodeError::Malformed),
+        };
+        if result.len() != len {
+            return Err(DecodeError::Malformed);
+        }
+        Ok(result)
+    }
+}
+
+/// Construct a zstd dictionary of at most `max_size` bytes from representative messages
+///
+/// Useful samples include typical snapshots; a few hundred samples and a dictionary of several
+/// kilobytes are typical.
+#[cfg(feature = "zstd")]
+pub fn train_dictionary<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => LZ4,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { .. } => ZSTD,
+        }
+    }
+}
+
+const STORED: u8 = 0;
+#[cfg(feature = "lz4")]
+const LZ4: u8 = 1;
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(compression: Compression) {
+        let data = b"position 1.0 2.0 3.0; position 1.0 2.0 3.0; position 1.0 2.0 3.0".repeat(4);
+        let mut compressor = Compressor::new(compression).unwrap();
+        let mut decompressor = Decompressor::new(1024).unwrap();
+        let mut buf = Vec::new();
+        compressor.compress(&data, &mut buf);
+        assert!(buf.len() < data.len() / 2);
+        assert_eq!(decompressor.decompress(&buf), Ok(data.clone()));
+
+        let mut buf = Vec::new();
+        compressor.compress(b"tiny", &mut buf);
+        assert_eq!(buf, b"\0tiny", "stored when incompressible");
+        assert_eq!(decompressor.decompress(&buf), Ok(b"tiny".to_vec()));
+
+        let mut decompressor = Decompressor::new(16).unwrap();
+        let mut buf = Vec::new();
+        compressor.compress(&data, &mut buf);
+        assert_eq!(decompressor.decompress(&buf), Err(DecodeError::Overflow));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4() {
+        roundtrip(Compression::Lz4);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        roundtrip(Compression::Zstd { level: 3 });
+
+        let samples = (0..256u32)
+            .map(|i| format!("{{\"id\":{i},\"health\":{},\"name\":\"goblin\"}}", i % 7))
+            .collect::<Vec<_>>();
+        let dictionary = train_dictionary(&samples, 1024).unwrap();
+        let mut compressor = Compressor::with_dictionary(3, &dictionary).unwrap();
+        let mut decompressor = Decompressor::with_dictionary(1024, &dictionary).unwrap();
+        let message = b"{\"id\":1000,\"health\":3,\"name\":\"goblin\"}";
+        let mut buf = Vec::new();
+        compressor.compress(message, &mut buf);
+        assert!(buf.len() < message.len());
+        assert_eq!(decompressor.decompress(&buf), Ok(message.to_vec()));
+    }
+}
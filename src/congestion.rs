@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use crate::Instant;
+
+/// Loss- and RTT-reactive send rate controller
+///
+/// Sending at a fixed rate is fine on a healthy link, but on a constrained one it fills queues
+/// along the path, inflating latency and eventually causing loss, which further degrades the
+/// experience. This controller alternates between two modes: in [`Good`](CongestionMode::Good)
+/// mode the full configured rates are recommended, while in [`Bad`](CongestionMode::Bad) mode
+/// reduced rates are recommended until conditions have been acceptable for a recovery period.
+///
+/// To avoid oscillating on a link that can only just sustain the good rates, the recovery period
+/// doubles whenever conditions deteriorate shortly after recovering, and is gradually relaxed
+/// again while conditions remain good.
+#[derive(Debug, Clone)]
+pub struct CongestionController {
+    config: CongestionConfig,
+    mode: CongestionMode,
+    /// How long conditions must remain good in bad mode before switching back
+    recovery: Duration,
+    /// Time at which we last switched modes
+    switched: Option<Instant>,
+    /// Time at which conditions most recently became good, if they're good now
+    good_since: Option<Instant>,
+    /// Time at which `recovery` was last relaxed, or at which we entered good mode
+    relaxed: Option<Instant>,
+}
+
+impl CongestionController {
+    pub fn new(config: CongestionConfig) -> Self {
+        Self {
+            recovery: config.min_recovery,
+            config,
+            mode: CongestionMode::Good,
+            switched: None,
+            good_since: None,
+            relaxed: None,
+        }
+    }
+
+    /// Incorporate the latest link measurements
+    ///
+    /// Should be called regularly, e.g. whenever new acknowledgements are received.
+    ///
+    /// - `rtt` - Smoothed round-trip time
+    /// - `loss` - Fraction of recently sent packets that were lost, from 0 to 1
+    pub fn update(&mut self, now: Instant, rtt: Duration, loss: f32) {
+        let good = rtt <= self.config.rtt_threshold && loss <= self.config.loss_threshold;
+        if !good {
+            self.good_since = None;
+        } else if self.good_since.is_none() {
+            self.good_since = Some(now);
+        }
+
+        match self.mode {
+            CongestionMode::Good if !good => {
+                if self
+                    .switched
+                    .is_some_and(|t| now - t < self.config.stable_period)
+                {
+                    // We didn't stay in good mode for long; be more cautious next time
+                    self.recovery = Ord::min(self.recovery * 2, self.config.max_recovery);
+                }
+                self.mode = CongestionMode::Bad;
+                self.switched = Some(now);
+            }
+            CongestionMode::Good => {
+                let relaxed = *self.relaxed.get_or_insert(now);
+                if now - relaxed >= self.config.stable_period {
+                    self.recovery = Ord::max(self.recovery / 2, self.config.min_recovery);
+                    self.relaxed = Some(now);
+                }
+            }
+            CongestionMode::Bad => {
+                if self.good_since.is_some_and(|t| now - t >= self.recovery) {
+                    self.mode = CongestionMode::Good;
+                    self.switched = Some(now);
+                    self.relaxed = Some(now);
+                }
+            }
+        }
+    }
+
+    /// Current operating mode
+    pub fn mode(&self) -> CongestionMode {
+        self.mode
+    }
+
+    /// How long conditions must remain good in bad mode before switching back to good mode
+    pub fn recovery_period(&self) -> Duration {
+        self.recovery
+    }
+
+    /// Recommended number of datagrams to send per second
+    pub fn datagram_rate(&self) -> f32 {
+        match self.mode {
+            CongestionMode::Good => self.config.good.datagram_rate,
+            CongestionMode::Bad => self.config.bad.datagram_rate,
+        }
+    }
+
+    /// Recommended number of snapshots to send per second
+    pub fn snapshot_rate(&self) -> f32 {
+        match self.mode {
+            CongestionMode::Good => self.config.good.snapshot_rate,
+            CongestionMode::Bad => self.config.bad.snapshot_rate,
+        }
+    }
+}
+
+/// Operating mode of a [`CongestionController`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CongestionMode {
+    /// The link is healthy; send at full rate
+    Good,
+    /// The link is congested; send at a reduced rate
+    Bad,
+}
+
+/// Parameters for a [`CongestionController`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CongestionConfig {
+    /// Rates to use in [`CongestionMode::Good`]
+    pub good: SendRates,
+    /// Rates to use in [`CongestionMode::Bad`]
+    pub bad: SendRates,
+    /// Round-trip time above which conditions are considered bad
+    pub rtt_threshold: Duration,
+    /// Fraction of packets lost above which conditions are considered bad
+    pub loss_threshold: f32,
+    /// Shortest time conditions must remain good before leaving bad mode
+    pub min_recovery: Duration,
+    /// Longest time conditions might be required to remain good before leaving bad mode
+    pub max_recovery: Duration,
+    /// If conditions deteriorate within this period of entering good mode, the recovery period is
+    /// doubled. Each further period spent in good mode halves it.
+    pub stable_period: Duration,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            good: SendRates {
+                datagram_rate: 60.0,
+                snapshot_rate: 30.0,
+            },
+            bad: SendRates {
+                datagram_rate: 20.0,
+                snapshot_rate: 10.0,
+            },
+            rtt_threshold: Duration::from_millis(250),
+            loss_threshold: 0.05,
+            min_recovery: Duration::from_secs(1),
+            max_recovery: Duration::from_secs(60),
+            stable_period: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Send rates recommended by a [`CongestionController`] in a particular mode
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SendRates {
+    /// Datagrams per second
+    pub datagram_rate: f32,
+    /// Snapshots per second
+    pub snapshot_rate: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD_RTT: Duration = Duration::from_millis(50);
+    const BAD_RTT: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn smoke() {
+        let config = CongestionConfig::default();
+        let mut cc = CongestionController::new(config.clone());
+        let start = Instant::now();
+        cc.update(start, GOOD_RTT, 0.0);
+        assert_eq!(cc.mode(), CongestionMode::Good);
+        assert_eq!(cc.datagram_rate(), config.good.datagram_rate);
+
+        cc.update(start + Duration::from_millis(100), BAD_RTT, 0.0);
+        assert_eq!(cc.mode(), CongestionMode::Bad);
+        assert_eq!(cc.datagram_rate(), config.bad.datagram_rate);
+        assert_eq!(cc.snapshot_rate(), config.bad.snapshot_rate);
+
+        cc.update(start + Duration::from_millis(200), GOOD_RTT, 0.0);
+        assert_eq!(cc.mode(), CongestionMode::Bad, "recovery takes time");
+        cc.update(start + Duration::from_millis(200), GOOD_RTT, 0.5);
+        cc.update(start + Duration::from_millis(300), GOOD_RTT, 0.0);
+        cc.update(start + Duration::from_millis(1200), GOOD_RTT, 0.0);
+        assert_eq!(cc.mode(), CongestionMode::Bad, "loss resets recovery");
+        cc.update(start + Duration::from_millis(1300), GOOD_RTT, 0.0);
+        assert_eq!(cc.mode(), CongestionMode::Good);
+    }
+
+    #[test]
+    fn backoff() {
+        let config = CongestionConfig::default();
+        let mut cc = CongestionController::new(config.clone());
+        let mut now = Instant::now();
+        cc.update(now, BAD_RTT, 0.0);
+        assert_eq!(cc.recovery_period(), config.min_recovery);
+        for i in 1..4 {
+            // Recover, then immediately degrade again
+            cc.update(now, GOOD_RTT, 0.0);
+            now += cc.recovery_period();
+            cc.update(now, GOOD_RTT, 0.0);
+            assert_eq!(cc.mode(), CongestionMode::Good);
+            now += Duration::from_millis(100);
+            cc.update(now, BAD_RTT, 0.0);
+            assert_eq!(cc.mode(), CongestionMode::Bad);
+            assert_eq!(cc.recovery_period(), config.min_recovery * 2u32.pow(i));
+        }
+
+        // Sustained good conditions relax the recovery period
+        cc.update(now, GOOD_RTT, 0.0);
+        now += cc.recovery_period();
+        cc.update(now, GOOD_RTT, 0.0);
+        assert_eq!(cc.mode(), CongestionMode::Good);
+        now += config.stable_period;
+        cc.update(now, GOOD_RTT, 0.0);
+        assert_eq!(cc.recovery_period(), config.min_recovery * 4);
+    }
+}
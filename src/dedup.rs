@@ -0,0 +1,115 @@
+use alloc::collections::VecDeque;
+
+use crate::seq;
+
+/// Rejects duplicate and excessively old sequence numbers
+///
+/// Remembers which of the most recent `window` sequence numbers have been seen. Anything older
+/// than that can't be distinguished from a duplicate or a replay, so it's rejected too. Larger
+/// windows accept packets that are more severely delayed, at the cost of a byte of memory per
+/// sequence number.
+#[derive(Debug, Clone)]
+pub struct DuplicateFilter {
+    window: usize,
+    /// Whether each recent sequence number has been seen, ending at `latest`
+    seen: VecDeque<bool>,
+    latest: u16,
+}
+
+impl DuplicateFilter {
+    /// Construct a filter remembering the most recent `window` sequence numbers
+    ///
+    /// `window` is clamped to between 1 and half of the sequence number space, beyond which newer
+    /// and older sequence numbers can't be distinguished.
+    pub fn new(window: u16) -> Self {
+        let window = window.clamp(1, u16::MAX / 2 + 1) as usize;
+        Self {
+            window,
+            seen: VecDeque::with_capacity(window),
+            latest: 0,
+        }
+    }
+
+    /// Record `sequence` as seen
+    ///
+    /// Returns `false` if it was already seen or is too old to tell.
+    pub fn insert(&mut self, sequence: u16) -> bool {
+        if self.seen.is_empty() {
+            self.latest = sequence;
+            self.seen.push_back(true);
+            return true;
+        }
+        let diff = seq::diff(sequence, self.latest);
+        if diff > 0 {
+            for _ in 1..diff {
+                self.seen.push_back(false);
+            }
+            self.seen.push_back(true);
+            self.latest = sequence;
+            while self.seen.len() > self.window {
+                self.seen.pop_front();
+            }
+            return true;
+        }
+        match self.index(sequence) {
+            Some(index) => !core::mem::replace(&mut self.seen[index], true),
+            None => false,
+        }
+    }
+
+    /// Whether `sequence` has been seen
+    ///
+    /// Returns `false` for sequence numbers outside the window.
+    pub fn contains(&self, sequence: u16) -> bool {
+        self.index(sequence).is_some_and(|i| self.seen[i])
+    }
+
+    /// Most recent sequence number seen, if any
+    pub fn latest(&self) -> Option<u16> {
+        (!self.seen.is_empty()).then_some(self.latest)
+    }
+
+    /// Number of sequence numbers remembered
+    pub fn window(&self) -> u16 {
+        self.window as u16
+    }
+
+    fn index(&self, sequence: u16) -> Option<usize> {
+        let age = self.latest.wrapping_sub(sequence) as usize;
+        (age < self.seen.len()).then(|| self.seen.len() - 1 - age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut filter = DuplicateFilter::new(4);
+        assert_eq!(filter.latest(), None);
+        assert!(filter.insert(10));
+        assert!(!filter.insert(10));
+        assert!(filter.insert(12));
+        assert!(filter.insert(11), "late but unseen");
+        assert!(!filter.insert(11));
+        assert!(filter.insert(14));
+        assert!(!filter.insert(10), "outside window");
+        assert!(filter.contains(12));
+        assert!(!filter.contains(13));
+        assert!(filter.insert(13));
+        assert_eq!(filter.latest(), Some(14));
+    }
+
+    #[test]
+    fn wrap() {
+        let mut filter = DuplicateFilter::new(u16::MAX);
+        assert_eq!(filter.window(), 32768);
+        assert!(filter.insert(u16::MAX));
+        assert!(filter.insert(0));
+        assert!(!filter.insert(u16::MAX));
+        assert!(filter.insert(2));
+        assert!(filter.insert(1));
+        assert!(filter.contains(u16::MAX));
+    }
+}
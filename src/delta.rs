@@ -0,0 +1,410 @@
+use alloc::collections::VecDeque;
+
+use crate::{BitReader, BitWriter, DecodeError, seq};
+
+/// State that can be encoded relative to an earlier version of itself
+///
+/// Most state changes little from one tick to the next, so encoding only what differs from a
+/// version the receiver already has (a *baseline*) is far more compact than encoding it in full.
+/// Implementations are free to choose any representation, so long as `decode_delta` inverts
+/// `encode_delta` given the same baseline.
+pub trait Delta: Sized {
+    /// Encode `self` without reference to any baseline
+    fn encode(&self, w: &mut BitWriter);
+    /// Decode a value written by [`encode`](Self::encode)
+    fn decode(r: &mut BitReader<'_>) -> Result<Self, DecodeError>;
+    /// Encode `self` relative to `baseline`
+    fn encode_delta(&self, baseline: &Self, w: &mut BitWriter);
+    /// Decode a value written by [`encode_delta`](Self::encode_delta) with the same `baseline`
+    fn decode_delta(baseline: &Self, r: &mut BitReader<'_>) -> Result<Self, DecodeError>;
+}
+
+/// Write `state`, relative to `baseline` if one is available
+///
+/// `baseline` identifies a previously sent state by its tick. The tick is written so the receiver
+/// can find the same baseline; if none is available, e.g. for a newly connected client, `state`
+/// is written in full.
+pub fn encode_delta<T: Delta>(w: &mut BitWriter, state: &T, baseline: Option<(u16, &T)>) {
+    match baseline {
+        Some((tick, baseline)) => {
+            w.write_bool(true);
+            w.write_bits(tick.into(), 16);
+            state.encode_delta(baseline, w);
+        }
+        None => {
+            w.write_bool(false);
+            state.encode(w);
+        }
+    }
+}
+
+/// Read a state written with [`encode_delta`]
+///
+/// `baseline` is called to look up the baseline state for a tick, if one was used. Fails with
+/// [`DecodeError::UnknownBaseline`] if it isn't found.
+pub fn decode_delta<'a, T: Delta + 'a>(
+    r: &mut BitReader<'_>,
+    baseline: impl FnOnce(u16) -> Option<&'a T>,
+) -> Result<T, DecodeError> {
+    if !r.read_bool()? {
+        return T::decode(r);
+    }
+    let tick = r.read_bits(16)? as u16;
+    let baseline = baseline(tick).ok_or(DecodeError::UnknownBaseline)?;
+    T::decode_delta(baseline, r)
+}
+
+/// Recent states, keyed by tick, available for use as delta baselines
+///
+/// The sender keeps the states it sent to each client; the receiver keeps the states it
+/// decoded. Both must retain a state for as long as it might be used as a baseline.
+#[derive(Debug, Clone)]
+pub struct BaselineBuffer<T> {
+    capacity: usize,
+    /// States in the order they were inserted
+    states: VecDeque<(u16, T)>,
+}
+
+impl<T> BaselineBuffer<T> {
+    /// Construct a buffer retaining at most `capacity` states
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            states: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Store `state` for `tick`, evicting the oldest state if full
+    pub fn insert(&mut self, tick: u16, state: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.states.len() >= self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back((tick, state));
+    }
+
+    /// Look up the state for `tick`
+    pub fn get(&self, tick: u16) -> Option<&T> {
+        self.states.iter().rev().find(|x| x.0 == tick).map(|x| &x.1)
+    }
+
+    /// Look up the state for `tick`, if any, in the form accepted by [`encode_delta`]
+    pub fn baseline(&self, tick: Option<u16>) -> Option<(u16, &T)> {
+        let tick = tick?;
+        Some((tick, self.get(tick)?))
+    }
+
+    /// Discard states older than `tick`, which will never be used as baselines again
+    pub fn discard_before(&mut self, tick: u16) {
+        while self
+            .states
+            .front()
+            .is_some_and(|x| seq::is_newer(tick, x.0))
+        {
+            self.states.pop_front();
+        }
+    }
+
+    /// Number of states retained
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Whether no states are retained
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+/// Strategy for selecting the baseline of each state sent on a [`DeltaEncoder`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeltaMode {
+    /// Encode each state relative to the previous one sent
+    ///
+    /// Cheap and needs no acknowledgements, but every state depends on its predecessor, so this
+    /// suits reliable streams, or unreliable streams that [`reset`](DeltaEncoder::reset) on loss.
+    Temporal,
+    /// Encode each state relative to the most recent one acknowledged by the receiver
+    ///
+    /// Robust to arbitrary loss and reordering, at the cost of larger deltas as round-trip time
+    /// grows.
+    Acked,
+}
+
+/// Sending half of a stream of delta-encoded states
+///
+/// Selects a baseline for each state according to a [`DeltaMode`], and retains sent states for as
+/// long as they might be needed. Pair with a [`DeltaDecoder`] on the receiver.
+#[derive(Debug, Clone)]
+pub struct DeltaEncoder<T> {
+    mode: DeltaMode,
+    sent: BaselineBuffer<T>,
+    /// Tick of the state to encode against, if any
+    baseline: Option<u16>,
+}
+
+impl<T: Delta + Clone> DeltaEncoder<T> {
+    /// Construct an encoder retaining up to `capacity` unacknowledged states in `Acked` mode
+    pub fn new(mode: DeltaMode, capacity: usize) -> Self {
+        let capacity = match mode {
+            DeltaMode::Temporal => 1,
+            DeltaMode::Acked => capacity.max(1),
+        };
+        Self {
+            mode,
+            sent: BaselineBuffer::new(capacity),
+            baseline: None,
+        }
+    }
+
+    pub fn mode(&self) -> DeltaMode {
+        self.mode
+    }
+
+    /// Write `state`, the state as of `tick`, relative to the current baseline, if any
+    pub fn encode(&mut self, w: &mut BitWriter, tick: u16, state: T) {
+        encode_delta(w, &state, self.sent.baseline(self.baseline));
+        if self.mode == DeltaMode::Temporal {
+            self.baseline = Some(tick);
+        }
+        self.sent.insert(tick, state);
+    }
+
+    /// Note that the receiver has acknowledged the state from `tick`
+    ///
+    /// Has no effect in `Temporal` mode.
+    pub fn on_acked(&mut self, tick: u16) {
+        if self.mode != DeltaMode::Acked
+            || self.baseline.is_some_and(|x| !seq::is_newer(tick, x))
+            || self.sent.get(tick).is_none()
+        {
+            return;
+        }
+        self.baseline = Some(tick);
+        self.sent.discard_before(tick);
+    }
+
+    /// Send the next state in full, e.g. after a state sent in `Temporal` mode was lost
+    pub fn reset(&mut self) {
+        self.baseline = None;
+    }
+}
+
+/// Receiving half of a stream of delta-encoded states
+///
+/// Decodes states written by a [`DeltaEncoder`], retaining them for use as future baselines.
+/// States older than the newest already decoded are discarded, so the stream is sequenced.
+#[derive(Debug, Clone)]
+pub struct DeltaDecoder<T> {
+    received: BaselineBuffer<T>,
+    /// Tick of the newest state decoded
+    latest: Option<u16>,
+}
+
+impl<T: Delta + Clone> DeltaDecoder<T> {
+    /// Construct a decoder retaining up to `capacity` states
+    ///
+    /// `capacity` must be at least that of the corresponding [`DeltaEncoder`].
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            received: BaselineBuffer::new(capacity.max(1)),
+            latest: None,
+        }
+    }
+
+    /// Read the state as of `tick`
+    ///
+    /// Returns `None` if a newer state has already been decoded.
+    pub fn decode(&mut self, r: &mut BitReader<'_>, tick: u16) -> Result<Option<T>, DecodeError> {
+        if self.latest.is_some_and(|x| !seq::is_newer(tick, x)) {
+            return Ok(None);
+        }
+        let mut used = None;
+        let state = decode_delta(r, |t| {
+            used = Some(t);
+            self.received.get(t)
+        })?;
+        if let Some(baseline) = used {
+            // The encoder will never again use an older baseline
+            self.received.discard_before(baseline);
+        }
+        self.received.insert(tick, state.clone());
+        self.latest = Some(tick);
+        Ok(Some(state))
+    }
+}
+
+macro_rules! impl_delta_int {
+    ($($ty:ty, $signed:ty, $bits:expr;)*) => {
+        $(
+            impl Delta for $ty {
+                fn encode(&self, w: &mut BitWriter) {
+                    w.write_bits(*self as u64, $bits);
+                }
+
+                fn decode(r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+                    Ok(r.read_bits($bits)? as $ty)
+                }
+
+                fn encode_delta(&self, baseline: &Self, w: &mut BitWriter) {
+                    // Reinterpret as signed so small decreases are encoded compactly
+                    w.write_signed_varint(self.wrapping_sub(*baseline) as $signed as i64);
+                }
+
+                fn decode_delta(baseline: &Self, r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+                    Ok(baseline.wrapping_add(r.read_signed_varint()? as $ty))
+                }
+            }
+        )*
+    };
+}
+
+impl_delta_int! {
+    u8, i8, 8; u16, i16, 16; u32, i32, 32; u64, i64, 64;
+    i8, i8, 8; i16, i16, 16; i32, i32, 32; i64, i64, 64;
+}
+
+impl Delta for bool {
+    fn encode(&self, w: &mut BitWriter) {
+        w.write_bool(*self);
+    }
+
+    fn decode(r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        r.read_bool()
+    }
+
+    fn encode_delta(&self, _: &Self, w: &mut BitWriter) {
+        w.write_bool(*self);
+    }
+
+    fn decode_delta(_: &Self, r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        r.read_bool()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut sent = BaselineBuffer::new(4);
+        let mut received = BaselineBuffer::<u32>::new(4);
+
+        // No baseline yet
+        let mut w = BitWriter::new();
+        encode_delta(&mut w, &1_000_000u32, sent.baseline(None));
+        sent.insert(0, 1_000_000u32);
+        let buf = w.finish();
+        assert_eq!(buf.len(), 5);
+        let state = decode_delta(&mut BitReader::new(&buf), |t| received.get(t)).unwrap();
+        assert_eq!(state, 1_000_000);
+        received.insert(0, state);
+
+        // Baseline acknowledged
+        let mut w = BitWriter::new();
+        encode_delta(&mut w, &999_999u32, sent.baseline(Some(0)));
+        let buf = w.finish();
+        assert_eq!(buf.len(), 4, "deltas are smaller");
+        let state = decode_delta(&mut BitReader::new(&buf), |t| received.get(t)).unwrap();
+        assert_eq!(state, 999_999);
+
+        // Baseline unknown to the receiver
+        let mut w = BitWriter::new();
+        encode_delta(&mut w, &5u32, Some((7, &0)));
+        let buf = w.finish();
+        assert_eq!(
+            decode_delta(&mut BitReader::new(&buf), |t| received.get(t)),
+            Err(DecodeError::UnknownBaseline)
+        );
+    }
+
+    #[test]
+    fn buffer() {
+        let mut buffer = BaselineBuffer::new(3);
+        for tick in [u16::MAX - 1, u16::MAX, 0, 1] {
+            buffer.insert(tick, tick);
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.get(u16::MAX - 1), None);
+        assert_eq!(buffer.baseline(Some(0)), Some((0, &0)));
+        buffer.discard_before(0);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.get(u16::MAX), None);
+        assert_eq!(buffer.get(1), Some(&1));
+
+        let mut buffer = BaselineBuffer::new(0);
+        buffer.insert(0, 0);
+        buffer.insert(1, 1);
+        assert_eq!(buffer.len(), 0, "zero capacity retains nothing");
+    }
+
+    #[test]
+    fn modes() {
+        // Temporal: each state depends on the last
+        let mut encoder = DeltaEncoder::new(DeltaMode::Temporal, 8);
+        let mut decoder = DeltaDecoder::<u32>::new(8);
+        let mut packets = Vec::new();
+        for tick in 0..3 {
+            let mut w = BitWriter::new();
+            encoder.encode(&mut w, tick, 1000 + u32::from(tick));
+            packets.push(w.finish());
+        }
+        assert_eq!(
+            decoder.decode(&mut BitReader::new(&packets[0]), 0),
+            Ok(Some(1000))
+        );
+        assert_eq!(
+            decoder.decode(&mut BitReader::new(&packets[2]), 2),
+            Err(DecodeError::UnknownBaseline),
+            "predecessor was lost"
+        );
+        encoder.reset();
+        let mut w = BitWriter::new();
+        encoder.encode(&mut w, 3, 1003);
+        assert_eq!(
+            decoder.decode(&mut BitReader::new(&w.finish()), 3),
+            Ok(Some(1003))
+        );
+
+        // Acked: loss is harmless
+        let mut encoder = DeltaEncoder::new(DeltaMode::Acked, 8);
+        let mut decoder = DeltaDecoder::<u32>::new(8);
+        let mut packets = Vec::new();
+        for tick in 0..4 {
+            if tick == 2 {
+                encoder.on_acked(0);
+            }
+            let mut w = BitWriter::new();
+            encoder.encode(&mut w, tick, 1000 + u32::from(tick));
+            packets.push(w.finish());
+        }
+        assert_eq!(
+            decoder.decode(&mut BitReader::new(&packets[0]), 0),
+            Ok(Some(1000))
+        );
+        assert_eq!(
+            decoder.decode(&mut BitReader::new(&packets[3]), 3),
+            Ok(Some(1003))
+        );
+        assert_eq!(
+            decoder.decode(&mut BitReader::new(&packets[2]), 2),
+            Ok(None),
+            "stale"
+        );
+    }
+
+    #[test]
+    fn ints() {
+        let mut w = BitWriter::new();
+        (-5i8).encode_delta(&120, &mut w);
+        u64::MAX.encode_delta(&0, &mut w);
+        let buf = w.finish();
+        let mut r = BitReader::new(&buf);
+        assert_eq!(i8::decode_delta(&120, &mut r), Ok(-5));
+        assert_eq!(u64::decode_delta(&0, &mut r), Ok(u64::MAX));
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::BTreeMap, hash::Hasher};
+
+/// Cheap, deterministic hash of simulation state
+///
+/// Unlike [`std::collections::hash_map::DefaultHasher`], the output is guaranteed to be identical
+/// across platforms, processes, and versions of this crate, so checksums computed by different
+/// peers can be compared. Uses 64-bit FNV-1a.
+#[derive(Debug, Copy, Clone)]
+pub struct Checksum(u64);
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Hash a floating-point value by its bit pattern
+    ///
+    /// Deterministic simulations produce bit-identical results, so this detects even the smallest
+    /// divergence.
+    pub fn write_f32(&mut self, x: f32) {
+        self.write_u32(x.to_bits());
+    }
+
+    /// Hash a floating-point value by its bit pattern
+    pub fn write_f64(&mut self, x: f64) {
+        self.write_u64(x.to_bits());
+    }
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for Checksum {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // Fixed byte order, for consistency across platforms
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Divergence between local and remote state, detected by [`DesyncDetector`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Desync {
+    /// The tick at which the states differed
+    pub tick: u64,
+    pub local: u64,
+    pub remote: u64,
+}
+
+/// Compares state checksums computed by two peers to detect divergence
+///
+/// Prediction and determinism bugs are often invisible until their effects compound. Both peers
+/// compute a [`Checksum`] of agreed-upon state on each tick for which
+/// [`should_checksum`](Self::should_checksum) returns true, and send it to the other. Once both
+/// checksums for a tick are known, they're compared.
+#[derive(Debug, Clone)]
+pub struct DesyncDetector {
+    interval: u64,
+    /// Checksums awaiting comparison, by tick: local, then remote
+    pending: BTreeMap<u64, (Option<u64>, Option<u64>)>,
+    /// Number of intervals to retain unmatched checksums for
+    window: u64,
+    /// Most recent tick verified to match
+    verified: Option<u64>,
+}
+
+impl DesyncDetector {
+    /// Compare checksums every `interval` ticks, forgetting those unmatched after `window`
+    /// comparisons
+    pub fn new(interval: u64, window: u64) -> Self {
+        Self {
+            interval: interval.max(1),
+            pending: BTreeMap::new(),
+            window: window.max(1),
+            verified: None,
+        }
+    }
+
+    /// Whether checksums should be computed and exchanged for `tick`
+    pub fn should_checksum(&self, tick: u64) -> bool {
+        tick.is_multiple_of(self.interval)
+    }
+
+    /// Record the checksum computed locally for `tick`
+    pub fn record_local(&mut self, tick: u64, checksum: u64) -> Option<Desync> {
+        self.pending.entry(tick).or_default().0 = Some(checksum);
+        self.check(tick)
+    }
+
+    /// Record the checksum computed by the remote peer for `tick`
+    pub fn record_remote(&mut self, tick: u64, checksum: u64) -> Option<Desync> {
+        if self.verified.is_some_and(|x| x >= tick) {
+            return None;
+        }
+        self.pending.entry(tick).or_default().1 = Some(checksum);
+        self.check(tick)
+    }
+
+    /// Most recent tick at which both peers were verified to agree
+    pub fn verified(&self) -> Option<u64> {
+        self.verified
+    }
+
+    fn check(&mut self, tick: u64) -> Option<Desync> {
+        let horizon = tick.saturating_sub(self.window * self.interval);
+        self.pending = self.pending.split_off(&horizon);
+        let (Some(local), Some(remote)) = *self.pending.get(&tick)? else {
+            return None;
+        };
+        self.pending.remove(&tick);
+        if local != remote {
+            return Some(Desync {
+                tick,
+                local,
+                remote,
+            });
+        }
+        if self.verified.is_none_or(|x| x < tick) {
+            self.verified = Some(tick);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::Hash;
+
+    #[test]
+    fn checksum() {
+        let mut a = Checksum::new();
+        (1u32, "foo").hash(&mut a);
+        a.write_f32(0.5);
+        let mut b = Checksum::new();
+        (1u32, "foo").hash(&mut b);
+        b.write_f32(0.5);
+        assert_eq!(a.finish(), b.finish());
+        b.write_f32(-0.0);
+        assert_ne!(a.finish(), b.finish());
+        assert_eq!(Checksum::new().finish(), 0xcbf2_9ce4_8422_2325);
+    }
+
+    #[test]
+    fn detect() {
+        let mut detector = DesyncDetector::new(10, 4);
+        assert!(detector.should_checksum(20));
+        assert!(!detector.should_checksum(21));
+        assert_eq!(detector.record_local(10, 1), None);
+        assert_eq!(detector.record_remote(10, 1), None);
+        assert_eq!(detector.verified(), Some(10));
+        assert_eq!(detector.record_remote(20, 2), None);
+        assert_eq!(
+            detector.record_local(20, 3),
+            Some(Desync {
+                tick: 20,
+                local: 3,
+                remote: 2
+            })
+        );
+        assert_eq!(detector.verified(), Some(10));
+    }
+}
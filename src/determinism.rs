@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use crate::{Clock, Desync, Instant, VirtualClock};
+
+/// A simulation whose determinism can be verified by a [`DeterminismChecker`]
+pub trait Deterministic {
+    type Input;
+
+    /// Advance the simulation by one tick
+    fn step(&mut self, tick: u64, input: &Self::Input);
+    /// Hash the current state, e.g. with a [`Checksum`](crate::Checksum)
+    fn checksum(&mut self) -> u64;
+}
+
+/// Parameters of a [`DeterminismChecker`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DeterminismConfig {
+    /// Amount the virtual clock advances each tick
+    pub tick_interval: Duration,
+    /// Number of ticks between state comparisons
+    ///
+    /// Checksumming every tick pinpoints divergence most precisely, but may be slow for large
+    /// states.
+    pub check_interval: u64,
+}
+
+impl Default for DeterminismConfig {
+    fn default() -> Self {
+        Self {
+            tick_interval: Duration::from_secs(1) / 60,
+            check_interval: 1,
+        }
+    }
+}
+
+/// Runs two instances of a simulation side by side to verify that they remain identical
+///
+/// Lockstep and rollback networking require every peer's simulation to produce bit-identical
+/// results from identical inputs. Common culprits for violating this include iterating over
+/// hash maps with randomized hashers, reading the system clock, and uninitialized or leftover
+/// state. Running two instances in the same process exposes most of these long before they
+/// cause a desync in the field.
+///
+/// Each instance is given its own [`VirtualClock`], advanced in lockstep, so simulations that
+/// read the time see the same values.
+#[derive(Debug, Copy, Clone)]
+pub struct DeterminismChecker {
+    config: DeterminismConfig,
+}
+
+impl DeterminismChecker {
+    pub fn new(config: DeterminismConfig) -> Self {
+        Self { config }
+    }
+
+    /// Construct two instances with `spawn` from `seed`, then feed both `inputs`, comparing their
+    /// states periodically and once all inputs are consumed
+    ///
+    /// Returns the first tick at which the states differed, with the first instance's checksum as
+    /// [`Desync::local`] and the second's as [`Desync::remote`].
+    pub fn run<S: Deterministic>(
+        &self,
+        seed: u64,
+        mut spawn: impl FnMut(u64, VirtualClock) -> S,
+        inputs: impl IntoIterator<Item = S::Input>,
+    ) -> Option<Desync> {
+        let start = Instant::now();
+        let clocks = [VirtualClock::new(start), VirtualClock::new(start)];
+        let mut sims = clocks.clone().map(|clock| spawn(seed, clock));
+        let mut tick = 0;
+        for input in inputs {
+            if tick % self.config.check_interval.max(1) == 0
+                && let Some(desync) = compare(tick, &mut sims)
+            {
+                return Some(desync);
+            }
+            for (sim, clock) in sims.iter_mut().zip(&clocks) {
+                sim.step(tick, &input);
+                clock.advance(self.config.tick_interval);
+            }
+            debug_assert_eq!(clocks[0].now(), clocks[1].now());
+            tick += 1;
+        }
+        compare(tick, &mut sims)
+    }
+}
+
+/// Report a [`Desync`] if the simulations' states differ
+fn compare<S: Deterministic>(tick: u64, sims: &mut [S; 2]) -> Option<Desync> {
+    let local = sims[0].checksum();
+    let remote = sims[1].checksum();
+    (local != remote).then_some(Desync {
+        tick,
+        local,
+        remote,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashSet},
+        hash::Hasher,
+    };
+
+    use super::*;
+    use crate::Checksum;
+
+    struct Sim<T> {
+        clock: VirtualClock,
+        start: Instant,
+        keys: T,
+        state: u64,
+    }
+
+    impl<T> Deterministic for Sim<T>
+    where
+        T: Extend<u64>,
+        for<'a> &'a T: IntoIterator<Item = &'a u64>,
+    {
+        type Input = u64;
+
+        fn step(&mut self, tick: u64, input: &u64) {
+            let elapsed = self.clock.now() - self.start;
+            self.state = self.state.wrapping_mul(31) ^ input ^ elapsed.as_millis() as u64;
+            if tick >= 10 {
+                self.keys.extend([self.state]);
+            }
+            for &key in &self.keys {
+                self.state = self.state.wrapping_mul(31).wrapping_add(key);
+            }
+        }
+
+        fn checksum(&mut self) -> u64 {
+            let mut hash = Checksum::new();
+            hash.write_u64(self.state);
+            hash.finish()
+        }
+    }
+
+    fn spawn<T: Default>(seed: u64, clock: VirtualClock) -> Sim<T> {
+        Sim {
+            start: clock.now(),
+            clock,
+            keys: T::default(),
+            state: seed,
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let checker = DeterminismChecker::new(DeterminismConfig::default());
+        assert_eq!(checker.run(7, spawn::<BTreeSet<u64>>, 0..100), None);
+    }
+
+    #[test]
+    fn divergent() {
+        let checker = DeterminismChecker::new(DeterminismConfig::default());
+        // Hash set iteration order depends on a per-instance random seed
+        let desync = checker.run(7, spawn::<HashSet<u64>>, 0..100).unwrap();
+        assert!(desync.tick > 10);
+    }
+}
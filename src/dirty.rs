@@ -0,0 +1,139 @@
+use core::ops::BitOr;
+
+use crate::{BitReader, BitWriter, DecodeError};
+
+/// Set of fields of a [`NetDirty`] type, identified by declaration order
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FieldMask(pub u64);
+
+impl FieldMask {
+    pub const EMPTY: Self = Self(0);
+
+    pub fn insert(&mut self, field: u32) {
+        self.0 |= 1 << field;
+    }
+
+    pub fn contains(self, field: u32) -> bool {
+        self.0 & (1 << field) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for FieldMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Structured state whose fields can be transmitted individually
+///
+/// Rather than re-encoding a whole struct every tick, only the fields that changed since a
+/// baseline are written, preceded by a [`FieldMask`] identifying them. Changed fields are found
+/// either by comparison against the baseline with [`changed_fields`](Self::changed_fields), or by
+/// tracking writes with setters.
+///
+/// With the `derive` feature, `#[derive(NetDirty)]` implements this for structs of up to 64
+/// fields, each of which must implement [`Delta`](crate::Delta), `PartialEq`, and `Clone`. A
+/// field of type [`FieldMask`] marked `#[net_dirty(mask)]` is excluded from replication, and
+/// instead causes a `set_<field>` method to be generated for each replicated field, recording
+/// which fields were written, along with a `take_dirty` method returning and clearing that
+/// record.
+pub trait NetDirty: Sized {
+    /// Number of replicated fields
+    const FIELDS: u32;
+
+    /// Fields of `self` that differ from `baseline`
+    fn changed_fields(&self, baseline: &Self) -> FieldMask;
+
+    /// Write the fields in `mask`, each relative to the corresponding field of `baseline`
+    fn encode_fields(&self, mask: FieldMask, baseline: &Self, w: &mut BitWriter);
+
+    /// Read the fields in `mask` written by [`encode_fields`](Self::encode_fields), taking the
+    /// remainder from `baseline`
+    fn decode_fields(
+        baseline: &Self,
+        mask: FieldMask,
+        r: &mut BitReader<'_>,
+    ) -> Result<Self, DecodeError>;
+
+    /// Write the fields that differ from `baseline`, preceded by a mask identifying them
+    fn encode_changes(&self, baseline: &Self, w: &mut BitWriter) {
+        self.encode_masked(self.changed_fields(baseline), baseline, w);
+    }
+
+    /// Write the fields in `mask`, preceded by the mask itself
+    fn encode_masked(&self, mask: FieldMask, baseline: &Self, w: &mut BitWriter) {
+        w.write_bits(mask.0, Self::FIELDS);
+        self.encode_fields(mask, baseline, w);
+    }
+
+    /// Read a value written by [`encode_changes`](Self::encode_changes) or
+    /// [`encode_masked`](Self::encode_masked)
+    fn decode_changes(baseline: &Self, r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        let mask = FieldMask(r.read_bits(Self::FIELDS)?);
+        Self::decode_fields(baseline, mask, r)
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use nettish_derive::NetDirty;
+
+    #[derive(Debug, Clone, PartialEq, NetDirty)]
+    struct Player {
+        health: u8,
+        score: u32,
+        alive: bool,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, NetDirty)]
+    struct Tracked {
+        x: i32,
+        y: i32,
+        #[net_dirty(mask)]
+        dirty: FieldMask,
+    }
+
+    #[test]
+    fn compare() {
+        let baseline = Player {
+            health: 100,
+            score: 0,
+            alive: true,
+        };
+        let current = Player {
+            score: 7,
+            ..baseline.clone()
+        };
+        assert_eq!(current.changed_fields(&baseline), FieldMask(0b010));
+        let mut w = BitWriter::new();
+        current.encode_changes(&baseline, &mut w);
+        assert_eq!(w.bit_len(), 3 + 8, "only the score is written");
+        let buf = w.finish();
+        assert_eq!(
+            Player::decode_changes(&baseline, &mut BitReader::new(&buf)),
+            Ok(current)
+        );
+    }
+
+    #[test]
+    fn setters() {
+        let baseline = Tracked::default();
+        let mut current = baseline.clone();
+        current.set_y(-3);
+        let mask = current.take_dirty();
+        assert_eq!(mask, FieldMask(0b10));
+        assert!(current.take_dirty().is_empty());
+        let mut w = BitWriter::new();
+        current.encode_masked(mask, &baseline, &mut w);
+        let buf = w.finish();
+        let decoded = Tracked::decode_changes(&baseline, &mut BitReader::new(&buf)).unwrap();
+        assert_eq!(decoded.y, -3);
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use crate::Instant;
+
+/// Periodically advertises a server to clients on the local network
+///
+/// Announcements should be sent from a socket with [`UdpSocket::set_broadcast`] enabled to
+/// `255.255.255.255` or a multicast group, at a port agreed on with clients' [`LanBrowser`]s.
+/// They identify the game, so unrelated games sharing the port are ignored, and the port on which
+/// the server accepts connections, so the discovery port need not be the game's. Arbitrary
+/// metadata, such as a server name or player count, may be attached, but should be kept small
+/// enough for the announcement to fit in a single datagram.
+#[derive(Debug, Clone)]
+pub struct LanAnnouncer {
+    game_id: u64,
+    port: u16,
+    metadata: Vec<u8>,
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl LanAnnouncer {
+    /// Announce a server for `game_id` accepting connections on `port` every `interval`
+    pub fn new(game_id: u64, port: u16, interval: Duration) -> Self {
+        Self {
+            game_id,
+            port,
+            metadata: Vec::new(),
+            interval,
+            next: None,
+        }
+    }
+
+    /// Change the metadata attached to announcements, announcing it promptly
+    pub fn set_metadata(&mut self, metadata: Vec<u8>) {
+        self.metadata = metadata;
+        self.next = None;
+    }
+
+    /// Announcement to broadcast at `now`, if one is due
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if self.next.is_some_and(|next| now < next) {
+            return None;
+        }
+        self.next = Some(now + self.interval);
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.metadata.len());
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&self.game_id.to_le_bytes());
+        buf.extend_from_slice(&self.port.to_le_bytes());
+        buf.extend_from_slice(&self.metadata);
+        Some(buf)
+    }
+
+    /// Time at which [`poll`](Self::poll) will next produce an announcement
+    pub fn next_announcement(&self) -> Option<Instant> {
+        self.next
+    }
+}
+
+/// Tracks servers announced by [`LanAnnouncer`]s on the local network
+///
+/// Servers are forgotten if no announcement is received from them within a timeout, which should
+/// span several announcement intervals to tolerate loss.
+#[derive(Debug, Clone)]
+pub struct LanBrowser {
+    game_id: u64,
+    timeout: Duration,
+    servers: HashMap<SocketAddr, LanServer>,
+}
+
+impl LanBrowser {
+    /// Track servers for `game_id`, forgetting those not heard from in `timeout`
+    pub fn new(game_id: u64, timeout: Duration) -> Self {
+        Self {
+            game_id,
+            timeout,
+            servers: HashMap::new(),
+        }
+    }
+
+    /// Process a datagram received from `from` on the discovery port
+    ///
+    /// Returns the announced server, or `None` if the datagram wasn't an announcement for this
+    /// game.
+    pub fn handle(
+        &mut self,
+        now: Instant,
+        from: SocketAddr,
+        datagram: &[u8],
+    ) -> Option<&LanServer> {
+        let data = datagram.strip_prefix(MAGIC)?;
+        if data.len() < HEADER_LEN - MAGIC.len() {
+            return None;
+        }
+        let (game_id, data) = data.split_at(8);
+        let (port, metadata) = data.split_at(2);
+        if u64::from_le_bytes(game_id.try_into().unwrap()) != self.game_id {
+            return None;
+        }
+        let address = SocketAddr::new(from.ip(), u16::from_le_bytes(port.try_into().unwrap()));
+        let server = LanServer {
+            address,
+            metadata: metadata.to_vec(),
+            last_seen: now,
+        };
+        Some(self.servers.entry(address).insert_entry(server).into_mut())
+    }
+
+    /// Receive all pending datagrams from a non-blocking `socket` bound to the discovery port
+    pub fn receive(&mut self, now: Instant, socket: &UdpSocket) -> io::Result<()> {
+        // Large enough for any UDP datagram
+        let mut buf = vec![0; 65536];
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    self.handle(now, from, &buf[..len]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Servers heard from recently, in no particular order
+    pub fn servers(&mut self, now: Instant) -> impl ExactSizeIterator<Item = &LanServer> {
+        let timeout = self.timeout;
+        self.servers
+            .retain(|_, server| now.saturating_duration_since(server.last_seen) < timeout);
+        self.servers.values()
+    }
+}
+
+/// A server discovered by a [`LanBrowser`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanServer {
+    /// Address at which the server accepts connections
+    pub address: SocketAddr,
+    /// Data attached by [`LanAnnouncer::set_metadata`]
+    pub metadata: Vec<u8>,
+    /// Time at which the latest announcement was received
+    pub last_seen: Instant,
+}
+
+/// Identifies an announcement and its format version
+const MAGIC: &[u8] = b"nettish-lan\x01";
+
+/// Size of an announcement without metadata
+const HEADER_LEN: usize = MAGIC.len() + 8 + 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discover() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(1);
+        let mut announcer = LanAnnouncer::new(42, 7000, interval);
+        announcer.set_metadata(b"lobby".to_vec());
+        let announcement = announcer.poll(start).unwrap();
+        assert_eq!(announcer.poll(start + interval / 2), None);
+        assert!(announcer.poll(start + interval).is_some());
+
+        let mut browser = LanBrowser::new(42, interval * 3);
+        let from = "192.168.1.5:9000".parse().unwrap();
+        let server = browser.handle(start, from, &announcement).unwrap();
+        assert_eq!(server.address, "192.168.1.5:7000".parse().unwrap());
+        assert_eq!(server.metadata, b"lobby");
+
+        let mut foreign = LanBrowser::new(43, interval * 3);
+        assert_eq!(foreign.handle(start, from, &announcement), None);
+        assert_eq!(foreign.handle(start, from, &announcement[..10]), None);
+        assert_eq!(foreign.servers(start).len(), 0);
+
+        assert_eq!(browser.servers(start + interval * 2).len(), 1);
+        assert_eq!(browser.servers(start + interval * 3).len(), 0, "expired");
+    }
+}
@@ -0,0 +1,389 @@
+//! Replication of [`hecs`] worlds
+
+use std::{collections::HashSet, marker::PhantomData};
+
+use hecs::{ChangeTracker, Component, Entity, World};
+
+use crate::{BitReader, BitWriter, DecodeError, Delta, NetId, NetIdMap};
+
+/// Generates updates describing changes to replicated entities in a [`World`]
+///
+/// Entities are replicated once passed to [`replicate`](Self::replicate), and only components of
+/// types passed to [`register`](Self::register) are sent. Each call to
+/// [`write_changes`](Self::write_changes) describes everything that changed since the last:
+/// entities spawned and despawned, and components added, modified, and removed. Modified
+/// components are delta-encoded against their previous values.
+///
+/// Because each update builds on the last, updates must be delivered reliably and in order, e.g.
+/// with an [`OrderedReceiver`](crate::OrderedReceiver), and applied by a [`HecsClient`] with
+/// components registered in the same order.
+pub struct HecsServer {
+    ids: NetIdMap<Entity>,
+    components: Vec<Box<dyn ServerComponent>>,
+    /// Entities replicated since the last update
+    spawned: HashSet<Entity>,
+    /// Entities no longer replicated since the last update
+    forgotten: Vec<NetId>,
+}
+
+impl HecsServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replicate components of type `T`
+    pub fn register<T: Component + Delta + Clone + PartialEq>(&mut self) {
+        self.components
+            .push(Box::new(ChangeTracker::<T>::new()) as Box<dyn ServerComponent>);
+    }
+
+    /// Begin replicating `entity`, returning its ID
+    pub fn replicate(&mut self, entity: Entity) -> NetId {
+        if let Some(id) = self.ids.id(&entity) {
+            return id;
+        }
+        self.spawned.insert(entity);
+        self.ids.allocate(entity)
+    }
+
+    /// Stop replicating `entity`, despawning it on clients
+    ///
+    /// Replicated entities despawned from the world are forgotten automatically.
+    pub fn forget(&mut self, entity: Entity) {
+        let Some(id) = self.ids.id(&entity) else {
+            return;
+        };
+        self.ids.remove(id);
+        if !self.spawned.remove(&entity) {
+            self.forgotten.push(id);
+        }
+    }
+
+    /// The ID of `entity`, if replicated
+    pub fn id(&self, entity: Entity) -> Option<NetId> {
+        self.ids.id(&entity)
+    }
+
+    /// Write the changes to `world` since the last call
+    pub fn write_changes(&mut self, world: &mut World, w: &mut BitWriter) {
+        let despawned = self
+            .ids
+            .iter()
+            .filter(|&(_, &e)| !world.contains(e))
+            .map(|(id, &e)| (id, e))
+            .collect::<Vec<_>>();
+        for (id, entity) in despawned {
+            self.ids.remove(id);
+            if !self.spawned.remove(&entity) {
+                self.forgotten.push(id);
+            }
+        }
+        w.write_varint(self.forgotten.len() as u64);
+        for id in self.forgotten.drain(..) {
+            write_id(w, id);
+        }
+
+        w.write_varint(self.spawned.len() as u64);
+        for &entity in &self.spawned {
+            write_id(w, self.ids.id(&entity).unwrap());
+            for component in &self.components {
+                component.write_full(world, entity, w);
+            }
+        }
+
+        for component in &mut self.components {
+            component.write_changes(world, &self.ids, &self.spawned, w);
+        }
+        self.spawned.clear();
+    }
+
+    /// Write the complete state of every replicated entity, e.g. for a newly connected client
+    ///
+    /// Must be called immediately after [`write_changes`](Self::write_changes), without
+    /// intervening changes to `world`, so that subsequent updates apply cleanly.
+    pub fn write_snapshot(&self, world: &World, w: &mut BitWriter) {
+        w.write_varint(0);
+        let live = self
+            .ids
+            .iter()
+            .filter(|&(_, &e)| world.contains(e) && !self.spawned.contains(&e))
+            .collect::<Vec<_>>();
+        w.write_varint(live.len() as u64);
+        for (id, &entity) in live {
+            write_id(w, id);
+            for component in &self.components {
+                component.write_full(world, entity, w);
+            }
+        }
+        for _ in &self.components {
+            for _ in 0..3 {
+                w.write_varint(0);
+            }
+        }
+    }
+}
+
+impl Default for HecsServer {
+    fn default() -> Self {
+        Self {
+            ids: NetIdMap::new(),
+            components: Vec::new(),
+            spawned: HashSet::new(),
+            forgotten: Vec::new(),
+        }
+    }
+}
+
+/// Applies updates from a [`HecsServer`] to a [`World`]
+///
+/// Entities are spawned and despawned as directed, and their components kept up to date.
+/// Replicated entities must not be despawned, nor their replicated components removed, by other
+/// means.
+pub struct HecsClient {
+    ids: NetIdMap<Entity>,
+    components: Vec<Box<dyn ClientComponent>>,
+}
+
+impl HecsClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replicate components of type `T`
+    ///
+    /// Must be called in the same order as [`HecsServer::register`].
+    pub fn register<T: Component + Delta>(&mut self) {
+        self.components
+            .push(Box::new(PhantomData::<fn() -> T>) as Box<dyn ClientComponent>);
+    }
+
+    /// Apply an update written by [`HecsServer::write_changes`] or
+    /// [`HecsServer::write_snapshot`]
+    pub fn apply(&mut self, world: &mut World, r: &mut BitReader<'_>) -> Result<(), DecodeError> {
+        for _ in 0..r.read_varint()? {
+            if let Some(entity) = self.ids.remove(read_id(r)?) {
+                _ = world.despawn(entity);
+            }
+        }
+        for _ in 0..r.read_varint()? {
+            let id = read_id(r)?;
+            let entity = world.spawn(());
+            if let Some(old) = self.ids.insert(id, entity) {
+                _ = world.despawn(old);
+            }
+            for component in &self.components {
+                component.read_full(world, entity, r)?;
+            }
+        }
+        for component in &self.components {
+            component.read_changes(world, &self.ids, r)?;
+        }
+        Ok(())
+    }
+
+    /// The local entity identified by `id`
+    pub fn entity(&self, id: NetId) -> Option<Entity> {
+        self.ids.get(id).copied()
+    }
+}
+
+impl Default for HecsClient {
+    fn default() -> Self {
+        Self {
+            ids: NetIdMap::new(),
+            components: Vec::new(),
+        }
+    }
+}
+
+trait ServerComponent {
+    /// Write `entity`'s component, if any, in full
+    fn write_full(&self, world: &World, entity: Entity, w: &mut BitWriter);
+    /// Write components added, changed, and removed since the last call, except on entities in
+    /// `spawned`
+    fn write_changes(
+        &mut self,
+        world: &mut World,
+        ids: &NetIdMap<Entity>,
+        spawned: &HashSet<Entity>,
+        w: &mut BitWriter,
+    );
+}
+
+impl<T: Component + Delta + Clone + PartialEq> ServerComponent for ChangeTracker<T> {
+    fn write_full(&self, world: &World, entity: Entity, w: &mut BitWriter) {
+        match world.get::<&T>(entity) {
+            Ok(x) => {
+                w.write_bool(true);
+                x.encode(w);
+            }
+            Err(_) => w.write_bool(false),
+        }
+    }
+
+    fn write_changes(
+        &mut self,
+        world: &mut World,
+        ids: &NetIdMap<Entity>,
+        spawned: &HashSet<Entity>,
+        w: &mut BitWriter,
+    ) {
+        // Entities that were already described in full, or aren't replicated, are skipped
+        let id = |e: Entity| ids.id(&e).filter(|_| !spawned.contains(&e));
+        let mut changes = self.track(world);
+        let added = changes
+            .added()
+            .filter_map(|(e, x)| Some((id(e)?, x.clone())))
+            .collect::<Vec<_>>();
+        let changed = changes
+            .changed()
+            .filter_map(|(e, old, new)| Some((id(e)?, old, new.clone())))
+            .collect::<Vec<_>>();
+        let removed = changes
+            .removed()
+            .filter_map(|(e, _)| id(e))
+            .collect::<Vec<_>>();
+
+        w.write_varint(added.len() as u64);
+        for (id, x) in added {
+            write_id(w, id);
+            x.encode(w);
+        }
+        w.write_varint(changed.len() as u64);
+        for (id, old, new) in changed {
+            write_id(w, id);
+            new.encode_delta(&old, w);
+        }
+        w.write_varint(removed.len() as u64);
+        for id in removed {
+            write_id(w, id);
+        }
+    }
+}
+
+trait ClientComponent {
+    fn read_full(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        r: &mut BitReader<'_>,
+    ) -> Result<(), DecodeError>;
+    fn read_changes(
+        &self,
+        world: &mut World,
+        ids: &NetIdMap<Entity>,
+        r: &mut BitReader<'_>,
+    ) -> Result<(), DecodeError>;
+}
+
+impl<T: Component + Delta> ClientComponent for PhantomData<fn() -> T> {
+    fn read_full(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        r: &mut BitReader<'_>,
+    ) -> Result<(), DecodeError> {
+        if r.read_bool()? {
+            world.insert_one(entity, T::decode(r)?).unwrap();
+        }
+        Ok(())
+    }
+
+    fn read_changes(
+        &self,
+        world: &mut World,
+        ids: &NetIdMap<Entity>,
+        r: &mut BitReader<'_>,
+    ) -> Result<(), DecodeError> {
+        // Updates for unknown entities indicate a lost or misordered update, and can't be skipped
+        // since deltas can't be decoded without their baselines
+        let entity =
+            |r: &mut BitReader<'_>| ids.get(read_id(r)?).copied().ok_or(DecodeError::Malformed);
+        for _ in 0..r.read_varint()? {
+            let entity = entity(r)?;
+            world
+                .insert_one(entity, T::decode(r)?)
+                .map_err(|_| DecodeError::Malformed)?;
+        }
+        for _ in 0..r.read_varint()? {
+            let entity = entity(r)?;
+            let mut x = world
+                .get::<&mut T>(entity)
+                .map_err(|_| DecodeError::Malformed)?;
+            *x = T::decode_delta(&x, r)?;
+        }
+        for _ in 0..r.read_varint()? {
+            _ = world.remove_one::<T>(entity(r)?);
+        }
+        Ok(())
+    }
+}
+
+fn write_id(w: &mut BitWriter, id: NetId) {
+    w.write_bits(id.to_bits().into(), 32);
+}
+
+fn read_id(r: &mut BitReader<'_>) -> Result<NetId, DecodeError> {
+    Ok(NetId::from_bits(r.read_bits(32)? as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut server_world = World::new();
+        let mut server = HecsServer::new();
+        server.register::<u32>();
+        server.register::<bool>();
+        let mut client_world = World::new();
+        let mut client = HecsClient::new();
+        client.register::<u32>();
+        client.register::<bool>();
+
+        let mut sync = |server: &mut HecsServer, world: &mut World| {
+            let mut w = BitWriter::new();
+            server.write_changes(world, &mut w);
+            let buf = w.finish();
+            client
+                .apply(&mut client_world, &mut BitReader::new(&buf))
+                .unwrap();
+            let mut w = BitWriter::new();
+            server.write_snapshot(world, &mut w);
+            let buf = w.finish();
+            let mut late_world = World::new();
+            let mut late = HecsClient::new();
+            late.register::<u32>();
+            late.register::<bool>();
+            late.apply(&mut late_world, &mut BitReader::new(&buf))
+                .unwrap();
+            (
+                client_world
+                    .query_mut::<(&u32, Option<&bool>)>()
+                    .into_iter()
+                    .map(|(&x, y)| (x, y.copied()))
+                    .collect::<Vec<_>>(),
+                late_world.len(),
+            )
+        };
+
+        let a = server_world.spawn((7u32,));
+        server_world.spawn((8u32,)); // Not replicated
+        server.replicate(a);
+        assert_eq!(sync(&mut server, &mut server_world), (vec![(7, None)], 1));
+
+        *server_world.get::<&mut u32>(a).unwrap() = 9;
+        server_world.insert_one(a, true).unwrap();
+        assert_eq!(
+            sync(&mut server, &mut server_world),
+            (vec![(9, Some(true))], 1)
+        );
+
+        server_world.remove_one::<bool>(a).unwrap();
+        assert_eq!(sync(&mut server, &mut server_world), (vec![(9, None)], 1));
+
+        server_world.despawn(a).unwrap();
+        assert_eq!(sync(&mut server, &mut server_world), (vec![], 0));
+    }
+}
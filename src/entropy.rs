@@ -0,0 +1,220 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{BitReader, BitWriter, DecodeError};
+
+/// Static Huffman code for bytes
+///
+/// After delta encoding and quantization, some byte values are typically far more common than
+/// others. Entropy coding with a table trained on representative traffic exploits this skew,
+/// giving common bytes short codes at the expense of rare ones. Sender and receiver must use the
+/// same table; typically it's trained offline and shipped with the game as
+/// [`lengths`](Self::lengths).
+#[derive(Debug, Clone)]
+pub struct HuffmanTable {
+    /// Code length of each byte value
+    lengths: [u8; 256],
+    /// Bit-reversed code for each byte value, for writing LSB-first
+    codes: [u32; 256],
+    /// Number of codes of each length
+    counts: [u16; MAX_LEN as usize + 1],
+    /// Byte values ordered by code
+    symbols: Vec<u8>,
+}
+
+impl HuffmanTable {
+    /// Construct a table suited to data resembling `samples`
+    ///
+    /// Every byte value remains encodable, even if absent from `samples`.
+    pub fn train<'a>(samples: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut frequencies = [0u64; 256];
+        for sample in samples {
+            for &byte in sample {
+                frequencies[usize::from(byte)] += 1;
+            }
+        }
+        Self::from_frequencies(&frequencies)
+    }
+
+    /// Construct a table optimized for byte values occurring with relative `frequencies`
+    pub fn from_frequencies(frequencies: &[u64; 256]) -> Self {
+        let mut frequencies = frequencies.map(|x| x.max(1));
+        loop {
+            let lengths = code_lengths(&frequencies);
+            if lengths.iter().all(|&x| x <= MAX_LEN) {
+                return Self::from_lengths(lengths).unwrap();
+            }
+            // Flatten the distribution until no code is too long
+            frequencies = frequencies.map(|x| (x / 2).max(1));
+        }
+    }
+
+    /// Reconstruct a table from its [`lengths`](Self::lengths)
+    ///
+    /// Fails with [`DecodeError::Malformed`] if `lengths` do not describe a valid code.
+    pub fn from_lengths(lengths: [u8; 256]) -> Result<Self, DecodeError> {
+        let mut counts = [0u16; MAX_LEN as usize + 1];
+        for &len in &lengths {
+            if len > MAX_LEN {
+                return Err(DecodeError::Malformed);
+            }
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+        // Kraft's inequality
+        let space = (1..=MAX_LEN)
+            .map(|len| u64::from(counts[usize::from(len)]) << (MAX_LEN - len))
+            .sum::<u64>();
+        if space > 1 << MAX_LEN {
+            return Err(DecodeError::Malformed);
+        }
+
+        // Assign canonical codes: shorter codes first, ties broken by byte value
+        let mut symbols = (0..=255u8)
+            .filter(|&x| lengths[usize::from(x)] != 0)
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|&x| lengths[usize::from(x)]);
+        let mut codes = [0; 256];
+        let mut code = 0u32;
+        let mut prev_len = 0;
+        for &symbol in &symbols {
+            let len = lengths[usize::from(symbol)];
+            code <<= len - prev_len;
+            prev_len = len;
+            codes[usize::from(symbol)] = code.reverse_bits() >> (32 - u32::from(len));
+            code += 1;
+        }
+        Ok(Self {
+            lengths,
+            codes,
+            counts,
+            symbols,
+        })
+    }
+
+    /// Code length of each byte value, sufficient to reconstruct the table
+    pub fn lengths(&self) -> &[u8; 256] {
+        &self.lengths
+    }
+
+    /// Write `data`, prefixed by its length
+    ///
+    /// Panics if `data` contains a byte value with no code, which is only possible for tables
+    /// constructed with [`from_lengths`](Self::from_lengths).
+    pub fn encode(&self, data: &[u8], w: &mut BitWriter) {
+        w.write_varint(data.len() as u64);
+        for &byte in data {
+            let len = self.lengths[usize::from(byte)];
+            assert!(len != 0, "no code for {byte}");
+            w.write_bits(self.codes[usize::from(byte)].into(), len.into());
+        }
+    }
+
+    /// Read data written by [`encode`](Self::encode), if it's at most `max_len` bytes long
+    pub fn decode(&self, r: &mut BitReader<'_>, max_len: usize) -> Result<Vec<u8>, DecodeError> {
+        let len = r.read_varint()?;
+        if len > max_len as u64 {
+            return Err(DecodeError::Overflow);
+        }
+        (0..len).map(|_| self.decode_symbol(r)).collect()
+    }
+
+    fn decode_symbol(&self, r: &mut BitReader<'_>) -> Result<u8, DecodeError> {
+        // Offset of the current length's codes within `symbols`
+        let mut index = 0;
+        // First code of the current length
+        let mut first = 0;
+        let mut code = 0;
+        for &count in &self.counts[1..] {
+            code |= u32::from(r.read_bool()?);
+            let count = u32::from(count);
+            if code < first + count {
+                return Ok(self.symbols[index + (code - first) as usize]);
+            }
+            index += count as usize;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(DecodeError::Malformed)
+    }
+}
+
+/// Longest permitted code
+const MAX_LEN: u8 = 24;
+
+/// Compute optimal code lengths for each byte value
+fn code_lengths(frequencies: &[u64; 256]) -> [u8; 256] {
+    // Each node is a leaf for a byte value, or an internal node with two children
+    let mut parents = vec![0usize; 256];
+    let mut heap = frequencies
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| Reverse((x, i)))
+        .collect::<BinaryHeap<_>>();
+    while heap.len() > 1 {
+        let Reverse((a, i)) = heap.pop().unwrap();
+        let Reverse((b, j)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[i] = node;
+        parents[j] = node;
+        heap.push(Reverse((a + b, node)));
+    }
+    let root = parents.len() - 1;
+    let mut lengths = [0; 256];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        let mut node = symbol;
+        let mut depth = 0u32;
+        while node != root {
+            node = parents[node];
+            depth += 1;
+        }
+        *length = depth.min(u8::MAX.into()) as u8;
+    }
+    lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let sample = [0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0xff, 0, 2, 0, 0, 1];
+        let table = HuffmanTable::train(std::iter::repeat_n(&sample[..], 100));
+        let table = HuffmanTable::from_lengths(*table.lengths()).unwrap();
+        let mut w = BitWriter::new();
+        table.encode(&sample, &mut w);
+        assert!(w.bit_len() < 8 + sample.len() * 3, "compresses skewed data");
+        table.encode(&[0x42, 0, 0x80], &mut w);
+        let buf = w.finish();
+        let mut r = BitReader::new(&buf);
+        assert_eq!(table.decode(&mut r, 16).unwrap(), sample);
+        assert_eq!(table.decode(&mut r, 2), Err(DecodeError::Overflow));
+    }
+
+    #[test]
+    fn length_limited() {
+        // Fibonacci frequencies produce maximally unbalanced trees
+        let mut frequencies = [0; 256];
+        let (mut a, mut b) = (1u64, 1u64);
+        for x in frequencies.iter_mut().take(60) {
+            *x = a;
+            (a, b) = (b, a + b);
+        }
+        let table = HuffmanTable::from_frequencies(&frequencies);
+        assert!(table.lengths().iter().all(|&x| x > 0 && x <= MAX_LEN));
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut w = BitWriter::new();
+        table.encode(&data, &mut w);
+        let buf = w.finish();
+        assert_eq!(table.decode(&mut BitReader::new(&buf), 256).unwrap(), data);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            HuffmanTable::from_lengths([1; 256]).map(|_| ()),
+            Err(DecodeError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+use crate::{AckEvent, Instant, OrderedReceiver, StateChannel};
+
+/// A discrete gameplay event, such as an explosion or hit confirmation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickedEvent<E, T> {
+    /// The entity or system that produced the event
+    pub emitter: E,
+    /// Position in the emitter's sequence of events
+    pub sequence: u16,
+    /// Simulation tick at which the event occurred, so it can be presented at the right moment
+    pub tick: u64,
+    pub payload: T,
+}
+
+/// Sends events reliably, exactly once, and in order per emitter
+///
+/// Events are retransmitted when the packets carrying them are lost, until delivered. Ordering
+/// is only enforced among events from the same emitter, so a lost event only delays events from
+/// the same source.
+#[derive(Debug, Clone)]
+pub struct EventSender<E, T> {
+    next_sequence: HashMap<E, u16>,
+    /// Events not yet delivered
+    events: HashMap<(E, u16), (u64, T)>,
+    channel: StateChannel<(E, u16)>,
+    /// Events carried by each packet in flight
+    in_flight: HashMap<u16, Vec<(E, u16)>>,
+}
+
+impl<E: Hash + Eq + Clone, T> EventSender<E, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `payload`, emitted by `emitter` at `tick`, for transmission
+    pub fn push(&mut self, emitter: E, tick: u64, payload: T) {
+        let next = self.next_sequence.entry(emitter.clone()).or_default();
+        let key = (emitter, *next);
+        *next = next.wrapping_add(1);
+        self.events.insert(key.clone(), (tick, payload));
+        self.channel.mark_dirty(key);
+    }
+
+    /// Get the next event to write to an outgoing packet
+    ///
+    /// Report which events were written with [`on_sent`](Self::on_sent).
+    pub fn pop(&mut self) -> Option<TickedEvent<E, &T>> {
+        loop {
+            let key = self.channel.pop_dirty()?;
+            // Skip events delivered by an earlier transmission
+            if let Some(&(tick, ref payload)) = self.events.get(&key) {
+                return Some(TickedEvent {
+                    emitter: key.0,
+                    sequence: key.1,
+                    tick,
+                    payload,
+                });
+            }
+        }
+    }
+
+    /// Record that the packet with `sequence` carried the events identified by `events`
+    pub fn on_sent(&mut self, sequence: u16, events: Vec<(E, u16)>) {
+        self.in_flight.insert(sequence, events.clone());
+        self.channel.on_sent(sequence, events);
+    }
+
+    /// Process a delivery notification from the [`AckTracker`](crate::AckTracker) that assigned
+    /// the sequence numbers passed to [`on_sent`](Self::on_sent)
+    pub fn on_ack_event(&mut self, event: &AckEvent) {
+        let sequence = match *event {
+            AckEvent::Delivered(ref x) => x.sequence,
+            AckEvent::Lost(ref x) => x.sequence,
+        };
+        let events = self.in_flight.remove(&sequence);
+        if let (AckEvent::Delivered(_), Some(events)) = (event, events) {
+            for key in events {
+                self.events.remove(&key);
+                self.channel.remove(&key);
+            }
+        }
+        self.channel.on_ack_event(event);
+    }
+
+    /// Number of events not yet known to be delivered
+    pub fn undelivered(&self) -> usize {
+        self.events.len()
+    }
+}
+
+impl<E, T> Default for EventSender<E, T> {
+    fn default() -> Self {
+        Self {
+            next_sequence: HashMap::new(),
+            events: HashMap::new(),
+            channel: StateChannel::default(),
+            in_flight: HashMap::new(),
+        }
+    }
+}
+
+/// Receives events from an [`EventSender`], releasing each exactly once in per-emitter order
+#[derive(Debug, Clone)]
+pub struct EventReceiver<E, T> {
+    emitters: HashMap<E, OrderedReceiver<(u64, T)>>,
+    ready: VecDeque<TickedEvent<E, T>>,
+    window: u16,
+}
+
+impl<E: Hash + Eq + Clone, T> EventReceiver<E, T> {
+    /// Construct a receiver buffering at most `window` events per emitter ahead of a missing one
+    pub fn new(window: u16) -> Self {
+        Self {
+            emitters: HashMap::new(),
+            ready: VecDeque::new(),
+            window,
+        }
+    }
+
+    /// Accept an event received at `now`
+    ///
+    /// Returns false if it's a duplicate or too far ahead of a missing event to buffer.
+    pub fn receive(&mut self, event: TickedEvent<E, T>, now: Instant) -> bool {
+        let window = self.window;
+        let emitter = self
+            .emitters
+            .entry(event.emitter.clone())
+            .or_insert_with(|| OrderedReceiver::new(0, window));
+        if !emitter.insert(event.sequence, (event.tick, event.payload), 0, now) {
+            return false;
+        }
+        while let Some((tick, payload)) = emitter.pop(now) {
+            self.ready.push_back(TickedEvent {
+                emitter: event.emitter.clone(),
+                sequence: emitter.next_sequence().wrapping_sub(1),
+                tick,
+                payload,
+            });
+        }
+        true
+    }
+
+    /// Get the next event ready for presentation
+    pub fn poll(&mut self) -> Option<TickedEvent<E, T>> {
+        self.ready.pop_front()
+    }
+
+    /// Forget `emitter`, e.g. when it's destroyed
+    ///
+    /// Any events from it that are still held back are discarded.
+    pub fn remove_emitter(&mut self, emitter: &E) {
+        self.emitters.remove(emitter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LostPacket, PacketInfo};
+    use std::time::Duration;
+
+    fn owned<E, T: Clone>(x: TickedEvent<E, &T>) -> TickedEvent<E, T> {
+        TickedEvent {
+            emitter: x.emitter,
+            sequence: x.sequence,
+            tick: x.tick,
+            payload: x.payload.clone(),
+        }
+    }
+
+    #[test]
+    fn smoke() {
+        let now = Instant::now();
+        let mut sender = EventSender::new();
+        let mut receiver = EventReceiver::new(16);
+        sender.push("gun", 10, "bang");
+        sender.push("gun", 11, "click");
+        sender.push("bomb", 11, "boom");
+
+        // First packet carries the first event, and is lost
+        let first = owned(sender.pop().unwrap());
+        assert_eq!(first.sequence, 0);
+        assert_eq!(first.tick, 10);
+        sender.on_sent(0, vec![("gun", 0)]);
+
+        // Second packet carries the rest, and is delivered
+        let mut keys = Vec::new();
+        while let Some(event) = sender.pop() {
+            keys.push((event.emitter, event.sequence));
+            assert!(receiver.receive(owned(event), now));
+        }
+        sender.on_sent(1, keys);
+        sender.on_ack_event(&AckEvent::Delivered(PacketInfo {
+            sequence: 1,
+            bytes: 0,
+            sent: now,
+            rtt: Duration::ZERO,
+        }));
+        assert_eq!(receiver.poll().unwrap().payload, "boom");
+        assert_eq!(receiver.poll(), None, "click awaits bang");
+
+        sender.on_ack_event(&AckEvent::Lost(LostPacket {
+            sequence: 0,
+            bytes: 0,
+            sent: now,
+        }));
+        let resent = owned(sender.pop().unwrap());
+        assert_eq!(resent, first);
+        assert_eq!(sender.pop(), None);
+        assert!(receiver.receive(resent.clone(), now));
+        assert!(!receiver.receive(resent, now), "exactly once");
+        assert_eq!(receiver.poll().unwrap().payload, "bang");
+        assert_eq!(receiver.poll().unwrap().payload, "click");
+        assert_eq!(sender.undelivered(), 1);
+    }
+}
@@ -0,0 +1,277 @@
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{BitReader, BitWriter, DecodeError, Delta, PredictionQueue};
+
+/// Inputs sent by one peer to another in a peer-to-peer session
+///
+/// Carries every local input the recipient hasn't yet acknowledged, so a message may be lost
+/// without consequence as long as a later one arrives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputMessage<I> {
+    /// The sender's player
+    pub player: usize,
+    /// Frame of the first input in `inputs`
+    pub start: u32,
+    pub inputs: Vec<I>,
+    /// Number of consecutive frames, starting from 0, for which the sender holds the recipient's
+    /// inputs
+    pub ack: u32,
+    /// The next frame the sender will simulate
+    pub frame: u32,
+    /// How many frames the sender was ahead of the recipient when it last heard from it, as
+    /// measured by the sender
+    ///
+    /// Always zero in lockstep sessions, which can't get ahead.
+    pub advantage: i16,
+    /// The sender's most recent state checksum and the frame it describes, if any
+    pub checksum: Option<(u32, u64)>,
+}
+
+impl<I: Delta + PartialEq + Clone> InputMessage<I> {
+    /// Write the message, collapsing runs of repeated inputs
+    pub fn encode(&self, w: &mut BitWriter) {
+        w.write_varint(self.player as u64);
+        w.write_varint(self.start.into());
+        w.write_varint(self.ack.into());
+        w.write_varint(self.frame.into());
+        w.write_signed_varint(self.advantage.into());
+        w.write_bool(self.checksum.is_some());
+        if let Some((frame, checksum)) = self.checksum {
+            w.write_varint(frame.into());
+            w.write_bits(checksum, 64);
+        }
+        w.write_runs(&self.inputs);
+    }
+
+    /// Read a message carrying at most `max_inputs` inputs
+    pub fn decode(r: &mut BitReader<'_>, max_inputs: usize) -> Result<Self, DecodeError> {
+        Ok(Self {
+            player: r.read_varint()? as usize,
+            start: read_frame(r)?,
+            ack: read_frame(r)?,
+            frame: read_frame(r)?,
+            advantage: r
+                .read_signed_varint()?
+                .try_into()
+                .map_err(|_| DecodeError::Overflow)?,
+            checksum: match r.read_bool()? {
+                true => Some((read_frame(r)?, r.read_bits(64)?)),
+                false => None,
+            },
+            inputs: r.read_runs(max_inputs)?,
+        })
+    }
+}
+
+fn read_frame(r: &mut BitReader<'_>) -> Result<u32, DecodeError> {
+    r.read_varint()?
+        .try_into()
+        .map_err(|_| DecodeError::Overflow)
+}
+
+/// Exchanges per-frame inputs between the peers of a session
+///
+/// Each peer controls one player, identified by its index. Local inputs are retained in a
+/// [`PredictionQueue`] and resent to every remote peer until acknowledged. Inputs received from
+/// remote peers are *confirmed* once every earlier input from the same player has also arrived.
+///
+/// The first `delay` frames of every player are confirmed immediately with default inputs, so that
+/// the local input for frame `f` is collected `delay` frames early, giving it time to reach remote
+/// peers before it's needed.
+#[derive(Debug, Clone)]
+pub(crate) struct InputExchange<I> {
+    local: usize,
+    /// Local inputs not yet acknowledged by every remote peer
+    unacked: PredictionQueue<I>,
+    /// Confirmed inputs of each player
+    players: Vec<PlayerInputs<I>>,
+    /// Number of local inputs acknowledged by each player
+    acks: Vec<u32>,
+}
+
+impl<I: Clone + Default> InputExchange<I> {
+    pub(crate) fn new(players: usize, local: usize, delay: u32) -> Self {
+        assert!(local < players, "local player out of range");
+        Self {
+            local,
+            unacked: PredictionQueue::new(delay as u16),
+            players: (0..players)
+                .map(|_| PlayerInputs {
+                    start: 0,
+                    inputs: (0..delay).map(|_| I::default()).collect(),
+                })
+                .collect(),
+            acks: vec![delay; players],
+        }
+    }
+
+    /// Confirm the local input for the next frame, returning that frame
+    pub(crate) fn add_local(&mut self, input: I) -> u32 {
+        let frame = self.players[self.local].end();
+        self.players[self.local].inputs.push_back(input.clone());
+        self.unacked.record(input);
+        frame
+    }
+
+    /// Construct a message carrying unacknowledged local inputs to `player`, sent while the next
+    /// frame to simulate is `frame`
+    pub(crate) fn message(&self, player: usize, frame: u32) -> InputMessage<I> {
+        let end = self.players[self.local].end();
+        let unacked = self.unacked.iter().len() as u32;
+        let start = end - unacked;
+        let skip = self.acks[player].saturating_sub(start);
+        InputMessage {
+            player: self.local,
+            start: start + skip,
+            inputs: self.unacked.iter().skip(skip as usize).cloned().collect(),
+            ack: self.players[player].end(),
+            frame,
+            advantage: 0,
+            checksum: None,
+        }
+    }
+
+    /// Process a message from a remote peer, returning the frames newly confirmed for its player
+    pub(crate) fn receive(&mut self, msg: InputMessage<I>) -> Range<u32> {
+        if msg.player == self.local || msg.player >= self.players.len() {
+            return 0..0;
+        }
+        if msg.ack > self.acks[msg.player] {
+            self.acks[msg.player] = msg.ack.min(self.players[self.local].end());
+            let acked = (0..self.players.len())
+                .filter(|&p| p != self.local)
+                .map(|p| self.acks[p])
+                .min()
+                .unwrap_or(self.players[self.local].end());
+            let unacked_start = self.players[self.local].end() - self.unacked.iter().len() as u32;
+            if acked > unacked_start {
+                self.unacked.reconcile((acked - 1) as u16);
+            }
+        }
+
+        let player = &mut self.players[msg.player];
+        let end = player.end();
+        if msg.start > end {
+            // Gaps will be filled by a later message
+            return end..end;
+        }
+        let Some(msg_end) = u32::try_from(msg.inputs.len())
+            .ok()
+            .and_then(|len| msg.start.checked_add(len))
+        else {
+            return end..end;
+        };
+        if msg_end <= end {
+            // Old inputs are redundant
+            return end..end;
+        }
+        let known = (end - msg.start) as usize;
+        player.inputs.extend(msg.inputs.into_iter().skip(known));
+        end..msg_end
+    }
+}
+
+impl<I> InputExchange<I> {
+    pub(crate) fn players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub(crate) fn local(&self) -> usize {
+        self.local
+    }
+
+    /// The confirmed input of `player` for `frame`, if known
+    pub(crate) fn confirmed(&self, player: usize, frame: u32) -> Option<&I> {
+        self.players[player].get(frame)
+    }
+
+    /// The most recent confirmed input of `player`
+    pub(crate) fn last(&self, player: usize) -> Option<&I> {
+        self.players[player].inputs.back()
+    }
+
+    /// Number of consecutive frames for which `player`'s inputs are confirmed
+    pub(crate) fn confirmed_frames(&self, player: usize) -> u32 {
+        self.players[player].end()
+    }
+
+    /// Number of consecutive frames for which every player's inputs are confirmed
+    pub(crate) fn all_confirmed(&self) -> u32 {
+        self.players.iter().map(|p| p.end()).min().unwrap_or(0)
+    }
+
+    /// Forget confirmed inputs for frames before `frame`, retaining each player's most recent
+    pub(crate) fn discard_before(&mut self, frame: u32) {
+        for player in &mut self.players {
+            while player.start < frame && player.inputs.len() > 1 {
+                player.inputs.pop_front();
+                player.start += 1;
+            }
+        }
+    }
+}
+
+/// Contiguous confirmed inputs of a single player
+#[derive(Debug, Clone)]
+struct PlayerInputs<I> {
+    /// Frame of the first input in `inputs`
+    start: u32,
+    inputs: VecDeque<I>,
+}
+
+impl<I> PlayerInputs<I> {
+    /// The frame following the latest confirmed input
+    fn end(&self) -> u32 {
+        self.start + self.inputs.len() as u32
+    }
+
+    fn get(&self, frame: u32) -> Option<&I> {
+        self.inputs.get(frame.checked_sub(self.start)? as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let msg = InputMessage {
+            player: 1,
+            start: 70_000,
+            inputs: vec![3u8, 3, 3, 4],
+            ack: 69_990,
+            frame: 69_998,
+            advantage: -2,
+            checksum: Some((69_990, u64::MAX)),
+        };
+        let mut w = BitWriter::new();
+        msg.encode(&mut w);
+        let buf = w.finish();
+        assert_eq!(InputMessage::decode(&mut BitReader::new(&buf), 4), Ok(msg));
+        assert_eq!(
+            InputMessage::<u8>::decode(&mut BitReader::new(&buf), 3),
+            Err(DecodeError::Overflow)
+        );
+    }
+
+    #[test]
+    fn overflowing_start() {
+        let msg = InputMessage {
+            player: 1,
+            start: u32::MAX,
+            inputs: vec![1u8],
+            ack: 0,
+            frame: 0,
+            advantage: 0,
+            checksum: None,
+        };
+        let mut w = BitWriter::new();
+        msg.encode(&mut w);
+        let buf = w.finish();
+        let msg = InputMessage::decode(&mut BitReader::new(&buf), 4).unwrap();
+        let mut exchange = InputExchange::<u8>::new(2, 0, 0);
+        assert_eq!(exchange.receive(msg), 0..0);
+        assert_eq!(exchange.confirmed_frames(1), 0);
+    }
+}
@@ -0,0 +1,195 @@
+use core::{
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+    time::Duration,
+};
+
+/// A signed span of time in seconds, as a 32.32 fixed-point number
+///
+/// Floating-point results may differ between platforms, compilers, and optimization levels,
+/// which is fatal to deterministic lockstep and rollback simulations. Integer arithmetic doesn't
+/// have that problem, so simulations should measure time with `FixedTime`, e.g. from
+/// [`TickConfig::fixed_time`](crate::TickConfig::fixed_time), converting to floating point or
+/// [`Duration`] only for presentation.
+///
+/// Arithmetic panics on overflow in debug builds and wraps otherwise, like the underlying `i64`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FixedTime(i64);
+
+impl FixedTime {
+    pub const ZERO: Self = Self(0);
+    /// Number of bits of the representation that hold fractions of a second
+    pub const FRACTION_BITS: u32 = 32;
+    /// The smallest representable positive span, 2^-32 seconds
+    pub const EPSILON: Self = Self(1);
+    pub const MAX: Self = Self(i64::MAX);
+    pub const MIN: Self = Self(i64::MIN);
+
+    /// Construct from the underlying representation, in units of 2^-32 seconds
+    pub const fn from_raw(raw: i64) -> Self {
+        Self(raw)
+    }
+
+    /// The underlying representation, in units of 2^-32 seconds
+    pub const fn to_raw(self) -> i64 {
+        self.0
+    }
+
+    pub const fn from_secs(secs: i32) -> Self {
+        Self((secs as i64) << Self::FRACTION_BITS)
+    }
+
+    /// `numerator / denominator` seconds, rounded towards negative infinity
+    ///
+    /// Panics if `denominator` is zero, or if the result is outside [`MIN`](Self::MIN) to
+    /// [`MAX`](Self::MAX).
+    pub fn from_ratio(numerator: i64, denominator: i64) -> Self {
+        let numerator = i128::from(numerator) << Self::FRACTION_BITS;
+        let denominator = i128::from(denominator);
+        let mut raw = numerator / denominator;
+        if numerator % denominator != 0 && (numerator < 0) != (denominator < 0) {
+            raw -= 1;
+        }
+        Self(i64::try_from(raw).expect("ratio out of range"))
+    }
+
+    /// Whole seconds, rounded towards negative infinity
+    pub const fn whole_secs(self) -> i64 {
+        self.0 >> Self::FRACTION_BITS
+    }
+
+    /// Convert from a [`Duration`], rounding down to a multiple of [`EPSILON`](Self::EPSILON)
+    ///
+    /// Saturates at [`MAX`](Self::MAX).
+    pub fn from_duration(duration: Duration) -> Self {
+        let raw = (duration.as_nanos() << Self::FRACTION_BITS) / 1_000_000_000;
+        Self(raw.min(i64::MAX as u128) as i64)
+    }
+
+    /// Convert to a [`Duration`] for presentation, rounding down to a whole nanosecond
+    ///
+    /// Negative spans saturate to zero.
+    pub fn to_duration(self) -> Duration {
+        let nanos = (u128::try_from(self.0).unwrap_or(0) * 1_000_000_000) >> Self::FRACTION_BITS;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+
+    /// Convert to seconds for presentation
+    pub fn as_secs_f64(self) -> f64 {
+        self.0 as f64 / (1u64 << Self::FRACTION_BITS) as f64
+    }
+
+    /// Convert to seconds for presentation
+    pub fn as_secs_f32(self) -> f32 {
+        self.as_secs_f64() as f32
+    }
+
+    /// Scale `value`, e.g. a velocity in any fixed-point format, by this many seconds
+    ///
+    /// The result has the same format as `value`, rounded towards negative infinity.
+    pub fn scale(self, value: i64) -> i64 {
+        ((i128::from(self.0) * i128::from(value)) >> Self::FRACTION_BITS) as i64
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+}
+
+impl Add for FixedTime {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl AddAssign for FixedTime {
+    fn add_assign(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for FixedTime {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl SubAssign for FixedTime {
+    fn sub_assign(&mut self, other: Self) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for FixedTime {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul<i64> for FixedTime {
+    type Output = Self;
+    fn mul(self, factor: i64) -> Self {
+        Self(self.0 * factor)
+    }
+}
+
+/// Rounds towards zero
+impl Div<i64> for FixedTime {
+    type Output = Self;
+    fn div(self, divisor: i64) -> Self {
+        Self(self.0 / divisor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let half = FixedTime::from_ratio(1, 2);
+        assert_eq!(half.to_raw(), 1 << 31);
+        assert_eq!(half.to_duration(), Duration::from_millis(500));
+        assert_eq!(half.as_secs_f64(), 0.5);
+        assert_eq!(FixedTime::from_duration(Duration::from_millis(500)), half);
+        assert_eq!(half * 3, FixedTime::from_secs(1) + half);
+        assert_eq!((-half).whole_secs(), -1);
+        assert_eq!((-half).to_duration(), Duration::ZERO);
+        assert_eq!(
+            FixedTime::from_ratio(-1, 3),
+            -FixedTime::from_ratio(1, 3) - FixedTime::EPSILON
+        );
+        assert_eq!(FixedTime::from_ratio(1, -3), FixedTime::from_ratio(-1, 3));
+        assert_eq!(FixedTime::from_ratio(-1, -3), FixedTime::from_ratio(1, 3));
+        // 1.5s at 10 units per second, with 8 fractional bits
+        assert_eq!((half * 3).scale(10 << 8), 15 << 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "ratio out of range")]
+    fn ratio_overflow() {
+        FixedTime::from_ratio(i64::MAX, 1);
+    }
+}
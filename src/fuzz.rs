@@ -0,0 +1,147 @@
+use crate::{AckHeader, PredictionQueue};
+
+/// An operation on a [`PredictionQueue`], for generating sequences of operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueOp<I> {
+    /// [`record`](PredictionQueue::record) an input
+    Record(I),
+    /// [`reconcile`](PredictionQueue::reconcile) the sequence number `behind` steps before the
+    /// most recently recorded input
+    ///
+    /// Large values wrap around to sequence numbers newer than any recorded.
+    Reconcile { behind: u16 },
+}
+
+impl<I> QueueOp<I> {
+    /// Perform the operation on `queue`
+    pub fn apply(self, queue: &mut PredictionQueue<I>) {
+        match self {
+            Self::Record(input) => queue.record(input),
+            Self::Reconcile { behind } => queue.reconcile(
+                queue
+                    .next_sequence_number()
+                    .wrapping_sub(1)
+                    .wrapping_sub(behind),
+            ),
+        }
+    }
+}
+
+/// Sequence numbers within this distance of a boundary are generated preferentially
+const EDGE: u16 = 64;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls {
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::*;
+
+    /// Choose a sequence number, favoring those near where wrapping comparisons change their
+    /// result
+    fn arbitrary_sequence(u: &mut Unstructured<'_>) -> Result<u16> {
+        let offset = u.int_in_range(0..=2 * EDGE)?.wrapping_sub(EDGE);
+        Ok(match u.int_in_range(0..=3)? {
+            0 => offset,
+            1 => (u16::MAX / 2).wrapping_add(offset),
+            _ => u.arbitrary()?,
+        })
+    }
+
+    impl<'a> Arbitrary<'a> for AckHeader {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            let sequence = arbitrary_sequence(u)?;
+            Ok(Self {
+                sequence,
+                // Peers' sequence numbers are usually close to one another
+                ack: if u.arbitrary()? {
+                    sequence.wrapping_add(arbitrary_sequence(u)?)
+                } else {
+                    arbitrary_sequence(u)?
+                },
+                ack_bits: u.arbitrary()?,
+            })
+        }
+    }
+
+    impl<'a, I: Arbitrary<'a>> Arbitrary<'a> for QueueOp<I> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(if u.ratio(3, 4)? {
+                Self::Record(u.arbitrary()?)
+            } else {
+                Self::Reconcile {
+                    behind: arbitrary_sequence(u)?,
+                }
+            })
+        }
+    }
+}
+
+/// Strategy generating sequence numbers, favoring those near where wrapping comparisons change
+/// their result
+#[cfg(feature = "proptest")]
+pub fn sequence_strategy() -> impl proptest::strategy::Strategy<Value = u16> {
+    use proptest::prelude::*;
+    prop_oneof![
+        (0..=2 * EDGE).prop_map(|x| x.wrapping_sub(EDGE)),
+        (0..=2 * EDGE).prop_map(|x| (u16::MAX / 2).wrapping_add(x).wrapping_sub(EDGE)),
+        any::<u16>(),
+    ]
+}
+
+/// Strategy generating [`AckHeader`]s
+#[cfg(feature = "proptest")]
+pub fn ack_header_strategy() -> impl proptest::strategy::Strategy<Value = AckHeader> {
+    use proptest::prelude::*;
+    (sequence_strategy(), sequence_strategy(), any::<u32>()).prop_map(
+        |(sequence, ack, ack_bits)| AckHeader {
+            sequence,
+            ack,
+            ack_bits,
+        },
+    )
+}
+
+/// Strategy generating sequences of up to `max_len` [`QueueOp`]s, with inputs drawn from `input`
+#[cfg(feature = "proptest")]
+pub fn queue_ops_strategy<S: proptest::strategy::Strategy>(
+    input: S,
+    max_len: usize,
+) -> impl proptest::strategy::Strategy<Value = Vec<QueueOp<S::Value>>> {
+    use proptest::prelude::*;
+    let op = prop_oneof![
+        3 => input.prop_map(QueueOp::Record),
+        1 => sequence_strategy().prop_map(|behind| QueueOp::Reconcile { behind }),
+    ];
+    proptest::collection::vec(op, 0..=max_len)
+}
+
+#[cfg(all(test, feature = "proptest"))]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn ack_header_roundtrip(header in ack_header_strategy()) {
+            prop_assert_eq!(AckHeader::decode(&header.encode()), Some(header));
+        }
+
+        #[test]
+        fn queue_ops(ops in queue_ops_strategy(any::<u8>(), 256)) {
+            let mut queue = PredictionQueue::new(u16::MAX - 8);
+            for op in ops {
+                let before = queue.iter().count();
+                let reconcile = matches!(op, QueueOp::Reconcile { .. });
+                op.apply(&mut queue);
+                // Reconciling never adds inputs, and recording always adds exactly one
+                let after = queue.iter().count();
+                if reconcile {
+                    prop_assert!(after <= before);
+                } else {
+                    prop_assert_eq!(after, before + 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Construct a bounded queue for passing values from one thread to another
+///
+/// Intended for moving received inputs and snapshots from a network thread into the simulation
+/// thread's [`InputQueue`](crate::InputQueue)s and snapshot buffers without locking. Both ends
+/// are wait-free: neither ever blocks on, or retries because of, the other. Inputs should be sent
+/// with their time of receipt, so they can be pushed into an `InputQueue` as if received by the
+/// simulation thread directly.
+///
+/// # Panics
+///
+/// If `capacity` is zero.
+pub fn handoff<T>(capacity: usize) -> (HandoffSender<T>, HandoffReceiver<T>) {
+    assert!(capacity > 0, "capacity must be nonzero");
+    let shared = Arc::new(Shared {
+        capacity,
+        // A power of two, so that slot indices remain consistent when the indices wrap
+        slots: (0..capacity.next_power_of_two())
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        read: CachePadded(AtomicUsize::new(0)),
+        write: CachePadded(AtomicUsize::new(0)),
+    });
+    (
+        HandoffSender {
+            shared: shared.clone(),
+            write: 0,
+            read: 0,
+        },
+        HandoffReceiver {
+            shared,
+            read: 0,
+            write: 0,
+        },
+    )
+}
+
+/// Sending half of a [`handoff`] queue
+pub struct HandoffSender<T> {
+    shared: Arc<Shared<T>>,
+    /// Index of the next slot to write, owned by this half
+    write: usize,
+    /// Most recently observed read index, to avoid contending for it when not nearly full
+    read: usize,
+}
+
+impl<T> HandoffSender<T> {
+    /// Enqueue `value`, or return it if the queue is full
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let capacity = self.shared.capacity;
+        if self.write.wrapping_sub(self.read) == capacity {
+            self.read = self.shared.read.load(Ordering::Acquire);
+            if self.write.wrapping_sub(self.read) == capacity {
+                return Err(value);
+            }
+        }
+        // SAFETY: The slot is not between the read and write indices, so the receiver won't
+        // access it until after the write index is advanced below
+        unsafe {
+            (*self.shared.slot(self.write).get()).write(value);
+        }
+        self.write = self.write.wrapping_add(1);
+        self.shared.write.store(self.write, Ordering::Release);
+        Ok(())
+    }
+
+    /// Whether the receiver has been dropped
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+}
+
+/// Receiving half of a [`handoff`] queue
+pub struct HandoffReceiver<T> {
+    shared: Arc<Shared<T>>,
+    /// Index of the next slot to read, owned by this half
+    read: usize,
+    /// Most recently observed write index, to avoid contending for it when not nearly empty
+    write: usize,
+}
+
+impl<T> HandoffReceiver<T> {
+    /// Dequeue the oldest value, if any
+    pub fn pop(&mut self) -> Option<T> {
+        if self.read == self.write {
+            self.write = self.shared.write.load(Ordering::Acquire);
+            if self.read == self.write {
+                return None;
+            }
+        }
+        // SAFETY: The slot is between the read and write indices, so was initialized by the
+        // sender, which won't access it again until after the read index is advanced below
+        let value = unsafe { (*self.shared.slot(self.read).get()).assume_init_read() };
+        self.read = self.read.wrapping_add(1);
+        self.shared.read.store(self.read, Ordering::Release);
+        Some(value)
+    }
+
+    /// Dequeue values until the queue is empty
+    ///
+    /// Values pushed concurrently may or may not be included.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.pop())
+    }
+
+    /// Whether the sender has been dropped
+    ///
+    /// Values sent before the sender was dropped may remain to be received.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+}
+
+impl<T> fmt::Debug for HandoffSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandoffSender")
+            .field("capacity", &self.shared.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for HandoffReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandoffReceiver")
+            .field("capacity", &self.shared.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Shared<T> {
+    capacity: usize,
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Number of values ever popped, wrapping
+    read: CachePadded<AtomicUsize>,
+    /// Number of values ever pushed, wrapping
+    write: CachePadded<AtomicUsize>,
+}
+
+// SAFETY: Each slot is accessed by at most one thread at a time, as governed by the indices
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn slot(&self, index: usize) -> &UnsafeCell<MaybeUninit<T>> {
+        &self.slots[index & (self.slots.len() - 1)]
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        let read = *self.read.get_mut();
+        let write = *self.write.get_mut();
+        let mut i = read;
+        while i != write {
+            // SAFETY: Slots between the read and write indices are initialized, and both halves
+            // are gone
+            unsafe { (*self.slot(i).get()).assume_init_drop() };
+            i = i.wrapping_add(1);
+        }
+    }
+}
+
+/// Aligns a value to a cache line so that the sender and receiver don't contend for one
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let (mut send, mut recv) = handoff(2);
+        assert_eq!(recv.pop(), None);
+        assert_eq!(send.push(1), Ok(()));
+        assert_eq!(send.push(2), Ok(()));
+        assert_eq!(send.push(3), Err(3), "full");
+        assert_eq!(recv.pop(), Some(1));
+        assert_eq!(send.push(3), Ok(()));
+        assert_eq!(recv.drain().collect::<Vec<_>>(), [2, 3]);
+        assert!(!send.is_abandoned());
+        drop(recv);
+        assert!(send.is_abandoned());
+    }
+
+    #[test]
+    fn drops_remaining() {
+        let value = Arc::new(());
+        let (mut send, recv) = handoff(3);
+        for _ in 0..3 {
+            send.push(value.clone()).unwrap();
+        }
+        drop((send, recv));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn threaded() {
+        const COUNT: u32 = 100_000;
+        let (mut send, mut recv) = handoff(16);
+        let sender = thread::spawn(move || {
+            for mut i in 0..COUNT {
+                while let Err(x) = send.push(i) {
+                    i = x;
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < COUNT {
+            match recv.pop() {
+                Some(i) => {
+                    assert_eq!(i, expected);
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        sender.join().unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use crate::{Bandwidth, Instant, LossTracker, RttEstimator};
+
+/// Health of every connected client at a moment in time, e.g. for an admin dashboard
+///
+/// With the `serde` feature, reports can be serialized for transmission to external tools.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HealthReport<K> {
+    /// Each client's identifier and health, in the order they were supplied
+    pub clients: Vec<(K, ClientHealth)>,
+}
+
+impl<K> HealthReport<K> {
+    /// Gather the health of each client
+    ///
+    /// Typically called with an iterator mapping each of a server's connections to its identifier
+    /// and [`ClientHealth::new`].
+    pub fn collect(clients: impl IntoIterator<Item = (K, ClientHealth)>) -> Self {
+        Self {
+            clients: clients.into_iter().collect(),
+        }
+    }
+
+    /// The client with the highest smoothed round-trip time
+    pub fn worst_rtt(&self) -> Option<&(K, ClientHealth)> {
+        self.clients
+            .iter()
+            .filter(|(_, x)| x.rtt.is_some())
+            .max_by_key(|(_, x)| x.rtt)
+    }
+
+    /// The client with the highest packet loss
+    pub fn worst_loss(&self) -> Option<&(K, ClientHealth)> {
+        self.clients
+            .iter()
+            .max_by(|(_, x), (_, y)| x.loss.total_cmp(&y.loss))
+    }
+
+    /// Clients that haven't been heard from within `timeout`
+    pub fn idle(&self, timeout: Duration) -> impl Iterator<Item = &(K, ClientHealth)> {
+        self.clients.iter().filter(move |(_, x)| x.idle >= timeout)
+    }
+}
+
+/// The state of a single client's connection, as reported in a [`HealthReport`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientHealth {
+    /// Smoothed round-trip time, if measured
+    pub rtt: Option<Duration>,
+    /// Variation in round-trip time
+    pub jitter: Duration,
+    /// Fraction of packets lost, from 0 to 1
+    pub loss: f32,
+    /// Number of inputs awaiting simulation, e.g. from [`InputQueue::len`](crate::InputQueue::len)
+    pub input_queue_len: usize,
+    /// Throughput of the connection
+    pub bandwidth: Bandwidth,
+    /// Time since anything was received from the client
+    pub idle: Duration,
+}
+
+impl ClientHealth {
+    /// Summarize a client's per-connection components as of `now`
+    pub fn new(
+        now: Instant,
+        rtt: &RttEstimator,
+        loss: &LossTracker,
+        input_queue_len: usize,
+        bandwidth: Bandwidth,
+        last_activity: Instant,
+    ) -> Self {
+        Self {
+            rtt: rtt.smoothed(),
+            jitter: rtt.variation(),
+            loss: loss.smoothed(),
+            input_queue_len,
+            bandwidth,
+            idle: now.saturating_duration_since(last_activity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LossConfig;
+
+    #[test]
+    fn report() {
+        let start = Instant::now();
+        let now = start + Duration::from_secs(10);
+        let loss = LossTracker::new(LossConfig::default());
+        let mut near = RttEstimator::new();
+        near.sample(Duration::from_millis(20));
+        let mut far = RttEstimator::new();
+        far.sample(Duration::from_millis(200));
+        let report = HealthReport::collect([
+            (
+                1,
+                ClientHealth::new(now, &near, &loss, 2, Bandwidth::default(), now),
+            ),
+            (
+                2,
+                ClientHealth::new(now, &far, &loss, 0, Bandwidth::default(), start),
+            ),
+            (
+                3,
+                ClientHealth::new(
+                    now,
+                    &RttEstimator::new(),
+                    &loss,
+                    0,
+                    Bandwidth::default(),
+                    now,
+                ),
+            ),
+        ]);
+        assert_eq!(report.worst_rtt().unwrap().0, 2);
+        let idle = report.idle(Duration::from_secs(5)).collect::<Vec<_>>();
+        assert_eq!(idle.len(), 1);
+        assert_eq!(idle[0].0, 2);
+        assert_eq!(idle[0].1.idle, Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,139 @@
+use alloc::collections::VecDeque;
+
+/// Recent world states, retrievable by tick
+///
+/// Delta encoding needs the states previously sent to each client, and lag compensation needs the
+/// states clients were looking at when they acted. This retains the most recent `depth` states,
+/// along with an estimate of the memory they occupy.
+#[derive(Debug, Clone)]
+pub struct SnapshotHistory<T> {
+    depth: usize,
+    states: VecDeque<(u64, T)>,
+    size: fn(&T) -> usize,
+    memory_usage: usize,
+}
+
+impl<T> SnapshotHistory<T> {
+    /// Construct a history retaining at most `depth` states
+    pub fn new(depth: usize) -> Self {
+        Self::with_size(depth, |_| size_of::<T>())
+    }
+
+    /// Construct a history that estimates the memory used by each state with `size`
+    ///
+    /// Useful when states own heap allocations.
+    pub fn with_size(depth: usize, size: fn(&T) -> usize) -> Self {
+        Self {
+            depth: depth.max(1),
+            states: VecDeque::new(),
+            size,
+            memory_usage: 0,
+        }
+    }
+
+    /// Record `state` as of `tick`, discarding the oldest state if full
+    ///
+    /// Panics if `tick` isn't newer than every previously recorded tick.
+    pub fn insert(&mut self, tick: u64, state: T) {
+        assert!(
+            self.states.back().is_none_or(|x| x.0 < tick),
+            "ticks must increase"
+        );
+        if self.states.len() == self.depth {
+            self.pop_front();
+        }
+        self.memory_usage += (self.size)(&state);
+        self.states.push_back((tick, state));
+    }
+
+    /// Look up the state as of `tick`
+    pub fn get(&self, tick: u64) -> Option<&T> {
+        let i = self.states.binary_search_by_key(&tick, |x| x.0).ok()?;
+        Some(&self.states[i].1)
+    }
+
+    /// Look up the newest state as of `tick` or earlier
+    pub fn at_or_before(&self, tick: u64) -> Option<(u64, &T)> {
+        let i = self
+            .states
+            .partition_point(|x| x.0 <= tick)
+            .checked_sub(1)?;
+        let (tick, ref state) = self.states[i];
+        Some((tick, state))
+    }
+
+    /// Iterate over states from `start` to `end` inclusive, oldest first
+    pub fn range(&self, start: u64, end: u64) -> impl Iterator<Item = (u64, &T)> + '_ {
+        let first = self.states.partition_point(|x| x.0 < start);
+        let last = self.states.partition_point(|x| x.0 <= end).max(first);
+        self.states
+            .range(first..last)
+            .map(|&(tick, ref state)| (tick, state))
+    }
+
+    /// The most recent tick and state
+    pub fn latest(&self) -> Option<(u64, &T)> {
+        self.states.back().map(|&(tick, ref state)| (tick, state))
+    }
+
+    /// The oldest tick retained
+    pub fn oldest_tick(&self) -> Option<u64> {
+        self.states.front().map(|x| x.0)
+    }
+
+    /// Discard states older than `tick`
+    pub fn discard_before(&mut self, tick: u64) {
+        while self.states.front().is_some_and(|x| x.0 < tick) {
+            self.pop_front();
+        }
+    }
+
+    /// Estimated memory occupied by retained states, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    /// Number of states retained
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Whether no states are retained
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    fn pop_front(&mut self) {
+        if let Some((_, state)) = self.states.pop_front() {
+            self.memory_usage -= (self.size)(&state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut history = SnapshotHistory::with_size(3, |x: &Vec<u8>| x.len());
+        for tick in [10, 11, 13, 14] {
+            history.insert(tick, vec![0; tick as usize]);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.memory_usage(), 11 + 13 + 14);
+        assert_eq!(history.get(10), None);
+        assert_eq!(history.get(13).map(Vec::len), Some(13));
+        assert_eq!(history.get(12), None);
+        assert_eq!(history.at_or_before(12).map(|x| x.0), Some(11));
+        assert_eq!(history.at_or_before(9), None);
+        assert_eq!(
+            history.range(12, 100).map(|x| x.0).collect::<Vec<_>>(),
+            [13, 14]
+        );
+        assert_eq!(history.range(15, 12).count(), 0);
+        history.discard_before(14);
+        assert_eq!(history.oldest_tick(), Some(14));
+        assert_eq!(history.memory_usage(), 14);
+    }
+}
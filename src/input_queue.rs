@@ -1,7 +1,6 @@
-use std::{
-    collections::VecDeque,
-    time::{Duration, Instant},
-};
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{Instant, Stamped, SubTick};
 
 /// A jitter-tolerant queue of inputs received from a client
 ///
@@ -34,6 +33,8 @@ impl<T> InputQueue<T> {
     pub fn push(&mut self, max: usize, input: T, now: Instant) -> bool {
         let overrun = self.queue.len() == max;
         if overrun {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(target: "nettish::input_queue", len = max, "input queue overrun");
             self.queue.pop_front();
         }
         self.queue.push_back(input);
@@ -58,6 +59,8 @@ impl<T> InputQueue<T> {
         if result.is_none() {
             // Queue under-run; the client may have fallen behind, so we need to re-establish our
             // margin for error.
+            #[cfg(feature = "tracing")]
+            tracing::debug!(target: "nettish::input_queue", "input queue underrun");
             self.epoch = None;
         }
         result
@@ -74,6 +77,21 @@ impl<T> InputQueue<T> {
     }
 }
 
+impl<T> InputQueue<Stamped<T>> {
+    /// Like [`take`](Self::take), for the step simulating `tick`
+    ///
+    /// Timestamps later than `tick` are clamped to its end, since a well-behaved client can't act
+    /// on a moment that hasn't yet been simulated.
+    pub fn take_stamped(&mut self, now: Instant, delay: Duration, tick: u64) -> Option<Stamped<T>> {
+        let mut input = self.take(now, delay)?;
+        input.time = input.time.min(SubTick {
+            tick,
+            fraction: u16::MAX,
+        });
+        Some(input)
+    }
+}
+
 impl<T> Default for InputQueue<T> {
     fn default() -> Self {
         Self {
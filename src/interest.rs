@@ -0,0 +1,148 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Determines which entities are relevant to each client based on proximity
+///
+/// Entities are bucketed into a uniform grid of `D`-dimensional cells, so that finding those near
+/// an observer only requires examining nearby cells. Large worlds typically use `D = 2`, ignoring
+/// height. Cells should be comparable in size to typical observer radii.
+///
+/// Relevant entities can then be passed to a per-client
+/// [`PriorityAccumulator`](crate::PriorityAccumulator), perhaps with priority decreasing with
+/// distance.
+#[derive(Debug, Clone)]
+pub struct InterestGrid<C, E, const D: usize> {
+    cell_size: f32,
+    cells: HashMap<[i32; D], Vec<E>>,
+    entities: HashMap<E, [f32; D]>,
+    observers: HashMap<C, Observer<D>>,
+}
+
+impl<C: Hash + Eq, E: Hash + Eq + Clone, const D: usize> InterestGrid<C, E, D> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entities: HashMap::new(),
+            observers: HashMap::new(),
+        }
+    }
+
+    /// Insert `entity` at `position`, or move it there
+    pub fn set_entity(&mut self, entity: E, position: [f32; D]) {
+        let cell = self.cell(position);
+        if let Some(old) = self.entities.insert(entity.clone(), position) {
+            let old = self.cell(old);
+            if old == cell {
+                return;
+            }
+            self.remove_from_cell(old, &entity);
+        }
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    /// Stop tracking `entity`
+    pub fn remove_entity(&mut self, entity: &E) {
+        if let Some(position) = self.entities.remove(entity) {
+            self.remove_from_cell(self.cell(position), entity);
+        }
+    }
+
+    /// Record that `client` is interested in entities within `radius` of `position`
+    pub fn set_observer(&mut self, client: C, position: [f32; D], radius: f32) {
+        self.observers.insert(client, Observer { position, radius });
+    }
+
+    /// Stop tracking `client`
+    pub fn remove_observer(&mut self, client: &C) {
+        self.observers.remove(client);
+    }
+
+    /// Entities relevant to `client`, and their distances from it
+    pub fn relevant(&self, client: &C) -> Vec<(E, f32)> {
+        match self.observers.get(client) {
+            Some(observer) => self.query(observer.position, observer.radius),
+            None => Vec::new(),
+        }
+    }
+
+    /// Entities within `radius` of `position`, and their distances from it
+    pub fn query(&self, position: [f32; D], radius: f32) -> Vec<(E, f32)> {
+        let min = self.cell(position.map(|x| x - radius));
+        let max = self.cell(position.map(|x| x + radius));
+        let mut result = Vec::new();
+        let mut cell = min;
+        'cells: loop {
+            for entity in self.cells.get(&cell).into_iter().flatten() {
+                let distance = distance(position, self.entities[entity]);
+                if distance <= radius {
+                    result.push((entity.clone(), distance));
+                }
+            }
+            // Advance to the next cell in the box, like an odometer
+            for axis in 0..D {
+                if cell[axis] < max[axis] {
+                    cell[axis] += 1;
+                    continue 'cells;
+                }
+                cell[axis] = min[axis];
+            }
+            break;
+        }
+        result
+    }
+
+    fn cell(&self, position: [f32; D]) -> [i32; D] {
+        position.map(|x| (x / self.cell_size).floor() as i32)
+    }
+
+    fn remove_from_cell(&mut self, cell: [i32; D], entity: &E) {
+        let Some(entities) = self.cells.get_mut(&cell) else {
+            return;
+        };
+        entities.retain(|x| x != entity);
+        if entities.is_empty() {
+            self.cells.remove(&cell);
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Observer<const D: usize> {
+    position: [f32; D],
+    radius: f32,
+}
+
+fn distance<const D: usize>(a: [f32; D], b: [f32; D]) -> f32 {
+    a.iter()
+        .zip(&b)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut grid = InterestGrid::<&str, u32, 2>::new(10.0);
+        grid.set_entity(0, [0.0, 0.0]);
+        grid.set_entity(1, [15.0, -5.0]);
+        grid.set_entity(2, [100.0, 100.0]);
+        grid.set_observer("alice", [5.0, 0.0], 12.0);
+        grid.set_observer("bob", [95.0, 95.0], 12.0);
+
+        let mut relevant = grid.relevant(&"alice");
+        relevant.sort_by_key(|x| x.0);
+        assert_eq!(relevant, [(0, 5.0), (1, 125f32.sqrt())]);
+        assert_eq!(grid.relevant(&"bob").len(), 1);
+
+        grid.set_entity(2, [-5.0, 5.0]);
+        assert!(grid.relevant(&"bob").is_empty());
+        assert_eq!(grid.relevant(&"alice").len(), 3);
+        grid.remove_entity(&0);
+        assert_eq!(grid.relevant(&"alice").len(), 2);
+        assert!(grid.relevant(&"carol").is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// A reference to an interned value, as transmitted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interned<T> {
+    /// A value previously defined by the sender and acknowledged by the receiver
+    Id(u16),
+    /// A value which the receiver might not know yet, and the ID it's being assigned
+    Define { id: u16, value: T },
+}
+
+/// Replaces repeatedly transmitted values, such as asset paths, with short IDs
+///
+/// The first time a value is sent, it's transmitted in full along with a newly assigned ID. Once
+/// the receiver acknowledges a definition, later references send only the ID. Until then, the
+/// full value is sent with every reference, so no message depends on the delivery of any other.
+///
+/// When the table is full, the least recently used value is evicted and its ID reassigned. An ID
+/// may therefore refer to different values over time, so references must be processed in the
+/// order they were produced, e.g. by discarding stale packets.
+#[derive(Debug, Clone)]
+pub struct InternSender<T> {
+    slots: Vec<Slot<T>>,
+    ids: HashMap<T, u16>,
+    /// Incremented on every use, for LRU eviction
+    clock: u64,
+    capacity: u16,
+}
+
+impl<T: Hash + Eq + Clone> InternSender<T> {
+    /// Construct a table retaining at most `capacity` values
+    ///
+    /// The corresponding [`InternReceiver`] must have at least the same capacity.
+    pub fn new(capacity: u16) -> Self {
+        Self {
+            slots: Vec::new(),
+            ids: HashMap::new(),
+            clock: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Get a reference to `value` suitable for transmission
+    pub fn intern(&mut self, value: &T) -> Interned<T> {
+        self.clock += 1;
+        if let Some(&id) = self.ids.get(value) {
+            let slot = &mut self.slots[usize::from(id)];
+            slot.last_used = self.clock;
+            return if slot.acked {
+                Interned::Id(id)
+            } else {
+                Interned::Define {
+                    id,
+                    value: value.clone(),
+                }
+            };
+        }
+
+        let slot = Slot {
+            value: value.clone(),
+            acked: false,
+            last_used: self.clock,
+        };
+        let id = if self.slots.len() < usize::from(self.capacity) {
+            self.slots.push(slot);
+            self.slots.len() as u16 - 1
+        } else {
+            let id = (0..self.capacity)
+                .min_by_key(|&i| self.slots[usize::from(i)].last_used)
+                .unwrap();
+            let old = std::mem::replace(&mut self.slots[usize::from(id)], slot);
+            self.ids.remove(&old.value);
+            id
+        };
+        self.ids.insert(value.clone(), id);
+        Interned::Define {
+            id,
+            value: value.clone(),
+        }
+    }
+
+    /// Note that the receiver has received the definition of `id` as `value`
+    ///
+    /// Definitions of values that have since been evicted are ignored.
+    pub fn on_acked(&mut self, id: u16, value: &T) {
+        if let Some(slot) = self.slots.get_mut(usize::from(id))
+            && slot.value == *value
+        {
+            slot.acked = true;
+        }
+    }
+
+    /// Forget all definitions, e.g. when the receiver reconnects
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.ids.clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    value: T,
+    /// Whether the receiver has this definition
+    acked: bool,
+    last_used: u64,
+}
+
+/// Resolves references produced by an [`InternSender`]
+#[derive(Debug, Clone)]
+pub struct InternReceiver<T> {
+    slots: Vec<Option<T>>,
+}
+
+impl<T> InternReceiver<T> {
+    pub fn new(capacity: u16) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| None).collect(),
+        }
+    }
+
+    /// Look up the value referred to by `interned`, recording any definition
+    ///
+    /// Returns `None` if the ID is unknown or out of range.
+    pub fn resolve(&mut self, interned: Interned<T>) -> Option<&T> {
+        match interned {
+            Interned::Id(id) => self.get(id),
+            Interned::Define { id, value } => {
+                let slot = self.slots.get_mut(usize::from(id))?;
+                Some(slot.insert(value))
+            }
+        }
+    }
+
+    /// Look up the value currently assigned to `id`
+    pub fn get(&self, id: u16) -> Option<&T> {
+        self.slots.get(usize::from(id))?.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut sender = InternSender::new(2);
+        let mut receiver = InternReceiver::new(2);
+        let a = "models/goblin.glb";
+        let b = "models/orc.glb";
+        let c = "models/troll.glb";
+
+        let def = sender.intern(&a);
+        assert_eq!(def, Interned::Define { id: 0, value: a });
+        assert_eq!(sender.intern(&a), def, "redefined until acknowledged");
+        assert_eq!(receiver.resolve(def), Some(&a));
+        sender.on_acked(0, &a);
+        assert_eq!(sender.intern(&a), Interned::Id(0));
+        assert_eq!(receiver.resolve(Interned::Id(0)), Some(&a));
+
+        assert_eq!(sender.intern(&b), Interned::Define { id: 1, value: b });
+        sender.intern(&a);
+        // `b` is least recently used
+        let def = sender.intern(&c);
+        assert_eq!(def, Interned::Define { id: 1, value: c });
+        sender.on_acked(1, &b);
+        assert_eq!(sender.intern(&c), def, "stale acknowledgement ignored");
+        assert_eq!(receiver.resolve(def), Some(&c));
+        assert_eq!(receiver.get(7), None);
+    }
+}
@@ -0,0 +1,38 @@
+/// Values that can be blended smoothly between two samples
+///
+/// Used wherever state must be presented at a time between the times it was sampled, e.g. when
+/// rendering between snapshots.
+pub trait Interpolate {
+    /// Blend between `self` at `t = 0` and `other` at `t = 1`
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * f64::from(t)
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        core::array::from_fn(|i| self[i].interpolate(&other[i], t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        assert_eq!(1.0f32.interpolate(&3.0, 0.5), 2.0);
+        assert_eq!(1.0f64.interpolate(&3.0, 0.25), 1.5);
+        assert_eq!([0.0f32, 10.0].interpolate(&[1.0, 0.0], 0.5), [0.5, 5.0]);
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    time::Duration,
+};
+
+use crate::{Interpolate, Quat, SnapshotHistory};
+
+/// Recent entity poses, for validating client actions against what the client saw
+///
+/// Clients see other entities as they were some time in the past, due to latency and
+/// interpolation delay. To judge e.g. whether a hitscan shot hit, the server rewinds hitboxes to
+/// the moment the shooter was looking at, as reported by the shooter. Poses `P` of each entity
+/// `E` are recorded every tick, retaining a bounded history.
+///
+/// View times are measured in simulation time since tick 0, such that tick `n` occurs at
+/// `n * tick_interval`.
+///
+/// Memory use grows with the number of entities, the length of history, and the size of `P`. To
+/// limit it, record only the entities that can be hit, e.g. using [`LagConfig::opt_in`], and
+/// consider storing poses at reduced precision, e.g. as [`QuantizedPose`]s.
+#[derive(Debug, Clone)]
+pub struct LagCompensator<E, P> {
+    history: SnapshotHistory<HashMap<E, P>>,
+    tick_interval: Duration,
+    /// Entities to record, if not all
+    tracked: Option<HashSet<E>>,
+}
+
+impl<E: Hash + Eq, P> LagCompensator<E, P> {
+    pub fn new(config: LagConfig) -> Self {
+        let interval = config.tick_interval.as_nanos().max(1);
+        let depth = config.history.as_nanos().div_ceil(interval) as usize + 1;
+        Self {
+            history: SnapshotHistory::with_size(depth, |x| {
+                size_of::<HashMap<E, P>>() + x.capacity() * size_of::<(E, P)>()
+            }),
+            tick_interval: config.tick_interval,
+            tracked: config.opt_in.then(HashSet::new),
+        }
+    }
+
+    /// Record the `poses` of every entity as of `tick`
+    ///
+    /// If [`LagConfig::opt_in`] was set, only [`track`](Self::track)ed entities are retained.
+    /// Panics if `tick` isn't newer than every previously recorded tick.
+    pub fn record(&mut self, tick: u64, poses: impl IntoIterator<Item = (E, P)>) {
+        let poses = poses.into_iter();
+        let poses = match self.tracked {
+            None => poses.collect(),
+            Some(ref tracked) => poses.filter(|x| tracked.contains(&x.0)).collect(),
+        };
+        self.history.insert(tick, poses);
+    }
+
+    /// Begin recording `entity`, if [`LagConfig::opt_in`] was set
+    pub fn track(&mut self, entity: E) {
+        if let Some(ref mut tracked) = self.tracked {
+            tracked.insert(entity);
+        }
+    }
+
+    /// Stop recording `entity`, if [`LagConfig::opt_in`] was set
+    pub fn untrack(&mut self, entity: &E) {
+        if let Some(ref mut tracked) = self.tracked {
+            tracked.remove(entity);
+        }
+    }
+
+    /// The tick in effect at `view_time`
+    pub fn tick_at(&self, view_time: Duration) -> u64 {
+        (view_time.as_nanos() / self.tick_interval.as_nanos().max(1)) as u64
+    }
+
+    /// Poses of all entities as of the most recent tick at or before `view_time`, along with that
+    /// tick
+    ///
+    /// Returns `None` if `view_time` precedes the retained history.
+    pub fn rewind(&self, view_time: Duration) -> Option<(u64, &HashMap<E, P>)> {
+        self.history.at_or_before(self.tick_at(view_time))
+    }
+
+    /// Pose of `entity` as of `view_time`
+    pub fn pose(&self, view_time: Duration, entity: &E) -> Option<&P> {
+        self.rewind(view_time)?.1.get(entity)
+    }
+
+    /// Pose of `entity` at `view_time`, interpolated between the surrounding ticks
+    ///
+    /// Clients render between ticks, so fast-moving entities may be far from their pose as of
+    /// either tick. If the following tick hasn't been recorded, or `entity` is absent from it,
+    /// the pose as of the preceding tick is returned.
+    pub fn interpolated(&self, view_time: Duration, entity: &E) -> Option<P>
+    where
+        P: Interpolate + Clone,
+    {
+        let span = self.surrounding(view_time)?;
+        let a = span.before.get(entity)?;
+        match span.after.and_then(|x| x.get(entity)) {
+            Some(b) => Some(a.interpolate(b, span.t)),
+            None => Some(a.clone()),
+        }
+    }
+
+    /// Poses of all entities at `view_time`, interpolated between the surrounding ticks
+    ///
+    /// See [`interpolated`](Self::interpolated).
+    pub fn rewind_interpolated(&self, view_time: Duration) -> Option<HashMap<&E, P>>
+    where
+        P: Interpolate + Clone,
+    {
+        let span = self.surrounding(view_time)?;
+        Some(
+            span.before
+                .iter()
+                .map(|(e, a)| {
+                    let pose = match span.after.and_then(|x| x.get(e)) {
+                        Some(b) => a.interpolate(b, span.t),
+                        None => a.clone(),
+                    };
+                    (e, pose)
+                })
+                .collect(),
+        )
+    }
+
+    /// Poses as of the ticks before and after `view_time`
+    fn surrounding(&self, view_time: Duration) -> Option<Span<'_, E, P>> {
+        let tick = self.tick_at(view_time);
+        let (before, poses) = self.history.at_or_before(tick)?;
+        if before != tick {
+            // The tick in effect wasn't recorded, so the latest earlier pose is the best guess
+            return Some(Span {
+                before: poses,
+                after: None,
+                t: 0.0,
+            });
+        }
+        let interval = self.tick_interval.as_nanos().max(1);
+        let offset = view_time.as_nanos() % interval;
+        Some(Span {
+            before: poses,
+            after: self.history.get(tick + 1),
+            t: offset as f32 / interval as f32,
+        })
+    }
+
+    /// Summary of the memory occupied by retained poses
+    pub fn memory_report(&self) -> LagMemoryReport {
+        let poses = self.history.range(0, u64::MAX).map(|x| x.1.len()).sum();
+        LagMemoryReport {
+            ticks: self.history.len(),
+            poses,
+            bytes: self.history.memory_usage(),
+        }
+    }
+}
+
+/// Configuration for a [`LagCompensator`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LagConfig {
+    /// How far into the past poses are retained
+    ///
+    /// Should be at least [`RewindLimits::max_rewind`].
+    pub history: Duration,
+    /// Time between ticks
+    pub tick_interval: Duration,
+    /// Whether only entities passed to [`LagCompensator::track`] are recorded
+    pub opt_in: bool,
+}
+
+impl Default for LagConfig {
+    fn default() -> Self {
+        Self {
+            history: Duration::from_millis(250),
+            tick_interval: Duration::from_secs(1) / 64,
+            opt_in: false,
+        }
+    }
+}
+
+/// Memory occupied by a [`LagCompensator`], from [`LagCompensator::memory_report`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LagMemoryReport {
+    /// Number of ticks retained
+    pub ticks: usize,
+    /// Number of poses retained, across all ticks
+    pub poses: usize,
+    /// Estimated memory occupied, in bytes
+    pub bytes: usize,
+}
+
+/// A position and orientation stored at reduced precision
+///
+/// Occupies 20 bytes rather than the 28 of an `f32` position and [`Quat`]. Positions are stored as
+/// multiples of a resolution supplied when converting, e.g. 1mm, which limits their range;
+/// rotation components are stored with 16 bits each.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct QuantizedPose {
+    position: [i32; 3],
+    rotation: [i16; 4],
+}
+
+impl QuantizedPose {
+    /// Quantize `position` to multiples of `resolution`, and `rotation`
+    pub fn new(position: [f32; 3], rotation: Quat, resolution: f32) -> Self {
+        Self {
+            position: position.map(|x| (x / resolution).round() as i32),
+            rotation: rotation.0.map(|x| (x * f32::from(i16::MAX)).round() as i16),
+        }
+    }
+
+    /// Recover the position, given the `resolution` used to construct the pose
+    pub fn position(&self, resolution: f32) -> [f32; 3] {
+        self.position.map(|x| x as f32 * resolution)
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat(self.rotation.map(|x| f32::from(x) / f32::from(i16::MAX))).normalize()
+    }
+}
+
+impl Interpolate for QuantizedPose {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let mut position = self.position;
+        for (x, &y) in position.iter_mut().zip(&other.position) {
+            *x += ((y as f32 - *x as f32) * t).round() as i32;
+        }
+        let rotation = self.rotation().interpolate(&other.rotation(), t);
+        Self {
+            position,
+            rotation: rotation.0.map(|x| (x * f32::from(i16::MAX)).round() as i16),
+        }
+    }
+}
+
+/// Limits on how far a [`LagCompensator`] may rewind on behalf of a client
+///
+/// Without limits, a client with high latency, or one that lies about what it saw, could act on
+/// the distant past, e.g. hitting targets that have long since taken cover. Claimed view times are
+/// checked against the view time expected given the client's measured round-trip time and
+/// interpolation delay, and limited to a maximum age.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RewindLimits {
+    /// Furthest into the past a client may act
+    pub max_rewind: Duration,
+    /// Whether view times older than `max_rewind` are clamped to it, rather than rejected
+    pub clamp: bool,
+    /// Largest acceptable difference between claimed and expected view times
+    ///
+    /// Should accommodate jitter and error in the RTT estimate.
+    pub tolerance: Duration,
+}
+
+impl Default for RewindLimits {
+    fn default() -> Self {
+        Self {
+            max_rewind: Duration::from_millis(250),
+            clamp: true,
+            tolerance: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RewindLimits {
+    /// Check a view time `claimed` by a client in a request received at simulation time `now`
+    ///
+    /// The client is expected to be viewing the simulation as of roughly `now - rtt -
+    /// interpolation_delay`. Returns the view time to rewind to.
+    pub fn check(
+        &self,
+        now: Duration,
+        claimed: Duration,
+        rtt: Duration,
+        interpolation_delay: Duration,
+    ) -> Result<Duration, RewindRejection> {
+        let expected = now.saturating_sub(rtt + interpolation_delay);
+        if claimed > now || claimed.abs_diff(expected) > self.tolerance {
+            return Err(RewindRejection::Inconsistent { claimed, expected });
+        }
+        let limit = now.saturating_sub(self.max_rewind);
+        if claimed < limit {
+            if !self.clamp {
+                return Err(RewindRejection::TooOld { claimed, limit });
+            }
+            return Ok(limit);
+        }
+        Ok(claimed)
+    }
+}
+
+/// Reasons a view time was refused by [`RewindLimits::check`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RewindRejection {
+    /// The view time is implausible given the client's latency, suggesting cheating or a badly
+    /// behaved client
+    Inconsistent {
+        claimed: Duration,
+        expected: Duration,
+    },
+    /// The view time is older than the maximum rewind
+    TooOld { claimed: Duration, limit: Duration },
+}
+
+impl fmt::Display for RewindRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RewindRejection::Inconsistent { claimed, expected } => write!(
+                f,
+                "claimed view time {claimed:?} inconsistent with expected {expected:?}"
+            ),
+            RewindRejection::TooOld { claimed, limit } => {
+                write!(f, "view time {claimed:?} older than limit {limit:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RewindRejection {}
+
+/// Poses as of the ticks surrounding a moment
+struct Span<'a, E, P> {
+    before: &'a HashMap<E, P>,
+    after: Option<&'a HashMap<E, P>>,
+    /// Fraction of the way from `before` to `after`
+    t: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let interval = Duration::from_millis(10);
+        let mut lag = LagCompensator::new(LagConfig {
+            history: interval * 3,
+            tick_interval: interval,
+            opt_in: false,
+        });
+        for tick in 0..6 {
+            lag.record(tick, [("target", tick as f32)]);
+        }
+        assert_eq!(lag.tick_at(Duration::from_millis(45)), 4);
+        assert_eq!(lag.pose(Duration::from_millis(45), &"target"), Some(&4.0));
+        assert_eq!(lag.rewind(Duration::from_millis(59)).unwrap().0, 5);
+        assert_eq!(lag.rewind(Duration::from_millis(100)).unwrap().0, 5);
+        assert!(
+            lag.rewind(Duration::from_millis(15)).is_none(),
+            "older than retained history"
+        );
+    }
+
+    #[test]
+    fn interpolated() {
+        let interval = Duration::from_millis(10);
+        let mut lag = LagCompensator::new(LagConfig {
+            history: interval * 3,
+            tick_interval: interval,
+            opt_in: false,
+        });
+        lag.record(0, [("a", 0.0f32), ("b", 5.0)]);
+        lag.record(1, [("a", 10.0f32)]);
+        let view = Duration::from_millis(2);
+        assert!((lag.interpolated(view, &"a").unwrap() - 2.0).abs() < 1e-3);
+        assert_eq!(lag.interpolated(view, &"b"), Some(5.0), "despawned");
+        let all = lag.rewind_interpolated(Duration::from_millis(15)).unwrap();
+        assert_eq!(all[&"a"], 10.0, "no later tick");
+        assert_eq!(all.len(), 1);
+    }
+
+    #[test]
+    fn limits() {
+        let ms = Duration::from_millis;
+        let mut limits = RewindLimits::default();
+        let now = ms(1000);
+        assert_eq!(limits.check(now, ms(880), ms(100), ms(20)), Ok(ms(880)));
+        assert_eq!(
+            limits.check(now, ms(500), ms(100), ms(20)),
+            Err(RewindRejection::Inconsistent {
+                claimed: ms(500),
+                expected: ms(880)
+            })
+        );
+        assert!(limits.check(now, ms(1010), ms(0), ms(0)).is_err(), "future");
+        assert_eq!(
+            limits.check(now, ms(700), ms(280), ms(20)),
+            Ok(ms(750)),
+            "clamped"
+        );
+        limits.clamp = false;
+        assert!(matches!(
+            limits.check(now, ms(700), ms(280), ms(20)),
+            Err(RewindRejection::TooOld { .. })
+        ));
+    }
+
+    #[test]
+    fn memory() {
+        let mut lag = LagCompensator::new(LagConfig {
+            opt_in: true,
+            ..LagConfig::default()
+        });
+        lag.track(1);
+        for tick in 0..100 {
+            lag.record(tick, (0..10).map(|e| (e, QuantizedPose::default())));
+        }
+        let report = lag.memory_report();
+        assert_eq!(report.ticks, 17);
+        assert_eq!(report.poses, 17, "only the tracked entity");
+        assert!(report.bytes > 0);
+    }
+
+    #[test]
+    fn quantized() {
+        let a = QuantizedPose::new([1.0, 2.0, 3.0], Quat::IDENTITY, 0.001);
+        let b = QuantizedPose::new([2.0, 2.0, 3.0], Quat::IDENTITY, 0.001);
+        let mid = a.interpolate(&b, 0.5);
+        assert!((mid.position(0.001)[0] - 1.5).abs() < 1e-3);
+        assert!((mid.rotation().0[3] - 1.0).abs() < 1e-3);
+        assert_eq!(size_of::<QuantizedPose>(), 20);
+    }
+}
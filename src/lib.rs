@@ -1,8 +1,435 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+/// A point in time, as measured by a monotonic clock
+///
+/// [`std::time::Instant`] panics on the web, so `web-time`'s equivalent is used there instead.
+#[cfg(all(
+    feature = "std",
+    not(all(target_family = "wasm", target_os = "unknown"))
+))]
+pub use std::time::Instant;
+#[cfg(all(feature = "std", target_family = "wasm", target_os = "unknown"))]
+pub use web_time::Instant;
+
+#[cfg(feature = "std")]
 mod input_queue;
+#[cfg(feature = "std")]
 pub use input_queue::InputQueue;
 
 mod prediction;
 pub use prediction::PredictionQueue;
 
 mod throttle;
-pub use throttle::throttle;
+pub use throttle::{ThrottleRegime, throttle, throttle_secs_f64};
+
+#[cfg(feature = "std")]
+mod token_bucket;
+#[cfg(feature = "std")]
+pub use token_bucket::TokenBucket;
+
+#[cfg(feature = "std")]
+mod congestion;
+#[cfg(feature = "std")]
+pub use congestion::{CongestionConfig, CongestionController, CongestionMode, SendRates};
+
+#[cfg(feature = "std")]
+mod ack;
+#[cfg(feature = "std")]
+pub use ack::{AckConfig, AckEvent, AckHeader, AckTracker, LostPacket, PacketInfo};
+
+#[cfg(feature = "std")]
+mod bandwidth;
+#[cfg(feature = "std")]
+pub use bandwidth::{Bandwidth, BandwidthEstimator};
+
+#[cfg(feature = "std")]
+mod loss;
+#[cfg(feature = "std")]
+pub use loss::{LossConfig, LossTracker};
+
+mod reorder;
+pub use reorder::{ReorderStats, ReorderTracker};
+
+#[cfg(feature = "std")]
+mod nack;
+#[cfg(feature = "std")]
+pub use nack::{NackConfig, NackGenerator, RepairBuffer};
+
+#[cfg(feature = "std")]
+mod send_queue;
+#[cfg(feature = "std")]
+pub use send_queue::SendQueue;
+
+#[cfg(feature = "std")]
+mod state_channel;
+#[cfg(feature = "std")]
+pub use state_channel::StateChannel;
+
+#[cfg(feature = "std")]
+mod bulk;
+#[cfg(feature = "std")]
+pub use bulk::{BulkConfig, BulkReceiver, BulkSender, Chunk, TransferProgress};
+
+mod dedup;
+pub use dedup::DuplicateFilter;
+
+#[cfg(feature = "std")]
+mod ordered;
+#[cfg(feature = "std")]
+pub use ordered::{Blocking, OrderedReceiver};
+
+mod bits;
+pub use bits::{BitReader, BitWriter, DecodeError};
+
+#[cfg(feature = "std")]
+mod quantize;
+#[cfg(feature = "std")]
+pub use quantize::{dequantize_f32, quantization_error, quantize_f32};
+
+mod interpolate;
+pub use interpolate::Interpolate;
+
+#[cfg(feature = "std")]
+mod quat;
+#[cfg(feature = "std")]
+pub use quat::{Quat, quat_bits};
+
+mod varint;
+pub use varint::{read_varint, varint_len, write_varint, zigzag_decode, zigzag_encode};
+
+#[cfg(feature = "std")]
+mod direction;
+#[cfg(feature = "std")]
+pub use direction::{octahedral_decode, octahedral_encode};
+
+mod delta;
+pub use delta::{
+    BaselineBuffer, Delta, DeltaDecoder, DeltaEncoder, DeltaMode, decode_delta, encode_delta,
+};
+
+#[cfg(feature = "std")]
+mod baseline_tracker;
+#[cfg(feature = "std")]
+pub use baseline_tracker::BaselineTracker;
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+mod compress;
+#[cfg(feature = "zstd")]
+pub use compress::train_dictionary;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub use compress::{Compression, Compressor, Decompressor};
+
+#[cfg(feature = "std")]
+mod codec;
+#[cfg(feature = "postcard")]
+pub use codec::Postcard;
+#[cfg(feature = "bincode")]
+pub use codec::{Bincode, BincodeError};
+#[cfg(feature = "std")]
+pub use codec::{BitCodec, Codec};
+
+mod bitset;
+
+#[cfg(feature = "std")]
+mod intern;
+#[cfg(feature = "std")]
+pub use intern::{InternReceiver, InternSender, Interned};
+
+#[cfg(feature = "entropy")]
+mod entropy;
+#[cfg(feature = "entropy")]
+pub use entropy::HuffmanTable;
+
+#[cfg(feature = "std")]
+mod priority;
+#[cfg(feature = "std")]
+pub use priority::PriorityAccumulator;
+
+#[cfg(feature = "std")]
+mod interest;
+#[cfg(feature = "std")]
+pub use interest::InterestGrid;
+
+#[cfg(feature = "std")]
+mod visibility;
+#[cfg(feature = "std")]
+pub use visibility::{VisibilityChange, VisibilityFilter};
+
+#[cfg(feature = "std")]
+mod net_id;
+#[cfg(feature = "std")]
+pub use net_id::{NetId, NetIdMap};
+
+mod history;
+pub use history::SnapshotHistory;
+
+#[cfg(feature = "std")]
+mod desync;
+#[cfg(feature = "std")]
+pub use desync::{Checksum, Desync, DesyncDetector};
+
+#[cfg(feature = "std")]
+mod property;
+#[cfg(feature = "std")]
+pub use property::{PropertyReceiver, PropertySender};
+
+#[cfg(feature = "std")]
+mod event;
+#[cfg(feature = "std")]
+pub use event::{EventReceiver, EventSender, TickedEvent};
+
+#[cfg(feature = "std")]
+mod lifecycle;
+#[cfg(feature = "std")]
+pub use lifecycle::{LifecycleMessage, LifecycleReceiver, LifecycleSender};
+
+#[cfg(feature = "std")]
+mod rules;
+#[cfg(feature = "std")]
+pub use rules::{BudgetReport, Reliability, ReplicationRule, ReplicationScheduler};
+
+mod dirty;
+pub use dirty::{FieldMask, NetDirty};
+#[cfg(feature = "derive")]
+pub use nettish_derive::NetDirty;
+// Allow derived code, which refers to `::nettish`, to be tested within the crate
+#[cfg(all(test, feature = "derive"))]
+extern crate self as nettish;
+
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "std")]
+pub use cache::{CacheState, ReplicationCache};
+
+#[cfg(feature = "std")]
+mod authority;
+#[cfg(feature = "std")]
+pub use authority::{AuthorityArbiter, AuthorityConfig, AuthorityMessage, AuthorityView};
+
+#[cfg(feature = "std")]
+mod validate;
+#[cfg(feature = "std")]
+pub use validate::{Clamp, ClientField, Validation, Validator};
+
+#[cfg(feature = "hecs")]
+mod ecs;
+#[cfg(feature = "hecs")]
+pub use ecs::{HecsClient, HecsServer};
+
+#[cfg(feature = "bevy")]
+mod bevy;
+#[cfg(feature = "bevy")]
+pub use bevy::{
+    ClientPlugin, LocalInput, NettishSystems, Prediction, ServerAck, ServerInputs, ServerPlugin,
+    TimeController,
+};
+
+#[cfg(feature = "std")]
+mod lag;
+#[cfg(feature = "std")]
+pub use lag::{
+    LagCompensator, LagConfig, LagMemoryReport, QuantizedPose, RewindLimits, RewindRejection,
+};
+
+mod subtick;
+pub use subtick::{Stamped, SubTick};
+
+#[cfg(feature = "std")]
+mod exchange;
+#[cfg(feature = "std")]
+pub use exchange::InputMessage;
+
+#[cfg(feature = "std")]
+mod rollback;
+#[cfg(feature = "std")]
+pub use rollback::{
+    InputPredictor, RepeatLast, RollbackCallbacks, RollbackConfig, RollbackSession, RollbackStats,
+};
+
+#[cfg(feature = "std")]
+mod lockstep;
+#[cfg(feature = "std")]
+pub use lockstep::{LockstepConfig, LockstepSession, Stall};
+
+#[cfg(feature = "std")]
+mod negotiate;
+#[cfg(feature = "std")]
+pub use negotiate::{DelayNegotiator, NegotiationConfig, NegotiationMessage, SessionTiming};
+
+#[cfg(feature = "std")]
+mod synctest;
+#[cfg(feature = "std")]
+pub use synctest::SyncTestSession;
+
+#[cfg(feature = "std")]
+mod spectator;
+#[cfg(feature = "std")]
+pub use spectator::{SpectatorMessage, SpectatorReplay};
+
+#[cfg(feature = "std")]
+mod simulator;
+#[cfg(feature = "std")]
+pub use simulator::{
+    ImpairmentSchedule, LinkLimit, LossModel, NetworkSimulator, SimulatorConfig, SimulatorStats,
+};
+
+#[cfg(feature = "std")]
+mod transport;
+#[cfg(feature = "std")]
+pub use transport::{LoopbackError, LoopbackTransport, Transport};
+
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
+pub use clock::{Clock, SystemClock, VirtualClock};
+
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "std")]
+pub use trace::{Trace, TraceEvent, TraceReplay, TracingTransport};
+
+#[cfg(feature = "std")]
+mod scenario;
+#[cfg(feature = "std")]
+pub use scenario::{Scenario, ScenarioConfig, ScenarioGame, ScenarioMetrics};
+
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+mod fuzz;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub use fuzz::QueueOp;
+#[cfg(feature = "proptest")]
+pub use fuzz::{ack_header_strategy, queue_ops_strategy, sequence_strategy};
+
+#[cfg(feature = "std")]
+mod determinism;
+#[cfg(feature = "std")]
+pub use determinism::{DeterminismChecker, DeterminismConfig, Deterministic};
+
+#[cfg(feature = "quinn")]
+mod quic;
+#[cfg(feature = "quinn")]
+pub use quic::{QuinnError, QuinnTransport};
+
+#[cfg(feature = "tokio")]
+mod udp;
+#[cfg(feature = "tokio")]
+pub use udp::TokioUdpTransport;
+
+#[cfg(all(target_family = "wasm", feature = "websocket"))]
+mod web;
+#[cfg(all(target_family = "wasm", feature = "webtransport"))]
+pub use web::{BrowserTransport, WebTransportDatagrams};
+#[cfg(all(target_family = "wasm", feature = "websocket"))]
+pub use web::{WebError, WebSocketTransport};
+
+#[cfg(feature = "webrtc")]
+mod rtc;
+#[cfg(feature = "webrtc")]
+pub use rtc::{WebRtcError, WebRtcTransport, data_channel_init};
+
+#[cfg(feature = "steamworks")]
+mod steam;
+#[cfg(feature = "steamworks")]
+pub use steam::{SteamError, SteamTransport};
+
+#[cfg(feature = "renet")]
+mod bridge;
+#[cfg(feature = "renet")]
+pub use bridge::{RenetClientTransport, RenetError, RenetServerTransport, renet_channel_config};
+
+#[cfg(feature = "std")]
+mod socket;
+#[cfg(feature = "std")]
+pub use socket::UdpTransport;
+
+#[cfg(feature = "std")]
+mod handoff;
+#[cfg(feature = "std")]
+pub use handoff::{HandoffReceiver, HandoffSender, handoff};
+
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+pub use shared::{SharedInputQueue, SharedInputQueues};
+
+#[cfg(feature = "std")]
+mod discovery;
+#[cfg(feature = "std")]
+pub use discovery::{LanAnnouncer, LanBrowser, LanServer};
+
+#[cfg(feature = "std")]
+mod query;
+#[cfg(feature = "std")]
+pub use query::{QueryClient, QueryReply, QueryResponder};
+
+#[cfg(feature = "std")]
+mod metrics;
+#[cfg(feature = "std")]
+pub use metrics::{MemoryMetrics, Metrics, NoMetrics, Prefixed, ReportMetrics};
+
+#[cfg(feature = "std")]
+mod rtt;
+#[cfg(feature = "std")]
+pub use rtt::RttEstimator;
+
+#[cfg(feature = "std")]
+mod quality;
+#[cfg(feature = "std")]
+pub use quality::{ConnectionQuality, QualityConfig, QualityFactor};
+
+#[cfg(feature = "std")]
+mod netgraph;
+#[cfg(feature = "std")]
+pub use netgraph::{NetGraph, TimeSeries};
+
+#[cfg(feature = "egui")]
+mod overlay;
+#[cfg(feature = "egui")]
+pub use overlay::DebugOverlay;
+
+#[cfg(feature = "openmetrics")]
+mod openmetrics;
+#[cfg(feature = "openmetrics")]
+pub use openmetrics::{LabeledMetrics, OpenMetricsExporter};
+
+#[cfg(feature = "std")]
+mod health;
+#[cfg(feature = "std")]
+pub use health::{ClientHealth, HealthReport};
+
+#[cfg(feature = "std")]
+mod profile;
+#[cfg(feature = "std")]
+pub use profile::{ReplayProfiler, ReplayStats};
+
+#[cfg(feature = "std")]
+mod audio;
+#[cfg(feature = "std")]
+pub use audio::{AudioJitterBuffer, AudioJitterConfig, AudioJitterStats};
+
+#[cfg(feature = "std")]
+mod pacing;
+#[cfg(feature = "std")]
+pub use pacing::{Pace, Pacer};
+
+mod tickrate;
+pub use tickrate::{TickConfig, TickConversion, TickRateMessage, TickTimeline, choose_tick_rate};
+
+mod adaptive;
+pub use adaptive::{TickRateController, TickRateControllerConfig};
+
+mod catch_up;
+pub use catch_up::{CatchUp, CatchUpConfig};
+
+pub mod seq;
+
+mod sampling;
+pub use sampling::{AxisAverage, ButtonLatch, ButtonState, InputAccumulator};
+
+mod fixed;
+pub use fixed::FixedTime;
+
+mod client_prediction;
+pub use client_prediction::{ClientPrediction, PredictionStep};
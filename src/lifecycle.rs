@@ -0,0 +1,262 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    time::Duration,
+};
+
+use crate::{AckEvent, Instant, StateChannel};
+
+/// A message creating or destroying a replicated entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleMessage<E, P> {
+    /// Construct `entity` from `payload`
+    Create { entity: E, payload: P },
+    /// Destroy `entity`
+    Destroy { entity: E },
+}
+
+/// Reliably informs a client of entities being created and destroyed
+///
+/// Creation messages carry whatever payload is needed to construct the entity, and are resent
+/// until acknowledged. Updates to an entity must not be sent until its creation has been
+/// acknowledged, as reported by [`can_update`](Self::can_update), since the client can't apply
+/// them without it. Destruction is likewise resent until acknowledged.
+///
+/// Use one sender per client.
+#[derive(Debug, Clone)]
+pub struct LifecycleSender<E, P> {
+    entities: HashMap<E, State<P>>,
+    channel: StateChannel<E>,
+    /// Entities whose lifecycle messages were carried by each packet in flight, and whether each
+    /// message was a creation
+    in_flight: HashMap<u16, Vec<(E, bool)>>,
+}
+
+impl<E: Hash + Eq + Clone, P> LifecycleSender<E, P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Begin replicating `entity`, constructed on the client from `payload`
+    pub fn spawn(&mut self, entity: E, payload: P) {
+        self.entities
+            .insert(entity.clone(), State::Creating(payload));
+        self.channel.mark_dirty(entity);
+    }
+
+    /// Stop replicating `entity`, destroying it on the client
+    pub fn despawn(&mut self, entity: E) {
+        if self.entities.contains_key(&entity) {
+            self.entities.insert(entity.clone(), State::Destroying);
+            self.channel.mark_dirty(entity);
+        }
+    }
+
+    /// Get the next lifecycle message to write to an outgoing packet
+    pub fn pop(&mut self) -> Option<LifecycleMessage<E, &P>> {
+        loop {
+            let entity = self.channel.pop_dirty()?;
+            match self.entities.get(&entity) {
+                Some(State::Creating(payload)) => {
+                    return Some(LifecycleMessage::Create { entity, payload });
+                }
+                Some(State::Destroying) => return Some(LifecycleMessage::Destroy { entity }),
+                Some(State::Alive) | None => {}
+            }
+        }
+    }
+
+    /// Record that the packet with `sequence` carried the lifecycle messages for `entities`
+    pub fn on_sent(&mut self, sequence: u16, entities: Vec<E>) {
+        let messages = entities
+            .iter()
+            .map(|e| {
+                let creating = matches!(self.entities.get(e), Some(State::Creating(_)));
+                (e.clone(), creating)
+            })
+            .collect();
+        self.in_flight.insert(sequence, messages);
+        self.channel.on_sent(sequence, entities);
+    }
+
+    /// Process a delivery notification from the [`AckTracker`](crate::AckTracker) that assigned
+    /// the sequence numbers passed to [`on_sent`](Self::on_sent)
+    pub fn on_ack_event(&mut self, event: &AckEvent) {
+        let sequence = match *event {
+            AckEvent::Delivered(ref x) => x.sequence,
+            AckEvent::Lost(ref x) => x.sequence,
+        };
+        let messages = self.in_flight.remove(&sequence);
+        if let (AckEvent::Delivered(_), Some(messages)) = (event, messages) {
+            for (entity, create) in messages {
+                match (self.entities.get(&entity), create) {
+                    (Some(State::Creating(_)), true) => {
+                        self.entities.insert(entity.clone(), State::Alive);
+                        self.channel.remove(&entity);
+                    }
+                    (Some(State::Destroying), false) => {
+                        self.entities.remove(&entity);
+                        self.channel.remove(&entity);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.channel.on_ack_event(event);
+    }
+
+    /// Whether the client is known to have created `entity`, so updates may be sent
+    pub fn can_update(&self, entity: &E) -> bool {
+        matches!(self.entities.get(entity), Some(State::Alive))
+    }
+}
+
+impl<E, P> Default for LifecycleSender<E, P> {
+    fn default() -> Self {
+        Self {
+            entities: HashMap::new(),
+            channel: StateChannel::default(),
+            in_flight: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum State<P> {
+    Creating(P),
+    Alive,
+    Destroying,
+}
+
+/// Applies lifecycle messages from a [`LifecycleSender`]
+///
+/// Since messages may be duplicated and reordered, a stale creation message could arrive after
+/// the entity was destroyed. To prevent such resurrection, destroyed entities are remembered for
+/// a period, during which creation messages for them are ignored. Entity identifiers should not be
+/// reused within that period.
+#[derive(Debug, Clone)]
+pub struct LifecycleReceiver<E> {
+    alive: HashSet<E>,
+    tombstones: HashSet<E>,
+    /// Members of `tombstones` in the order they were created
+    tombstone_order: VecDeque<(Instant, E)>,
+    tombstone_duration: Duration,
+}
+
+impl<E: Hash + Eq + Clone> LifecycleReceiver<E> {
+    /// Construct a receiver that remembers destroyed entities for `tombstone_duration`
+    ///
+    /// `tombstone_duration` should exceed the longest time a packet might be delayed in transit.
+    pub fn new(tombstone_duration: Duration) -> Self {
+        Self {
+            alive: HashSet::new(),
+            tombstones: HashSet::new(),
+            tombstone_order: VecDeque::new(),
+            tombstone_duration,
+        }
+    }
+
+    /// Handle a creation message received at `now`
+    ///
+    /// Returns true if the entity should be constructed, or false if the message is a duplicate
+    /// or stale.
+    pub fn create(&mut self, entity: E, now: Instant) -> bool {
+        self.expire(now);
+        !self.tombstones.contains(&entity) && self.alive.insert(entity)
+    }
+
+    /// Handle a destruction message received at `now`
+    ///
+    /// Returns true if the entity existed and should be destroyed.
+    pub fn destroy(&mut self, entity: E, now: Instant) -> bool {
+        self.expire(now);
+        if self.tombstones.insert(entity.clone()) {
+            self.tombstone_order.push_back((now, entity.clone()));
+        }
+        self.alive.remove(&entity)
+    }
+
+    /// Whether `entity` exists, so updates to it should be applied
+    pub fn is_alive(&self, entity: &E) -> bool {
+        self.alive.contains(entity)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(time, ref entity)) = self.tombstone_order.front()
+            && now.saturating_duration_since(time) >= self.tombstone_duration
+        {
+            self.tombstones.remove(entity);
+            self.tombstone_order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LostPacket, PacketInfo};
+
+    fn delivered(sequence: u16) -> AckEvent {
+        AckEvent::Delivered(PacketInfo {
+            sequence,
+            bytes: 0,
+            sent: Instant::now(),
+            rtt: Duration::ZERO,
+        })
+    }
+
+    #[test]
+    fn sender() {
+        let mut sender = LifecycleSender::new();
+        sender.spawn('a', "goblin");
+        assert_eq!(
+            sender.pop(),
+            Some(LifecycleMessage::Create {
+                entity: 'a',
+                payload: &"goblin"
+            })
+        );
+        sender.on_sent(0, vec!['a']);
+        assert!(!sender.can_update(&'a'));
+        sender.on_ack_event(&AckEvent::Lost(LostPacket {
+            sequence: 0,
+            bytes: 0,
+            sent: Instant::now(),
+        }));
+        assert!(matches!(
+            sender.pop(),
+            Some(LifecycleMessage::Create { entity: 'a', .. })
+        ));
+        sender.on_sent(1, vec!['a']);
+        sender.on_ack_event(&delivered(1));
+        assert!(sender.can_update(&'a'));
+        assert_eq!(sender.pop(), None);
+
+        sender.despawn('a');
+        assert!(!sender.can_update(&'a'));
+        assert_eq!(
+            sender.pop(),
+            Some(LifecycleMessage::Destroy { entity: 'a' })
+        );
+        sender.on_sent(2, vec!['a']);
+        sender.on_ack_event(&delivered(2));
+        assert_eq!(sender.pop(), None);
+    }
+
+    #[test]
+    fn receiver() {
+        let now = Instant::now();
+        let mut receiver = LifecycleReceiver::new(Duration::from_secs(1));
+        assert!(receiver.create('a', now));
+        assert!(!receiver.create('a', now), "duplicate");
+        assert!(receiver.is_alive(&'a'));
+        assert!(receiver.destroy('a', now));
+        assert!(!receiver.is_alive(&'a'));
+        assert!(
+            !receiver.create('a', now),
+            "stale creation doesn't resurrect"
+        );
+        assert!(!receiver.destroy('a', now));
+        assert!(receiver.create('a', now + Duration::from_secs(2)), "reuse");
+    }
+}
@@ -0,0 +1,262 @@
+use std::time::Duration;
+
+use crate::{
+    FixedTime, InputMessage, Instant, SpectatorMessage, TickConfig, exchange::InputExchange,
+    spectator::SpectatorOutput,
+};
+
+/// Parameters of a [`LockstepSession`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LockstepConfig {
+    /// Number of participating players, each controlled by a separate peer
+    pub players: usize,
+    /// Number of frames between a local input being collected and taking effect
+    ///
+    /// Should exceed the one-way latency to the most distant peer, measured in frames, to avoid
+    /// stalling.
+    pub input_delay: u32,
+    /// How long the session must wait for inputs before reporting a [`Stall`]
+    pub stall_timeout: Duration,
+    /// If set, retain confirmed inputs for spectators, releasing them this many frames after
+    /// they're confirmed
+    pub spectator_delay: Option<u32>,
+    /// Rate at which frames are simulated, from which simulation time is derived
+    pub tick: TickConfig,
+}
+
+impl Default for LockstepConfig {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            input_delay: 4,
+            stall_timeout: Duration::from_millis(500),
+            spectator_delay: None,
+            tick: TickConfig::default(),
+        }
+    }
+}
+
+/// A [`LockstepSession`] waiting on inputs for longer than its configured timeout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stall {
+    /// The frame that can't be simulated
+    pub frame: u32,
+    /// How long the session has been waiting
+    pub duration: Duration,
+    /// Players whose inputs for `frame` haven't arrived
+    pub missing: Vec<usize>,
+}
+
+/// Peer-to-peer deterministic lockstep
+///
+/// Every peer advances to frame `f` only once every player's input for `f` is known, so no peer
+/// ever simulates speculatively, at the cost of the whole session stalling whenever any input is
+/// late. Local inputs are collected [`LockstepConfig::input_delay`] frames before they take
+/// effect to hide latency.
+///
+/// Each frame, pass the local input to [`add_local_input`](Self::add_local_input), exchange
+/// [`message`](Self::message)s with remote peers, and simulate every frame returned by
+/// [`advance`](Self::advance).
+#[derive(Debug, Clone)]
+pub struct LockstepSession<I> {
+    exchange: InputExchange<I>,
+    delay: u32,
+    stall_timeout: Duration,
+    /// The next frame to simulate
+    frame: u32,
+    /// When we began waiting for inputs for `frame`
+    waiting_since: Option<Instant>,
+    spectators: Option<SpectatorOutput>,
+    tick: TickConfig,
+}
+
+impl<I: Clone + Default> LockstepSession<I> {
+    /// Begin a session in which the local peer controls player `local`
+    pub fn new(config: LockstepConfig, local: usize) -> Self {
+        Self {
+            exchange: InputExchange::new(config.players, local, config.input_delay),
+            delay: config.input_delay,
+            stall_timeout: config.stall_timeout,
+            frame: 0,
+            waiting_since: None,
+            spectators: config.spectator_delay.map(SpectatorOutput::new),
+            tick: config.tick,
+        }
+    }
+
+    /// Collect the local input for the frame `input_delay` frames after the next to be simulated
+    ///
+    /// Returns `false` if that frame's input was already collected, e.g. because the session is
+    /// stalled or the delay was reduced. If earlier frames' inputs weren't collected, e.g.
+    /// because the delay was increased, they're filled with copies of `input`.
+    pub fn add_local_input(&mut self, input: I) -> bool {
+        let target = self.frame + self.delay;
+        if self.exchange.confirmed_frames(self.exchange.local()) > target {
+            return false;
+        }
+        while self.exchange.confirmed_frames(self.exchange.local()) < target {
+            self.exchange.add_local(input.clone());
+        }
+        self.exchange.add_local(input);
+        true
+    }
+
+    /// Change the number of frames between a local input being collected and taking effect,
+    /// e.g. as agreed by a [`DelayNegotiator`](crate::DelayNegotiator)
+    pub fn set_input_delay(&mut self, delay: u32) {
+        self.delay = delay;
+    }
+
+    /// Construct a message carrying local inputs to the peer controlling `player`
+    pub fn message(&self, player: usize) -> InputMessage<I> {
+        self.exchange.message(player, self.frame)
+    }
+
+    /// Process a message from a remote peer
+    pub fn receive(&mut self, msg: InputMessage<I>) {
+        self.exchange.receive(msg);
+    }
+
+    /// Collect confirmed inputs for spectators that haven't yet been collected
+    ///
+    /// Returns `None` if there are none, or if spectators weren't enabled by
+    /// [`LockstepConfig::spectator_delay`]. The result should be delivered reliably to every
+    /// spectator and passed to a [`SpectatorReplay`](crate::SpectatorReplay).
+    pub fn spectator_message(&mut self) -> Option<SpectatorMessage<I>> {
+        self.spectators.as_mut()?.take(&self.exchange)
+    }
+
+    /// Take every player's input for the next frame, if all are known
+    pub fn advance(&mut self, now: Instant) -> Option<(u32, Vec<I>)> {
+        if self.exchange.all_confirmed() <= self.frame {
+            self.waiting_since.get_or_insert(now);
+            return None;
+        }
+        self.waiting_since = None;
+        let frame = self.frame;
+        let inputs = (0..self.exchange.players())
+            .map(|p| self.exchange.confirmed(p, frame).unwrap().clone())
+            .collect();
+        self.frame += 1;
+        let retain = self
+            .spectators
+            .map_or(self.frame, |x| x.next().min(self.frame));
+        self.exchange.discard_before(retain);
+        Some((frame, inputs))
+    }
+}
+
+impl<I> LockstepSession<I> {
+    /// The next frame to be simulated
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Simulation time at the start of `frame`, as a deterministic [`FixedTime`]
+    pub fn frame_time(&self, frame: u32) -> FixedTime {
+        self.tick.fixed_time(frame.into())
+    }
+
+    /// Simulation time that elapses during `frame`
+    pub fn frame_step(&self, frame: u32) -> FixedTime {
+        self.tick.fixed_step(frame.into())
+    }
+
+    /// The player controlled by the local peer
+    pub fn local_player(&self) -> usize {
+        self.exchange.local()
+    }
+
+    /// Players whose inputs for the next frame haven't arrived
+    pub fn waiting_on(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.exchange.players()).filter(|&p| self.exchange.confirmed_frames(p) <= self.frame)
+    }
+
+    /// Report if [`advance`](Self::advance) has been waiting on inputs for longer than the
+    /// configured timeout
+    ///
+    /// Persistent stalls typically indicate that a peer has disconnected.
+    pub fn stall(&self, now: Instant) -> Option<Stall> {
+        let duration = now.saturating_duration_since(self.waiting_since?);
+        (duration >= self.stall_timeout).then(|| Stall {
+            frame: self.frame,
+            duration,
+            missing: self.waiting_on().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockstep() {
+        let config = LockstepConfig {
+            input_delay: 2,
+            ..LockstepConfig::default()
+        };
+        let mut a = LockstepSession::new(config, 0);
+        let mut b = LockstepSession::new(config, 1);
+        let now = Instant::now();
+        assert!(a.add_local_input(7));
+        assert!(!a.add_local_input(8), "one input per frame");
+        assert_eq!(a.advance(now), Some((0, vec![0, 0])));
+        assert_eq!(a.advance(now), Some((1, vec![0, 0])));
+        assert_eq!(a.advance(now), None);
+        assert_eq!(a.waiting_on().collect::<Vec<_>>(), [1]);
+        assert_eq!(a.stall(now), None);
+        let later = now + config.stall_timeout;
+        assert_eq!(
+            a.stall(later),
+            Some(Stall {
+                frame: 2,
+                duration: config.stall_timeout,
+                missing: vec![1],
+            })
+        );
+
+        assert!(b.add_local_input(9));
+        a.receive(b.message(0));
+        b.receive(a.message(1));
+        assert_eq!(a.advance(later), Some((2, vec![7, 9])));
+        assert_eq!(a.stall(later), None);
+        for frame in 0..3 {
+            assert_eq!(b.advance(now).map(|x| x.0), Some(frame));
+        }
+        assert_eq!(b.advance(now), None);
+
+        // 60Hz by default
+        assert_eq!(a.frame_time(60), FixedTime::from_secs(1));
+        assert_eq!(a.frame_time(3) + a.frame_step(3), a.frame_time(4));
+    }
+
+    #[test]
+    fn spectate() {
+        let config = LockstepConfig {
+            input_delay: 1,
+            spectator_delay: Some(0),
+            ..LockstepConfig::default()
+        };
+        let mut a = LockstepSession::new(config, 0);
+        let mut b = LockstepSession::new(config, 1);
+        let now = Instant::now();
+        for frame in 0..4 {
+            a.add_local_input(frame);
+            b.add_local_input(10 + frame);
+            a.receive(b.message(0));
+            b.receive(a.message(1));
+            a.advance(now).unwrap();
+            b.advance(now).unwrap();
+        }
+        let msg = a.spectator_message().unwrap();
+        assert_eq!(msg.start, 0);
+        assert_eq!(
+            msg.frames,
+            [[0, 0], [0, 10], [1, 11], [2, 12], [3, 13]].map(Vec::from)
+        );
+        assert_eq!(a.spectator_message(), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+use crate::{AckEvent, seq};
+
+/// Measures the fraction of packets lost in one direction of a connection
+///
+/// Loss of outgoing packets is measured by feeding in [`AckEvent`]s from the connection's
+/// [`AckTracker`](crate::AckTracker). Loss of incoming packets is measured by feeding in the
+/// sequence number of every packet received, such that gaps are presumed lost until they turn up.
+/// Use a separate tracker for each direction.
+///
+/// Two figures are produced: the [`instantaneous`](Self::instantaneous) loss over the most recent
+/// `window` sequence numbers, which responds quickly but is noisy, and a
+/// [`smoothed`](Self::smoothed) figure which incorporates each packet's final outcome once it
+/// leaves the window.
+#[derive(Debug, Clone)]
+pub struct LossTracker {
+    config: LossConfig,
+    /// Outcome of each sequence number in the window, ending at `latest`
+    outcomes: VecDeque<Outcome>,
+    latest: u16,
+    smoothed: Option<f32>,
+}
+
+impl LossTracker {
+    pub fn new(config: LossConfig) -> Self {
+        Self {
+            outcomes: VecDeque::with_capacity(config.window.into()),
+            config,
+            latest: 0,
+            smoothed: None,
+        }
+    }
+
+    /// Record receipt of a packet from the peer
+    ///
+    /// Sequence numbers skipped over are presumed lost until received.
+    pub fn on_received(&mut self, sequence: u16) {
+        self.record(sequence, Outcome::Delivered, Outcome::Lost);
+    }
+
+    /// Record the outcome of a packet sent to the peer
+    pub fn on_ack_event(&mut self, event: &AckEvent) {
+        match *event {
+            AckEvent::Delivered(ref packet) => {
+                self.record(packet.sequence, Outcome::Delivered, Outcome::Unknown)
+            }
+            AckEvent::Lost(ref packet) => {
+                self.record(packet.sequence, Outcome::Lost, Outcome::Unknown)
+            }
+        }
+    }
+
+    /// Fraction of packets in the window that were lost, from 0 to 1
+    pub fn instantaneous(&self) -> f32 {
+        let mut lost = 0;
+        let mut known = 0;
+        for &outcome in &self.outcomes {
+            match outcome {
+                Outcome::Unknown => {}
+                Outcome::Delivered => known += 1,
+                Outcome::Lost => {
+                    known += 1;
+                    lost += 1;
+                }
+            }
+        }
+        if known == 0 {
+            return 0.0;
+        }
+        lost as f32 / known as f32
+    }
+
+    /// Exponentially weighted moving average of loss, from 0 to 1
+    ///
+    /// Falls back to the instantaneous figure until a packet has left the window.
+    pub fn smoothed(&self) -> f32 {
+        self.smoothed.unwrap_or_else(|| self.instantaneous())
+    }
+
+    /// Set the outcome of `sequence`, filling any newly-skipped sequence numbers with `skipped`
+    fn record(&mut self, sequence: u16, outcome: Outcome, skipped: Outcome) {
+        if self.outcomes.is_empty() {
+            self.latest = sequence;
+            self.outcomes.push_back(outcome);
+            return;
+        }
+        let diff = seq::diff(sequence, self.latest);
+        if diff > 0 {
+            for _ in 1..diff {
+                self.push(skipped);
+            }
+            self.push(outcome);
+            self.latest = sequence;
+            return;
+        }
+        let age = diff.unsigned_abs() as usize;
+        if age < self.outcomes.len() {
+            let index = self.outcomes.len() - 1 - age;
+            self.outcomes[index] = outcome;
+        }
+    }
+
+    fn push(&mut self, outcome: Outcome) {
+        if self.outcomes.len() >= usize::from(self.config.window) {
+            let lost = match self.outcomes.pop_front().unwrap() {
+                Outcome::Unknown => None,
+                Outcome::Delivered => Some(0.0),
+                Outcome::Lost => Some(1.0),
+            };
+            if let Some(lost) = lost {
+                let smoothed = self.smoothed.get_or_insert(lost);
+                *smoothed += self.config.smoothing * (lost - *smoothed);
+            }
+        }
+        self.outcomes.push_back(outcome);
+    }
+}
+
+/// Parameters for a [`LossTracker`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LossConfig {
+    /// Number of recent sequence numbers to compute instantaneous loss over
+    ///
+    /// Larger windows tolerate more reordering before a late packet is counted as lost.
+    pub window: u16,
+    /// Weight given to each packet's outcome in the smoothed loss, from 0 to 1
+    pub smoothing: f32,
+}
+
+impl Default for LossConfig {
+    fn default() -> Self {
+        Self {
+            window: 64,
+            smoothing: 0.02,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Outcome {
+    Unknown,
+    Delivered,
+    Lost,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps() {
+        let mut loss = LossTracker::new(LossConfig::default());
+        for seq in [0, 1, 3, 4] {
+            loss.on_received(seq);
+        }
+        assert_eq!(loss.instantaneous(), 0.2);
+        loss.on_received(2);
+        assert_eq!(loss.instantaneous(), 0.0, "late arrivals aren't lost");
+    }
+
+    #[test]
+    fn smoothing() {
+        let mut loss = LossTracker::new(LossConfig {
+            window: 4,
+            smoothing: 0.5,
+        });
+        for seq in (0..8).step_by(2) {
+            loss.on_received(seq);
+        }
+        assert_eq!(loss.instantaneous(), 0.5);
+        assert!(loss.smoothed() > 0.0 && loss.smoothed() < 1.0);
+        for seq in 8..100 {
+            loss.on_received(seq);
+        }
+        assert_eq!(loss.instantaneous(), 0.0);
+        assert!(loss.smoothed() < 0.001);
+    }
+
+    #[test]
+    fn wrap() {
+        let mut loss = LossTracker::new(LossConfig::default());
+        loss.on_received(u16::MAX);
+        loss.on_received(1);
+        assert!((loss.instantaneous() - 1.0 / 3.0).abs() < 1e-6);
+    }
+}
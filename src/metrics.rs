@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    AckTracker, CongestionController, InputQueue, LossTracker, NetworkSimulator, ReorderTracker,
+    RollbackSession, RttEstimator, Scenario, ScenarioGame, SharedInputQueue,
+};
+
+/// Destination for numbers reported by the crate's components, e.g. a metrics exporter
+///
+/// Components implementing [`ReportMetrics`] report their current figures on demand, so no sink
+/// need be threaded through them. All methods do nothing by default, so a sink need only
+/// implement those it's interested in. Names are dot-separated paths, which may be qualified
+/// with [`Prefixed`] to distinguish multiple instances of a component.
+pub trait Metrics {
+    /// Report the running total of a count that only increases, e.g. packets sent
+    fn counter(&mut self, name: &str, total: u64) {
+        let _ = (name, total);
+    }
+
+    /// Report the current value of a quantity that may rise and fall, e.g. a queue's length
+    fn gauge(&mut self, name: &str, value: f64) {
+        let _ = (name, value);
+    }
+
+    /// Record one observation of a distribution, e.g. a round-trip time
+    fn histogram(&mut self, name: &str, value: f64) {
+        let _ = (name, value);
+    }
+}
+
+/// A component that can report its state to a [`Metrics`] sink
+pub trait ReportMetrics {
+    fn report_metrics(&self, metrics: &mut dyn Metrics);
+}
+
+/// A [`Metrics`] sink that discards everything
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
+
+/// A [`Metrics`] sink that retains the latest counter and gauge values and every histogram
+/// observation, e.g. for tests or a debug overlay
+#[derive(Debug, Clone, Default)]
+pub struct MemoryMetrics {
+    pub counters: BTreeMap<String, u64>,
+    pub gauges: BTreeMap<String, f64>,
+    pub histograms: BTreeMap<String, Vec<f64>>,
+}
+
+impl MemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Metrics for MemoryMetrics {
+    fn counter(&mut self, name: &str, total: u64) {
+        self.counters.insert(name.into(), total);
+    }
+
+    fn gauge(&mut self, name: &str, value: f64) {
+        self.gauges.insert(name.into(), value);
+    }
+
+    fn histogram(&mut self, name: &str, value: f64) {
+        self.histograms.entry(name.into()).or_default().push(value);
+    }
+}
+
+/// Qualifies the names of metrics passed to another sink with a prefix
+///
+/// For example, a server might report each client's [`InputQueue`] under `client.{id}`.
+pub struct Prefixed<'a> {
+    prefix: &'a str,
+    inner: &'a mut dyn Metrics,
+}
+
+impl<'a> Prefixed<'a> {
+    pub fn new(prefix: &'a str, inner: &'a mut dyn Metrics) -> Self {
+        Self { prefix, inner }
+    }
+
+    fn name(&self, name: &str) -> String {
+        format!("{}.{}", self.prefix, name)
+    }
+}
+
+impl Metrics for Prefixed<'_> {
+    fn counter(&mut self, name: &str, total: u64) {
+        let name = self.name(name);
+        self.inner.counter(&name, total);
+    }
+
+    fn gauge(&mut self, name: &str, value: f64) {
+        let name = self.name(name);
+        self.inner.gauge(&name, value);
+    }
+
+    fn histogram(&mut self, name: &str, value: f64) {
+        let name = self.name(name);
+        self.inner.histogram(&name, value);
+    }
+}
+
+impl<T> ReportMetrics for InputQueue<T> {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        metrics.gauge("input_queue.len", self.len() as f64);
+    }
+}
+
+impl<T> ReportMetrics for SharedInputQueue<T> {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        metrics.gauge("input_queue.len", self.len() as f64);
+        metrics.counter("input_queue.overruns", self.overruns());
+        metrics.counter("input_queue.misses", self.misses());
+    }
+}
+
+impl ReportMetrics for AckTracker {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        metrics.gauge("ack.in_flight", self.in_flight() as f64);
+    }
+}
+
+impl ReportMetrics for LossTracker {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        metrics.gauge("loss.smoothed", self.smoothed().into());
+    }
+}
+
+impl ReportMetrics for RttEstimator {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        if let Some(rtt) = self.smoothed() {
+            metrics.gauge("rtt.smoothed", rtt.as_secs_f64());
+        }
+        metrics.gauge("rtt.variation", self.variation().as_secs_f64());
+    }
+}
+
+impl ReportMetrics for ReorderTracker {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        let stats = self.stats();
+        metrics.counter("reorder.received", stats.received);
+        metrics.counter("reorder.late", stats.late);
+        metrics.gauge("reorder.late_fraction", stats.late_fraction.into());
+        metrics.gauge("reorder.mean_displacement", stats.mean_displacement.into());
+    }
+}
+
+impl ReportMetrics for CongestionController {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        metrics.gauge("congestion.datagram_rate", self.datagram_rate().into());
+        metrics.gauge("congestion.snapshot_rate", self.snapshot_rate().into());
+    }
+}
+
+impl<I, S, P> ReportMetrics for RollbackSession<I, S, P> {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        let stats = self.stats();
+        metrics.counter("rollback.rollbacks", stats.rollbacks);
+        metrics.counter("rollback.resimulated_frames", stats.resimulated_frames);
+        metrics.counter("rollback.predictions", stats.predictions);
+        metrics.counter("rollback.mispredictions", stats.mispredictions);
+        metrics.gauge("rollback.mean_depth", stats.mean_depth().into());
+        self.profiler()
+            .report_metrics(&mut Prefixed::new("rollback", metrics));
+    }
+}
+
+impl<T: Clone> ReportMetrics for NetworkSimulator<T> {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        let stats = self.stats();
+        metrics.counter("simulator.sent", stats.sent);
+        metrics.counter("simulator.lost", stats.lost);
+        metrics.counter("simulator.duplicated", stats.duplicated);
+        metrics.counter("simulator.reordered", stats.reordered);
+        metrics.counter("simulator.dropped", stats.dropped);
+    }
+}
+
+impl<G: ScenarioGame> ReportMetrics for Scenario<G> {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        let stats = self.metrics();
+        metrics.counter("scenario.frames", stats.frames);
+        metrics.counter("scenario.input_underruns", stats.input_underruns);
+        metrics.counter("scenario.snapshot_underruns", stats.snapshot_underruns);
+        metrics.counter("scenario.mispredictions", stats.mispredictions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instant;
+
+    #[test]
+    fn prefixed() {
+        let mut queue = InputQueue::new();
+        queue.push(4, (), Instant::now());
+        let mut metrics = MemoryMetrics::new();
+        queue.report_metrics(&mut Prefixed::new("client.7", &mut metrics));
+        Prefixed::new("server", &mut metrics).histogram("tick_time", 0.5);
+        NoMetrics.counter("ignored", 1);
+        assert_eq!(metrics.gauges["client.7.input_queue.len"], 1.0);
+        assert_eq!(metrics.histograms["server.tick_time"], [0.5]);
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{Instant, seq};
+
+/// Detects gaps in received sequence numbers and decides when to request their repair
+///
+/// Waiting for the sender to notice loss takes at least a round trip, plus however long it waits
+/// to be confident a packet isn't just delayed. A receiver can notice a gap as soon as a later
+/// packet arrives, so requesting repair with a negative acknowledgement (NACK) can recover much
+/// sooner. Pair with a [`RepairBuffer`] on the sending side.
+#[derive(Debug, Clone)]
+pub struct NackGenerator {
+    config: NackConfig,
+    latest: Option<u16>,
+    /// Sequence numbers not yet received, in ascending order
+    missing: VecDeque<Missing>,
+}
+
+impl NackGenerator {
+    pub fn new(config: NackConfig) -> Self {
+        Self {
+            config,
+            latest: None,
+            missing: VecDeque::new(),
+        }
+    }
+
+    /// Record receipt of a packet
+    pub fn on_received(&mut self, sequence: u16, now: Instant) {
+        let Some(latest) = self.latest else {
+            self.latest = Some(sequence);
+            return;
+        };
+        let diff = seq::diff(sequence, latest);
+        if diff <= 0 {
+            // Fill in a gap, if it's one we're tracking
+            if let Some(index) = self.missing.iter().position(|x| x.sequence == sequence) {
+                self.missing.remove(index);
+            }
+            return;
+        }
+        // Skip gaps that would immediately fall outside the window
+        let first = (diff - i32::from(self.config.window)).max(1);
+        for i in first..diff {
+            self.missing.push_back(Missing {
+                sequence: latest.wrapping_add(i as u16),
+                detected: now,
+                requested: None,
+                attempts: 0,
+            });
+        }
+        self.latest = Some(sequence);
+        // Forget gaps too old to be worth repairing
+        while let Some(oldest) = self.missing.front() {
+            if seq::diff(sequence, oldest.sequence) <= self.config.window.into() {
+                break;
+            }
+            self.missing.pop_front();
+        }
+    }
+
+    /// Sequence numbers whose repair should be requested at `now`
+    ///
+    /// Each call marks the returned sequence numbers as requested, so they will not be returned
+    /// again until `retry_interval` has passed.
+    pub fn poll(&mut self, now: Instant) -> Vec<u16> {
+        let mut result = Vec::new();
+        for missing in &mut self.missing {
+            let due = match missing.requested {
+                None => now.saturating_duration_since(missing.detected) >= self.config.delay,
+                Some(t) => now.saturating_duration_since(t) >= self.config.retry_interval,
+            };
+            if due && missing.attempts < self.config.max_attempts {
+                missing.requested = Some(now);
+                missing.attempts += 1;
+                result.push(missing.sequence);
+            }
+        }
+        // Give up on gaps we've exhausted our attempts on
+        let max_attempts = self.config.max_attempts;
+        self.missing
+            .retain(|x| x.attempts < max_attempts || x.requested == Some(now));
+        result
+    }
+
+    /// Number of sequence numbers currently believed missing
+    pub fn missing(&self) -> usize {
+        self.missing.len()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Missing {
+    sequence: u16,
+    detected: Instant,
+    requested: Option<Instant>,
+    attempts: u32,
+}
+
+/// Parameters for a [`NackGenerator`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct NackConfig {
+    /// How long to wait after detecting a gap before requesting repair
+    ///
+    /// Small nonzero values avoid spurious requests when packets are merely reordered.
+    pub delay: Duration,
+    /// How long to wait for a repair before requesting it again
+    ///
+    /// Should be somewhat greater than the round-trip time.
+    pub retry_interval: Duration,
+    /// Maximum number of times to request repair of a single packet
+    pub max_attempts: u32,
+    /// How far behind the latest received sequence number a gap may be before it's forgotten
+    pub window: u16,
+}
+
+impl Default for NackConfig {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(5),
+            retry_interval: Duration::from_millis(250),
+            max_attempts: 3,
+            window: 128,
+        }
+    }
+}
+
+/// Recently sent payloads, retained so they can be resent in response to NACKs
+///
+/// Not all data is worth repairing: by the time a NACK arrives, newer data may have made a lost
+/// payload obsolete. [`repairs`](Self::repairs) therefore lets the sender filter out anything no
+/// longer relevant.
+#[derive(Debug, Clone)]
+pub struct RepairBuffer<T> {
+    capacity: usize,
+    /// Payloads in the order they were sent
+    sent: VecDeque<(u16, T)>,
+}
+
+impl<T> RepairBuffer<T> {
+    /// Construct a buffer that retains up to `capacity` payloads
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            sent: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Retain `payload`, sent with sequence number `sequence`, for possible repair
+    pub fn push(&mut self, sequence: u16, payload: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.sent.len() >= self.capacity {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((sequence, payload));
+    }
+
+    /// Look up the payload sent with `sequence`, if still retained
+    pub fn get(&self, sequence: u16) -> Option<&T> {
+        self.sent
+            .iter()
+            .rev()
+            .find(|x| x.0 == sequence)
+            .map(|x| &x.1)
+    }
+
+    /// Payloads requested by the peer in `nacks` for which `relevant` returns `true`
+    ///
+    /// Sequence numbers that are no longer retained are skipped.
+    pub fn repairs<'a>(
+        &'a self,
+        nacks: &'a [u16],
+        mut relevant: impl FnMut(u16, &T) -> bool + 'a,
+    ) -> impl Iterator<Item = (u16, &'a T)> + 'a {
+        nacks.iter().filter_map(move |&sequence| {
+            let payload = self.get(sequence)?;
+            relevant(sequence, payload).then_some((sequence, payload))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let config = NackConfig::default();
+        let mut nacks = NackGenerator::new(config);
+        let start = Instant::now();
+        for seq in [0, 1, 4, 5] {
+            nacks.on_received(seq, start);
+        }
+        assert_eq!(nacks.missing(), 2);
+        assert!(nacks.poll(start).is_empty(), "wait for reordering");
+        let now = start + config.delay;
+        assert_eq!(nacks.poll(now), [2, 3]);
+        assert!(nacks.poll(now).is_empty(), "not immediately repeated");
+
+        nacks.on_received(3, now);
+        assert_eq!(nacks.missing(), 1);
+        let now = now + config.retry_interval;
+        assert_eq!(nacks.poll(now), [2]);
+        let now = now + config.retry_interval;
+        assert_eq!(nacks.poll(now), [2]);
+        let now = now + config.retry_interval;
+        assert!(nacks.poll(now).is_empty(), "gave up");
+        assert_eq!(nacks.missing(), 0);
+    }
+
+    #[test]
+    fn window() {
+        let mut nacks = NackGenerator::new(NackConfig {
+            window: 10,
+            ..NackConfig::default()
+        });
+        let now = Instant::now();
+        nacks.on_received(u16::MAX - 5, now);
+        nacks.on_received(u16::MAX, now);
+        assert_eq!(nacks.missing(), 4);
+        nacks.on_received(9, now);
+        assert_eq!(nacks.missing(), 9, "older gaps are forgotten");
+        nacks.on_received(30_000, now);
+        assert_eq!(nacks.missing(), 10, "large jumps track only the window");
+    }
+
+    #[test]
+    fn repair() {
+        let mut buffer = RepairBuffer::new(2);
+        buffer.push(0, "a");
+        buffer.push(1, "b");
+        buffer.push(2, "c");
+        assert_eq!(buffer.get(0), None);
+        assert_eq!(
+            buffer
+                .repairs(&[0, 1, 2], |_, &payload| payload != "b")
+                .collect::<Vec<_>>(),
+            [(2, &"c")]
+        );
+
+        let mut buffer = RepairBuffer::new(0);
+        buffer.push(0, "a");
+        buffer.push(1, "b");
+        assert_eq!(buffer.get(1), None, "zero capacity retains nothing");
+    }
+}
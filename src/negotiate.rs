@@ -0,0 +1,238 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{Instant, RttEstimator};
+
+/// Parameters governing the session timing chosen by a [`DelayNegotiator`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct NegotiationConfig {
+    /// Duration of a simulation frame
+    pub frame_interval: Duration,
+    pub min_delay: u32,
+    /// Largest acceptable input delay
+    ///
+    /// Latency in excess of this is covered by rollback instead. Lockstep sessions, which can't
+    /// roll back, should set this high.
+    pub max_delay: u32,
+    /// Frames of rollback allowed beyond the latency not covered by input delay, to absorb
+    /// unexpected spikes
+    pub rollback_margin: u32,
+    /// Largest acceptable rollback window
+    pub max_rollback: u32,
+}
+
+impl Default for NegotiationConfig {
+    fn default() -> Self {
+        Self {
+            frame_interval: Duration::from_secs(1) / 60,
+            min_delay: 1,
+            max_delay: 3,
+            rollback_margin: 2,
+            max_rollback: 12,
+        }
+    }
+}
+
+/// Input delay and rollback window for a peer-to-peer session
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SessionTiming {
+    pub input_delay: u32,
+    pub max_rollback: u32,
+}
+
+/// A message exchanged between [`DelayNegotiator`]s
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NegotiationMessage {
+    /// Request for an immediate [`Pong`](Self::Pong), to measure round-trip time
+    Ping {
+        id: u16,
+    },
+    Pong {
+        id: u16,
+    },
+    /// The sender's preferred timing, superseding proposals with lower revisions
+    Propose {
+        revision: u32,
+        timing: SessionTiming,
+    },
+}
+
+/// Agrees on an input delay and rollback window suited to the connections between peers
+///
+/// Each peer periodically [`ping`](Self::ping)s the others to measure round-trip time, and
+/// [`propose`](Self::propose)s the timing that would hide the latency and jitter of its slowest
+/// connection. The agreed timing is the most conservative of every peer's latest proposal, so all
+/// peers converge on the same result once proposals have been exchanged. Proposals are revised
+/// as conditions change, allowing the timing to be renegotiated mid-session.
+#[derive(Debug, Clone)]
+pub struct DelayNegotiator {
+    config: NegotiationConfig,
+    local: usize,
+    peers: Vec<Peer>,
+    next_ping: u16,
+    /// Pings awaiting a response
+    pings: VecDeque<(u16, Instant)>,
+    /// The local peer's latest proposal
+    proposal: Option<(u32, SessionTiming)>,
+}
+
+impl DelayNegotiator {
+    /// Negotiate on behalf of player `local` among `players` players
+    pub fn new(config: NegotiationConfig, players: usize, local: usize) -> Self {
+        Self {
+            config,
+            local,
+            peers: vec![Peer::default(); players],
+            next_ping: 0,
+            pings: VecDeque::new(),
+            proposal: None,
+        }
+    }
+
+    /// Construct a ping to send to every remote peer
+    pub fn ping(&mut self, now: Instant) -> NegotiationMessage {
+        let id = self.next_ping;
+        self.next_ping = self.next_ping.wrapping_add(1);
+        if self.pings.len() >= MAX_PINGS {
+            self.pings.pop_front();
+        }
+        self.pings.push_back((id, now));
+        NegotiationMessage::Ping { id }
+    }
+
+    /// Process a message from the peer controlling `player`, returning any response to send it
+    pub fn receive(
+        &mut self,
+        player: usize,
+        msg: NegotiationMessage,
+        now: Instant,
+    ) -> Option<NegotiationMessage> {
+        let peer = self
+            .peers
+            .get_mut(player)
+            .filter(|_| player != self.local)?;
+        match msg {
+            NegotiationMessage::Ping { id } => return Some(NegotiationMessage::Pong { id }),
+            NegotiationMessage::Pong { id } => {
+                let &(_, sent) = self.pings.iter().find(|&&(x, _)| x == id)?;
+                peer.rtt.sample(now.saturating_duration_since(sent));
+            }
+            NegotiationMessage::Propose { revision, timing } => {
+                if peer.proposal.is_none_or(|(r, _)| revision > r) {
+                    peer.proposal = Some((revision, timing));
+                }
+            }
+        }
+        None
+    }
+
+    /// Smoothed round-trip time to the peer controlling `player`, if measured
+    pub fn rtt(&self, player: usize) -> Option<Duration> {
+        self.peers.get(player)?.rtt.smoothed()
+    }
+
+    /// Construct a proposal to send to every remote peer, if the preferred timing has changed
+    ///
+    /// Returns `None` until round-trip time to every peer has been measured. Should be called
+    /// periodically, e.g. after each round of pings. Decreases in delay are subject to
+    /// hysteresis, to avoid flapping on connections near a threshold.
+    pub fn propose(&mut self) -> Option<NegotiationMessage> {
+        let mut latency = Duration::ZERO;
+        for (player, peer) in self.peers.iter().enumerate() {
+            if player != self.local {
+                latency = latency.max(peer.rtt.latency()?);
+            }
+        }
+        let frames = latency
+            .as_nanos()
+            .div_ceil(self.config.frame_interval.as_nanos().max(1));
+        let frames = u32::try_from(frames).unwrap_or(u32::MAX);
+        let mut input_delay = frames.clamp(self.config.min_delay, self.config.max_delay);
+        if let Some((_, old)) = self.proposal
+            && input_delay < old.input_delay
+            && input_delay + 1 >= old.input_delay
+        {
+            input_delay = old.input_delay;
+        }
+        let timing = SessionTiming {
+            input_delay,
+            max_rollback: (frames.saturating_sub(input_delay) + self.config.rollback_margin)
+                .min(self.config.max_rollback),
+        };
+        let revision = match self.proposal {
+            Some((_, old)) if old == timing => return None,
+            Some((revision, _)) => revision + 1,
+            None => 0,
+        };
+        self.proposal = Some((revision, timing));
+        Some(NegotiationMessage::Propose { revision, timing })
+    }
+
+    /// The timing agreed by all peers, once every peer has made a proposal
+    ///
+    /// May change over time as proposals are revised, in which case the new timing should be
+    /// applied to the session, e.g. with
+    /// [`RollbackSession::set_timing`](crate::RollbackSession::set_timing).
+    pub fn agreed(&self) -> Option<SessionTiming> {
+        let mut agreed = self.proposal?.1;
+        for (player, peer) in self.peers.iter().enumerate() {
+            if player != self.local {
+                let (_, timing) = peer.proposal?;
+                agreed.input_delay = agreed.input_delay.max(timing.input_delay);
+                agreed.max_rollback = agreed.max_rollback.max(timing.max_rollback);
+            }
+        }
+        Some(agreed)
+    }
+}
+
+/// Number of unanswered pings to remember
+const MAX_PINGS: usize = 16;
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Peer {
+    rtt: RttEstimator,
+    /// Latest proposal and its revision
+    proposal: Option<(u32, SessionTiming)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let config = NegotiationConfig::default();
+        let mut a = DelayNegotiator::new(config, 2, 0);
+        let mut b = DelayNegotiator::new(config, 2, 1);
+        let now = Instant::now();
+        let exchange = |a: &mut DelayNegotiator, b: &mut DelayNegotiator, rtt| {
+            for _ in 0..20 {
+                let pong = b.receive(0, a.ping(now), now).unwrap();
+                a.receive(1, pong, now + rtt);
+                let pong = a.receive(1, b.ping(now), now).unwrap();
+                b.receive(0, pong, now + rtt);
+            }
+            if let Some(msg) = a.propose() {
+                b.receive(0, msg, now);
+            }
+            if let Some(msg) = b.propose() {
+                a.receive(1, msg, now);
+            }
+        };
+
+        assert_eq!(a.agreed(), None);
+        exchange(&mut a, &mut b, Duration::from_millis(50));
+        let timing = a.agreed().unwrap();
+        assert_eq!(b.agreed(), Some(timing));
+        assert_eq!(timing.input_delay, 2);
+
+        // Latency beyond the maximum delay is covered by rollback
+        exchange(&mut a, &mut b, Duration::from_millis(200));
+        let timing = a.agreed().unwrap();
+        assert_eq!(b.agreed(), Some(timing));
+        assert_eq!(timing.input_delay, config.max_delay);
+        assert!(timing.max_rollback > config.rollback_margin);
+    }
+}
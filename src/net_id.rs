@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    hash::Hash,
+};
+
+/// Authoritative network identifier for a replicated entity
+///
+/// Combines a 24-bit index with an 8-bit generation, incremented each time the index is reused,
+/// so that stale references to a destroyed entity don't resolve to its successor.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NetId(u32);
+
+impl NetId {
+    const INDEX_BITS: u32 = 24;
+
+    /// Reconstruct an ID from [`to_bits`](Self::to_bits)
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Compact representation for transmission
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn index(self) -> u32 {
+        self.0 & ((1 << Self::INDEX_BITS) - 1)
+    }
+
+    pub fn generation(self) -> u8 {
+        (self.0 >> Self::INDEX_BITS) as u8
+    }
+
+    fn new(index: u32, generation: u8) -> Self {
+        Self(index | (u32::from(generation) << Self::INDEX_BITS))
+    }
+}
+
+impl fmt::Debug for NetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NetId({}v{})", self.index(), self.generation())
+    }
+}
+
+/// Bidirectional mapping between [`NetId`]s and local entity handles
+///
+/// The server [`allocate`](Self::allocate)s IDs for the entities it replicates; clients
+/// [`insert`](Self::insert) the IDs they're told about. A client may also spawn an entity
+/// speculatively with [`predict`](Self::predict), sending the returned token to the server, which
+/// echoes it back with the authoritative ID to be passed to [`confirm`](Self::confirm), or
+/// refuses it, leading to [`reject`](Self::reject).
+///
+/// Freed indices are reused in first-in first-out order, with a new generation, to minimize the
+/// chance of a delayed message being misattributed.
+#[derive(Debug, Clone)]
+pub struct NetIdMap<L> {
+    /// Current ID and local entity for each index in use
+    by_index: HashMap<u32, (NetId, L)>,
+    by_local: HashMap<L, Binding>,
+    /// Outstanding predictions
+    pending: HashMap<u32, L>,
+    next_prediction: u32,
+    /// Current generation of each index allocated so far
+    generations: Vec<u8>,
+    /// Indices available for reuse
+    free: VecDeque<u32>,
+}
+
+impl<L: Hash + Eq + Clone> NetIdMap<L> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign a new ID to `local`
+    ///
+    /// Panics if more than 2^24 IDs are simultaneously allocated.
+    pub fn allocate(&mut self, local: L) -> NetId {
+        let id = match self.free.pop_front() {
+            Some(index) => NetId::new(index, self.generations[index as usize]),
+            None => {
+                let index = self.generations.len() as u32;
+                assert!(index < 1 << NetId::INDEX_BITS, "too many IDs allocated");
+                self.generations.push(0);
+                NetId::new(index, 0)
+            }
+        };
+        self.bind(id, local);
+        id
+    }
+
+    /// Record that the entity identified by `id` is known locally as `local`
+    ///
+    /// Returns the local entity previously associated with `id`, or with an older generation of
+    /// the same index, which should be considered destroyed.
+    pub fn insert(&mut self, id: NetId, local: L) -> Option<L> {
+        let old = self
+            .by_index
+            .get(&id.index())
+            .map(|x| x.0)
+            .and_then(|old| self.remove(old));
+        self.bind(id, local);
+        old
+    }
+
+    /// Forget `id`, returning the local entity it referred to
+    ///
+    /// IDs obtained from [`allocate`](Self::allocate) become available for reuse.
+    pub fn remove(&mut self, id: NetId) -> Option<L> {
+        if self.by_index.get(&id.index())?.0 != id {
+            return None;
+        }
+        let (_, local) = self.by_index.remove(&id.index())?;
+        self.by_local.remove(&local);
+        if let Some(generation) = self.generations.get_mut(id.index() as usize)
+            && *generation == id.generation()
+        {
+            *generation = generation.wrapping_add(1);
+            self.free.push_back(id.index());
+        }
+        Some(local)
+    }
+
+    /// Begin tracking a speculatively spawned entity, returning a token to send to the server
+    pub fn predict(&mut self, local: L) -> u32 {
+        let token = self.next_prediction;
+        self.next_prediction = self.next_prediction.wrapping_add(1);
+        self.pending.insert(token, local.clone());
+        self.by_local.insert(local, Binding::Pending(token));
+        token
+    }
+
+    /// Associate the entity predicted with `token` with its authoritative `id`
+    ///
+    /// Returns the entity, or `None` if `token` is unknown.
+    pub fn confirm(&mut self, token: u32, id: NetId) -> Option<&L> {
+        let local = self.pending.remove(&token)?;
+        self.insert(id, local);
+        self.get(id)
+    }
+
+    /// Forget the entity predicted with `token`, returning it so it can be despawned
+    pub fn reject(&mut self, token: u32) -> Option<L> {
+        let local = self.pending.remove(&token)?;
+        self.by_local.remove(&local);
+        Some(local)
+    }
+
+    /// Look up the local entity for `id`
+    pub fn get(&self, id: NetId) -> Option<&L> {
+        let (current, local) = self.by_index.get(&id.index())?;
+        (*current == id).then_some(local)
+    }
+
+    /// Look up the ID of `local`, if confirmed
+    pub fn id(&self, local: &L) -> Option<NetId> {
+        match *self.by_local.get(local)? {
+            Binding::Confirmed(id) => Some(id),
+            Binding::Pending(_) => None,
+        }
+    }
+
+    /// The token `local` was predicted with, if it awaits confirmation
+    pub fn prediction(&self, local: &L) -> Option<u32> {
+        match *self.by_local.get(local)? {
+            Binding::Confirmed(_) => None,
+            Binding::Pending(token) => Some(token),
+        }
+    }
+
+    /// Whether `local` was predicted and awaits confirmation
+    pub fn is_pending(&self, local: &L) -> bool {
+        self.prediction(local).is_some()
+    }
+
+    /// Iterate over confirmed IDs and their local entities
+    pub fn iter(&self) -> impl Iterator<Item = (NetId, &L)> {
+        self.by_index.values().map(|(id, local)| (*id, local))
+    }
+
+    fn bind(&mut self, id: NetId, local: L) {
+        self.by_local.insert(local.clone(), Binding::Confirmed(id));
+        self.by_index.insert(id.index(), (id, local));
+    }
+}
+
+impl<L> Default for NetIdMap<L> {
+    fn default() -> Self {
+        Self {
+            by_index: HashMap::new(),
+            by_local: HashMap::new(),
+            pending: HashMap::new(),
+            next_prediction: 0,
+            generations: Vec::new(),
+            free: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Binding {
+    Confirmed(NetId),
+    Pending(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server() {
+        let mut map = NetIdMap::new();
+        let a = map.allocate("a");
+        let b = map.allocate("b");
+        assert_ne!(a, b);
+        assert_eq!(map.get(a), Some(&"a"));
+        assert_eq!(map.id(&"b"), Some(b));
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.id(&"a"), None);
+        let c = map.allocate("c");
+        assert_eq!(c.index(), a.index(), "reused");
+        assert_ne!(c, a);
+        assert_eq!(map.get(a), None, "stale IDs don't resolve");
+        assert_eq!(NetId::from_bits(c.to_bits()), c);
+    }
+
+    #[test]
+    fn client() {
+        let mut map = NetIdMap::new();
+        let server_id = NetId::new(7, 0);
+        assert_eq!(map.insert(server_id, "remote"), None);
+        let token = map.predict("bullet");
+        assert_eq!(map.prediction(&"bullet"), Some(token));
+        assert_eq!(map.id(&"bullet"), None);
+        assert_eq!(map.confirm(token, NetId::new(8, 0)), Some(&"bullet"));
+        assert_eq!(map.id(&"bullet"), Some(NetId::new(8, 0)));
+        assert!(!map.is_pending(&"bullet"));
+
+        let token = map.predict("misfire");
+        assert_eq!(map.reject(token), Some("misfire"));
+        assert_eq!(map.confirm(token, NetId::new(9, 0)), None);
+
+        assert_eq!(
+            map.insert(NetId::new(7, 1), "successor"),
+            Some("remote"),
+            "reuse displaces stale entity"
+        );
+    }
+}
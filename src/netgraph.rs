@@ -0,0 +1,182 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::RttEstimator;
+
+/// The most recent samples of a quantity, oldest first, e.g. for plotting
+///
+/// Once full, recording a sample discards the oldest. Samples that couldn't be measured, such as
+/// round-trip time before any packets were acknowledged, are recorded as NaN.
+#[derive(Debug, Clone)]
+pub struct TimeSeries {
+    capacity: usize,
+    samples: VecDeque<f32>,
+}
+
+impl TimeSeries {
+    /// Construct a series retaining at most `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            samples: VecDeque::with_capacity(capacity.max(1)),
+        }
+    }
+
+    /// Record `sample`, discarding the oldest sample if full
+    pub fn push(&mut self, sample: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Retained samples, from oldest to newest
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = f32> + DoubleEndedIterator + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// The newest sample
+    pub fn latest(&self) -> Option<f32> {
+        self.samples.back().copied()
+    }
+
+    /// Smallest retained sample, ignoring NaN, e.g. to scale a plot
+    pub fn min(&self) -> Option<f32> {
+        self.iter().filter(|x| !x.is_nan()).reduce(f32::min)
+    }
+
+    /// Largest retained sample, ignoring NaN, e.g. to scale a plot
+    pub fn max(&self) -> Option<f32> {
+        self.iter().filter(|x| !x.is_nan()).reduce(f32::max)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Maximum number of samples retained
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// Per-frame history of a client's connection and time control, for a scrolling netgraph
+///
+/// Call [`record_snapshot`](Self::record_snapshot) for each snapshot received and
+/// [`record_frame`](Self::record_frame) once per rendered frame. Every series gains exactly one
+/// sample per frame, so samples at the same index describe the same frame.
+#[derive(Debug, Clone)]
+pub struct NetGraph {
+    /// Smoothed round-trip time, in seconds
+    pub rtt: TimeSeries,
+    /// Total size of snapshots received during the frame, in bytes
+    pub snapshot_size: TimeSeries,
+    /// Simulation time buffered after the frame, in seconds, as passed to
+    /// [`throttle`](crate::throttle)
+    pub buffer_remaining: TimeSeries,
+    /// Rate at which simulation time advanced relative to real time
+    pub throttle_scale: TimeSeries,
+    pending_snapshot_size: usize,
+}
+
+impl NetGraph {
+    /// Construct a netgraph retaining `capacity` frames
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            rtt: TimeSeries::new(capacity),
+            snapshot_size: TimeSeries::new(capacity),
+            buffer_remaining: TimeSeries::new(capacity),
+            throttle_scale: TimeSeries::new(capacity),
+            pending_snapshot_size: 0,
+        }
+    }
+
+    /// Account for a snapshot of `size` bytes received during the current frame
+    pub fn record_snapshot(&mut self, size: usize) {
+        self.pending_snapshot_size += size;
+    }
+
+    /// Record a frame in which `real_time` passed and the simulation advanced by `sim_time`,
+    /// leaving `buffer_remaining`
+    ///
+    /// `sim_time` is typically the result of [`throttle`](crate::throttle).
+    pub fn record_frame(
+        &mut self,
+        rtt: &RttEstimator,
+        real_time: Duration,
+        sim_time: Duration,
+        buffer_remaining: Duration,
+    ) {
+        self.rtt
+            .push(rtt.smoothed().map_or(f32::NAN, |x| x.as_secs_f32()));
+        self.snapshot_size
+            .push(std::mem::take(&mut self.pending_snapshot_size) as f32);
+        self.buffer_remaining.push(buffer_remaining.as_secs_f32());
+        self.throttle_scale.push(if real_time.is_zero() {
+            f32::NAN
+        } else {
+            sim_time.div_duration_f32(real_time)
+        });
+    }
+
+    /// Number of frames recorded, up to the capacity
+    pub fn len(&self) -> usize {
+        self.rtt.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rtt.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.rtt.clear();
+        self.snapshot_size.clear();
+        self.buffer_remaining.clear();
+        self.throttle_scale.clear();
+        self.pending_snapshot_size = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series() {
+        let mut series = TimeSeries::new(3);
+        assert_eq!(series.max(), None);
+        for x in [1.0, f32::NAN, 3.0, 4.0] {
+            series.push(x);
+        }
+        assert_eq!(series.len(), 3);
+        assert!(series.iter().next().unwrap().is_nan(), "oldest discarded");
+        assert_eq!(series.latest(), Some(4.0));
+        assert_eq!(series.min(), Some(3.0));
+        assert_eq!(series.max(), Some(4.0));
+    }
+
+    #[test]
+    fn netgraph() {
+        let mut graph = NetGraph::new(60);
+        let mut rtt = RttEstimator::new();
+        let frame = Duration::from_millis(16);
+        graph.record_frame(&rtt, frame, frame / 2, Duration::ZERO);
+        rtt.sample(Duration::from_millis(100));
+        graph.record_snapshot(100);
+        graph.record_snapshot(50);
+        graph.record_frame(&rtt, frame, frame, Duration::from_millis(50));
+        assert_eq!(graph.len(), 2);
+        assert!(graph.rtt.iter().next().unwrap().is_nan());
+        assert_eq!(graph.rtt.latest(), Some(0.1));
+        assert_eq!(graph.snapshot_size.iter().collect::<Vec<_>>(), [0.0, 150.0]);
+        assert_eq!(graph.buffer_remaining.latest(), Some(0.05));
+        assert_eq!(graph.throttle_scale.iter().collect::<Vec<_>>(), [0.5, 1.0]);
+    }
+}
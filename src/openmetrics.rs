@@ -0,0 +1,246 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+use crate::Metrics;
+
+/// A [`Metrics`] sink that renders the OpenMetrics text format, e.g. for a Prometheus scraper
+///
+/// Metric names are qualified with `nettish_`, with dots replaced by underscores. To distinguish
+/// components of the same type, such as each client's [`InputQueue`](crate::InputQueue), report
+/// them through [`labeled`](Self::labeled) rather than [`Prefixed`](crate::Prefixed). Histogram
+/// observations are summarized by their count and sum.
+///
+/// Serving the rendered text over HTTP, with the content type [`CONTENT_TYPE`](Self::CONTENT_TYPE),
+/// is left to the application.
+#[derive(Debug, Clone, Default)]
+pub struct OpenMetricsExporter {
+    families: BTreeMap<String, Family>,
+}
+
+impl OpenMetricsExporter {
+    /// Media type of [`render`](Self::render)'s output
+    pub const CONTENT_TYPE: &'static str =
+        "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink that attaches `labels` to every metric reported through it
+    ///
+    /// Label names should match `[a-zA-Z_][a-zA-Z0-9_]*`.
+    pub fn labeled<'a>(&'a mut self, labels: &[(&str, &str)]) -> LabeledMetrics<'a> {
+        let mut rendered = String::new();
+        for (i, (name, value)) in labels.iter().enumerate() {
+            if i != 0 {
+                rendered.push(',');
+            }
+            write!(rendered, "{name}=\"").unwrap();
+            for c in value.chars() {
+                match c {
+                    '\\' => rendered.push_str("\\\\"),
+                    '"' => rendered.push_str("\\\""),
+                    '\n' => rendered.push_str("\\n"),
+                    _ => rendered.push(c),
+                }
+            }
+            rendered.push('"');
+        }
+        LabeledMetrics {
+            exporter: self,
+            labels: rendered,
+        }
+    }
+
+    /// Render everything reported so far
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out).unwrap();
+        out
+    }
+
+    /// Write everything reported so far to `out`
+    pub fn write(&self, out: &mut impl Write) -> fmt::Result {
+        for (name, family) in &self.families {
+            let kind = match family.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+                Kind::Summary => "summary",
+            };
+            writeln!(out, "# TYPE {name} {kind}")?;
+            for (labels, value) in &family.samples {
+                let labels = if labels.is_empty() {
+                    String::new()
+                } else {
+                    format!("{{{labels}}}")
+                };
+                match *value {
+                    Value::Counter(total) => writeln!(out, "{name}_total{labels} {total}")?,
+                    Value::Gauge(value) => writeln!(out, "{name}{labels} {}", Float(value))?,
+                    Value::Summary { count, sum } => {
+                        writeln!(out, "{name}_count{labels} {count}")?;
+                        writeln!(out, "{name}_sum{labels} {}", Float(sum))?;
+                    }
+                }
+            }
+        }
+        out.write_str("# EOF\n")
+    }
+
+    /// Forget everything reported so far, e.g. to stop exporting departed clients' metrics
+    pub fn clear(&mut self) {
+        self.families.clear();
+    }
+
+    fn record(&mut self, name: &str, labels: &str, kind: Kind, value: Value) {
+        let family = self.families.entry(family_name(name)).or_insert(Family {
+            kind,
+            samples: BTreeMap::new(),
+        });
+        if family.kind != kind {
+            // A name reported as different kinds can't be represented; keep the first
+            return;
+        }
+        match (family.samples.get_mut(labels), value) {
+            (
+                Some(Value::Summary { count, sum }),
+                Value::Summary {
+                    count: n,
+                    sum: total,
+                },
+            ) => {
+                *count += n;
+                *sum += total;
+            }
+            _ => {
+                family.samples.insert(labels.into(), value);
+            }
+        }
+    }
+}
+
+impl Metrics for OpenMetricsExporter {
+    fn counter(&mut self, name: &str, total: u64) {
+        self.record(name, "", Kind::Counter, Value::Counter(total));
+    }
+
+    fn gauge(&mut self, name: &str, value: f64) {
+        self.record(name, "", Kind::Gauge, Value::Gauge(value));
+    }
+
+    fn histogram(&mut self, name: &str, value: f64) {
+        self.record(name, "", Kind::Summary, summary(value));
+    }
+}
+
+/// Reports metrics to an [`OpenMetricsExporter`] with labels attached
+///
+/// Constructed by [`OpenMetricsExporter::labeled`].
+pub struct LabeledMetrics<'a> {
+    exporter: &'a mut OpenMetricsExporter,
+    labels: String,
+}
+
+impl Metrics for LabeledMetrics<'_> {
+    fn counter(&mut self, name: &str, total: u64) {
+        self.exporter
+            .record(name, &self.labels, Kind::Counter, Value::Counter(total));
+    }
+
+    fn gauge(&mut self, name: &str, value: f64) {
+        self.exporter
+            .record(name, &self.labels, Kind::Gauge, Value::Gauge(value));
+    }
+
+    fn histogram(&mut self, name: &str, value: f64) {
+        self.exporter
+            .record(name, &self.labels, Kind::Summary, summary(value));
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Family {
+    kind: Kind,
+    /// Values keyed by rendered label set
+    samples: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Summary,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Value {
+    Counter(u64),
+    Gauge(f64),
+    Summary { count: u64, sum: f64 },
+}
+
+fn summary(value: f64) -> Value {
+    Value::Summary {
+        count: 1,
+        sum: value,
+    }
+}
+
+/// Convert a dot-separated metric name into a valid OpenMetrics family name
+fn family_name(name: &str) -> String {
+    let mut result = String::from("nettish_");
+    result.extend(name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+        _ => '_',
+    }));
+    result
+}
+
+/// Formats a number as OpenMetrics requires
+struct Float(f64);
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_nan() {
+            f.write_str("NaN")
+        } else if self.0.is_infinite() {
+            f.write_str(if self.0 > 0.0 { "+Inf" } else { "-Inf" })
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InputQueue, Instant, ReportMetrics};
+
+    #[test]
+    fn render() {
+        let mut exporter = OpenMetricsExporter::new();
+        let mut queue = InputQueue::new();
+        queue.push(4, (), Instant::now());
+        queue.report_metrics(&mut exporter.labeled(&[("client", "7")]));
+        queue.push(4, (), Instant::now());
+        queue.report_metrics(&mut exporter.labeled(&[("client", "a\"b")]));
+        exporter.counter("server.ticks", 42);
+        exporter.histogram("server.tick_time", 0.25);
+        exporter.histogram("server.tick_time", 0.5);
+        assert_eq!(
+            exporter.render(),
+            "# TYPE nettish_input_queue_len gauge
+nettish_input_queue_len{client=\"7\"} 1
+nettish_input_queue_len{client=\"a\\\"b\"} 2
+# TYPE nettish_server_tick_time summary
+nettish_server_tick_time_count 2
+nettish_server_tick_time_sum 0.75
+# TYPE nettish_server_ticks counter
+nettish_server_ticks_total 42
+# EOF
+"
+        );
+    }
+}
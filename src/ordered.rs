@@ -0,0 +1,181 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::Instant;
+
+/// Receiving end of a reliable, ordered message stream
+///
+/// Messages are released strictly in sequence order, so a single lost message holds back
+/// everything sent after it until it's retransmitted. This *head-of-line blocking* is inherent to
+/// ordered delivery, and is a common source of hitches when ordered channels are used for data
+/// that doesn't really need to be ordered. [`blocking`](Self::blocking) reports the state of any
+/// current stall, and [`total_blocked`](Self::total_blocked) the cumulative cost, to help spot
+/// such misuse.
+#[derive(Debug, Clone)]
+pub struct OrderedReceiver<T> {
+    /// Sequence number of the next message to release
+    next: u16,
+    /// Messages received but not yet released, starting at `next`
+    buffer: VecDeque<Option<Entry<T>>>,
+    /// Maximum distance ahead of `next` a message may be buffered
+    window: u16,
+    /// When `next` became the cause of a stall
+    blocked_since: Option<Instant>,
+    queued_bytes: usize,
+    total_blocked: Duration,
+}
+
+impl<T> OrderedReceiver<T> {
+    /// Construct a receiver expecting `next` as the first sequence number, and buffering
+    /// messages at most `window` sequence numbers beyond it
+    ///
+    /// `window` is limited to 32768, half the sequence space. Beyond that, late duplicates of
+    /// released messages would be indistinguishable from messages yet to be released.
+    pub fn new(next: u16, window: u16) -> Self {
+        Self {
+            next,
+            buffer: VecDeque::new(),
+            window: window.min(u16::MAX / 2 + 1),
+            blocked_since: None,
+            queued_bytes: 0,
+            total_blocked: Duration::ZERO,
+        }
+    }
+
+    /// Accept a message of `bytes` bytes received at `now`
+    ///
+    /// Returns `false` if the message is a duplicate, was already released, or is too far ahead
+    /// to buffer.
+    pub fn insert(&mut self, sequence: u16, message: T, bytes: usize, now: Instant) -> bool {
+        let offset = sequence.wrapping_sub(self.next);
+        if offset >= self.window {
+            return false;
+        }
+        let offset = usize::from(offset);
+        if self.buffer.len() <= offset {
+            self.buffer.resize_with(offset + 1, || None);
+        }
+        if self.buffer[offset].is_some() {
+            return false;
+        }
+        self.buffer[offset] = Some(Entry { message, bytes });
+        self.queued_bytes += bytes;
+        if offset != 0 && self.buffer[0].is_none() && self.blocked_since.is_none() {
+            self.blocked_since = Some(now);
+        }
+        true
+    }
+
+    /// Release the next message, if it's been received
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        let entry = self.buffer.front_mut()?.take()?;
+        self.buffer.pop_front();
+        self.next = self.next.wrapping_add(1);
+        self.queued_bytes -= entry.bytes;
+        if let Some(since) = self.blocked_since.take() {
+            self.total_blocked += now.saturating_duration_since(since);
+        }
+        if self.buffer.front().is_some_and(|x| x.is_none()) {
+            // Stalled on a new gap immediately
+            self.blocked_since = Some(now);
+        }
+        Some(entry.message)
+    }
+
+    /// Details of the current stall, if any
+    pub fn blocking(&self, now: Instant) -> Option<Blocking> {
+        let since = self.blocked_since?;
+        Some(Blocking {
+            sequence: self.next,
+            duration: now.saturating_duration_since(since),
+            queued_messages: self.buffer.iter().filter(|x| x.is_some()).count(),
+            queued_bytes: self.queued_bytes,
+        })
+    }
+
+    /// Cumulative time spent stalled, excluding any ongoing stall
+    pub fn total_blocked(&self) -> Duration {
+        self.total_blocked
+    }
+
+    /// Sequence number of the next message to be released
+    pub fn next_sequence(&self) -> u16 {
+        self.next
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    message: T,
+    bytes: usize,
+}
+
+/// A stall in an [`OrderedReceiver`] caused by a missing message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Blocking {
+    /// Sequence number of the missing message
+    pub sequence: u16,
+    /// How long the stall has lasted
+    pub duration: Duration,
+    /// Number of messages received but held back
+    pub queued_messages: usize,
+    /// Total size of messages received but held back
+    pub queued_bytes: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let start = Instant::now();
+        let mut rx = OrderedReceiver::new(0, 16);
+        assert!(rx.insert(0, 'a', 10, start));
+        assert_eq!(rx.blocking(start), None);
+        assert!(rx.insert(2, 'c', 10, start));
+        assert!(rx.insert(3, 'd', 10, start));
+        assert!(!rx.insert(3, 'd', 10, start), "duplicate");
+        assert_eq!(rx.pop(start), Some('a'));
+        assert_eq!(rx.pop(start), None);
+
+        let now = start + Duration::from_millis(100);
+        assert_eq!(
+            rx.blocking(now),
+            Some(Blocking {
+                sequence: 1,
+                duration: Duration::from_millis(100),
+                queued_messages: 2,
+                queued_bytes: 20,
+            })
+        );
+
+        assert!(rx.insert(1, 'b', 10, now));
+        assert_eq!(rx.pop(now), Some('b'));
+        assert_eq!(rx.blocking(now), None);
+        assert_eq!(rx.total_blocked(), Duration::from_millis(100));
+        assert_eq!(rx.pop(now), Some('c'));
+        assert_eq!(rx.pop(now), Some('d'));
+        assert_eq!(rx.pop(now), None);
+        assert!(!rx.insert(0, 'a', 10, now), "already released");
+        assert!(!rx.insert(20, 'x', 10, now), "beyond window");
+
+        let mut rx = OrderedReceiver::new(0, u16::MAX);
+        assert!(rx.insert(0, 'a', 10, now));
+        assert_eq!(rx.pop(now), Some('a'));
+        assert!(
+            !rx.insert(0, 'a', 10, now),
+            "late duplicate with oversized window"
+        );
+    }
+
+    #[test]
+    fn wrap() {
+        let now = Instant::now();
+        let mut rx = OrderedReceiver::new(u16::MAX, 16);
+        assert!(rx.insert(0, 1, 1, now));
+        assert!(rx.insert(u16::MAX, 0, 1, now));
+        assert_eq!(rx.pop(now), Some(0));
+        assert_eq!(rx.pop(now), Some(1));
+        assert_eq!(rx.next_sequence(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! Debug overlay for the [egui](https://github.com/emilk/egui) immediate-mode GUI
+
+use egui::{Color32, Context, Grid, ProgressBar, Sense, Stroke, Ui, Window, pos2, vec2};
+
+use crate::{Bandwidth, NetGraph, ThrottleRegime, TimeSeries};
+
+/// A panel visualizing a connection's state for diagnosis in-game
+///
+/// Constructed afresh each frame from whichever components the application has on hand, then
+/// drawn with [`show`](Self::show) as a floating window or embedded in an existing [`Ui`] with
+/// [`ui`](Self::ui). Sections with no data are omitted.
+#[derive(Debug, Clone, Default)]
+pub struct DebugOverlay<'a> {
+    netgraph: Option<&'a NetGraph>,
+    throttle: Option<ThrottleRegime>,
+    queues: Vec<(&'a str, usize, usize)>,
+    channels: Vec<(&'a str, Bandwidth)>,
+}
+
+impl<'a> DebugOverlay<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plot the history recorded in `netgraph`
+    pub fn netgraph(mut self, netgraph: &'a NetGraph) -> Self {
+        self.netgraph = Some(netgraph);
+        self
+    }
+
+    /// Display the current [`throttle`](crate::throttle) regime
+    pub fn throttle(mut self, regime: ThrottleRegime) -> Self {
+        self.throttle = Some(regime);
+        self
+    }
+
+    /// Display the occupancy of a queue holding `len` of at most `capacity` elements, e.g. an
+    /// [`InputQueue`](crate::InputQueue)
+    pub fn queue(mut self, name: &'a str, len: usize, capacity: usize) -> Self {
+        self.queues.push((name, len, capacity));
+        self
+    }
+
+    /// Display the throughput of a channel, as measured by a
+    /// [`BandwidthEstimator`](crate::BandwidthEstimator)
+    pub fn bandwidth(mut self, name: &'a str, bandwidth: Bandwidth) -> Self {
+        self.channels.push((name, bandwidth));
+        self
+    }
+
+    /// Draw the overlay in a floating window
+    pub fn show(&self, ctx: &Context) {
+        Window::new("nettish")
+            .default_width(GRAPH_WIDTH)
+            .resizable(false)
+            .show(ctx, |ui| self.ui(ui));
+    }
+
+    /// Draw the overlay's contents into `ui`
+    pub fn ui(&self, ui: &mut Ui) {
+        if let Some(graph) = self.netgraph {
+            sparkline(ui, "RTT", &graph.rtt, |x| format!("{:.0} ms", x * 1e3));
+            sparkline(ui, "Snapshots", &graph.snapshot_size, |x| {
+                format!("{x:.0} B")
+            });
+            sparkline(ui, "Buffer", &graph.buffer_remaining, |x| {
+                format!("{:.0} ms", x * 1e3)
+            });
+            sparkline(ui, "Time scale", &graph.throttle_scale, |x| {
+                format!("{x:.2}×")
+            });
+        }
+        if let Some(regime) = self.throttle {
+            ui.label(format!("Throttle: {regime:?}"));
+        }
+        for &(name, len, capacity) in &self.queues {
+            let fill = if capacity == 0 {
+                0.0
+            } else {
+                len as f32 / capacity as f32
+            };
+            ui.add(ProgressBar::new(fill).text(format!("{name}: {len}/{capacity}")));
+        }
+        if !self.channels.is_empty() {
+            Grid::new("nettish_bandwidth").striped(true).show(ui, |ui| {
+                for heading in ["Channel", "Sent", "Delivered", "Lost"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for &(name, ref bandwidth) in &self.channels {
+                    ui.label(name);
+                    for rate in [bandwidth.sent, bandwidth.delivered, bandwidth.lost] {
+                        ui.label(format!("{:.1} KiB/s", rate / 1024.0));
+                    }
+                    ui.end_row();
+                }
+            });
+        }
+    }
+}
+
+/// Plot `series` scaled to fit, labeled with its latest value
+fn sparkline(ui: &mut Ui, name: &str, series: &TimeSeries, format: impl Fn(f32) -> String) {
+    let latest = series
+        .latest()
+        .filter(|x| !x.is_nan())
+        .map_or_else(|| "-".into(), format);
+    ui.label(format!("{name}: {latest}"));
+    let (rect, _) = ui.allocate_exact_size(vec2(GRAPH_WIDTH, GRAPH_HEIGHT), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let (Some(min), Some(max)) = (series.min(), series.max()) else {
+        return;
+    };
+    // Anchor at zero so that small fluctuations aren't exaggerated
+    let min = min.min(0.0);
+    let range = if max > min { max - min } else { 1.0 };
+    let step = rect.width() / series.capacity().saturating_sub(1).max(1) as f32;
+    let stroke = Stroke::new(1.0, Color32::LIGHT_GREEN);
+    let mut segment = Vec::new();
+    for (i, x) in series.iter().enumerate() {
+        if x.is_nan() {
+            // Leave a gap for missing samples
+            painter.line(std::mem::take(&mut segment), stroke);
+            continue;
+        }
+        let y = rect.bottom() - (x - min) / range * rect.height();
+        segment.push(pos2(rect.left() + i as f32 * step, y));
+    }
+    painter.line(segment, stroke);
+}
+
+const GRAPH_WIDTH: f32 = 240.0;
+const GRAPH_HEIGHT: f32 = 32.0;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::RttEstimator;
+
+    #[test]
+    fn smoke() {
+        let mut graph = NetGraph::new(60);
+        let mut rtt = RttEstimator::new();
+        let frame = Duration::from_millis(16);
+        for i in 0..100 {
+            if i > 10 {
+                rtt.sample(Duration::from_millis(50 + i % 7));
+            }
+            graph.record_snapshot(200);
+            graph.record_frame(&rtt, frame, frame, Duration::from_millis(i));
+        }
+        let overlay = DebugOverlay::new()
+            .netgraph(&graph)
+            .throttle(ThrottleRegime::Normal)
+            .queue("inputs", 3, 8)
+            .bandwidth("unreliable", Bandwidth::default());
+        let ctx = Context::default();
+        let output = ctx.run_ui(Default::default(), |ui| overlay.show(ui.ctx()));
+        assert!(!output.shapes.is_empty());
+        output.drop_without_applying_deltas();
+    }
+}
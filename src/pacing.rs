@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use crate::Instant;
+
+/// Schedules waking at regular tick boundaries, e.g. for a server's main loop
+///
+/// Sleeping is efficient but imprecise: the OS scheduler may wake a thread well after the
+/// requested time. Spinning is precise but burns CPU. A pacer sleeps until shortly before each
+/// deadline, leaving a margin for the oversleep it has observed, then spins for the remainder.
+///
+/// Deadlines are computed from a fixed origin rather than from the time each tick was actually
+/// processed, so waking late doesn't push back every subsequent tick. If the caller falls more
+/// than `max_lag` behind, e.g. after a hang, missed ticks are skipped rather than run back to
+/// back.
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    interval: Duration,
+    max_lag: Duration,
+    /// Time at which the next tick is due
+    next: Instant,
+    /// Pessimistic estimate of how late sleeps end
+    oversleep: Duration,
+}
+
+impl Pacer {
+    /// Construct a pacer whose first tick is due at `start`, followed by one every `interval`
+    pub fn new(start: Instant, interval: Duration) -> Self {
+        Self {
+            interval,
+            max_lag: interval * 4,
+            next: start,
+            oversleep: Duration::from_millis(1),
+        }
+    }
+
+    /// Set how far behind schedule the caller may fall before ticks are skipped
+    pub fn set_max_lag(&mut self, max_lag: Duration) {
+        self.max_lag = max_lag;
+    }
+
+    /// Change the tick interval, effective after the next tick
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Determine what to do at `now`
+    ///
+    /// Returns [`Pace::Tick`] once for each tick that's due, so a caller that's fallen slightly
+    /// behind will be told to tick repeatedly until it catches up.
+    pub fn poll(&mut self, now: Instant) -> Pace {
+        let Some(remaining) = self.next.checked_duration_since(now) else {
+            return self.tick(now);
+        };
+        if remaining.is_zero() {
+            return self.tick(now);
+        }
+        match remaining.checked_sub(self.oversleep) {
+            Some(sleep) if !sleep.is_zero() => Pace::Sleep(sleep),
+            _ => Pace::Spin,
+        }
+    }
+
+    /// Account for a sleep of `requested` that actually took `actual`
+    ///
+    /// Sleeps recommended by [`poll`](Self::poll) should be measured and reported here so that
+    /// the margin left for oversleep tracks the scheduler's real behavior.
+    pub fn record_sleep(&mut self, requested: Duration, actual: Duration) {
+        let sample = actual.saturating_sub(requested);
+        // Rise immediately so that a worse scheduler is accommodated right away, but decay slowly
+        // so that an occasional punctual wake doesn't cause oversleeping
+        self.oversleep = if sample > self.oversleep {
+            sample
+        } else {
+            (self.oversleep * 15 + sample) / 16
+        };
+    }
+
+    /// Block the current thread until the next tick is due, returning its deadline
+    #[cfg(not(target_family = "wasm"))]
+    pub fn wait(&mut self) -> Instant {
+        loop {
+            let deadline = self.next;
+            match self.poll(Instant::now()) {
+                Pace::Tick => return deadline,
+                Pace::Sleep(duration) => {
+                    let start = Instant::now();
+                    std::thread::sleep(duration);
+                    self.record_sleep(duration, start.elapsed());
+                }
+                Pace::Spin => std::hint::spin_loop(),
+            }
+        }
+    }
+
+    /// Time at which the next tick is due
+    pub fn next_tick(&self) -> Instant {
+        self.next
+    }
+
+    /// Current estimate of how late sleeps end
+    pub fn oversleep(&self) -> Duration {
+        self.oversleep
+    }
+
+    fn tick(&mut self, now: Instant) -> Pace {
+        self.next += self.interval;
+        if now.saturating_duration_since(self.next) > self.max_lag {
+            // Hopelessly behind; resume from the present
+            self.next = now + self.interval;
+        }
+        Pace::Tick
+    }
+}
+
+/// What a [`Pacer`] recommends doing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pace {
+    /// A tick is due; run it, then poll again
+    Tick,
+    /// Sleep for the given duration, then poll again
+    Sleep(Duration),
+    /// The next tick is due too soon to sleep safely; poll again immediately
+    Spin,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pace() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut pacer = Pacer::new(start, ms(16));
+        assert_eq!(pacer.poll(start), Pace::Tick);
+        assert_eq!(pacer.poll(start), Pace::Sleep(ms(15)));
+        pacer.record_sleep(ms(15), ms(18));
+        assert_eq!(pacer.oversleep(), ms(3));
+        assert_eq!(pacer.poll(start), Pace::Sleep(ms(13)));
+        assert_eq!(pacer.poll(start + ms(14)), Pace::Spin);
+        assert_eq!(pacer.poll(start + ms(16)), Pace::Tick);
+
+        // Slightly behind: catch up
+        assert_eq!(pacer.poll(start + ms(50)), Pace::Tick);
+        assert_eq!(pacer.poll(start + ms(50)), Pace::Tick);
+        assert_eq!(pacer.next_tick(), start + ms(64));
+
+        // Far behind: skip
+        assert_eq!(pacer.poll(start + ms(1000)), Pace::Tick);
+        assert_eq!(pacer.next_tick(), start + ms(1016));
+    }
+}
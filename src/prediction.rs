@@ -1,4 +1,9 @@
-use std::collections::{vec_deque, VecDeque};
+use alloc::{
+    collections::{VecDeque, vec_deque},
+    vec::Vec,
+};
+
+use crate::{BitReader, BitWriter, DecodeError, Delta, Stamped, SubTick, seq};
 
 /// Sequence of inputs transmitted to the server
 ///
@@ -38,18 +43,29 @@ impl<Input> PredictionQueue<Input> {
     /// Future inputs will be associated with sequence numbers greater than `sequence_number`,
     /// ensuring we re-synchronize after falling behind.
     pub fn reconcile(&mut self, sequence_number: u16) {
-        let diff = self.next_sequence_number.wrapping_sub(sequence_number);
-        if diff >= u16::MAX / 2 {
+        let diff = seq::diff(self.next_sequence_number, sequence_number);
+        if diff <= 0 {
             // `sequence_number` is newer than anything we've recorded
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                target: "nettish::prediction",
+                sequence = sequence_number,
+                discarded = self.in_flight.len(),
+                "prediction resynchronized"
+            );
             self.next_sequence_number = sequence_number.wrapping_add(1);
             self.in_flight.clear();
             return;
         }
-        self.in_flight.drain(
-            0..self
-                .in_flight
-                .len()
-                .saturating_sub(diff.wrapping_sub(1) as usize),
+        let acknowledged = self.in_flight.len().saturating_sub(diff as usize - 1);
+        self.in_flight.drain(0..acknowledged);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            target: "nettish::prediction",
+            sequence = sequence_number,
+            acknowledged,
+            in_flight = self.in_flight.len(),
+            "reconciled"
         );
     }
 
@@ -59,6 +75,27 @@ impl<Input> PredictionQueue<Input> {
     }
 }
 
+impl<Input: PartialEq> PredictionQueue<Input> {
+    /// Iterate over runs of identical consecutive inputs, with the length of each
+    ///
+    /// Players often hold the same input for many consecutive time steps, so transmitting runs
+    /// rather than individual inputs, e.g. with [`BitWriter::write_runs`], can greatly reduce the
+    /// size of packets that redundantly carry every unacknowledged input.
+    pub fn runs(&self) -> impl Iterator<Item = (&Input, usize)> + '_ {
+        runs(self.in_flight.iter())
+    }
+}
+
+impl<Input> PredictionQueue<Stamped<Input>> {
+    /// Track an input sampled at `time`
+    ///
+    /// `time` should be the moment the player perceived when acting, typically their
+    /// interpolated view of the server's timeline, so the server can compensate for it.
+    pub fn record_at(&mut self, input: Input, time: SubTick) {
+        self.record(Stamped { time, value: input });
+    }
+}
+
 impl<'a, Input> IntoIterator for &'a PredictionQueue<Input> {
     type Item = &'a Input;
     type IntoIter = vec_deque::Iter<'a, Input>;
@@ -68,8 +105,59 @@ impl<'a, Input> IntoIterator for &'a PredictionQueue<Input> {
     }
 }
 
+fn runs<'a, T: PartialEq + 'a>(
+    items: impl IntoIterator<Item = &'a T>,
+) -> impl Iterator<Item = (&'a T, usize)> {
+    let mut items = items.into_iter().peekable();
+    core::iter::from_fn(move || {
+        let first = items.next()?;
+        let mut len = 1;
+        while items.next_if(|x| *x == first).is_some() {
+            len += 1;
+        }
+        Some((first, len))
+    })
+}
+
+impl BitWriter {
+    /// Write a sequence of values, collapsing runs of identical consecutive values
+    ///
+    /// Each run is written as the value followed by its length, so sequences with few repeats
+    /// cost about one byte per value more than writing them individually.
+    pub fn write_runs<'a, T: Delta + PartialEq + 'a>(
+        &mut self,
+        values: impl IntoIterator<Item = &'a T>,
+    ) {
+        let runs = runs(values).collect::<Vec<_>>();
+        self.write_varint(runs.len() as u64);
+        for (value, len) in runs {
+            value.encode(self);
+            self.write_varint(len as u64 - 1);
+        }
+    }
+}
+
+impl BitReader<'_> {
+    /// Read a sequence written with [`BitWriter::write_runs`], containing at most `max_len` values
+    pub fn read_runs<T: Delta + Clone>(&mut self, max_len: usize) -> Result<Vec<T>, DecodeError> {
+        let count = self.read_varint()?;
+        let mut values = Vec::new();
+        for _ in 0..count {
+            let value = T::decode(self)?;
+            let len = self.read_varint()?.saturating_add(1);
+            if len > (max_len - values.len()) as u64 {
+                return Err(DecodeError::Overflow);
+            }
+            values.extend(core::iter::repeat_n(value, len as usize));
+        }
+        Ok(values)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    // Empty expectations are spelled `Vec::<u16>::new()` rather than `&[]` because the `webrtc`
+    // feature pulls in `serde_json`, whose `PartialEq` impls leave `&[]`'s element type ambiguous.
     use super::*;
 
     #[test]
@@ -87,13 +175,34 @@ mod tests {
         q.reconcile(3);
         assert_eq!(q.iter().copied().collect::<Vec<_>>(), &[4]);
         q.reconcile(4);
-        assert_eq!(q.iter().copied().collect::<Vec<_>>(), &[]);
+        assert_eq!(q.iter().copied().collect::<Vec<_>>(), Vec::<u16>::new());
         q.reconcile(4);
-        assert_eq!(q.iter().copied().collect::<Vec<_>>(), &[]);
+        assert_eq!(q.iter().copied().collect::<Vec<_>>(), Vec::<u16>::new());
         q.record(5);
         assert_eq!(q.iter().copied().collect::<Vec<_>>(), &[5]);
     }
 
+    #[test]
+    fn runs() {
+        let mut q = PredictionQueue::<u16>::new(0);
+        for i in [1, 1, 1, 2, 1, 1] {
+            q.record(i);
+        }
+        assert_eq!(q.runs().collect::<Vec<_>>(), &[(&1, 3), (&2, 1), (&1, 2)]);
+        let mut w = BitWriter::new();
+        w.write_runs(&q);
+        let buf = w.finish();
+        assert_eq!(buf.len(), 1 + 3 * 3);
+        assert_eq!(
+            BitReader::new(&buf).read_runs::<u16>(6).unwrap(),
+            &[1, 1, 1, 2, 1, 1]
+        );
+        assert_eq!(
+            BitReader::new(&buf).read_runs::<u16>(5),
+            Err(DecodeError::Overflow)
+        );
+    }
+
     #[test]
     fn wrap() {
         const START: u16 = u16::MAX - 1;
@@ -128,7 +237,7 @@ mod tests {
         q.reconcile(10);
         assert_eq!(
             q.iter().copied().collect::<Vec<_>>(),
-            &[],
+            Vec::<u16>::new(),
             "sequence numbers we haven't reached yet obsolete all inputs"
         );
         q.record(11);
@@ -140,4 +249,28 @@ mod tests {
             "inputs are queued with future sequence numbers"
         );
     }
+
+    #[test]
+    fn resynchronize() {
+        let mut q = PredictionQueue::<u16>::new(0);
+        for i in 0..3 {
+            q.record(i);
+        }
+        q.reconcile(q.next_sequence_number());
+        assert_eq!(
+            q.iter().count(),
+            0,
+            "the server consumed an input we never sent"
+        );
+        assert_eq!(q.next_sequence_number(), 4);
+
+        // Half the sequence space or more behind is treated as ahead
+        q.record(4);
+        q.reconcile(5u16.wrapping_sub(32767));
+        assert_eq!(q.iter().count(), 1);
+        assert_eq!(q.next_sequence_number(), 5);
+        q.reconcile(5u16.wrapping_sub(32768));
+        assert_eq!(q.iter().count(), 0);
+        assert_eq!(q.next_sequence_number(), 5u16.wrapping_sub(32767));
+    }
 }
@@ -0,0 +1,215 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Selects which entities to replicate when bandwidth is insufficient for all of them
+///
+/// Each entity has a base priority that is added to its accumulated priority every tick. When
+/// building a packet, entities are taken in order of accumulated priority until the budget is
+/// exhausted, and the accumulated priority of each entity sent is reset. Entities that are
+/// skipped grow steadily more urgent, so every entity is eventually sent, with important entities
+/// sent more often.
+///
+/// Use one accumulator per client.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "E: serde::Deserialize<'de> + Hash + Eq"))
+)]
+pub struct PriorityAccumulator<E> {
+    entities: HashMap<E, Entry>,
+    max_staleness: Option<u32>,
+}
+
+impl<E: Hash + Eq + Clone> PriorityAccumulator<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Begin tracking `entity`, or update its base priority if already tracked
+    ///
+    /// Newly tracked entities start with an accumulated priority equal to their base priority.
+    pub fn insert(&mut self, entity: E, priority: f32) {
+        self.entities
+            .entry(entity)
+            .and_modify(|x| x.base = priority)
+            .or_insert(Entry {
+                base: priority,
+                accumulated: priority,
+                stale: 0,
+            });
+    }
+
+    /// Guarantee that entities are selected at least once every `ticks` calls to
+    /// [`accumulate`](Self::accumulate), even if that exceeds the budget
+    ///
+    /// Without a cap, low-priority entities may go unsent for long periods when bandwidth is
+    /// scarce, making them appear frozen.
+    pub fn set_max_staleness(&mut self, ticks: Option<u32>) {
+        self.max_staleness = ticks;
+    }
+
+    /// Stop tracking `entity`
+    pub fn remove(&mut self, entity: &E) {
+        self.entities.remove(entity);
+    }
+
+    /// Stop tracking entities for which `f` returns false, e.g. those no longer relevant
+    pub fn retain(&mut self, mut f: impl FnMut(&E) -> bool) {
+        self.entities.retain(|e, _| f(e));
+    }
+
+    /// Increase `entity`'s accumulated priority by `amount`, e.g. in response to a significant
+    /// event
+    pub fn boost(&mut self, entity: &E, amount: f32) {
+        if let Some(x) = self.entities.get_mut(entity) {
+            x.accumulated += amount;
+        }
+    }
+
+    /// Add each entity's base priority to its accumulated priority
+    ///
+    /// Should be called once per tick.
+    pub fn accumulate(&mut self) {
+        for x in self.entities.values_mut() {
+            x.accumulated += x.base;
+            x.stale = x.stale.saturating_add(1);
+        }
+    }
+
+    /// Accumulated priority of `entity`
+    pub fn priority(&self, entity: &E) -> Option<f32> {
+        self.entities.get(entity).map(|x| x.accumulated)
+    }
+
+    /// Select the highest-priority entities whose combined `size` fits within `budget`
+    ///
+    /// Entities too large for the remaining budget are skipped in favor of smaller,
+    /// lower-priority entities, except for those that have reached the
+    /// [maximum staleness](Self::set_max_staleness), which are always selected first. The
+    /// accumulated priority of each selected entity is reset to zero. Returns entities in
+    /// descending order of priority, stale entities first.
+    pub fn select(&mut self, budget: usize, mut size: impl FnMut(&E) -> usize) -> Vec<E> {
+        self.select_inner(budget, |e| Some(size(e)), false)
+    }
+
+    /// Select the highest-priority entities for which `size` returns `Some`, whose combined size
+    /// never exceeds `budget`
+    ///
+    /// Like [`select`](Self::select), except that entities that have reached the maximum
+    /// staleness are only selected first if they fit, providing a hard limit. Entities for which
+    /// `size` returns `None`, e.g. because they have nothing to send, are ignored, and their
+    /// accumulated priority is retained.
+    pub fn select_within(
+        &mut self,
+        budget: usize,
+        size: impl FnMut(&E) -> Option<usize>,
+    ) -> Vec<E> {
+        self.select_inner(budget, size, true)
+    }
+
+    fn select_inner(
+        &mut self,
+        mut budget: usize,
+        mut size: impl FnMut(&E) -> Option<usize>,
+        strict: bool,
+    ) -> Vec<E> {
+        let max_staleness = self.max_staleness.unwrap_or(u32::MAX);
+        let mut candidates = self
+            .entities
+            .iter()
+            .map(|(e, x)| (e, x.stale >= max_staleness, x.accumulated))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+        let mut selected = Vec::new();
+        for (entity, stale, _) in candidates {
+            let Some(size) = size(entity) else {
+                continue;
+            };
+            if (stale && !strict) || size <= budget {
+                budget = budget.saturating_sub(size);
+                selected.push(entity.clone());
+            }
+        }
+        for entity in &selected {
+            let x = self.entities.get_mut(entity).unwrap();
+            x.accumulated = 0.0;
+            x.stale = 0;
+        }
+        selected
+    }
+
+    /// Number of entities tracked
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Whether no entities are tracked
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+impl<E> Default for PriorityAccumulator<E> {
+    fn default() -> Self {
+        Self {
+            entities: HashMap::new(),
+            max_staleness: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Entry {
+    base: f32,
+    accumulated: f32,
+    /// Calls to `accumulate` since last selected
+    stale: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut acc = PriorityAccumulator::new();
+        acc.insert("player", 10.0);
+        acc.insert("tree", 1.0);
+        acc.insert("boss", 5.0);
+        let size = |e: &&str| if *e == "boss" { 200 } else { 100 };
+
+        assert_eq!(acc.select(250, size), ["player", "tree"], "boss didn't fit");
+        let mut sends = HashMap::<&str, u32>::new();
+        for _ in 0..100 {
+            acc.accumulate();
+            for e in acc.select(100, |_| 100) {
+                *sends.entry(e).or_default() += 1;
+            }
+        }
+        assert!(sends["player"] > sends["boss"]);
+        assert!(sends["boss"] > sends["tree"]);
+        assert!(sends["tree"] > 0, "low priorities aren't starved");
+
+        acc.retain(|e| *e != "tree");
+        assert_eq!(acc.priority(&"tree"), None);
+        assert_eq!(acc.len(), 2);
+    }
+
+    #[test]
+    fn staleness() {
+        let mut acc = PriorityAccumulator::new();
+        acc.insert("player", 100.0);
+        acc.insert("tree", 1.0);
+        acc.set_max_staleness(Some(3));
+        let mut selections = Vec::new();
+        for _ in 0..6 {
+            acc.accumulate();
+            selections.push(acc.select(1, |e| if *e == "tree" { 2 } else { 1 }));
+        }
+        assert_eq!(selections[0], ["player"]);
+        assert_eq!(selections[2], ["tree"], "budget exceeded");
+        assert_eq!(selections[3], ["player"]);
+        assert_eq!(selections[5], ["tree"]);
+    }
+}
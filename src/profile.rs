@@ -0,0 +1,136 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{Instant, Metrics, ReportMetrics};
+
+/// Measures the cost of replaying inputs after reconciling with authoritative state
+///
+/// Call [`measure`](Self::measure) around each replay, e.g. when reapplying a
+/// [`PredictionQueue`](crate::PredictionQueue)'s unacknowledged inputs to a fresh server
+/// snapshot. A [`RollbackSession`](crate::RollbackSession) profiles its own resimulation.
+///
+/// Totals and worst cases are available via [`ReportMetrics`]. Individual replays are retained
+/// until passed to a [`Metrics`] sink's histograms by [`flush`](Self::flush), up to a limit
+/// beyond which the oldest are discarded.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayProfiler {
+    stats: ReplayStats,
+    pending: VecDeque<(usize, Duration)>,
+}
+
+impl ReplayProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `replay`, which replays `inputs` inputs, recording how long it takes
+    pub fn measure<R>(&mut self, inputs: usize, replay: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = replay();
+        self.record(inputs, start.elapsed());
+        result
+    }
+
+    /// Record a replay of `inputs` inputs that took `elapsed`
+    pub fn record(&mut self, inputs: usize, elapsed: Duration) {
+        let stats = &mut self.stats;
+        stats.replays += 1;
+        stats.inputs += inputs as u64;
+        stats.time += elapsed;
+        stats.max_inputs = stats.max_inputs.max(inputs);
+        stats.max_time = stats.max_time.max(elapsed);
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((inputs, elapsed));
+    }
+
+    /// Report replays recorded since the last flush as histogram observations
+    pub fn flush(&mut self, metrics: &mut dyn Metrics) {
+        for (inputs, elapsed) in self.pending.drain(..) {
+            metrics.histogram("replay.inputs", inputs as f64);
+            metrics.histogram("replay.seconds", elapsed.as_secs_f64());
+        }
+    }
+
+    pub fn stats(&self) -> &ReplayStats {
+        &self.stats
+    }
+
+    /// Reset statistics and discard unflushed replays
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl ReportMetrics for ReplayProfiler {
+    fn report_metrics(&self, metrics: &mut dyn Metrics) {
+        let stats = &self.stats;
+        metrics.counter("replay.replays", stats.replays);
+        metrics.counter("replay.inputs_total", stats.inputs);
+        metrics.gauge("replay.seconds_total", stats.time.as_secs_f64());
+        metrics.gauge("replay.max_inputs", stats.max_inputs as f64);
+        metrics.gauge("replay.max_seconds", stats.max_time.as_secs_f64());
+    }
+}
+
+/// Totals gathered by a [`ReplayProfiler`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// Number of replays performed
+    pub replays: u64,
+    /// Total number of inputs replayed
+    pub inputs: u64,
+    /// Total wall-clock time spent replaying
+    pub time: Duration,
+    /// Most inputs replayed at once
+    pub max_inputs: usize,
+    /// Longest time spent on a single replay
+    pub max_time: Duration,
+}
+
+impl ReplayStats {
+    /// Mean number of inputs replayed per replay
+    pub fn mean_inputs(&self) -> f32 {
+        if self.replays == 0 {
+            return 0.0;
+        }
+        self.inputs as f32 / self.replays as f32
+    }
+
+    /// Mean wall-clock time per replay
+    pub fn mean_time(&self) -> Duration {
+        if self.replays == 0 {
+            return Duration::ZERO;
+        }
+        self.time.div_f64(self.replays as f64)
+    }
+}
+
+/// Maximum number of unflushed replays retained
+const MAX_PENDING: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryMetrics;
+
+    #[test]
+    fn profile() {
+        let mut profiler = ReplayProfiler::new();
+        assert_eq!(profiler.measure(3, || 7), 7);
+        profiler.record(5, Duration::from_millis(2));
+        let stats = *profiler.stats();
+        assert_eq!(stats.replays, 2);
+        assert_eq!(stats.inputs, 8);
+        assert_eq!(stats.max_inputs, 5);
+        assert!(stats.max_time >= Duration::from_millis(2));
+        assert_eq!(stats.mean_inputs(), 4.0);
+
+        let mut metrics = MemoryMetrics::new();
+        profiler.flush(&mut metrics);
+        profiler.flush(&mut metrics);
+        assert_eq!(metrics.histograms["replay.inputs"], [3.0, 5.0]);
+        profiler.report_metrics(&mut metrics);
+        assert_eq!(metrics.counters["replay.replays"], 2);
+    }
+}
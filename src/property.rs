@@ -0,0 +1,153 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{AckEvent, StateChannel, seq};
+
+/// Replicates keyed values for which only the latest matters, such as scores or door states
+///
+/// Setting a value supersedes any earlier value for the same key that hasn't been sent yet, and
+/// values whose packets are lost are resent, unless they've since been superseded. Each value is
+/// tagged with a per-key version so the [`PropertyReceiver`] can discard stale values delivered
+/// out of order.
+#[derive(Debug, Clone)]
+pub struct PropertySender<K, V> {
+    channel: StateChannel<K>,
+    values: HashMap<K, (u16, V)>,
+}
+
+impl<K: Hash + Eq + Clone, V> PropertySender<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of `key`, scheduling it for transmission
+    pub fn set(&mut self, key: K, value: V) {
+        match self.values.get_mut(&key) {
+            Some(x) => *x = (x.0.wrapping_add(1), value),
+            None => {
+                self.values.insert(key.clone(), (0, value));
+            }
+        }
+        self.channel.mark_dirty(key);
+    }
+
+    /// Current value of `key`
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key).map(|x| &x.1)
+    }
+
+    /// Stop replicating `key`
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.channel.remove(key);
+        self.values.remove(key).map(|x| x.1)
+    }
+
+    /// Get the next value to write to an outgoing packet, with its key and version
+    pub fn pop(&mut self) -> Option<(K, u16, &V)> {
+        let key = self.channel.pop_dirty()?;
+        let (version, ref value) = self.values[&key];
+        Some((key, version, value))
+    }
+
+    /// Record that the packet with `sequence` carried the current values of `keys`
+    pub fn on_sent(&mut self, sequence: u16, keys: Vec<K>) {
+        self.channel.on_sent(sequence, keys);
+    }
+
+    /// Process a delivery notification from the [`AckTracker`](crate::AckTracker) that assigned
+    /// the sequence numbers passed to [`on_sent`](Self::on_sent)
+    pub fn on_ack_event(&mut self, event: &AckEvent) {
+        self.channel.on_ack_event(event);
+    }
+
+    /// Number of values awaiting transmission
+    pub fn pending(&self) -> usize {
+        self.channel.dirty_len()
+    }
+}
+
+impl<K, V> Default for PropertySender<K, V> {
+    fn default() -> Self {
+        Self {
+            channel: StateChannel::default(),
+            values: HashMap::new(),
+        }
+    }
+}
+
+/// Receives values from a [`PropertySender`]
+#[derive(Debug, Clone)]
+pub struct PropertyReceiver<K, V> {
+    values: HashMap<K, (u16, V)>,
+}
+
+impl<K: Hash + Eq, V> PropertyReceiver<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a value received for `key`
+    ///
+    /// Returns false if `version` is older than the current value's, in which case it's ignored.
+    pub fn receive(&mut self, key: K, version: u16, value: V) -> bool {
+        if let Some(&(current, _)) = self.values.get(&key)
+            && !seq::is_newer(version, current)
+        {
+            return false;
+        }
+        self.values.insert(key, (version, value));
+        true
+    }
+
+    /// Latest value received for `key`
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key).map(|x| &x.1)
+    }
+
+    /// Forget `key`
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.values.remove(key).map(|x| x.1)
+    }
+}
+
+impl<K, V> Default for PropertyReceiver<K, V> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instant;
+    use crate::LostPacket;
+
+    #[test]
+    fn smoke() {
+        let mut sender = PropertySender::new();
+        let mut receiver = PropertyReceiver::new();
+        sender.set("door", 0u32);
+        sender.set("door", 1);
+        sender.set("score", 0);
+        assert_eq!(sender.pending(), 2, "superseded");
+        assert_eq!(sender.pop(), Some(("door", 1, &1)));
+        let (key, version, &value) = sender.pop().unwrap();
+        assert_eq!((key, version), ("score", 0));
+        sender.on_sent(0, vec!["door", "score"]);
+        assert!(receiver.receive(key, version, value));
+
+        sender.on_ack_event(&AckEvent::Lost(LostPacket {
+            sequence: 0,
+            bytes: 0,
+            sent: Instant::now(),
+        }));
+        sender.set("score", 5);
+        assert_eq!(sender.pop(), Some(("door", 1, &1)), "resent");
+        assert_eq!(sender.pop(), Some(("score", 1, &5)), "latest value");
+
+        assert!(receiver.receive("score", 1, 5));
+        assert!(!receiver.receive("score", 0, 0), "stale");
+        assert_eq!(receiver.get(&"score"), Some(&5));
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use crate::{LossTracker, RttEstimator};
+
+/// Limits defining each grade of [`ConnectionQuality`]
+///
+/// Each array holds the worst value permitted for five, four, three, and two bars respectively;
+/// anything worse earns one bar. Appropriate limits depend on how sensitive a game is to each
+/// factor: a fighting game might demand much lower latency than a strategy game.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct QualityConfig {
+    /// Maximum smoothed round-trip time
+    pub rtt: [Duration; 4],
+    /// Maximum round-trip time variation
+    pub jitter: [Duration; 4],
+    /// Maximum fraction of packets lost, from 0 to 1
+    pub loss: [f32; 4],
+    /// Minimum amount of buffered data remaining, as passed to [`throttle`](crate::throttle)
+    pub buffer: [Duration; 4],
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        let ms = Duration::from_millis;
+        Self {
+            rtt: [ms(50), ms(100), ms(150), ms(250)],
+            jitter: [ms(5), ms(10), ms(20), ms(40)],
+            loss: [0.005, 0.02, 0.05, 0.1],
+            buffer: [ms(30), ms(20), ms(10), ms(1)],
+        }
+    }
+}
+
+/// A summary of how well a connection is performing, e.g. for display as signal bars
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ConnectionQuality {
+    /// Overall grade from 1 (worst) to 5 (best), or 0 if round-trip time hasn't been measured
+    pub bars: u8,
+    /// The factor responsible for the grade, if less than perfect
+    pub limit: Option<QualityFactor>,
+    pub rtt: Option<Duration>,
+    pub jitter: Duration,
+    pub loss: f32,
+    pub buffer: Option<Duration>,
+}
+
+impl ConnectionQuality {
+    /// Grade the connection according to `config`
+    ///
+    /// `buffer_remaining` should be supplied by clients that [`throttle`](crate::throttle) their
+    /// simulation according to buffered snapshot data.
+    pub fn assess(
+        config: &QualityConfig,
+        rtt: &RttEstimator,
+        loss: &LossTracker,
+        buffer_remaining: Option<Duration>,
+    ) -> Self {
+        let mut result = Self {
+            bars: 5,
+            limit: None,
+            rtt: rtt.smoothed(),
+            jitter: rtt.variation(),
+            loss: loss.smoothed(),
+            buffer: buffer_remaining,
+        };
+        let Some(smoothed) = result.rtt else {
+            result.bars = 0;
+            return result;
+        };
+        result.limit_to(QualityFactor::Rtt, grade(&config.rtt, |&x| smoothed <= x));
+        result.limit_to(
+            QualityFactor::Jitter,
+            grade(&config.jitter, |&x| result.jitter <= x),
+        );
+        result.limit_to(
+            QualityFactor::Loss,
+            grade(&config.loss, |&x| result.loss <= x),
+        );
+        if let Some(buffer) = buffer_remaining {
+            result.limit_to(
+                QualityFactor::Buffer,
+                grade(&config.buffer, |&x| buffer >= x),
+            );
+        }
+        result
+    }
+
+    fn limit_to(&mut self, factor: QualityFactor, bars: u8) {
+        if bars < self.bars {
+            self.bars = bars;
+            self.limit = Some(factor);
+        }
+    }
+}
+
+/// An input to [`ConnectionQuality`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QualityFactor {
+    Rtt,
+    Jitter,
+    Loss,
+    Buffer,
+}
+
+/// Number of bars earned by satisfying `ok` for the limits in `limits`, from best to worst
+fn grade<T>(limits: &[T; 4], ok: impl Fn(&T) -> bool) -> u8 {
+    5 - limits.iter().position(ok).unwrap_or(4) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LossConfig;
+
+    #[test]
+    fn assess() {
+        let config = QualityConfig::default();
+        let mut rtt = RttEstimator::new();
+        let mut loss = LossTracker::new(LossConfig::default());
+        let quality = ConnectionQuality::assess(&config, &rtt, &loss, None);
+        assert_eq!(quality.bars, 0);
+
+        for _ in 0..100 {
+            rtt.sample(Duration::from_millis(40));
+        }
+        for seq in 0..100 {
+            loss.on_received(seq);
+        }
+        let quality = ConnectionQuality::assess(&config, &rtt, &loss, None);
+        assert_eq!(quality.bars, 5);
+        assert_eq!(quality.limit, None);
+
+        let quality = ConnectionQuality::assess(&config, &rtt, &loss, Some(Duration::ZERO));
+        assert_eq!(quality.bars, 1);
+        assert_eq!(quality.limit, Some(QualityFactor::Buffer));
+
+        for _ in 0..100 {
+            rtt.sample(Duration::from_millis(120));
+        }
+        let quality = ConnectionQuality::assess(&config, &rtt, &loss, None);
+        assert_eq!(quality.bars, 3);
+        assert_eq!(quality.limit, Some(QualityFactor::Rtt));
+    }
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::{BitReader, BitWriter, DecodeError, Interpolate, dequantize_f32};
+
+/// A rotation, represented as a unit quaternion
+///
+/// Components are stored in `[x, y, z, w]` order, matching most math libraries.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quat(pub [f32; 4]);
+
+impl Quat {
+    pub const IDENTITY: Self = Self([0.0, 0.0, 0.0, 1.0]);
+
+    /// Scale to unit length
+    pub fn normalize(self) -> Self {
+        let len = self.0.iter().map(|x| x * x).sum::<f32>().sqrt();
+        Self(self.0.map(|x| x / len))
+    }
+
+    fn dot(&self, other: &Self) -> f32 {
+        self.0.iter().zip(&other.0).map(|(a, b)| a * b).sum()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Interpolate for Quat {
+    /// Normalized linear interpolation along the shortest path
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        // `q` and `-q` represent the same rotation; pick whichever is closer
+        let other = if self.dot(other) < 0.0 {
+            Quat(other.0.map(|x| -x))
+        } else {
+            *other
+        };
+        Quat(self.0.interpolate(&other.0, t)).normalize()
+    }
+}
+
+impl BitWriter {
+    /// Write a rotation with the "smallest three" encoding, using `bits` bits per component
+    ///
+    /// Since a unit quaternion's components have a known magnitude, the largest can be omitted
+    /// and recovered from the other three, which are then known to lie within ±1/√2. With a
+    /// 2-bit index identifying the omitted component, this takes `2 + 3 * bits` bits. 9 bits per
+    /// component is adequate for most gameplay purposes; 10-12 bits suits cases where subtle
+    /// jitter would be visible, such as first-person camera rotation.
+    pub fn write_quat(&mut self, rotation: Quat, bits: u32) {
+        let q = rotation.normalize().0;
+        let largest = (0..4)
+            .max_by(|&a, &b| q[a].abs().total_cmp(&q[b].abs()))
+            .unwrap();
+        // Ensure the omitted component is positive so its sign needn't be sent
+        let sign = if q[largest] < 0.0 { -1.0 } else { 1.0 };
+        self.write_bits(largest as u64, 2);
+        for (i, &x) in q.iter().enumerate() {
+            if i != largest {
+                self.write_quantized_f32(sign * x, -FRAC_1_SQRT_2, FRAC_1_SQRT_2, bits);
+            }
+        }
+    }
+}
+
+impl BitReader<'_> {
+    /// Read a rotation written with [`BitWriter::write_quat`]
+    pub fn read_quat(&mut self, bits: u32) -> Result<Quat, DecodeError> {
+        let largest = self.read_bits(2)? as usize;
+        let mut q = [0.0; 4];
+        let mut sum = 0.0;
+        for (i, x) in q.iter_mut().enumerate() {
+            if i == largest {
+                continue;
+            }
+            let quantized = self.read_bits(bits)? as u32;
+            *x = dequantize_f32(quantized, -FRAC_1_SQRT_2, FRAC_1_SQRT_2, bits);
+            sum += *x * *x;
+        }
+        q[largest] = f32::max(0.0, 1.0 - sum).sqrt();
+        Ok(Quat(q).normalize())
+    }
+}
+
+/// Number of bits written by [`BitWriter::write_quat`] with `bits` bits per component
+pub const fn quat_bits(bits: u32) -> u32 {
+    2 + 3 * bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn angle_between(a: Quat, b: Quat) -> f32 {
+        2.0 * a.dot(&b).abs().min(1.0).acos()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let rotations = [
+            Quat::IDENTITY,
+            Quat([0.0, 0.0, 0.0, -1.0]),
+            Quat([0.5, 0.5, 0.5, 0.5]),
+            Quat([0.1, -0.7, 0.2, 0.3]).normalize(),
+            Quat([-0.9, 0.1, 0.0, 0.1]).normalize(),
+        ];
+        for bits in [9, 12, 16] {
+            let mut w = BitWriter::new();
+            for &q in &rotations {
+                w.write_quat(q, bits);
+            }
+            assert_eq!(w.bit_len(), rotations.len() * quat_bits(bits) as usize);
+            let buf = w.finish();
+            let mut r = BitReader::new(&buf);
+            // Error in the omitted component is bounded by the sum of the others' errors
+            let tolerance =
+                4.0 * crate::quantization_error(-FRAC_1_SQRT_2, FRAC_1_SQRT_2, bits) + 1e-6;
+            for &q in &rotations {
+                let decoded = r.read_quat(bits).unwrap();
+                let sign = q.dot(&decoded).signum();
+                for (a, b) in q.0.iter().zip(&decoded.0) {
+                    assert!(
+                        (a - sign * b).abs() <= tolerance,
+                        "{bits} bits: {q:?} became {decoded:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn interpolate() {
+        let a = Quat::IDENTITY;
+        let b = Quat([0.0, 0.0, -FRAC_1_SQRT_2, -FRAC_1_SQRT_2]);
+        let mid = a.interpolate(&b, 0.5);
+        assert!(mid.0[3] > 0.0, "takes the shortest path");
+        assert!((angle_between(a, mid) - angle_between(mid, b)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    time::Duration,
+};
+
+use crate::Instant;
+
+/// Answers [`QueryClient`] requests with a small status blob, outside of any connection
+///
+/// Stateless, so can be run on a server's game socket, distinguishing queries by their prefix, or
+/// on a dedicated port. Replies are never larger than the request that prompted them, so a
+/// responder can't be used to amplify a denial of service attack by an attacker spoofing a
+/// victim's address. A request too small to carry the status is answered with the size required
+/// instead.
+#[derive(Debug, Clone, Default)]
+pub struct QueryResponder {
+    status: Vec<u8>,
+}
+
+impl QueryResponder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the data returned to queries, e.g. a server name and player count
+    pub fn set_status(&mut self, status: Vec<u8>) {
+        self.status = status;
+    }
+
+    /// Reply to `request`, if it's a query
+    pub fn respond(&self, request: &[u8]) -> Option<Vec<u8>> {
+        if request.len() < MIN_REQUEST_LEN {
+            return None;
+        }
+        let nonce = &parse(request, Kind::Request)?[..8];
+        let mut reply = Vec::with_capacity(HEADER_LEN + self.status.len());
+        reply.extend_from_slice(MAGIC);
+        let required = HEADER_LEN + 8 + self.status.len();
+        if required <= request.len() {
+            reply.push(Kind::Status as u8);
+            reply.extend_from_slice(nonce);
+            reply.extend_from_slice(&self.status);
+        } else {
+            reply.push(Kind::TooLarge as u8);
+            reply.extend_from_slice(nonce);
+            reply.extend_from_slice(&(required as u32).to_le_bytes());
+        }
+        debug_assert!(reply.len() <= request.len());
+        Some(reply)
+    }
+}
+
+/// Measures latency to servers and retrieves their status, e.g. for a server browser
+///
+/// Each request is identified by an unpredictable nonce, so replies can't be forged by an
+/// attacker that can't observe the request.
+#[derive(Debug, Clone)]
+pub struct QueryClient {
+    timeout: Duration,
+    pending: HashMap<u64, Instant>,
+    hasher: RandomState,
+    next: u64,
+}
+
+impl QueryClient {
+    /// Construct a client that forgets requests not answered within `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: HashMap::new(),
+            hasher: RandomState::new(),
+            next: 0,
+        }
+    }
+
+    /// Construct a query to send to a server at `now`
+    ///
+    /// The request is padded to `len` bytes, which limits the size of the reply. Requests
+    /// smaller than the largest expected reply will be answered with
+    /// [`QueryReply::TooLarge`].
+    pub fn request(&mut self, now: Instant, len: usize) -> Vec<u8> {
+        self.expire(now);
+        let nonce = self.hasher.hash_one(self.next);
+        self.next += 1;
+        self.pending.insert(nonce, now);
+        let mut request = Vec::with_capacity(len);
+        request.extend_from_slice(MAGIC);
+        request.push(Kind::Request as u8);
+        request.extend_from_slice(&nonce.to_le_bytes());
+        request.resize(len.max(MIN_REQUEST_LEN), 0);
+        request
+    }
+
+    /// Process a datagram received at `now`, returning the reply it contains, if any
+    ///
+    /// Replies to unknown or expired requests are ignored.
+    pub fn handle(&mut self, now: Instant, datagram: &[u8]) -> Option<QueryReply> {
+        self.expire(now);
+        let (&kind, body) = datagram.strip_prefix(MAGIC)?.split_first()?;
+        let (nonce, data) = body.split_at_checked(8)?;
+        let nonce = u64::from_le_bytes(nonce.try_into().unwrap());
+        let rtt = now.saturating_duration_since(*self.pending.get(&nonce)?);
+        let reply = if kind == Kind::Status as u8 {
+            QueryReply::Status {
+                rtt,
+                status: data.to_vec(),
+            }
+        } else if kind == Kind::TooLarge as u8 {
+            let required = u32::from_le_bytes(data.get(..4)?.try_into().unwrap());
+            QueryReply::TooLarge {
+                rtt,
+                required: required as usize,
+            }
+        } else {
+            return None;
+        };
+        self.pending.remove(&nonce);
+        Some(reply)
+    }
+
+    /// Number of requests awaiting replies
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        self.pending
+            .retain(|_, &mut sent| now.saturating_duration_since(sent) < timeout);
+    }
+}
+
+/// A server's reply to a [`QueryClient`] request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryReply {
+    /// The server's status, as passed to [`QueryResponder::set_status`]
+    Status { rtt: Duration, status: Vec<u8> },
+    /// The request was too small to carry the status
+    ///
+    /// A request padded to `required` bytes will succeed, unless the status grows.
+    TooLarge { rtt: Duration, required: usize },
+}
+
+impl QueryReply {
+    /// Round-trip time between sending the request and receiving the reply
+    pub fn rtt(&self) -> Duration {
+        match *self {
+            QueryReply::Status { rtt, .. } | QueryReply::TooLarge { rtt, .. } => rtt,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+#[repr(u8)]
+enum Kind {
+    Request,
+    Status,
+    TooLarge,
+}
+
+/// If `datagram` is a message of `kind`, get its body
+fn parse(datagram: &[u8], kind: Kind) -> Option<&[u8]> {
+    let (&actual, body) = datagram.strip_prefix(MAGIC)?.split_first()?;
+    (actual == kind as u8).then_some(body)
+}
+
+/// Identifies a query message and its format version
+const MAGIC: &[u8] = b"nettish-query\x01";
+
+/// Size of a message's magic and kind
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// Size of the smallest request that can be answered without amplification
+const MIN_REQUEST_LEN: usize = HEADER_LEN + 8 + 4;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query() {
+        let start = Instant::now();
+        let mut responder = QueryResponder::new();
+        responder.set_status(vec![7; 100]);
+        let mut client = QueryClient::new(Duration::from_secs(1));
+
+        let request = client.request(start, 64);
+        assert_eq!(request.len(), 64);
+        let reply = responder.respond(&request).unwrap();
+        assert!(reply.len() <= request.len());
+        let rtt = Duration::from_millis(20);
+        let Some(QueryReply::TooLarge { required, .. }) = client.handle(start + rtt, &reply) else {
+            panic!("status should not fit");
+        };
+        assert_eq!(client.handle(start + rtt, &reply), None, "duplicate");
+
+        let request = client.request(start, required);
+        let reply = responder.respond(&request).unwrap();
+        assert_eq!(
+            client.handle(start + rtt, &reply),
+            Some(QueryReply::Status {
+                rtt,
+                status: vec![7; 100]
+            })
+        );
+        assert_eq!(responder.respond(&reply), None, "not a request");
+        assert_eq!(responder.respond(&request[..MIN_REQUEST_LEN - 1]), None);
+
+        client.request(start, 0);
+        assert_eq!(client.pending(), 1);
+        client.request(start + Duration::from_secs(1), 0);
+        assert_eq!(client.pending(), 1, "expired");
+    }
+}
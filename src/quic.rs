@@ -0,0 +1,158 @@
+use std::{
+    fmt,
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use crate::Transport;
+
+/// [`Transport`] over the unreliable datagrams of a QUIC connection
+///
+/// QUIC datagrams are encrypted and congestion controlled, but otherwise behave like UDP. The
+/// maximum datagram size grows as QUIC discovers the path MTU, and is zero if the peer doesn't
+/// support datagrams. Data that must arrive reliably and in order, such as that produced by a
+/// [`BulkSender`](crate::BulkSender), is better carried by the connection's streams, which can be
+/// opened from [`connection`](Self::connection).
+///
+/// Doesn't depend on any particular async runtime: received datagrams are polled without
+/// blocking, so [`try_recv`](Transport::try_recv) may be called from a game loop.
+#[derive(Debug, Clone)]
+pub struct QuinnTransport {
+    connection: quinn::Connection,
+}
+
+impl QuinnTransport {
+    pub fn new(connection: quinn::Connection) -> Self {
+        Self { connection }
+    }
+
+    /// The underlying connection, e.g. to open streams
+    pub fn connection(&self) -> &quinn::Connection {
+        &self.connection
+    }
+}
+
+impl Transport for QuinnTransport {
+    type Error = QuinnError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), QuinnError> {
+        self.connection
+            .send_datagram(datagram.to_vec().into())
+            .map_err(QuinnError::Send)
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, QuinnError> {
+        // A datagram that's already been received is returned on the first poll, so there's no
+        // need to retain the future between calls
+        let read = pin!(self.connection.read_datagram());
+        match read.poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(Ok(datagram)) => Ok(Some(datagram.into())),
+            Poll::Ready(Err(e)) => Err(QuinnError::Connection(e)),
+            Poll::Pending => Ok(None),
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.connection.max_datagram_size().unwrap_or(0)
+    }
+}
+
+/// Errors produced by [`QuinnTransport`]
+#[derive(Debug)]
+pub enum QuinnError {
+    Send(quinn::SendDatagramError),
+    Connection(quinn::ConnectionError),
+}
+
+impl fmt::Display for QuinnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            QuinnError::Send(ref e) => write!(f, "sending datagram failed: {e}"),
+            QuinnError::Connection(ref e) => write!(f, "connection lost: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for QuinnError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, sync::Arc};
+
+    use quinn::{
+        ClientConfig, Endpoint, ServerConfig,
+        rustls::{RootCertStore, pki_types::PrivatePkcs8KeyDer},
+    };
+
+    use super::*;
+
+    /// Connect a client and server on localhost, returning the endpoints to keep them alive
+    async fn connect() -> ([Endpoint; 2], QuinnTransport, QuinnTransport) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let key = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+        let server_config =
+            ServerConfig::with_single_cert(vec![cert.cert.der().clone()], key.into()).unwrap();
+        let server = Endpoint::server(server_config, (Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.cert.der().clone()).unwrap();
+        let mut client = Endpoint::client((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        client.set_default_client_config(
+            ClientConfig::with_root_certificates(Arc::new(roots)).unwrap(),
+        );
+
+        let connecting = client
+            .connect(server.local_addr().unwrap(), "localhost")
+            .unwrap();
+        let connecting = tokio::spawn(connecting);
+        let accepted = server.accept().await.unwrap().await.unwrap();
+        let connected = connecting.await.unwrap().unwrap();
+        (
+            [client, server],
+            QuinnTransport::new(connected),
+            QuinnTransport::new(accepted),
+        )
+    }
+
+    /// Poll `transport` until a datagram arrives
+    async fn recv(transport: &mut QuinnTransport) -> Vec<u8> {
+        loop {
+            match transport.try_recv().unwrap() {
+                Some(datagram) => return datagram,
+                None => tokio::task::yield_now().await,
+            }
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn exchange() {
+        runtime().block_on(async {
+            let (_endpoints, mut a, mut b) = connect().await;
+            assert_eq!(a.try_recv().unwrap(), None);
+            a.send(&[1, 2, 3]).unwrap();
+            assert_eq!(recv(&mut b).await, [1, 2, 3]);
+            b.send(&[4]).unwrap();
+            assert_eq!(recv(&mut a).await, [4]);
+        });
+    }
+
+    #[test]
+    fn too_large() {
+        runtime().block_on(async {
+            let (_endpoints, mut a, _b) = connect().await;
+            assert!(a.max_datagram_size() > 0);
+            assert!(matches!(
+                a.send(&vec![0; a.max_datagram_size() + 1]),
+                Err(QuinnError::Send(quinn::SendDatagramError::TooLarge))
+            ));
+        });
+    }
+}
@@ -0,0 +1,125 @@
+use crate::seq;
+
+/// Measures how far out of order packets arrive
+///
+/// Feed it the sequence number of every non-duplicate packet received. A packet is *late* if a
+/// packet with a newer sequence number was received before it, and its *displacement* is the
+/// number of sequence numbers by which it was overtaken. Displacement indicates how large a
+/// window must be to tolerate reordering without treating late packets as lost, and is a useful
+/// companion to [`LossTracker`](crate::LossTracker) statistics when choosing buffer sizes.
+#[derive(Debug, Clone)]
+pub struct ReorderTracker {
+    /// Weight given to each sample in moving averages
+    smoothing: f32,
+    latest: Option<u16>,
+    stats: ReorderStats,
+}
+
+impl ReorderTracker {
+    /// Construct a tracker that gives weight `smoothing`, from 0 to 1, to each sample in its
+    /// moving averages
+    pub fn new(smoothing: f32) -> Self {
+        Self {
+            smoothing,
+            latest: None,
+            stats: ReorderStats::default(),
+        }
+    }
+
+    /// Record receipt of a packet from the peer
+    pub fn on_received(&mut self, sequence: u16) {
+        self.stats.received += 1;
+        let Some(latest) = self.latest else {
+            self.latest = Some(sequence);
+            return;
+        };
+        let diff = seq::diff(sequence, latest);
+        if diff > 0 {
+            self.latest = Some(sequence);
+            self.stats.late_fraction -= self.smoothing * self.stats.late_fraction;
+            return;
+        }
+        let displacement = diff.unsigned_abs() as u16;
+        self.stats.late += 1;
+        self.stats.late_fraction += self.smoothing * (1.0 - self.stats.late_fraction);
+        self.stats.max_displacement = self.stats.max_displacement.max(displacement);
+        if self.stats.late == 1 {
+            self.stats.mean_displacement = displacement.into();
+        } else {
+            self.stats.mean_displacement +=
+                self.smoothing * (f32::from(displacement) - self.stats.mean_displacement);
+        }
+    }
+
+    /// Statistics gathered so far
+    pub fn stats(&self) -> ReorderStats {
+        self.stats
+    }
+
+    /// Forget accumulated statistics, e.g. to measure a fresh interval
+    ///
+    /// The latest sequence number is preserved so that subsequent late arrivals are still
+    /// recognized.
+    pub fn reset(&mut self) {
+        self.stats = ReorderStats::default();
+    }
+}
+
+impl Default for ReorderTracker {
+    fn default() -> Self {
+        Self::new(0.02)
+    }
+}
+
+/// Statistics gathered by a [`ReorderTracker`]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ReorderStats {
+    /// Number of packets received
+    pub received: u64,
+    /// Number of packets that arrived after a newer packet
+    pub late: u64,
+    /// Moving average of the fraction of packets that arrive late, from 0 to 1
+    pub late_fraction: f32,
+    /// Moving average of the displacement of late packets
+    pub mean_displacement: f32,
+    /// Largest displacement observed
+    pub max_displacement: u16,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut tracker = ReorderTracker::new(0.5);
+        for seq in [0, 1, 4, 2, 3, 5, u16::MAX] {
+            tracker.on_received(seq);
+        }
+        let stats = tracker.stats();
+        assert_eq!(stats.received, 7);
+        assert_eq!(stats.late, 3);
+        assert_eq!(stats.max_displacement, 6);
+        assert!(stats.late_fraction > 0.0);
+        assert!(stats.mean_displacement > 1.0);
+
+        tracker.reset();
+        tracker.on_received(4);
+        assert_eq!(
+            tracker.stats().late,
+            1,
+            "latest sequence number is preserved"
+        );
+        assert_eq!(tracker.stats().max_displacement, 1);
+    }
+
+    #[test]
+    fn in_order() {
+        let mut tracker = ReorderTracker::default();
+        for seq in 0..70_000u32 {
+            tracker.on_received(seq as u16);
+        }
+        assert_eq!(tracker.stats().late, 0);
+        assert_eq!(tracker.stats().late_fraction, 0.0);
+    }
+}
@@ -0,0 +1,857 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    Desync, DesyncDetector, FixedTime, InputMessage, Instant, ReplayProfiler, SessionTiming,
+    SpectatorMessage, TickConfig, exchange::InputExchange, spectator::SpectatorOutput, throttle,
+};
+
+/// Game simulation driven by a [`RollbackSession`]
+pub trait RollbackCallbacks<I, S> {
+    /// Capture the simulation state at the start of `frame` into `state`
+    ///
+    /// `state` is a reused slot holding a previously saved state, or a default value, so that
+    /// buffers can be overwritten in place rather than freshly allocated each frame.
+    fn save(&mut self, frame: u32, state: &mut S);
+    /// Restore the simulation state at the start of `frame`, previously captured by
+    /// [`save`](Self::save)
+    fn load(&mut self, frame: u32, state: &S);
+    /// Simulate `frame` with each player's input, indexed by player
+    fn advance(&mut self, frame: u32, inputs: &[I]);
+    /// Compute a deterministic hash of `state`, e.g. with a [`Checksum`](crate::Checksum)
+    ///
+    /// Only called if enabled by [`RollbackConfig::checksum_interval`].
+    fn checksum(&mut self, frame: u32, state: &S) -> u64 {
+        let _ = (frame, state);
+        0
+    }
+}
+
+/// Guesses the inputs of remote players that haven't yet arrived
+///
+/// Implemented for closures taking the same arguments as [`predict`](Self::predict).
+pub trait InputPredictor<I> {
+    /// Predict `player`'s input for `frame`, given their most recent known input, if any
+    fn predict(&mut self, player: usize, frame: u32, last: Option<&I>) -> I;
+}
+
+impl<I, F: FnMut(usize, u32, Option<&I>) -> I> InputPredictor<I> for F {
+    fn predict(&mut self, player: usize, frame: u32, last: Option<&I>) -> I {
+        self(player, frame, last)
+    }
+}
+
+/// An [`InputPredictor`] that assumes players hold their most recent input
+///
+/// Players typically hold the same input for many frames, so this is usually right.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RepeatLast;
+
+impl<I: Clone + Default> InputPredictor<I> for RepeatLast {
+    fn predict(&mut self, _: usize, _: u32, last: Option<&I>) -> I {
+        last.cloned().unwrap_or_default()
+    }
+}
+
+/// Parameters of a [`RollbackSession`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RollbackConfig {
+    /// Number of participating players, each controlled by a separate peer
+    pub players: usize,
+    /// Number of frames between a local input being collected and taking effect
+    ///
+    /// Larger delays give inputs more time to reach remote peers, reducing the frequency and
+    /// depth of rollbacks at the cost of responsiveness.
+    pub input_delay: u32,
+    /// Maximum number of frames to simulate past the last frame for which every input is known
+    pub max_rollback: u32,
+    /// Number of frames between state checksums exchanged to detect desyncs, or 0 to disable
+    pub checksum_interval: u32,
+    /// If set, retain confirmed inputs for spectators, releasing them this many frames after
+    /// they're confirmed
+    pub spectator_delay: Option<u32>,
+    /// Rate at which frames are simulated, from which simulation time is derived
+    pub tick: TickConfig,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            input_delay: 2,
+            max_rollback: 8,
+            checksum_interval: 0,
+            spectator_delay: None,
+            tick: TickConfig::default(),
+        }
+    }
+}
+
+/// Peer-to-peer rollback netcode in the style of GGPO
+///
+/// Each frame, the local player's input is passed to [`advance_frame`](Self::advance_frame), which
+/// simulates the frame immediately rather than waiting for remote inputs. Remote inputs that
+/// haven't arrived are predicted by an [`InputPredictor`], by default [`RepeatLast`]. When a
+/// remote input arrives that differs from its prediction, the session loads the state saved
+/// before the first mispredicted frame and resimulates up to the present.
+///
+/// Call [`message`](Self::message) to get the data to send to each remote peer, typically once
+/// per frame, and pass messages received from them to [`receive`](Self::receive). Simulations
+/// must be deterministic, so that every peer arrives at the same state given the same inputs. To
+/// verify this, peers can periodically exchange [`checksum`](RollbackCallbacks::checksum)s of
+/// states computed from confirmed inputs, reporting any mismatch as a [`desync`](Self::desync).
+#[derive(Debug, Clone)]
+pub struct RollbackSession<I, S, P = RepeatLast> {
+    exchange: InputExchange<I>,
+    predictor: P,
+    input_delay: u32,
+    max_rollback: u32,
+    /// The next frame to simulate
+    frame: u32,
+    /// States saved at the start of frames that may yet be rolled back to
+    states: VecDeque<(u32, S)>,
+    /// Slots for saved states no longer needed
+    pool: StatePool<S>,
+    /// Inputs simulated for each frame since the last for which every input was known
+    speculated: VecDeque<(u32, Vec<I>)>,
+    /// Earliest simulated frame whose predicted inputs turned out to be wrong
+    first_incorrect: Option<u32>,
+    /// Frame advantage over each player
+    advantages: Vec<Advantage>,
+    checksum_interval: u32,
+    /// Checksum comparisons with each player
+    detectors: Vec<DesyncDetector>,
+    /// The most recent local checksum and the frame it describes
+    checksum: Option<(u32, u64)>,
+    /// The first desync detected, and the player it was detected with
+    desync: Option<(usize, Desync)>,
+    stats: RollbackStats,
+    profiler: ReplayProfiler,
+    spectators: Option<SpectatorOutput>,
+    tick: TickConfig,
+}
+
+impl<I: Clone + Default + PartialEq, S: Default> RollbackSession<I, S> {
+    /// Begin a session in which the local peer controls player `local`
+    pub fn new(config: RollbackConfig, local: usize) -> Self {
+        Self::with_predictor(config, local, RepeatLast)
+    }
+}
+
+impl<I: Clone + Default + PartialEq, S: Default, P: InputPredictor<I>> RollbackSession<I, S, P> {
+    /// Begin a session in which the local peer controls player `local`, predicting remote inputs
+    /// with `predictor`
+    pub fn with_predictor(config: RollbackConfig, local: usize, predictor: P) -> Self {
+        Self {
+            exchange: InputExchange::new(config.players, local, config.input_delay),
+            predictor,
+            input_delay: config.input_delay,
+            max_rollback: config.max_rollback,
+            frame: 0,
+            states: VecDeque::new(),
+            pool: StatePool::new(config.max_rollback as usize + 2),
+            speculated: VecDeque::new(),
+            first_incorrect: None,
+            advantages: vec![Advantage::default(); config.players],
+            checksum_interval: config.checksum_interval,
+            detectors: vec![
+                DesyncDetector::new(config.checksum_interval.into(), CHECKSUM_WINDOW);
+                config.players
+            ],
+            checksum: None,
+            desync: None,
+            stats: RollbackStats::default(),
+            profiler: ReplayProfiler::new(),
+            spectators: config.spectator_delay.map(SpectatorOutput::new),
+            tick: config.tick,
+        }
+    }
+
+    /// Collect the local input and simulate the next frame, rolling back first if necessary
+    ///
+    /// Returns `false` without collecting `input` if the session is too far ahead of the inputs
+    /// received from remote peers, in which case the caller should try again on the next frame.
+    pub fn advance_frame(&mut self, input: I, game: &mut impl RollbackCallbacks<I, S>) -> bool {
+        self.rollback(game);
+        if self.frame >= self.exchange.all_confirmed() + self.max_rollback {
+            return false;
+        }
+        let target = self.frame + self.input_delay;
+        // After the delay changes, the local input stream must be stretched or compressed
+        while self.exchange.confirmed_frames(self.exchange.local()) < target {
+            self.exchange.add_local(input.clone());
+        }
+        if self.exchange.confirmed_frames(self.exchange.local()) == target {
+            self.exchange.add_local(input);
+        }
+        self.simulate(game);
+
+        let confirmed = self.exchange.all_confirmed().min(self.frame);
+        if self.checksum_interval != 0 {
+            self.checksum(confirmed, game);
+        }
+        while self.states.front().is_some_and(|&(f, _)| f < confirmed) {
+            let (_, state) = self.states.pop_front().unwrap();
+            self.pool.put(state);
+        }
+        while self.speculated.front().is_some_and(|&(f, _)| f < confirmed) {
+            self.speculated.pop_front();
+        }
+        let retain = self
+            .spectators
+            .map_or(confirmed, |x| x.next().min(confirmed));
+        self.exchange.discard_before(retain);
+        true
+    }
+
+    /// Collect confirmed inputs for spectators that haven't yet been collected
+    ///
+    /// Returns `None` if there are none, or if spectators weren't enabled by
+    /// [`RollbackConfig::spectator_delay`]. The result should be delivered reliably to every
+    /// spectator and passed to a [`SpectatorReplay`](crate::SpectatorReplay).
+    pub fn spectator_message(&mut self) -> Option<SpectatorMessage<I>> {
+        self.spectators.as_mut()?.take(&self.exchange)
+    }
+
+    /// Construct a message carrying local inputs to the peer controlling `player`
+    pub fn message(&self, player: usize) -> InputMessage<I> {
+        InputMessage {
+            advantage: self.advantages[player]
+                .local
+                .clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+            checksum: self.checksum,
+            ..self.exchange.message(player, self.frame)
+        }
+    }
+
+    /// Change the input delay and rollback window, e.g. as agreed by a
+    /// [`DelayNegotiator`](crate::DelayNegotiator)
+    ///
+    /// Increasing the delay repeats the next local input to fill the gap, while decreasing it
+    /// discards local inputs until the simulation catches up with those already collected.
+    pub fn set_timing(&mut self, timing: SessionTiming) {
+        self.input_delay = timing.input_delay;
+        self.max_rollback = timing.max_rollback;
+        self.pool.capacity = timing.max_rollback as usize + 2;
+    }
+
+    /// Process a message from a remote peer
+    ///
+    /// If it reveals a misprediction, the next call to [`advance_frame`](Self::advance_frame) will
+    /// roll back.
+    pub fn receive(&mut self, msg: InputMessage<I>) {
+        let player = msg.player;
+        if let Some(advantage) = self.advantages.get_mut(player) {
+            advantage.update(self.frame, &msg);
+        }
+        if let Some((frame, checksum)) = msg.checksum
+            && player != self.exchange.local()
+            && let Some(detector) = self.detectors.get_mut(player)
+            && let Some(desync) = detector.record_remote(frame.into(), checksum)
+        {
+            self.desync.get_or_insert((player, desync));
+        }
+        for frame in self.exchange.receive(msg) {
+            if frame >= self.frame {
+                break;
+            }
+            let Some(&(start, _)) = self.speculated.front() else {
+                break;
+            };
+            let inputs = &self.speculated[(frame - start) as usize].1;
+            self.stats.predictions += 1;
+            if self.exchange.confirmed(player, frame) != Some(&inputs[player]) {
+                self.stats.mispredictions += 1;
+                self.first_incorrect = Some(self.first_incorrect.map_or(frame, |f| f.min(frame)));
+            }
+        }
+    }
+
+    fn rollback(&mut self, game: &mut impl RollbackCallbacks<I, S>) {
+        let Some(frame) = self.first_incorrect.take() else {
+            return;
+        };
+        let start = Instant::now();
+        let index = self
+            .states
+            .iter()
+            .position(|&(f, _)| f == frame)
+            .expect("rolled back to unsaved frame");
+        game.load(frame, &self.states[index].1);
+        for (_, state) in self.states.drain(index..) {
+            self.pool.put(state);
+        }
+        while self.speculated.back().is_some_and(|&(f, _)| f >= frame) {
+            self.speculated.pop_back();
+        }
+        let end = self.frame;
+        self.frame = frame;
+        while self.frame < end {
+            self.simulate(game);
+        }
+
+        let depth = (end - frame) as usize;
+        let histogram = &mut self.stats.depths;
+        if histogram.len() <= depth {
+            histogram.resize(depth + 1, 0);
+        }
+        histogram[depth] += 1;
+        self.stats.rollbacks += 1;
+        self.stats.resimulated_frames += depth as u64;
+        let elapsed = start.elapsed();
+        self.stats.resimulation_time += elapsed;
+        self.profiler.record(depth, elapsed);
+    }
+
+    /// Simulate the next frame using the best available inputs
+    fn simulate(&mut self, game: &mut impl RollbackCallbacks<I, S>) {
+        let frame = self.frame;
+        let inputs = (0..self.exchange.players())
+            .map(|p| match self.exchange.confirmed(p, frame) {
+                Some(x) => x.clone(),
+                None => self.predictor.predict(p, frame, self.exchange.last(p)),
+            })
+            .collect::<Vec<_>>();
+        let speculative = frame >= self.exchange.all_confirmed();
+        let checkpoint =
+            self.checksum_interval != 0 && frame.is_multiple_of(self.checksum_interval);
+        if speculative || checkpoint {
+            // Some inputs are predicted, so we may need to return to this frame, or its state
+            // must be checksummed once confirmed
+            let mut state = self.pool.take();
+            game.save(frame, &mut state);
+            self.states.push_back((frame, state));
+        }
+        game.advance(frame, &inputs);
+        if speculative {
+            self.speculated.push_back((frame, inputs));
+        }
+        self.frame += 1;
+    }
+
+    /// Checksum states at checkpoints up to and including `confirmed`, which are known to result
+    /// from confirmed inputs
+    fn checksum(&mut self, confirmed: u32, game: &mut impl RollbackCallbacks<I, S>) {
+        for (frame, state) in &self.states {
+            if *frame > confirmed {
+                break;
+            }
+            if !frame.is_multiple_of(self.checksum_interval)
+                || self.checksum.is_some_and(|(f, _)| f >= *frame)
+            {
+                continue;
+            }
+            let checksum = game.checksum(*frame, state);
+            self.checksum = Some((*frame, checksum));
+            for (player, detector) in self.detectors.iter_mut().enumerate() {
+                if player == self.exchange.local() {
+                    continue;
+                }
+                if let Some(desync) = detector.record_local((*frame).into(), checksum) {
+                    self.desync.get_or_insert((player, desync));
+                }
+            }
+        }
+    }
+}
+
+impl<I, S, P> RollbackSession<I, S, P> {
+    /// The next frame to be simulated
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Simulation time at the start of `frame`, as a deterministic [`FixedTime`]
+    pub fn frame_time(&self, frame: u32) -> FixedTime {
+        self.tick.fixed_time(frame.into())
+    }
+
+    /// Simulation time that elapses during `frame`
+    pub fn frame_step(&self, frame: u32) -> FixedTime {
+        self.tick.fixed_step(frame.into())
+    }
+
+    /// Statistics gathered so far
+    pub fn stats(&self) -> &RollbackStats {
+        &self.stats
+    }
+
+    /// Forget accumulated statistics, e.g. to measure a fresh interval
+    pub fn reset_stats(&mut self) {
+        self.stats = RollbackStats::default();
+        self.profiler.reset();
+    }
+
+    /// Cost of each rollback, with a rolled back frame counted as one replayed input
+    pub fn profiler(&self) -> &ReplayProfiler {
+        &self.profiler
+    }
+
+    /// Mutable access to the [`profiler`](Self::profiler), e.g. to
+    /// [`flush`](ReplayProfiler::flush) it
+    pub fn profiler_mut(&mut self) -> &mut ReplayProfiler {
+        &mut self.profiler
+    }
+
+    /// The first desync detected, and the player whose checksum disagreed
+    ///
+    /// Once a desync occurs, the simulations have diverged and won't recover without
+    /// intervention, e.g. ending the session or transferring a full state.
+    pub fn desync(&self) -> Option<(usize, Desync)> {
+        self.desync
+    }
+
+    /// Smoothed number of frames by which the local peer is ahead of the furthest-behind peer
+    ///
+    /// Computed symmetrically from both peers' perspectives so that latency cancels out. A peer
+    /// running ahead forces its peers to roll back more often, since its inputs arrive later
+    /// relative to their simulations.
+    pub fn frame_advantage(&self) -> f32 {
+        (0..self.advantages.len())
+            .filter(|&p| p != self.exchange.local())
+            .map(|p| self.advantages[p].smoothed)
+            .fold(f32::NEG_INFINITY, f32::max)
+            .max(0.0)
+    }
+
+    /// Time to wait before simulating the next frame, after one frame interval of wall-clock time
+    /// has passed, to let peers catch up
+    ///
+    /// Nonzero only if the local peer is running ahead by more than half a frame, in which case
+    /// the delay is introduced gradually by [`throttle`] to avoid visible hitches. Typically a
+    /// fraction of a frame.
+    pub fn recommended_sleep(&self) -> Duration {
+        let frame_interval = self.tick.interval();
+        // Treat the margin by which we're ahead as a buffer to be kept within one to two frames
+        let buffer = frame_interval.mul_f32((1.5 - self.frame_advantage()).max(0.0));
+        frame_interval - throttle(frame_interval, buffer, frame_interval, frame_interval)
+    }
+
+    /// Number of consecutive frames, starting from 0, for which every player's input is known
+    pub fn confirmed_frames(&self) -> u32 {
+        self.exchange.all_confirmed()
+    }
+
+    /// The player controlled by the local peer
+    pub fn local_player(&self) -> usize {
+        self.exchange.local()
+    }
+}
+
+/// Statistics gathered by a [`RollbackSession`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollbackStats {
+    /// Number of rollbacks performed
+    pub rollbacks: u64,
+    /// Number of rollbacks of each depth, indexed by the number of frames resimulated
+    pub depths: Vec<u64>,
+    /// Total number of frames resimulated
+    pub resimulated_frames: u64,
+    /// Wall-clock time spent restoring and resimulating states
+    pub resimulation_time: Duration,
+    /// Number of remote inputs that were predicted before arriving
+    pub predictions: u64,
+    /// Number of predicted remote inputs that turned out to be wrong
+    pub mispredictions: u64,
+}
+
+impl RollbackStats {
+    /// Fraction of predicted remote inputs that were correct, from 0 to 1
+    pub fn hit_rate(&self) -> f32 {
+        if self.predictions == 0 {
+            return 1.0;
+        }
+        1.0 - self.mispredictions as f32 / self.predictions as f32
+    }
+
+    /// Mean number of frames resimulated per rollback
+    pub fn mean_depth(&self) -> f32 {
+        if self.rollbacks == 0 {
+            return 0.0;
+        }
+        self.resimulated_frames as f32 / self.rollbacks as f32
+    }
+}
+
+/// Reusable slots for saved states, so that each save needn't allocate
+#[derive(Debug, Clone)]
+pub(crate) struct StatePool<S> {
+    free: Vec<S>,
+    /// Maximum number of slots to retain
+    capacity: usize,
+}
+
+impl<S: Default> StatePool<S> {
+    /// Preallocate `capacity` slots, enough for a full rollback window
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            free: (0..capacity).map(|_| S::default()).collect(),
+            capacity,
+        }
+    }
+
+    pub(crate) fn take(&mut self) -> S {
+        self.free.pop().unwrap_or_default()
+    }
+
+    pub(crate) fn put(&mut self, state: S) {
+        if self.free.len() < self.capacity {
+            self.free.push(state);
+        }
+    }
+}
+
+/// Number of checkpoints for which to retain checksums awaiting comparison
+const CHECKSUM_WINDOW: u64 = 8;
+
+/// Estimated frame advantage over a single remote peer
+#[derive(Debug, Copy, Clone, Default)]
+struct Advantage {
+    /// Frames by which we were ahead of the peer's most recent report
+    local: i32,
+    /// Average of the difference between the local and remote measurements
+    smoothed: f32,
+}
+
+impl Advantage {
+    fn update<I>(&mut self, frame: u32, msg: &InputMessage<I>) {
+        self.local = frame.wrapping_sub(msg.frame) as i32;
+        // Each side's measurement is inflated by latency, so halving the difference cancels it
+        let sample = (self.local - i32::from(msg.advantage)) as f32 / 2.0;
+        self.smoothed += (sample - self.smoothed) / 16.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accumulates inputs, recording the state after each frame
+    #[derive(Default)]
+    struct Game {
+        state: u64,
+        history: Vec<u64>,
+        loads: usize,
+        /// Frame on which to diverge
+        bug: Option<u32>,
+    }
+
+    impl RollbackCallbacks<u8, u64> for Game {
+        fn save(&mut self, _: u32, state: &mut u64) {
+            *state = self.state;
+        }
+
+        fn load(&mut self, _: u32, state: &u64) {
+            self.state = *state;
+            self.loads += 1;
+        }
+
+        fn advance(&mut self, frame: u32, inputs: &[u8]) {
+            for (i, &x) in inputs.iter().enumerate() {
+                self.state = self.state.wrapping_mul(31) + (i as u64 + 1) * u64::from(x);
+            }
+            if self.bug == Some(frame) {
+                self.state += 1;
+            }
+            self.history.truncate(frame as usize);
+            self.history.push(self.state);
+        }
+
+        fn checksum(&mut self, _: u32, state: &u64) -> u64 {
+            *state
+        }
+    }
+
+    #[test]
+    fn converge() {
+        let config = RollbackConfig::default();
+        let mut sessions = [
+            RollbackSession::new(config, 0),
+            RollbackSession::new(config, 1),
+        ];
+        let mut games = [Game::default(), Game::default()];
+        // Messages arrive three frames late
+        let mut in_flight = VecDeque::new();
+        for frame in 0..40u32 {
+            in_flight.push_back([sessions[0].message(1), sessions[1].message(0)]);
+            if in_flight.len() > 3 {
+                let [a, b] = in_flight.pop_front().unwrap();
+                sessions[1].receive(a);
+                sessions[0].receive(b);
+            }
+            for (i, (session, game)) in sessions.iter_mut().zip(&mut games).enumerate() {
+                let input = (frame / (3 + i as u32)) as u8;
+                assert!(session.advance_frame(input, game));
+            }
+        }
+        assert!(games.iter().all(|g| g.loads > 0));
+        for (session, game) in sessions.iter().zip(&games) {
+            let stats = session.stats();
+            assert_eq!(stats.rollbacks, game.loads as u64);
+            assert_eq!(stats.depths.iter().sum::<u64>(), stats.rollbacks);
+            assert_eq!(session.profiler().stats().replays, stats.rollbacks);
+            assert_eq!(session.profiler().stats().inputs, stats.resimulated_frames);
+            assert!(stats.mean_depth() >= 1.0);
+            assert!(stats.hit_rate() > 0.5 && stats.hit_rate() < 1.0);
+        }
+        for [a, b] in in_flight.drain(..) {
+            sessions[1].receive(a);
+            sessions[0].receive(b);
+        }
+        let [a, b] = [sessions[0].message(1), sessions[1].message(0)];
+        sessions[1].receive(a);
+        sessions[0].receive(b);
+        for (session, game) in sessions.iter_mut().zip(&mut games) {
+            session.advance_frame(0, game);
+        }
+        assert!(sessions.iter().all(|s| s.confirmed_frames() > s.frame()));
+        assert_eq!(games[0].history, games[1].history);
+    }
+
+    #[test]
+    fn timing() {
+        let mut session = RollbackSession::new(RollbackConfig::default(), 0);
+        let mut remote = RollbackSession::<u8, u64>::new(RollbackConfig::default(), 1);
+        let mut game = Game::default();
+        session.advance_frame(1, &mut game);
+        session.set_timing(SessionTiming {
+            input_delay: 4,
+            max_rollback: 8,
+        });
+        session.advance_frame(2, &mut game);
+        remote.receive(session.message(1));
+        let inputs = (0..7)
+            .map(|f| remote.exchange.confirmed(0, f).copied())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            inputs,
+            [0, 0, 1, 2, 2, 2]
+                .map(Some)
+                .into_iter()
+                .chain([None])
+                .collect::<Vec<_>>()
+        );
+
+        session.set_timing(SessionTiming {
+            input_delay: 1,
+            max_rollback: 8,
+        });
+        for _ in 0..4 {
+            session.advance_frame(3, &mut game);
+        }
+        assert_eq!(session.exchange.confirmed_frames(0), 7, "inputs discarded");
+        session.advance_frame(3, &mut game);
+        assert_eq!(session.exchange.confirmed_frames(0), 8);
+    }
+
+    #[test]
+    fn advantage() {
+        let config = RollbackConfig {
+            max_rollback: 100,
+            ..RollbackConfig::default()
+        };
+        let mut a = RollbackSession::new(config, 0);
+        let mut b = RollbackSession::new(config, 1);
+        let mut games = [Game::default(), Game::default()];
+        let interval = config.tick.interval();
+        for _ in 0..10 {
+            a.advance_frame(0, &mut games[0]);
+        }
+        for _ in 0..60 {
+            a.advance_frame(0, &mut games[0]);
+            b.advance_frame(0, &mut games[1]);
+            a.receive(b.message(0));
+            b.receive(a.message(1));
+        }
+        assert!(a.frame_advantage() > 4.0);
+        assert_eq!(b.frame_advantage(), 0.0);
+        assert!(a.recommended_sleep() > Duration::ZERO);
+        assert!(a.recommended_sleep() <= interval);
+        assert_eq!(b.recommended_sleep(), Duration::ZERO);
+    }
+
+    #[test]
+    fn frame_time() {
+        let config = RollbackConfig {
+            tick: TickConfig::new(30),
+            ..RollbackConfig::default()
+        };
+        let session = RollbackSession::<u8, u8>::new(config, 0);
+        assert_eq!(session.frame_time(45), FixedTime::from_ratio(3, 2));
+        assert_eq!(
+            session.frame_time(45) + session.frame_step(45),
+            session.frame_time(46)
+        );
+    }
+
+    #[test]
+    fn desync() {
+        let config = RollbackConfig {
+            checksum_interval: 4,
+            ..RollbackConfig::default()
+        };
+        let mut sessions = [
+            RollbackSession::new(config, 0),
+            RollbackSession::new(config, 1),
+        ];
+        let mut games = [
+            Game::default(),
+            Game {
+                bug: Some(9),
+                ..Game::default()
+            },
+        ];
+        for frame in 0..20u32 {
+            let [a, b] = [sessions[0].message(1), sessions[1].message(0)];
+            sessions[1].receive(a);
+            sessions[0].receive(b);
+            for (session, game) in sessions.iter_mut().zip(&mut games) {
+                session.advance_frame(frame as u8, game);
+            }
+            if frame < 8 {
+                assert_eq!(sessions[0].desync(), None);
+            }
+        }
+        for (i, session) in sessions.iter().enumerate() {
+            let (player, desync) = session.desync().unwrap();
+            assert_eq!(player, 1 - i);
+            assert_eq!(desync.tick, 12, "first checkpoint after divergence");
+        }
+    }
+
+    #[test]
+    fn pool() {
+        /// Counts saves into reused buffers
+        #[derive(Default)]
+        struct Buffers {
+            reused: usize,
+        }
+
+        impl RollbackCallbacks<u8, Vec<u8>> for Buffers {
+            fn save(&mut self, frame: u32, state: &mut Vec<u8>) {
+                if state.capacity() > 0 {
+                    self.reused += 1;
+                }
+                state.clear();
+                state.extend_from_slice(&frame.to_le_bytes());
+            }
+
+            fn load(&mut self, _: u32, _: &Vec<u8>) {}
+
+            fn advance(&mut self, _: u32, _: &[u8]) {}
+        }
+
+        let config = RollbackConfig {
+            input_delay: 0,
+            ..RollbackConfig::default()
+        };
+        let mut a = RollbackSession::new(config, 0);
+        let mut b = RollbackSession::new(config, 1);
+        let mut games = [Buffers::default(), Buffers::default()];
+        for _ in 0..20 {
+            a.advance_frame(0, &mut games[0]);
+            b.advance_frame(0, &mut games[1]);
+            a.receive(b.message(0));
+            b.receive(a.message(1));
+        }
+        assert!(games.iter().all(|g| g.reused >= 15));
+    }
+
+    #[test]
+    fn predictor() {
+        let config = RollbackConfig {
+            input_delay: 0,
+            ..RollbackConfig::default()
+        };
+        let mut predictions = 0;
+        let mut session =
+            RollbackSession::with_predictor(config, 0, |player, _, last: Option<&u8>| {
+                assert_eq!(player, 1);
+                predictions += 1;
+                last.map_or(5, |x| x + 1)
+            });
+        let mut remote = RollbackSession::<u8, u64>::new(config, 1);
+        let mut game = Game::default();
+        let mut remote_game = Game::default();
+        remote.advance_frame(5, &mut remote_game);
+        session.advance_frame(0, &mut game);
+        remote.advance_frame(6, &mut remote_game);
+        session.receive(remote.message(0));
+        session.advance_frame(0, &mut game);
+        assert_eq!(game.loads, 0, "predicted correctly");
+        session.advance_frame(0, &mut game);
+        drop(session);
+        assert_eq!(predictions, 2);
+    }
+
+    #[test]
+    fn spectate() {
+        let config = RollbackConfig {
+            spectator_delay: Some(2),
+            ..RollbackConfig::default()
+        };
+        let mut sessions = [
+            RollbackSession::new(config, 0),
+            RollbackSession::new(config, 1),
+        ];
+        let mut games = [Game::default(), Game::default()];
+        let mut replay = crate::SpectatorReplay::new();
+        let mut spectator = Game::default();
+        for frame in 0..20u32 {
+            for (i, (session, game)) in sessions.iter_mut().zip(&mut games).enumerate() {
+                session.advance_frame((frame / (2 + i as u32)) as u8, game);
+            }
+            let [a, b] = [sessions[0].message(1), sessions[1].message(0)];
+            sessions[1].receive(a);
+            sessions[0].receive(b);
+            if let Some(msg) = sessions[0].spectator_message() {
+                assert!(replay.receive(msg));
+            }
+            while let Some((frame, inputs)) = replay.pop() {
+                spectator.advance(frame, &inputs);
+            }
+        }
+        let frames = spectator.history.len();
+        assert!(frames >= 15);
+        assert_eq!(spectator.history, games[0].history[..frames]);
+    }
+
+    #[test]
+    fn stall() {
+        let config = RollbackConfig::default();
+        let mut session = RollbackSession::new(config, 0);
+        let mut game = Game::default();
+        let limit = config.input_delay + config.max_rollback;
+        for _ in 0..limit {
+            assert!(session.advance_frame(1, &mut game));
+        }
+        assert!(!session.advance_frame(1, &mut game));
+        assert_eq!(session.frame(), limit);
+
+        let mut remote = RollbackSession::<u8, u64>::new(config, 1);
+        remote.advance_frame(0, &mut Game::default());
+        session.receive(remote.message(0));
+        assert!(session.advance_frame(1, &mut game));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_partial_config() {
+        use serde::{
+            Deserialize,
+            de::value::{Error, MapDeserializer},
+        };
+
+        // Omitted fields, including the nested tick config, take their defaults
+        let fields =
+            MapDeserializer::<_, Error>::new([("players", 4u32), ("input_delay", 3)].into_iter());
+        let config = RollbackConfig::deserialize(fields).unwrap();
+        let default = RollbackConfig::default();
+        assert_eq!(config.players, 4);
+        assert_eq!(config.input_delay, 3);
+        assert_eq!(config.max_rollback, default.max_rollback);
+        assert_eq!(config.spectator_delay, None);
+        assert_eq!(config.tick, default.tick);
+    }
+}
@@ -0,0 +1,253 @@
+use std::{fmt, sync::Arc};
+
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender, error::TryRecvError},
+    task::JoinHandle,
+};
+use webrtc::data_channel::{
+    RTCDataChannel, data_channel_init::RTCDataChannelInit, data_channel_state::RTCDataChannelState,
+};
+
+use crate::{Reliability, Transport, transport::DEFAULT_MAX_DATAGRAM_SIZE};
+
+/// Number of bytes that may be waiting to be sent on a data channel before further sends are
+/// dropped
+const MAX_BUFFERED: usize = 64 * 1024;
+
+/// Data channel configuration delivering messages with `reliability`
+///
+/// Only [`Reliability::Reliable`] data benefits from the data channel's own retransmission and
+/// ordering. [`Reliability::Eventual`] data is repaired by the crate resending the latest value,
+/// so a channel carrying it is unreliable and unordered too, avoiding head-of-line blocking.
+pub fn data_channel_init(reliability: Reliability) -> RTCDataChannelInit {
+    match reliability {
+        Reliability::Unreliable | Reliability::Eventual => RTCDataChannelInit {
+            ordered: Some(false),
+            max_retransmits: Some(0),
+            ..RTCDataChannelInit::default()
+        },
+        Reliability::Reliable => RTCDataChannelInit {
+            ordered: Some(true),
+            ..RTCDataChannelInit::default()
+        },
+    }
+}
+
+/// [`Transport`] over a WebRTC data channel, e.g. to a browser peer
+///
+/// The channel should be unreliable and unordered, as configured by
+/// [`data_channel_init(Reliability::Unreliable)`](data_channel_init), for its messages to behave
+/// like datagrams. Datagrams sent before the channel opens, or while more than 64KiB is waiting
+/// to be sent, are dropped, as they might be by a congested network.
+///
+/// A background task sends datagrams, so the [`Transport`] methods never block and may be called
+/// from a game loop outside the runtime. Send errors and closure of the channel are reported by
+/// [`try_recv`](Transport::try_recv).
+pub struct WebRtcTransport {
+    channel: Arc<RTCDataChannel>,
+    outgoing: UnboundedSender<Vec<u8>>,
+    incoming: UnboundedReceiver<Result<Vec<u8>, WebRtcError>>,
+    task: JoinHandle<()>,
+    max_datagram_size: usize,
+}
+
+impl WebRtcTransport {
+    /// Exchange datagrams over `channel`, replacing its message and close handlers
+    ///
+    /// Must be called from within a Tokio runtime, on which the background task is spawned.
+    pub fn new(channel: Arc<RTCDataChannel>) -> Self {
+        let (outgoing, outgoing_recv) = mpsc::unbounded_channel();
+        let (incoming_send, incoming) = mpsc::unbounded_channel();
+        channel.on_message(Box::new({
+            let incoming = incoming_send.clone();
+            move |message| {
+                // Text messages aren't produced by this transport, so are ignored
+                if !message.is_string {
+                    let _ = incoming.send(Ok(message.data.to_vec()));
+                }
+                Box::pin(async {})
+            }
+        }));
+        channel.on_close(Box::new({
+            let incoming = incoming_send.clone();
+            move || {
+                let _ = incoming.send(Err(WebRtcError::Closed));
+                Box::pin(async {})
+            }
+        }));
+        let task = tokio::spawn(send_task(channel.clone(), outgoing_recv, incoming_send));
+        Self {
+            channel,
+            outgoing,
+            incoming,
+            task,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        }
+    }
+
+    /// The underlying data channel
+    pub fn channel(&self) -> &Arc<RTCDataChannel> {
+        &self.channel
+    }
+
+    /// Change the size of the largest datagram that may be sent
+    ///
+    /// Defaults to a conservative 1200 bytes. Larger messages are fragmented, and lost entirely
+    /// if any fragment is lost.
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+}
+
+impl Transport for WebRtcTransport {
+    type Error = WebRtcError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), WebRtcError> {
+        match self.channel.ready_state() {
+            RTCDataChannelState::Closing | RTCDataChannelState::Closed => {
+                return Err(WebRtcError::Closed);
+            }
+            _ => {}
+        }
+        self.outgoing
+            .send(datagram.to_vec())
+            .map_err(|_| WebRtcError::Closed)
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, WebRtcError> {
+        match self.incoming.try_recv() {
+            Ok(result) => result.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(WebRtcError::Closed),
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
+
+impl fmt::Debug for WebRtcTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebRtcTransport")
+            .field("label", &self.channel.label())
+            .field("ready_state", &self.channel.ready_state())
+            .field("max_datagram_size", &self.max_datagram_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for WebRtcTransport {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn send_task(
+    channel: Arc<RTCDataChannel>,
+    mut outgoing: UnboundedReceiver<Vec<u8>>,
+    errors: UnboundedSender<Result<Vec<u8>, WebRtcError>>,
+) {
+    while let Some(datagram) = outgoing.recv().await {
+        if channel.ready_state() != RTCDataChannelState::Open
+            || channel.buffered_amount().await > MAX_BUFFERED
+        {
+            continue;
+        }
+        if let Err(e) = channel.send(&datagram.into()).await
+            && errors.send(Err(WebRtcError::Send(e))).is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Errors produced by [`WebRtcTransport`]
+#[derive(Debug)]
+pub enum WebRtcError {
+    /// The data channel was closed
+    Closed,
+    Send(webrtc::Error),
+}
+
+impl fmt::Display for WebRtcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WebRtcError::Closed => f.write_str("data channel closed"),
+            WebRtcError::Send(ref e) => write!(f, "sending message failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for WebRtcError {}
+
+#[cfg(test)]
+mod tests {
+    use webrtc::{
+        api::{APIBuilder, setting_engine::SettingEngine},
+        peer_connection::{RTCPeerConnection, configuration::RTCConfiguration},
+    };
+
+    use super::*;
+
+    /// Complete an offer/answer exchange between two local peers
+    async fn negotiate(offerer: &RTCPeerConnection, answerer: &RTCPeerConnection) {
+        let offer = offerer.create_offer(None).await.unwrap();
+        let mut gathered = offerer.gathering_complete_promise().await;
+        offerer.set_local_description(offer).await.unwrap();
+        gathered.recv().await;
+        let offer = offerer.local_description().await.unwrap();
+        answerer.set_remote_description(offer).await.unwrap();
+        let answer = answerer.create_answer(None).await.unwrap();
+        let mut gathered = answerer.gathering_complete_promise().await;
+        answerer.set_local_description(answer).await.unwrap();
+        gathered.recv().await;
+        let answer = answerer.local_description().await.unwrap();
+        offerer.set_remote_description(answer).await.unwrap();
+    }
+
+    #[test]
+    fn exchange() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut settings = SettingEngine::default();
+            settings.set_include_loopback_candidate(true);
+            let api = APIBuilder::new().with_setting_engine(settings).build();
+            let a = api
+                .new_peer_connection(RTCConfiguration::default())
+                .await
+                .unwrap();
+            let b = api
+                .new_peer_connection(RTCConfiguration::default())
+                .await
+                .unwrap();
+            let (channel_send, mut channel_recv) = mpsc::unbounded_channel();
+            b.on_data_channel(Box::new(move |channel| {
+                let _ = channel_send.send(channel);
+                Box::pin(async {})
+            }));
+            let init = data_channel_init(Reliability::Unreliable);
+            let channel = a.create_data_channel("game", Some(init)).await.unwrap();
+            let mut a_transport = WebRtcTransport::new(channel);
+            negotiate(&a, &b).await;
+            let mut b_transport = WebRtcTransport::new(channel_recv.recv().await.unwrap());
+            assert!(!b_transport.channel().ordered());
+
+            b_transport.send(&[42]).unwrap();
+            loop {
+                match a_transport.try_recv().unwrap() {
+                    Some(datagram) => {
+                        assert_eq!(datagram, [42]);
+                        break;
+                    }
+                    None => tokio::task::yield_now().await,
+                }
+            }
+            a.close().await.unwrap();
+            b.close().await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::AckEvent;
+
+/// Round-trip time smoothed as in TCP (RFC 6298)
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RttEstimator {
+    smoothed: Option<Duration>,
+    variation: Duration,
+}
+
+impl RttEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Incorporate a measured round-trip time
+    pub fn sample(&mut self, rtt: Duration) {
+        match self.smoothed {
+            None => {
+                self.smoothed = Some(rtt);
+                self.variation = rtt / 2;
+            }
+            Some(smoothed) => {
+                self.variation = (self.variation * 3 + smoothed.abs_diff(rtt)) / 4;
+                self.smoothed = Some((smoothed * 7 + rtt) / 8);
+            }
+        }
+    }
+
+    /// [`sample`](Self::sample) a round-trip time in seconds
+    ///
+    /// Panics if `rtt` is negative or not finite.
+    pub fn sample_secs_f64(&mut self, rtt: f64) {
+        self.sample(Duration::from_secs_f64(rtt));
+    }
+
+    /// Incorporate the round-trip time of a delivered packet
+    pub fn on_ack_event(&mut self, event: &AckEvent) {
+        if let AckEvent::Delivered(ref packet) = *event {
+            self.sample(packet.rtt);
+        }
+    }
+
+    /// Smoothed round-trip time, if any samples have been taken
+    pub fn smoothed(&self) -> Option<Duration> {
+        self.smoothed
+    }
+
+    /// Mean deviation of samples from the smoothed round-trip time, i.e. jitter
+    pub fn variation(&self) -> Duration {
+        self.variation
+    }
+
+    /// Conservative estimate of one-way latency, accounting for jitter
+    pub fn latency(&self) -> Option<Duration> {
+        Some((self.smoothed? + self.variation * 4) / 2)
+    }
+
+    /// [`smoothed`](Self::smoothed) in seconds
+    pub fn smoothed_secs_f64(&self) -> Option<f64> {
+        self.smoothed.map(|x| x.as_secs_f64())
+    }
+
+    /// [`variation`](Self::variation) in seconds
+    pub fn variation_secs_f64(&self) -> f64 {
+        self.variation.as_secs_f64()
+    }
+
+    /// [`latency`](Self::latency) in seconds
+    pub fn latency_secs_f64(&self) -> Option<f64> {
+        self.latency().map(|x| x.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut rtt = RttEstimator::new();
+        assert_eq!(rtt.smoothed(), None);
+        rtt.sample(Duration::from_millis(100));
+        assert_eq!(rtt.smoothed(), Some(Duration::from_millis(100)));
+        for _ in 0..100 {
+            rtt.sample(Duration::from_millis(100));
+        }
+        assert!(rtt.variation() < Duration::from_millis(1), "converges");
+        rtt.sample(Duration::from_millis(180));
+        assert_eq!(rtt.smoothed(), Some(Duration::from_millis(110)));
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::PriorityAccumulator;
+
+/// How changes to a field should be delivered
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reliability {
+    /// Sent once; lost updates are repaired only by later changes, e.g. continuously changing
+    /// transforms
+    Unreliable,
+    /// The latest value is resent until acknowledged, e.g. via a
+    /// [`PropertySender`](crate::PropertySender)
+    Eventual,
+    /// Every change is delivered in order, e.g. via an [`EventSender`](crate::EventSender)
+    Reliable,
+}
+
+/// Replication policy for a field or component
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ReplicationRule {
+    /// Minimum number of ticks between transmissions of changes
+    ///
+    /// Changes occurring more often are coalesced. 1 sends every change as it occurs.
+    pub interval: u32,
+    /// Bits of precision with which to encode the value, for use by codecs, if applicable
+    pub precision: Option<u32>,
+    pub reliability: Reliability,
+}
+
+impl Default for ReplicationRule {
+    fn default() -> Self {
+        Self {
+            interval: 1,
+            precision: None,
+            reliability: Reliability::Unreliable,
+        }
+    }
+}
+
+/// Decides when changed fields are sent, according to per-field [`ReplicationRule`]s
+///
+/// Not every field needs to be sent at the full tick rate: transforms might warrant every tick,
+/// but health only a few times per second. Fields are identified by the entity `K` they belong to
+/// and a field or component type `C`, which selects the rule.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        deserialize = "K: serde::Deserialize<'de> + Hash + Eq, C: serde::Deserialize<'de> + Hash + Eq"
+    ))
+)]
+pub struct ReplicationScheduler<K, C> {
+    rules: HashMap<C, ReplicationRule>,
+    default_rule: ReplicationRule,
+    dirty: HashSet<(K, C)>,
+    /// Tick at which each field was last due
+    last_sent: HashMap<(K, C), u64>,
+}
+
+impl<K: Hash + Eq + Clone, C: Hash + Eq + Clone> ReplicationScheduler<K, C> {
+    /// Construct a scheduler applying `default_rule` to fields without a specific rule
+    pub fn new(default_rule: ReplicationRule) -> Self {
+        Self {
+            rules: HashMap::new(),
+            default_rule,
+            dirty: HashSet::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Set the rule for fields of type `component`
+    pub fn set_rule(&mut self, component: C, rule: ReplicationRule) {
+        self.rules.insert(component, rule);
+    }
+
+    /// The rule governing fields of type `component`
+    pub fn rule(&self, component: &C) -> &ReplicationRule {
+        self.rules.get(component).unwrap_or(&self.default_rule)
+    }
+
+    /// Note that `entity`'s `component` has changed
+    pub fn mark_dirty(&mut self, entity: K, component: C) {
+        self.dirty.insert((entity, component));
+    }
+
+    /// Take the changed fields that may be sent on `tick`
+    ///
+    /// Fields changed too soon after they were last sent remain pending until their interval
+    /// has elapsed.
+    pub fn due(&mut self, tick: u64) -> Vec<(K, C, ReplicationRule)> {
+        let mut due = Vec::new();
+        self.dirty.retain(|field| {
+            let rule = *self.rules.get(&field.1).unwrap_or(&self.default_rule);
+            if self
+                .last_sent
+                .get(field)
+                .is_some_and(|&last| tick.saturating_sub(last) < u64::from(rule.interval))
+            {
+                return true;
+            }
+            self.last_sent.insert(field.clone(), tick);
+            due.push((field.0.clone(), field.1.clone(), rule));
+            false
+        });
+        due
+    }
+
+    /// Take the changed fields that may be sent on `tick`, limited to `budget` bytes
+    ///
+    /// Entities are chosen by [`PriorityAccumulator::select_within`], with each entity's size
+    /// being the sum of `size` for its due fields, so all of an entity's due fields are sent
+    /// together or not at all. Entities that don't fit remain pending, and grow more urgent as
+    /// `priorities` accumulates. Entities must be tracked by `priorities` to be sent at all, and
+    /// an entity too large for `budget` will never be sent.
+    pub fn due_within(
+        &mut self,
+        tick: u64,
+        budget: usize,
+        priorities: &mut PriorityAccumulator<K>,
+        mut size: impl FnMut(&K, &C) -> usize,
+    ) -> (Vec<(K, C, ReplicationRule)>, BudgetReport) {
+        let ready = self
+            .dirty
+            .iter()
+            .filter(|field| {
+                let rule = self.rule(&field.1);
+                self.last_sent
+                    .get(field)
+                    .is_none_or(|&last| tick.saturating_sub(last) >= u64::from(rule.interval))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut sizes = HashMap::<K, usize>::new();
+        for field in &ready {
+            *sizes.entry(field.0.clone()).or_default() += size(&field.0, &field.1);
+        }
+        let selected = priorities
+            .select_within(budget, |e| sizes.get(e).copied())
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut due = Vec::new();
+        for field in ready {
+            if !selected.contains(&field.0) {
+                continue;
+            }
+            self.dirty.remove(&field);
+            let rule = *self.rule(&field.1);
+            self.last_sent.insert(field.clone(), tick);
+            due.push((field.0, field.1, rule));
+        }
+        let report = BudgetReport {
+            budget,
+            used: selected.iter().map(|e| sizes[e]).sum(),
+            sent: selected.len(),
+            deferred: sizes.len() - selected.len(),
+        };
+        (due, report)
+    }
+
+    /// Stop tracking `entity`, e.g. because it was destroyed
+    pub fn remove(&mut self, entity: &K) {
+        self.dirty.retain(|x| x.0 != *entity);
+        self.last_sent.retain(|x, _| x.0 != *entity);
+    }
+}
+
+/// Outcome of [`ReplicationScheduler::due_within`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BudgetReport {
+    /// Bytes available
+    pub budget: usize,
+    /// Bytes used by the fields selected
+    pub used: usize,
+    /// Number of entities selected
+    pub sent: usize,
+    /// Number of entities with fields due that didn't fit
+    pub deferred: usize,
+}
+
+impl BudgetReport {
+    /// Fraction of the budget used
+    pub fn utilization(&self) -> f32 {
+        if self.budget == 0 {
+            return 0.0;
+        }
+        self.used as f32 / self.budget as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut scheduler = ReplicationScheduler::new(ReplicationRule::default());
+        scheduler.set_rule(
+            "health",
+            ReplicationRule {
+                interval: 6,
+                precision: None,
+                reliability: Reliability::Eventual,
+            },
+        );
+        let mut sent = Vec::new();
+        for tick in 0..12 {
+            scheduler.mark_dirty(1, "transform");
+            scheduler.mark_dirty(1, "health");
+            for (_, component, _) in scheduler.due(tick) {
+                sent.push((tick, component));
+            }
+        }
+        assert_eq!(sent.iter().filter(|x| x.1 == "transform").count(), 12);
+        let health = sent
+            .iter()
+            .filter(|x| x.1 == "health")
+            .map(|x| x.0)
+            .collect::<Vec<_>>();
+        assert_eq!(health, [0, 6]);
+        assert_eq!(scheduler.rule(&"health").reliability, Reliability::Eventual);
+
+        scheduler.remove(&1);
+        assert!(scheduler.due(100).is_empty());
+    }
+
+    #[test]
+    fn budget() {
+        let mut scheduler = ReplicationScheduler::new(ReplicationRule::default());
+        let mut priorities = PriorityAccumulator::new();
+        priorities.insert(1, 10.0);
+        priorities.insert(2, 1.0);
+        scheduler.mark_dirty(1, "transform");
+        scheduler.mark_dirty(1, "health");
+        scheduler.mark_dirty(2, "transform");
+
+        let (due, report) = scheduler.due_within(0, 100, &mut priorities, |_, _| 40);
+        assert_eq!(due.len(), 2);
+        assert!(due.iter().all(|x| x.0 == 1));
+        assert_eq!(
+            report,
+            BudgetReport {
+                budget: 100,
+                used: 80,
+                sent: 1,
+                deferred: 1
+            }
+        );
+        assert_eq!(report.utilization(), 0.8);
+
+        priorities.accumulate();
+        let (due, _) = scheduler.due_within(1, 100, &mut priorities, |_, _| 40);
+        assert_eq!(due.len(), 1, "carried over");
+        assert_eq!(due[0].0, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "postcard")]
+    fn persist() {
+        use crate::{Codec, Postcard};
+
+        let mut scheduler = ReplicationScheduler::<u32, String>::new(ReplicationRule::default());
+        scheduler.set_rule(
+            "health".into(),
+            ReplicationRule {
+                interval: 6,
+                precision: Some(8),
+                reliability: Reliability::Eventual,
+            },
+        );
+        scheduler.mark_dirty(1, "health".into());
+        assert_eq!(scheduler.due(0).len(), 1);
+        scheduler.mark_dirty(1, "health".into());
+
+        let mut bytes = Vec::new();
+        Postcard.encode(&scheduler, &mut bytes).unwrap();
+        let mut restored: ReplicationScheduler<u32, String> = Postcard.decode(&bytes).unwrap();
+        assert_eq!(restored.rule(&"health".into()).precision, Some(8));
+        assert!(restored.due(5).is_empty(), "last sent tick is retained");
+        assert_eq!(restored.due(6).len(), 1, "dirty fields are retained");
+    }
+}
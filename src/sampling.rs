@@ -0,0 +1,160 @@
+use crate::PredictionQueue;
+
+/// Gathers raw input events between simulation ticks, producing one input per tick
+///
+/// Clients typically poll input devices once per rendered frame, which may be much more often
+/// than the simulation ticks. Sampling input only at tick boundaries would lose a button pressed
+/// and released between them, and make analog input needlessly sensitive to exactly when the tick
+/// fell. Instead, implementers accumulate every frame's events, e.g. with [`ButtonLatch`]es and
+/// [`AxisAverage`]s, and coalesce them once per tick.
+pub trait InputAccumulator {
+    /// The coalesced input for a tick
+    type Input;
+
+    /// Produce the input for the tick just ended, and begin accumulating the next
+    fn finish(&mut self) -> Self::Input;
+
+    /// [`finish`](Self::finish) the current tick, recording its input in `queue`
+    fn record_into(&mut self, queue: &mut PredictionQueue<Self::Input>) {
+        queue.record(self.finish());
+    }
+}
+
+/// Tracks a digital button across a tick, latching presses and releases
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ButtonLatch {
+    down: bool,
+    pressed: bool,
+    released: bool,
+}
+
+impl ButtonLatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the button's current state
+    pub fn set(&mut self, down: bool) {
+        if down && !self.down {
+            self.pressed = true;
+        } else if !down && self.down {
+            self.released = true;
+        }
+        self.down = down;
+    }
+
+    /// Produce the button's state for the tick just ended, and begin the next
+    pub fn finish(&mut self) -> ButtonState {
+        let state = ButtonState {
+            down: self.down,
+            pressed: self.pressed,
+            released: self.released,
+        };
+        self.pressed = false;
+        self.released = false;
+        state
+    }
+}
+
+/// A button's state over a tick, as produced by a [`ButtonLatch`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ButtonState {
+    /// Whether the button was held at the end of the tick
+    pub down: bool,
+    /// Whether the button was pressed at any point during the tick
+    pub pressed: bool,
+    /// Whether the button was released at any point during the tick
+    pub released: bool,
+}
+
+impl ButtonState {
+    /// Whether the button was held at any point during the tick
+    ///
+    /// True for a press too brief to still be held when the tick ended.
+    pub fn active(&self) -> bool {
+        self.down || self.pressed
+    }
+}
+
+/// Averages an analog axis, such as a stick or trigger, over a tick
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AxisAverage {
+    sum: f32,
+    count: u32,
+    last: f32,
+}
+
+impl AxisAverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the axis's current value
+    pub fn sample(&mut self, value: f32) {
+        self.sum += value;
+        self.count += 1;
+        self.last = value;
+    }
+
+    /// Produce the mean value over the tick just ended, and begin the next
+    ///
+    /// If no samples were taken during the tick, e.g. because rendering is slower than the
+    /// simulation, the latest sample is repeated.
+    pub fn finish(&mut self) -> f32 {
+        let result = if self.count == 0 {
+            self.last
+        } else {
+            self.sum / self.count as f32
+        };
+        self.sum = 0.0;
+        self.count = 0;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Controls {
+        jump: ButtonLatch,
+        steer: AxisAverage,
+    }
+
+    impl InputAccumulator for Controls {
+        type Input = (ButtonState, f32);
+        fn finish(&mut self) -> (ButtonState, f32) {
+            (self.jump.finish(), self.steer.finish())
+        }
+    }
+
+    #[test]
+    fn coalesce() {
+        let mut controls = Controls::default();
+        let mut queue = PredictionQueue::new(0);
+
+        // A tap entirely between ticks
+        controls.jump.set(true);
+        controls.steer.sample(1.0);
+        controls.jump.set(false);
+        controls.steer.sample(0.0);
+        controls.record_into(&mut queue);
+
+        // No new samples
+        controls.record_into(&mut queue);
+
+        let inputs = queue.iter().copied().collect::<Vec<_>>();
+        assert_eq!(
+            inputs[0].0,
+            ButtonState {
+                down: false,
+                pressed: true,
+                released: true
+            }
+        );
+        assert!(inputs[0].0.active());
+        assert_eq!(inputs[0].1, 0.5);
+        assert_eq!(inputs[1], (ButtonState::default(), 0.0));
+    }
+}
@@ -0,0 +1,383 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    InputQueue, Instant, NetworkSimulator, PredictionQueue, SimulatorConfig, seq, throttle,
+};
+
+/// Game logic driven by a [`Scenario`]
+pub trait ScenarioGame {
+    type Input: Clone;
+    type State: Clone + PartialEq;
+
+    /// Sample the client's input for its `tick`th simulation step
+    fn input(&mut self, tick: u64) -> Self::Input;
+    /// Advance `state` by one step, deterministically
+    ///
+    /// `input` is `None` when the server had no input from the client in time for the step.
+    fn step(&mut self, state: &mut Self::State, input: Option<&Self::Input>);
+}
+
+/// Parameters of a [`Scenario`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ScenarioConfig {
+    /// Duration of a simulation step on both server and client
+    pub tick_interval: Duration,
+    /// Interval between client frames
+    pub frame_interval: Duration,
+    /// Impairments applied to inputs sent from client to server
+    pub uplink: SimulatorConfig,
+    /// Impairments applied to snapshots sent from server to client
+    pub downlink: SimulatorConfig,
+    /// Snapshot data the client tries to keep buffered, as in [`throttle`]
+    pub min_latency: Duration,
+    /// How long the server waits after an input arrives before consuming inputs, as in
+    /// [`InputQueue::take`]
+    pub input_delay: Duration,
+    /// Largest number of inputs the server will buffer
+    pub max_inputs: usize,
+    /// Seed for the network simulators
+    pub seed: u64,
+}
+
+impl Default for ScenarioConfig {
+    fn default() -> Self {
+        let tick_interval = Duration::from_secs(1) / 60;
+        Self {
+            tick_interval,
+            frame_interval: Duration::from_secs(1) / 144,
+            uplink: SimulatorConfig::default(),
+            downlink: SimulatorConfig::default(),
+            min_latency: Duration::from_millis(50),
+            input_delay: tick_interval * 2,
+            max_inputs: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// Measurements taken over the course of a [`Scenario`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ScenarioMetrics {
+    /// Number of client frames run
+    pub frames: u64,
+    pub server_ticks: u64,
+    pub client_ticks: u64,
+    /// Server steps simulated without an input from the client
+    pub input_underruns: u64,
+    /// Client frames that began with less than a frame's worth of snapshot data buffered, stalling
+    /// the client's simulation
+    pub snapshot_underruns: u64,
+    /// Number of predicted states checked against the server's
+    pub reconciliations: u64,
+    /// Number of predicted states that differed from the server's
+    pub mispredictions: u64,
+    /// Largest number of client inputs awaiting acknowledgement at once
+    pub max_unacknowledged: usize,
+}
+
+/// In-process client and server connected by [`NetworkSimulator`]s, for end-to-end testing
+///
+/// The server steps the game every tick with inputs buffered by an [`InputQueue`], and sends a
+/// snapshot of its state to the client. The client paces its own simulation with [`throttle`]
+/// according to the snapshot data it has buffered, records its inputs in a [`PredictionQueue`],
+/// and predicts the server's state by applying unacknowledged inputs to the latest snapshot. Each
+/// acknowledged prediction is checked against the server's state, so tests can assert on
+/// [`metrics`](Self::metrics) under various network conditions.
+///
+/// Time is simulated, so scenarios spanning minutes run in milliseconds.
+pub struct Scenario<G: ScenarioGame> {
+    config: ScenarioConfig,
+    game: G,
+    now: Instant,
+    metrics: ScenarioMetrics,
+    uplink: NetworkSimulator<Vec<(u16, G::Input)>>,
+    downlink: NetworkSimulator<Snapshot<G::State>>,
+
+    server_state: G::State,
+    next_server_tick: Instant,
+    inputs: InputQueue<(u16, G::Input)>,
+    /// Sequence number of the next input the server expects
+    next_input: u16,
+    /// Sequence number of the latest input the server applied
+    server_ack: Option<u16>,
+
+    prediction: PredictionQueue<G::Input>,
+    /// Predicted state after each unacknowledged input
+    predicted: VecDeque<G::State>,
+    /// State from the latest snapshot, and the latest acknowledgement checked
+    latest: Option<(u64, G::State)>,
+    client_ack: Option<u16>,
+    buffer_remaining: Duration,
+    /// Client simulation time not yet consumed by a step
+    client_time: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot<S> {
+    tick: u64,
+    ack: Option<u16>,
+    state: S,
+}
+
+impl<G: ScenarioGame> Scenario<G> {
+    /// Begin a scenario in which both client and server start from `state`
+    pub fn new(config: ScenarioConfig, game: G, state: G::State) -> Self {
+        let now = Instant::now();
+        Self {
+            config,
+            game,
+            now,
+            metrics: ScenarioMetrics::default(),
+            uplink: NetworkSimulator::new(config.uplink, config.seed),
+            downlink: NetworkSimulator::new(config.downlink, config.seed.wrapping_add(1)),
+
+            server_state: state,
+            next_server_tick: now,
+            inputs: InputQueue::new(),
+            next_input: 0,
+            server_ack: None,
+
+            prediction: PredictionQueue::new(0),
+            predicted: VecDeque::new(),
+            latest: None,
+            client_ack: None,
+            buffer_remaining: Duration::ZERO,
+            client_time: Duration::ZERO,
+        }
+    }
+
+    /// Run for at least `duration`
+    pub fn run(&mut self, duration: Duration) {
+        let end = self.now + duration;
+        while self.now < end {
+            self.frame();
+        }
+    }
+
+    /// Run a single client frame, and any server ticks due in the meantime
+    pub fn frame(&mut self) {
+        self.now += self.config.frame_interval;
+        while self.next_server_tick <= self.now {
+            self.server_tick();
+        }
+        self.client_frame();
+    }
+
+    /// Change the impairments applied to inputs sent from client to server
+    pub fn set_uplink(&mut self, config: SimulatorConfig) {
+        self.uplink.set_config(config);
+    }
+
+    /// Change the impairments applied to snapshots sent from server to client
+    pub fn set_downlink(&mut self, config: SimulatorConfig) {
+        self.downlink.set_config(config);
+    }
+
+    /// The current simulated time
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    pub fn metrics(&self) -> ScenarioMetrics {
+        self.metrics
+    }
+
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut G {
+        &mut self.game
+    }
+
+    pub fn server_state(&self) -> &G::State {
+        &self.server_state
+    }
+
+    /// The client's prediction of the server's state once its latest input is applied
+    pub fn predicted_state(&self) -> Option<&G::State> {
+        self.predicted
+            .back()
+            .or(self.latest.as_ref().map(|(_, state)| state))
+    }
+
+    /// Amount of snapshot data buffered by the client
+    pub fn buffer_remaining(&self) -> Duration {
+        self.buffer_remaining
+    }
+
+    fn server_tick(&mut self) {
+        let now = self.next_server_tick;
+        self.next_server_tick += self.config.tick_interval;
+        while let Some(packet) = self.uplink.receive(now) {
+            for (sequence, input) in packet {
+                // Inputs are sent redundantly until acknowledged; keep only the new ones
+                if seq::diff(sequence, self.next_input) >= 0 {
+                    self.next_input = sequence.wrapping_add(1);
+                    self.inputs
+                        .push(self.config.max_inputs, (sequence, input), now);
+                }
+            }
+        }
+        let input = self.inputs.take(now, self.config.input_delay);
+        match input {
+            Some((seq, _)) => self.server_ack = Some(seq),
+            None => self.metrics.input_underruns += 1,
+        }
+        self.game
+            .step(&mut self.server_state, input.as_ref().map(|(_, x)| x));
+        self.metrics.server_ticks += 1;
+        self.downlink.send(
+            now,
+            Snapshot {
+                tick: self.metrics.server_ticks,
+                ack: self.server_ack,
+                state: self.server_state.clone(),
+            },
+        );
+    }
+
+    fn client_frame(&mut self) {
+        self.metrics.frames += 1;
+        while let Some(snapshot) = self.downlink.receive(self.now) {
+            self.receive_snapshot(snapshot);
+        }
+        if self.latest.is_none() {
+            return;
+        }
+
+        if self.buffer_remaining < self.config.frame_interval {
+            self.metrics.snapshot_underruns += 1;
+        }
+        let elapsed = throttle(
+            self.config.frame_interval,
+            self.buffer_remaining,
+            self.config.min_latency,
+            self.config.tick_interval,
+        );
+        self.buffer_remaining -= elapsed;
+        self.client_time += elapsed;
+        while self.client_time >= self.config.tick_interval {
+            self.client_time -= self.config.tick_interval;
+            self.client_tick();
+        }
+    }
+
+    fn receive_snapshot(&mut self, snapshot: Snapshot<G::State>) {
+        let latest = self.latest.as_ref().map_or(0, |&(tick, _)| tick);
+        if latest >= snapshot.tick {
+            return;
+        }
+        // Each snapshot supersedes those before it, so lost snapshots don't leave gaps
+        self.buffer_remaining += self.config.tick_interval * (snapshot.tick - latest) as u32;
+        if let Some(ack) = snapshot.ack
+            && self.client_ack.is_none_or(|old| seq::is_newer(ack, old))
+        {
+            // The first unacknowledged input is the oldest prediction
+            let base = self
+                .prediction
+                .next_sequence_number()
+                .wrapping_sub(self.predicted.len() as u16);
+            let index = ack.wrapping_sub(base) as usize;
+            if let Some(predicted) = self.predicted.get(index) {
+                self.metrics.reconciliations += 1;
+                if *predicted != snapshot.state {
+                    self.metrics.mispredictions += 1;
+                }
+            }
+            self.client_ack = Some(ack);
+            self.prediction.reconcile(ack);
+        }
+
+        // Re-predict from the authoritative state
+        let mut state = snapshot.state.clone();
+        self.predicted.clear();
+        for input in self.prediction.iter() {
+            self.game.step(&mut state, Some(input));
+            self.predicted.push_back(state.clone());
+        }
+        self.latest = Some((snapshot.tick, snapshot.state));
+    }
+
+    fn client_tick(&mut self) {
+        let input = self.game.input(self.metrics.client_ticks);
+        self.metrics.client_ticks += 1;
+        let mut state = self.predicted_state().unwrap().clone();
+        self.game.step(&mut state, Some(&input));
+        self.predicted.push_back(state);
+
+        let first = self.prediction.next_sequence_number();
+        self.prediction.record(input);
+        let unacked = self.prediction.iter().count();
+        self.metrics.max_unacknowledged = self.metrics.max_unacknowledged.max(unacked);
+        let packet = self
+            .prediction
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let seq = first.wrapping_sub((unacked - 1 - i) as u16);
+                (seq, input.clone())
+            })
+            .collect();
+        self.uplink.send(self.now, packet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LossModel;
+
+    /// A counter incremented by each input
+    struct Counter;
+
+    impl ScenarioGame for Counter {
+        type Input = u64;
+        type State = u64;
+
+        fn input(&mut self, tick: u64) -> u64 {
+            tick % 3
+        }
+
+        fn step(&mut self, state: &mut u64, input: Option<&u64>) {
+            *state = state
+                .wrapping_mul(7)
+                .wrapping_add(input.map_or(100, |x| x + 1));
+        }
+    }
+
+    #[test]
+    fn ideal() {
+        let mut scenario = Scenario::new(ScenarioConfig::default(), Counter, 0);
+        scenario.run(Duration::from_secs(10));
+        let metrics = scenario.metrics();
+        assert!(metrics.client_ticks > 500);
+        assert!(metrics.reconciliations > 500);
+        // Only ticks before the first input arrived go without
+        assert!(metrics.input_underruns < 20);
+        assert!(metrics.mispredictions <= 1);
+        assert_eq!(metrics.snapshot_underruns, 0);
+    }
+
+    #[test]
+    fn degraded() {
+        let lossy = SimulatorConfig {
+            latency: Duration::from_millis(250),
+            jitter: Duration::from_millis(30),
+            loss: LossModel::Uniform(0.2),
+            ..SimulatorConfig::default()
+        };
+        let mut scenario = Scenario::new(ScenarioConfig::default(), Counter, 0);
+        scenario.run(Duration::from_secs(5));
+        let before = scenario.metrics();
+        scenario.set_uplink(lossy);
+        scenario.set_downlink(lossy);
+        scenario.run(Duration::from_secs(10));
+        let metrics = scenario.metrics();
+        assert!(metrics.mispredictions > before.mispredictions);
+        assert!(metrics.snapshot_underruns > before.snapshot_underruns);
+        assert!(metrics.max_unacknowledged > before.max_unacknowledged);
+    }
+}
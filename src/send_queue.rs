@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+
+use crate::Instant;
+
+/// Outgoing messages awaiting transmission, with support for expiry and replacement
+///
+/// When bandwidth is scarce, messages may wait in a queue for some time before being sent. State
+/// updates in particular lose their value as they age: once a newer update for the same thing
+/// exists, or once a deadline passes, sending the old one just wastes bandwidth. Each message may
+/// therefore have a *deadline* after which it's silently dropped, and a *key* such that pushing
+/// a new message with the same key replaces the old one in place, preserving its position in the
+/// queue.
+#[derive(Debug, Clone)]
+pub struct SendQueue<K, T> {
+    queue: VecDeque<Entry<K, T>>,
+}
+
+impl<K: PartialEq, T> SendQueue<K, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enqueue `payload` for transmission
+    ///
+    /// - `key` - If a message with the same key is already queued, it's replaced by this one.
+    /// - `deadline` - If the message hasn't been sent by this time, it's dropped.
+    ///
+    /// Returns the replaced message, if any.
+    pub fn push(&mut self, payload: T, key: Option<K>, deadline: Option<Instant>) -> Option<T> {
+        if let Some(key) = key.as_ref()
+            && let Some(existing) = self.queue.iter_mut().find(|x| x.key.as_ref() == Some(key))
+        {
+            existing.deadline = deadline;
+            return Some(std::mem::replace(&mut existing.payload, payload));
+        }
+        self.queue.push_back(Entry {
+            key,
+            deadline,
+            payload,
+        });
+        None
+    }
+
+    /// Dequeue the oldest message that hasn't expired by `now`
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        while let Some(entry) = self.queue.pop_front() {
+            if entry.deadline.is_some_and(|t| t < now) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(target: "nettish::send_queue", dropped = 1, "message expired");
+                continue;
+            }
+            return Some(entry.payload);
+        }
+        None
+    }
+
+    /// The oldest message that hasn't expired by `now`, without removing it
+    ///
+    /// Expired messages are discarded. Useful to check whether a message fits in the space
+    /// remaining in a packet before committing to [`pop`](Self::pop) it.
+    pub fn peek(&mut self, now: Instant) -> Option<&T> {
+        self.expire_front(now);
+        self.queue.front().map(|x| &x.payload)
+    }
+
+    /// Drop all messages that have expired by `now`, returning the number dropped
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before = self.queue.len();
+        self.queue.retain(|x| x.deadline.is_none_or(|t| t >= now));
+        let dropped = before - self.queue.len();
+        #[cfg(feature = "tracing")]
+        if dropped != 0 {
+            tracing::debug!(target: "nettish::send_queue", dropped, "message expired");
+        }
+        dropped
+    }
+
+    /// Remove the message with `key`, if any
+    pub fn remove(&mut self, key: &K) -> Option<T> {
+        let index = self
+            .queue
+            .iter()
+            .position(|x| x.key.as_ref() == Some(key))?;
+        self.queue.remove(index).map(|x| x.payload)
+    }
+
+    /// Number of messages queued, including any that have expired but not yet been dropped
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether any messages are queued
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn expire_front(&mut self, now: Instant) {
+        while self
+            .queue
+            .front()
+            .is_some_and(|x| x.deadline.is_some_and(|t| t < now))
+        {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(target: "nettish::send_queue", dropped = 1, "message expired");
+            self.queue.pop_front();
+        }
+    }
+}
+
+impl<K, T> Default for SendQueue<K, T> {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry<K, T> {
+    key: Option<K>,
+    deadline: Option<Instant>,
+    payload: T,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn replace() {
+        let now = Instant::now();
+        let mut q = SendQueue::new();
+        assert_eq!(q.push("a1", Some('a'), None), None);
+        assert_eq!(q.push("x", None, None), None);
+        assert_eq!(q.push("a2", Some('a'), None), Some("a1"));
+        assert_eq!(q.len(), 2);
+        assert_eq!(q.pop(now), Some("a2"), "replacement keeps its place");
+        assert_eq!(q.pop(now), Some("x"));
+        assert_eq!(q.pop(now), None);
+    }
+
+    #[test]
+    fn expiry() {
+        let start = Instant::now();
+        let later = start + Duration::from_millis(100);
+        let mut q = SendQueue::<(), _>::new();
+        q.push(1, None, Some(start));
+        q.push(2, None, None);
+        q.push(3, None, Some(start));
+        assert_eq!(q.peek(later), Some(&2));
+        assert_eq!(q.len(), 2);
+        assert_eq!(q.expire(later), 1);
+        assert_eq!(q.pop(later), Some(2));
+        assert_eq!(q.pop(later), None);
+
+        q.push(4, None, Some(start));
+        assert_eq!(q.pop(later), None, "expired messages are skipped");
+    }
+}
@@ -0,0 +1,78 @@
+//! Comparison and arithmetic on wrapping sequence numbers
+//!
+//! Sequence numbers identifying packets, inputs, or ticks are typically transmitted in a small
+//! number of bits and allowed to wrap around. Comparisons between them are only meaningful when
+//! they're known to be less than half the sequence space apart, in which case the shorter way
+//! around determines which is newer.
+
+/// An unsigned integer type used as a wrapping sequence number
+pub trait Sequence: Copy + Eq {
+    /// Signed distance from `other` to `self` the short way around
+    fn diff(self, other: Self) -> i32;
+
+    /// `self` moved `n` steps forward, or backward if negative
+    fn advance(self, n: i32) -> Self;
+}
+
+macro_rules! impl_sequence {
+    ($($ty:ty => $signed:ty),*) => {
+        $(
+            impl Sequence for $ty {
+                fn diff(self, other: Self) -> i32 {
+                    self.wrapping_sub(other) as $signed as i32
+                }
+
+                fn advance(self, n: i32) -> Self {
+                    self.wrapping_add(n as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_sequence!(u8 => i8, u16 => i16, u32 => i32);
+
+/// Signed distance from `b` to `a` the short way around, positive if `a` is newer
+///
+/// If `a` and `b` are exactly half the sequence space apart, `a` is considered older.
+pub fn diff<T: Sequence>(a: T, b: T) -> i32 {
+    a.diff(b)
+}
+
+/// Whether `a` is strictly newer than `b`
+pub fn is_newer<T: Sequence>(a: T, b: T) -> bool {
+    a.diff(b) > 0
+}
+
+/// `sequence` moved `n` steps forward, or backward if negative
+pub fn advance<T: Sequence>(sequence: T, n: i32) -> T {
+    sequence.advance(n)
+}
+
+/// The newer of `a` and `b`
+pub fn newest<T: Sequence>(a: T, b: T) -> T {
+    if is_newer(b, a) { b } else { a }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        assert!(is_newer(0u16, u16::MAX));
+        assert!(!is_newer(u16::MAX, 0u16));
+        assert!(!is_newer(7u8, 7));
+        assert_eq!(diff(2u8, 254), 4);
+        assert_eq!(diff(254u8, 2), -4);
+        assert_eq!(diff(0u32, u32::MAX), 1);
+        assert_eq!(
+            diff(0x8000u16, 0),
+            i32::from(i16::MIN),
+            "ambiguous midpoint is older"
+        );
+        assert_eq!(advance(u16::MAX, 2), 1);
+        assert_eq!(advance(1u8, -2), 255);
+        assert_eq!(newest(65530u16, 3), 3);
+    }
+}
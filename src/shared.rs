@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash, RandomState},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{InputQueue, Instant};
+
+/// An [`InputQueue`] that may be used from multiple threads, with statistics
+///
+/// The queue is guarded by its own lock, so contention only arises when multiple threads act on
+/// the same client at once. Statistics are atomic, and may be read without locking.
+pub struct SharedInputQueue<T> {
+    queue: Mutex<InputQueue<T>>,
+    overruns: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<T> SharedInputQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            queue: Mutex::new(InputQueue::new()),
+            overruns: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// See [`InputQueue::push`]
+    pub fn push(&self, max: usize, input: T, now: Instant) -> bool {
+        let overrun = self.queue.lock().unwrap().push(max, input, now);
+        if overrun {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        overrun
+    }
+
+    /// See [`InputQueue::take`]
+    pub fn take(&self, now: Instant, delay: Duration) -> Option<T> {
+        let input = self.queue.lock().unwrap().take(now, delay);
+        if input.is_none() {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        input
+    }
+
+    /// Number of inputs queued
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Whether any inputs are queued
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+
+    /// Number of inputs dropped by [`push`](Self::push) due to overrun
+    pub fn overruns(&self) -> u64 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Number of calls to [`take`](Self::take) that produced no input
+    ///
+    /// Includes steps where the queue was deliberately waiting out the delay after an under-run.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Default for SharedInputQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-client [`SharedInputQueue`]s, accessible from multiple threads
+///
+/// Clients are partitioned into independently locked shards, so clients joining and leaving only
+/// briefly block lookups of other clients in the same shard. For the cheapest possible
+/// [`take`](SharedInputQueue::take), a system that steps a client every tick should retain the
+/// [`Arc`] returned by [`insert`](Self::insert) or [`get`](Self::get) rather than looking the
+/// client up each time.
+pub struct SharedInputQueues<K, T> {
+    shards: Box<[Shard<K, T>]>,
+    hasher: RandomState,
+}
+
+impl<K: Hash + Eq, T> SharedInputQueues<K, T> {
+    /// Construct an empty collection with `shards` independently locked partitions
+    ///
+    /// A few times the number of threads expected to add and remove clients concurrently is
+    /// plenty.
+    pub fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Add a client with an empty queue, replacing any existing queue for the same client
+    pub fn insert(&self, client: K) -> Arc<SharedInputQueue<T>> {
+        let queue = Arc::new(SharedInputQueue::new());
+        self.shard(&client)
+            .write()
+            .unwrap()
+            .insert(client, queue.clone());
+        queue
+    }
+
+    /// Remove a client, returning its queue
+    pub fn remove(&self, client: &K) -> Option<Arc<SharedInputQueue<T>>> {
+        self.shard(client).write().unwrap().remove(client)
+    }
+
+    /// Look up a client's queue
+    pub fn get(&self, client: &K) -> Option<Arc<SharedInputQueue<T>>> {
+        self.shard(client).read().unwrap().get(client).cloned()
+    }
+
+    /// Invoke `f` on every client's queue
+    ///
+    /// No lock on the map is held while `f` runs, so `f` may itself add or remove clients.
+    /// Clients added or removed concurrently may or may not be visited.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &SharedInputQueue<T>))
+    where
+        K: Clone,
+    {
+        let mut visit = Vec::new();
+        for shard in &self.shards {
+            visit.extend(
+                shard
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(client, queue)| (client.clone(), queue.clone())),
+            );
+            for (client, queue) in visit.drain(..) {
+                f(&client, &queue);
+            }
+        }
+    }
+
+    /// Number of clients
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|x| x.read().unwrap().len()).sum()
+    }
+
+    /// Whether there are no clients
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|x| x.read().unwrap().is_empty())
+    }
+
+    fn shard(&self, client: &K) -> &Shard<K, T> {
+        let index = self.hasher.hash_one(client) as usize % self.shards.len();
+        &self.shards[index]
+    }
+}
+
+impl<K: Hash + Eq, T> Default for SharedInputQueues<K, T> {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+type Shard<K, T> = RwLock<HashMap<K, Arc<SharedInputQueue<T>>>>;
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let queues = SharedInputQueues::<u32, u32>::new(4);
+        let a = queues.insert(1);
+        queues.insert(2);
+        assert_eq!(queues.len(), 2);
+        let now = Instant::now();
+        assert!(!a.push(2, 10, now));
+        assert!(!a.push(2, 11, now));
+        assert!(a.push(2, 12, now));
+        assert_eq!(a.overruns(), 1);
+        assert_eq!(a.take(now, Duration::ZERO), Some(11));
+        assert!(queues.get(&2).unwrap().take(now, Duration::ZERO).is_none());
+        assert_eq!(queues.get(&2).unwrap().misses(), 1);
+        let mut total = 0;
+        queues.for_each(|_, queue| total += queue.len());
+        assert_eq!(total, 1);
+        // Callbacks may modify the map
+        queues.for_each(|&client, _| {
+            queues.remove(&client);
+            queues.insert(client);
+        });
+        assert_eq!(queues.len(), 2);
+        assert!(queues.remove(&1).is_some());
+        assert!(queues.get(&1).is_none());
+    }
+
+    #[test]
+    fn threaded() {
+        let queues = SharedInputQueues::<u32, u32>::default();
+        for client in 0..8 {
+            queues.insert(client);
+        }
+        let now = Instant::now();
+        thread::scope(|s| {
+            for client in 0..8 {
+                let queues = &queues;
+                s.spawn(move || {
+                    let queue = queues.get(&client).unwrap();
+                    for i in 0..100 {
+                        queue.push(1000, i, now);
+                    }
+                });
+            }
+        });
+        queues.for_each(|_, queue| assert_eq!(queue.len(), 100));
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+
+use crate::{Instant, TokenBucket};
+
+/// Impairments applied by a [`NetworkSimulator`]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SimulatorConfig {
+    /// Minimum one-way delay
+    pub latency: Duration,
+    /// Maximum additional delay, chosen uniformly at random for each packet
+    ///
+    /// Packets sent less than `jitter` apart may be delivered out of order.
+    pub jitter: Duration,
+    /// How packets are chosen to be dropped
+    pub loss: LossModel,
+    /// Probability of a packet being delivered twice, from 0 to 1
+    pub duplication: f32,
+    /// Probability of a packet being held back by an additional `reorder_delay`, from 0 to 1
+    pub reorder: f32,
+    pub reorder_delay: Duration,
+    /// Capacity of the link, if limited
+    ///
+    /// Packets sent faster than the link can carry them wait in a queue, adding delay, and are
+    /// dropped once the queue is full, as on a saturated uplink. Only packets sent with
+    /// [`send_sized`](NetworkSimulator::send_sized) occupy capacity.
+    pub link: Option<LinkLimit>,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(50),
+            jitter: Duration::ZERO,
+            loss: LossModel::Uniform(0.0),
+            duplication: 0.0,
+            reorder: 0.0,
+            reorder_delay: Duration::from_millis(20),
+            link: None,
+        }
+    }
+}
+
+/// Timeline of changes to the impairments applied by a [`NetworkSimulator`]
+///
+/// Scripts narratives like "perfect for 10 seconds, then a 500ms latency spike for 2 seconds,
+/// then 5% loss" by chaining calls to [`then`](Self::then). Applied with
+/// [`NetworkSimulator::set_schedule`].
+#[derive(Debug, Clone)]
+pub struct ImpairmentSchedule {
+    /// Each configuration and the time after the start of the schedule when it takes effect
+    stages: Vec<(Duration, SimulatorConfig)>,
+}
+
+impl ImpairmentSchedule {
+    /// Begin a schedule with `config` in effect
+    pub fn new(config: SimulatorConfig) -> Self {
+        Self {
+            stages: vec![(Duration::ZERO, config)],
+        }
+    }
+
+    /// Switch to `config` `after` the previous change
+    pub fn then(mut self, after: Duration, config: SimulatorConfig) -> Self {
+        let start = self.duration() + after;
+        self.stages.push((start, config));
+        self
+    }
+
+    /// The configuration in effect `elapsed` after the start of the schedule
+    pub fn config_at(&self, elapsed: Duration) -> SimulatorConfig {
+        let index = self.stages.partition_point(|&(start, _)| start <= elapsed);
+        self.stages[index.saturating_sub(1)].1
+    }
+
+    /// Time after the start of the schedule when the final configuration takes effect
+    pub fn duration(&self) -> Duration {
+        self.stages.last().unwrap().0
+    }
+}
+
+/// Capacity of a link simulated by a [`NetworkSimulator`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkLimit {
+    /// Bytes transmitted per second
+    pub rate: u64,
+    /// Bytes that can be transmitted at once after the link has been idle
+    ///
+    /// Packets larger than this are always dropped.
+    pub burst: u64,
+    /// Bytes that can wait for transmission before subsequent packets are dropped
+    pub queue: usize,
+}
+
+/// Determines which packets a [`NetworkSimulator`] drops
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LossModel {
+    /// Each packet is dropped independently with the given probability, from 0 to 1
+    Uniform(f32),
+    /// Gilbert–Elliott model of bursty loss
+    ///
+    /// The link alternates between a good state and a bad state, each with its own loss
+    /// probability, changing state randomly after each packet. Real-world loss tends to come in
+    /// bursts, e.g. due to congestion or interference, which this captures and
+    /// [`Uniform`](Self::Uniform) doesn't.
+    GilbertElliott {
+        /// Probability of entering the bad state after each packet sent in the good state
+        enter_bad: f32,
+        /// Probability of returning to the good state after each packet sent in the bad state
+        ///
+        /// The mean length of a bad period is the reciprocal of this, in packets.
+        exit_bad: f32,
+        /// Probability of dropping a packet in the good state
+        good_loss: f32,
+        /// Probability of dropping a packet in the bad state
+        bad_loss: f32,
+    },
+}
+
+impl LossModel {
+    /// Long-run fraction of packets dropped
+    pub fn mean_loss(&self) -> f32 {
+        match *self {
+            Self::Uniform(p) => p,
+            Self::GilbertElliott {
+                enter_bad,
+                exit_bad,
+                good_loss,
+                bad_loss,
+            } => {
+                let total = enter_bad + exit_bad;
+                if total == 0.0 {
+                    return good_loss;
+                }
+                let bad = enter_bad / total;
+                (1.0 - bad) * good_loss + bad * bad_loss
+            }
+        }
+    }
+}
+
+/// Counts of packets handled by a [`NetworkSimulator`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SimulatorStats {
+    /// Number of packets passed to [`send`](NetworkSimulator::send)
+    pub sent: u64,
+    pub lost: u64,
+    /// Number of extra copies of packets scheduled for delivery
+    pub duplicated: u64,
+    /// Number of packets held back to be reordered
+    pub reordered: u64,
+    /// Number of packets dropped because the link's queue was full
+    pub dropped: u64,
+}
+
+/// Deterministically simulates an unreliable one-way link carrying packets of type `T`
+///
+/// Packets passed to [`send`](Self::send) are subjected to latency, jitter, loss, duplication,
+/// and reordering, then made available from [`receive`](Self::receive) once their delivery time
+/// has passed. All randomness is derived from the seed passed to [`new`](Self::new), so a given
+/// sequence of calls always produces the same result, making this suitable for unit tests.
+///
+/// Use one simulator for each direction of a connection. Because time is passed in explicitly,
+/// tests can advance it as quickly as they like, e.g. by jumping to
+/// [`next_delivery`](Self::next_delivery).
+#[derive(Debug, Clone)]
+pub struct NetworkSimulator<T = Vec<u8>> {
+    config: SimulatorConfig,
+    /// Schedule governing `config`, and when it started
+    schedule: Option<(Instant, ImpairmentSchedule)>,
+    rng: Rng,
+    /// Packets in flight, by delivery time and then order of scheduling
+    in_flight: BTreeMap<(Instant, u64), T>,
+    next_id: u64,
+    /// Whether a [`LossModel::GilbertElliott`] link is in its bad state
+    bad: bool,
+    /// State of a link with limited capacity, created on first use
+    link: Option<Link>,
+    stats: SimulatorStats,
+}
+
+impl<T: Clone> NetworkSimulator<T> {
+    pub fn new(config: SimulatorConfig, seed: u64) -> Self {
+        Self {
+            config,
+            schedule: None,
+            rng: Rng::new(seed),
+            in_flight: BTreeMap::new(),
+            next_id: 0,
+            bad: false,
+            link: None,
+            stats: SimulatorStats::default(),
+        }
+    }
+
+    /// Transmit `packet` at `now`, occupying none of the link's capacity
+    pub fn send(&mut self, now: Instant, packet: T) {
+        self.send_sized(now, packet, 0);
+    }
+
+    /// Transmit `packet`, which occupies `size` bytes of the link's capacity, at `now`
+    pub fn send_sized(&mut self, now: Instant, packet: T, size: usize) {
+        if let Some((start, ref schedule)) = self.schedule {
+            self.config = schedule.config_at(now.saturating_duration_since(start));
+        }
+        self.stats.sent += 1;
+        let Some(now) = self.transmit(now, size) else {
+            self.stats.dropped += 1;
+            return;
+        };
+        if self.lose() {
+            self.stats.lost += 1;
+            return;
+        }
+        if self.rng.chance(self.config.duplication) {
+            self.stats.duplicated += 1;
+            self.schedule(now, packet.clone());
+        }
+        self.schedule(now, packet);
+    }
+
+    /// Take the next packet whose delivery time is at or before `now`
+    pub fn receive(&mut self, now: Instant) -> Option<T> {
+        let entry = self.in_flight.first_entry()?;
+        if entry.key().0 > now {
+            return None;
+        }
+        Some(entry.remove())
+    }
+
+    /// Time at which the next packet will be delivered, if any are in flight
+    pub fn next_delivery(&self) -> Option<Instant> {
+        self.in_flight.keys().next().map(|&(t, _)| t)
+    }
+
+    /// Number of packets in flight
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Bytes waiting for transmission at `now`
+    pub fn queued(&self, now: Instant) -> usize {
+        self.link.as_ref().map_or(0, |link| {
+            link.queue
+                .iter()
+                .filter(|&&(t, _)| t > now)
+                .map(|&(_, size)| size)
+                .sum()
+        })
+    }
+
+    /// Change the impairments applied to subsequently sent packets
+    ///
+    /// Cancels any schedule set by [`set_schedule`](Self::set_schedule), so conditions can be
+    /// toggled at runtime.
+    pub fn set_config(&mut self, config: SimulatorConfig) {
+        self.config = config;
+        self.schedule = None;
+    }
+
+    /// Vary the impairments applied to subsequently sent packets according to `schedule`,
+    /// beginning at `start`
+    pub fn set_schedule(&mut self, schedule: ImpairmentSchedule, start: Instant) {
+        self.config = schedule.config_at(Duration::ZERO);
+        self.schedule = Some((start, schedule));
+    }
+
+    /// The impairments most recently applied
+    pub fn config(&self) -> &SimulatorConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> SimulatorStats {
+        self.stats
+    }
+
+    fn lose(&mut self) -> bool {
+        match self.config.loss {
+            LossModel::Uniform(p) => self.rng.chance(p),
+            LossModel::GilbertElliott {
+                enter_bad,
+                exit_bad,
+                good_loss,
+                bad_loss,
+            } => {
+                let lost = self.rng.chance(if self.bad { bad_loss } else { good_loss });
+                if self.rng.chance(if self.bad { exit_bad } else { enter_bad }) {
+                    self.bad = !self.bad;
+                }
+                lost
+            }
+        }
+    }
+
+    /// Queue `size` bytes for transmission over the link at `now`, returning when transmission
+    /// completes, or `None` if the queue is full
+    fn transmit(&mut self, now: Instant, size: usize) -> Option<Instant> {
+        let Some(limit) = self.config.link else {
+            self.link = None;
+            return Some(now);
+        };
+        let link = self.link.get_or_insert_with(|| Link {
+            bucket: TokenBucket::new(limit.rate, limit.burst, now),
+            queue: VecDeque::new(),
+        });
+        if link.bucket.rate() != limit.rate {
+            link.bucket.set_rate(limit.rate, now);
+        }
+        if link.bucket.burst() != limit.burst {
+            link.bucket.set_burst(limit.burst, now);
+        }
+        while link.queue.front().is_some_and(|&(t, _)| t <= now) {
+            link.queue.pop_front();
+        }
+        let wait = link.bucket.delay(size as u64, now)?;
+        if !wait.is_zero() {
+            let queued = link.queue.iter().map(|&(_, size)| size).sum::<usize>();
+            if queued + size > limit.queue {
+                return None;
+            }
+            link.queue.push_back((now + wait, size));
+        }
+        link.bucket.consume(size as u64, now);
+        Some(now + wait)
+    }
+
+    fn schedule(&mut self, now: Instant, packet: T) {
+        let mut delay = self.config.latency + self.config.jitter.mul_f64(self.rng.unit());
+        if self.rng.chance(self.config.reorder) {
+            self.stats.reordered += 1;
+            delay += self.config.reorder_delay;
+        }
+        self.in_flight.insert((now + delay, self.next_id), packet);
+        self.next_id += 1;
+    }
+}
+
+/// A link whose capacity is limited by a token bucket
+#[derive(Debug, Clone)]
+struct Link {
+    /// Tokens are bytes; debt represents queued packets
+    bucket: TokenBucket,
+    /// Time at which transmission of each queued packet completes, and its size
+    queue: VecDeque<(Instant, usize)>,
+}
+
+/// Small, fast, seedable pseudorandom number generator (SplitMix64)
+///
+/// Not suitable for cryptographic use.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in [0, 1)
+    pub(crate) fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with probability `p`
+    pub(crate) fn chance(&mut self, p: f32) -> bool {
+        self.unit() < f64::from(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deliver_all(sim: &mut NetworkSimulator<u32>) -> Vec<(Duration, u32)> {
+        let start = Instant::now();
+        for i in 0..1000 {
+            sim.send(start + Duration::from_millis(i.into()), i);
+        }
+        let mut received = Vec::new();
+        while let Some(t) = sim.next_delivery() {
+            while let Some(x) = sim.receive(t) {
+                received.push((t - start, x));
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn impairments() {
+        let config = SimulatorConfig {
+            jitter: Duration::from_millis(5),
+            loss: LossModel::Uniform(0.1),
+            duplication: 0.05,
+            reorder: 0.05,
+            ..SimulatorConfig::default()
+        };
+        let mut sim = NetworkSimulator::new(config, 42);
+        let received = deliver_all(&mut sim);
+        let stats = sim.stats();
+        assert_eq!(stats.sent, 1000);
+        assert!((50..150).contains(&stats.lost));
+        assert!((20..80).contains(&stats.duplicated));
+        assert!((20..80).contains(&stats.reordered));
+        assert_eq!(
+            received.len() as u64,
+            stats.sent - stats.lost + stats.duplicated
+        );
+        assert!(received.windows(2).any(|w| w[0].1 > w[1].1), "reordered");
+        assert!(received.iter().all(|&(t, x)| {
+            let sent = Duration::from_millis(x.into());
+            t >= sent + config.latency
+                && t <= sent + config.latency + config.jitter + config.reorder_delay
+        }));
+
+        let mut again = NetworkSimulator::new(config, 42);
+        assert_eq!(deliver_all(&mut again), received, "deterministic");
+    }
+
+    #[test]
+    fn bursty() {
+        let loss = LossModel::GilbertElliott {
+            enter_bad: 0.01,
+            exit_bad: 0.2,
+            good_loss: 0.0,
+            bad_loss: 0.8,
+        };
+        let config = SimulatorConfig {
+            loss,
+            ..SimulatorConfig::default()
+        };
+        let mut sim = NetworkSimulator::new(config, 7);
+        let now = Instant::now();
+        let mut lost = Vec::new();
+        for i in 0..10_000 {
+            let before = sim.stats().lost;
+            sim.send(now, i);
+            lost.push(sim.stats().lost > before);
+        }
+        let rate = sim.stats().lost as f32 / 10_000.0;
+        assert!((rate - loss.mean_loss()).abs() < 0.01);
+        // Losses cluster: a loss is far more likely to follow another loss than on average
+        let pairs = lost.windows(2).filter(|w| w[0]).count();
+        let consecutive = lost.windows(2).filter(|w| w[0] && w[1]).count();
+        assert!(consecutive as f32 / pairs as f32 > 3.0 * rate);
+    }
+
+    #[test]
+    fn saturated() {
+        let config = SimulatorConfig {
+            link: Some(LinkLimit {
+                rate: 10_000,
+                burst: 1000,
+                queue: 5000,
+            }),
+            ..SimulatorConfig::default()
+        };
+        let mut sim = NetworkSimulator::new(config, 0);
+        let start = Instant::now();
+        // Twice as fast as the link can carry
+        let interval = Duration::from_millis(50);
+        let mut delays = Vec::new();
+        for i in 0..100 {
+            let now = start + interval * i;
+            sim.send_sized(now, i, 1000);
+            assert!(sim.queued(now) <= 5000);
+            while let Some(t) = sim.next_delivery() {
+                delays.push(t - now);
+                sim.receive(t);
+            }
+        }
+        // Delay grows as the queue fills, then plateaus as excess packets are dropped
+        assert_eq!(delays[0], config.latency);
+        assert!(delays.windows(2).take(10).all(|w| w[0] < w[1]));
+        assert!(
+            delays
+                .iter()
+                .all(|&d| d <= config.latency + Duration::from_millis(600))
+        );
+        assert!((45..55).contains(&sim.stats().dropped));
+
+        // Within capacity, there's no queueing
+        let mut sim = NetworkSimulator::new(config, 0);
+        for i in 0..100 {
+            let now = start + interval * 2 * i;
+            sim.send_sized(now, i, 1000);
+            assert_eq!(sim.next_delivery(), Some(now + config.latency));
+            sim.receive(now + config.latency);
+        }
+        assert_eq!(sim.stats().dropped, 0);
+    }
+
+    #[test]
+    fn schedule() {
+        let perfect = SimulatorConfig::default();
+        let spike = SimulatorConfig {
+            latency: Duration::from_millis(500),
+            ..perfect
+        };
+        let lossy = SimulatorConfig {
+            loss: LossModel::Uniform(1.0),
+            ..perfect
+        };
+        let schedule = ImpairmentSchedule::new(perfect)
+            .then(Duration::from_secs(10), spike)
+            .then(Duration::from_secs(2), lossy);
+        assert_eq!(schedule.duration(), Duration::from_secs(12));
+
+        let mut sim = NetworkSimulator::new(perfect, 0);
+        let start = Instant::now();
+        sim.set_schedule(schedule, start);
+        let delay = |sim: &mut NetworkSimulator<()>, t: u64| {
+            let now = start + Duration::from_secs(t);
+            sim.send(now, ());
+            let delivery = sim.next_delivery()?;
+            while sim.receive(delivery).is_some() {}
+            Some(delivery - now)
+        };
+        assert_eq!(delay(&mut sim, 0), Some(perfect.latency));
+        assert_eq!(delay(&mut sim, 10), Some(spike.latency));
+        assert_eq!(delay(&mut sim, 11), Some(spike.latency));
+        assert_eq!(delay(&mut sim, 12), None);
+        assert_eq!(delay(&mut sim, 100), None);
+
+        // Overridden at runtime
+        sim.set_config(perfect);
+        assert_eq!(delay(&mut sim, 101), Some(perfect.latency));
+    }
+
+    #[test]
+    fn timing() {
+        let mut sim = NetworkSimulator::new(SimulatorConfig::default(), 0);
+        let now = Instant::now();
+        sim.send(now, vec![1, 2, 3]);
+        assert_eq!(sim.receive(now), None);
+        assert_eq!(sim.next_delivery(), Some(now + Duration::from_millis(50)));
+        assert_eq!(
+            sim.receive(now + Duration::from_millis(50)),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(sim.in_flight(), 0);
+    }
+}
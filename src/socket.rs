@@ -0,0 +1,128 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use crate::{Transport, transport::DEFAULT_MAX_DATAGRAM_SIZE};
+
+/// [`Transport`] exchanging UDP datagrams with a single peer without an async runtime
+///
+/// The socket is made non-blocking, so [`try_recv`](Transport::try_recv) returns immediately
+/// when no datagram is ready and may be called until exhausted once per frame. Datagrams the OS
+/// has no room to buffer are dropped, as they might be by a congested network. To sleep until
+/// data arrives instead, e.g. on a dedicated server, register [`socket`](Self::socket) with a
+/// readiness poller such as `mio`.
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buffer: Box<[u8]>,
+    max_datagram_size: usize,
+}
+
+impl UdpTransport {
+    /// Exchange datagrams with `peer` over `socket`, which becomes connected and non-blocking
+    pub fn new(socket: UdpSocket, peer: SocketAddr) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        socket.connect(peer)?;
+        Ok(Self {
+            socket,
+            // Large enough for any UDP datagram
+            buffer: vec![0; 65536].into(),
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        })
+    }
+
+    /// The underlying socket
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Change the size of the largest datagram that may be sent
+    ///
+    /// Defaults to a conservative 1200 bytes. Larger datagrams may be fragmented or dropped by
+    /// the network.
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+}
+
+impl Transport for UdpTransport {
+    type Error = io::Error;
+
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        if datagram.len() > self.max_datagram_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "datagram exceeds maximum size",
+            ));
+        }
+        match self.socket.send(datagram) {
+            Err(e) if !transient(&e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn try_recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            match self.socket.recv(&mut self.buffer) {
+                Ok(len) => return Ok(Some(self.buffer[..len].to_vec())),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if transient(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
+
+/// Whether `e` reflects a condition of the network rather than of the socket
+///
+/// A peer that isn't listening yet causes ICMP errors to be reported on the next operation, and
+/// a full send buffer is congestion.
+fn transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn exchange() {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+        let mut a = UdpTransport::new(a, b_addr).unwrap();
+        let mut b = UdpTransport::new(b, a_addr).unwrap();
+        assert_eq!(b.try_recv().unwrap(), None);
+        for i in 0..10u8 {
+            a.send(&[i]).unwrap();
+        }
+        let mut received = Vec::new();
+        while received.len() < 10 {
+            match b.try_recv().unwrap() {
+                Some(datagram) => received.extend(datagram),
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        received.sort();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert_eq!(a.try_recv().unwrap(), None);
+        assert_eq!(
+            a.send(&vec![0; a.max_datagram_size() + 1])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
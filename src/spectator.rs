@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use crate::{BitReader, BitWriter, DecodeError, Delta, exchange::InputExchange};
+
+/// Confirmed inputs of every player for a range of frames, sent to spectators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpectatorMessage<I> {
+    /// Frame of the first entry in `frames`
+    pub start: u32,
+    /// Each player's input, indexed by player, for each frame
+    pub frames: Vec<Vec<I>>,
+}
+
+impl<I: Delta> SpectatorMessage<I> {
+    /// Write the message, which must have the same number of players in every frame
+    pub fn encode(&self, w: &mut BitWriter) {
+        w.write_varint(self.start.into());
+        w.write_varint(self.frames.len() as u64);
+        w.write_varint(self.frames.first().map_or(0, |x| x.len()) as u64);
+        for frame in &self.frames {
+            for input in frame {
+                input.encode(w);
+            }
+        }
+    }
+
+    /// Read a message carrying at most `max_frames` frames of at most `max_players` players
+    pub fn decode(
+        r: &mut BitReader<'_>,
+        max_frames: usize,
+        max_players: usize,
+    ) -> Result<Self, DecodeError> {
+        let start = r
+            .read_varint()?
+            .try_into()
+            .map_err(|_| DecodeError::Overflow)?;
+        let frames = r.read_varint()?;
+        let players = r.read_varint()?;
+        if frames > max_frames as u64 || players > max_players as u64 {
+            return Err(DecodeError::Overflow);
+        }
+        Ok(Self {
+            start,
+            frames: (0..frames)
+                .map(|_| (0..players).map(|_| I::decode(r)).collect())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Tracks which confirmed frames a session has yet to emit to spectators
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SpectatorOutput {
+    /// The first frame not yet emitted
+    next: u32,
+    delay: u32,
+}
+
+impl SpectatorOutput {
+    pub(crate) fn new(delay: u32) -> Self {
+        Self { next: 0, delay }
+    }
+
+    /// The first frame not yet emitted, whose inputs must be retained
+    pub(crate) fn next(&self) -> u32 {
+        self.next
+    }
+
+    /// Collect frames confirmed at least `delay` frames ago that haven't yet been emitted
+    pub(crate) fn take<I: Clone>(
+        &mut self,
+        exchange: &InputExchange<I>,
+    ) -> Option<SpectatorMessage<I>> {
+        let end = exchange.all_confirmed().saturating_sub(self.delay);
+        if end <= self.next {
+            return None;
+        }
+        let start = self.next;
+        self.next = end;
+        Some(SpectatorMessage {
+            start,
+            frames: (start..end)
+                .map(|frame| {
+                    (0..exchange.players())
+                        .map(|p| exchange.confirmed(p, frame).unwrap().clone())
+                        .collect()
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Replays the confirmed inputs of a session being spectated
+///
+/// Fed [`SpectatorMessage`]s from a [`RollbackSession`](crate::RollbackSession) or
+/// [`LockstepSession`](crate::LockstepSession) with spectators enabled, yielding each frame's
+/// inputs in order for the spectator's own simulation. Messages must arrive in order without gaps,
+/// e.g. by way of an [`OrderedReceiver`](crate::OrderedReceiver), but may overlap.
+#[derive(Debug, Clone)]
+pub struct SpectatorReplay<I> {
+    /// Frame of the first entry in `frames`
+    start: u32,
+    frames: VecDeque<Vec<I>>,
+}
+
+impl<I> SpectatorReplay<I> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer the frames carried by `msg`
+    ///
+    /// Returns `false` if `msg` starts after the last buffered frame, implying that an earlier
+    /// message was lost, in which case it's ignored.
+    pub fn receive(&mut self, msg: SpectatorMessage<I>) -> bool {
+        let end = self.start + self.frames.len() as u32;
+        if msg.start > end {
+            return false;
+        }
+        let known = (end - msg.start) as usize;
+        self.frames.extend(msg.frames.into_iter().skip(known));
+        true
+    }
+
+    /// Take every player's input for the next frame, if available
+    pub fn pop(&mut self) -> Option<(u32, Vec<I>)> {
+        let inputs = self.frames.pop_front()?;
+        let frame = self.start;
+        self.start += 1;
+        Some((frame, inputs))
+    }
+
+    /// Number of frames buffered
+    ///
+    /// A spectator can vary the rate at which it consumes frames to keep this in a comfortable
+    /// range, e.g. with [`throttle`](crate::throttle).
+    pub fn buffered(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+impl<I> Default for SpectatorReplay<I> {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            frames: VecDeque::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let mut replay = SpectatorReplay::new();
+        let msg = SpectatorMessage {
+            start: 0,
+            frames: vec![vec![1u8, 2], vec![3, 4]],
+        };
+        let mut w = BitWriter::new();
+        msg.encode(&mut w);
+        let buf = w.finish();
+        assert_eq!(
+            SpectatorMessage::decode(&mut BitReader::new(&buf), 2, 2),
+            Ok(msg.clone())
+        );
+        assert!(replay.receive(msg));
+        assert!(!replay.receive(SpectatorMessage {
+            start: 3,
+            frames: vec![vec![0, 0]],
+        }));
+        assert!(replay.receive(SpectatorMessage {
+            start: 1,
+            frames: vec![vec![3, 4], vec![5, 6]],
+        }));
+        assert_eq!(replay.buffered(), 3);
+        assert_eq!(replay.pop(), Some((0, vec![1, 2])));
+        assert_eq!(replay.pop(), Some((1, vec![3, 4])));
+        assert_eq!(replay.pop(), Some((2, vec![5, 6])));
+        assert_eq!(replay.pop(), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+use crate::AckEvent;
+
+/// Schedules transmission of keyed state such that loss is repaired with the latest value
+///
+/// Conventional reliability resends exactly the bytes that were lost. For state that changes
+/// over time, such as the position of an entity, those bytes are stale by the time the loss is
+/// detected. Instead, this tracks which keys were carried by each packet, and when a packet is
+/// lost, marks its keys dirty again so that their *current* values are sent. Keys that have since
+/// been sent again in a newer packet are left alone, since the newer packet supersedes the lost
+/// one.
+///
+/// Values are owned by the caller; this only decides which keys need to be written.
+#[derive(Debug, Clone)]
+pub struct StateChannel<K> {
+    /// Keys awaiting transmission, in the order they were marked
+    dirty: VecDeque<K>,
+    /// Members of `dirty`
+    dirty_set: HashSet<K>,
+    /// Keys carried by each packet in flight
+    in_flight: HashMap<u16, Vec<K>>,
+    /// Sequence number of the most recent packet each key was sent in, if not yet acknowledged
+    latest: HashMap<K, u16>,
+}
+
+impl<K: Hash + Eq + Clone> StateChannel<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that the value of `key` has changed and must be transmitted
+    pub fn mark_dirty(&mut self, key: K) {
+        if self.dirty_set.insert(key.clone()) {
+            self.dirty.push_back(key);
+        }
+    }
+
+    /// Get the next key whose current value should be written to an outgoing packet
+    pub fn pop_dirty(&mut self) -> Option<K> {
+        let key = self.dirty.pop_front()?;
+        self.dirty_set.remove(&key);
+        Some(key)
+    }
+
+    /// Stop tracking `key`, e.g. because the thing it identifies no longer exists
+    pub fn remove(&mut self, key: &K) {
+        if self.dirty_set.remove(key) {
+            self.dirty.retain(|x| x != key);
+        }
+        self.latest.remove(key);
+    }
+
+    /// Record that the packet with `sequence` carried the current values of `keys`
+    pub fn on_sent(&mut self, sequence: u16, keys: Vec<K>) {
+        for key in &keys {
+            self.latest.insert(key.clone(), sequence);
+        }
+        self.in_flight.insert(sequence, keys);
+    }
+
+    /// Process a delivery notification from the [`AckTracker`](crate::AckTracker) that assigned
+    /// the sequence numbers passed to [`on_sent`](Self::on_sent)
+    pub fn on_ack_event(&mut self, event: &AckEvent) {
+        match *event {
+            AckEvent::Delivered(ref packet) => {
+                let Some(keys) = self.in_flight.remove(&packet.sequence) else {
+                    return;
+                };
+                for key in keys {
+                    if self.latest.get(&key) == Some(&packet.sequence) {
+                        self.latest.remove(&key);
+                    }
+                }
+            }
+            AckEvent::Lost(ref packet) => {
+                let Some(keys) = self.in_flight.remove(&packet.sequence) else {
+                    return;
+                };
+                for key in keys {
+                    if self.latest.get(&key) == Some(&packet.sequence) {
+                        self.latest.remove(&key);
+                        self.mark_dirty(key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of keys awaiting transmission
+    pub fn dirty_len(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Whether `key` has been sent, but not yet acknowledged, since it was last marked dirty
+    pub fn is_unacknowledged(&self, key: &K) -> bool {
+        self.latest.contains_key(key)
+    }
+}
+
+impl<K> Default for StateChannel<K> {
+    fn default() -> Self {
+        Self {
+            dirty: VecDeque::new(),
+            dirty_set: HashSet::new(),
+            in_flight: HashMap::new(),
+            latest: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LostPacket, PacketInfo};
+    use std::time::Duration;
+
+    use crate::Instant;
+
+    fn lost(sequence: u16) -> AckEvent {
+        AckEvent::Lost(LostPacket {
+            sequence,
+            bytes: 0,
+            sent: Instant::now(),
+        })
+    }
+
+    fn delivered(sequence: u16) -> AckEvent {
+        AckEvent::Delivered(PacketInfo {
+            sequence,
+            bytes: 0,
+            sent: Instant::now(),
+            rtt: Duration::ZERO,
+        })
+    }
+
+    #[test]
+    fn smoke() {
+        let mut ch = StateChannel::new();
+        ch.mark_dirty('a');
+        ch.mark_dirty('b');
+        ch.mark_dirty('a');
+        assert_eq!(ch.dirty_len(), 2);
+        assert_eq!(ch.pop_dirty(), Some('a'));
+        assert_eq!(ch.pop_dirty(), Some('b'));
+        assert_eq!(ch.pop_dirty(), None);
+        ch.on_sent(0, vec!['a', 'b']);
+        assert!(ch.is_unacknowledged(&'a'));
+
+        ch.on_ack_event(&lost(0));
+        assert_eq!(ch.pop_dirty(), Some('a'), "lost keys are resent");
+        assert_eq!(ch.pop_dirty(), Some('b'));
+        ch.on_sent(1, vec!['a', 'b']);
+        ch.on_ack_event(&delivered(1));
+        assert!(!ch.is_unacknowledged(&'a'));
+    }
+
+    #[test]
+    fn superseded() {
+        let mut ch = StateChannel::new();
+        ch.on_sent(0, vec!['a', 'b']);
+        ch.on_sent(1, vec!['a']);
+        ch.on_ack_event(&lost(0));
+        assert_eq!(
+            ch.pop_dirty(),
+            Some('b'),
+            "keys sent again since aren't resent"
+        );
+        assert_eq!(ch.pop_dirty(), None);
+        ch.on_ack_event(&lost(1));
+        assert_eq!(ch.pop_dirty(), Some('a'));
+    }
+}
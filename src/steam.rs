@@ -0,0 +1,113 @@
+use std::{collections::VecDeque, fmt};
+
+use steamworks::{networking_sockets::NetConnection, networking_types::SendFlags};
+
+use crate::{Transport, transport::DEFAULT_MAX_DATAGRAM_SIZE};
+
+/// Largest number of messages fetched from Steam at once
+const RECV_BATCH: usize = 64;
+
+/// [`Transport`] over a Steam Networking Sockets connection
+///
+/// Datagrams are sent as unreliable messages without Nagle delay, so they may be relayed through
+/// Valve's network but otherwise behave like UDP. Messages Steam refuses to queue because the
+/// connection is congested or not yet established are dropped, as they might be by a congested
+/// network.
+///
+/// Steam dispatches callbacks, including those that drive connection state, from
+/// [`Client::run_callbacks`](steamworks::Client::run_callbacks), which must be called regularly
+/// as usual.
+pub struct SteamTransport {
+    connection: NetConnection,
+    received: VecDeque<Vec<u8>>,
+    max_datagram_size: usize,
+}
+
+impl SteamTransport {
+    /// Exchange datagrams over an accepted or requested `connection`
+    pub fn new(connection: NetConnection) -> Self {
+        Self {
+            connection,
+            received: VecDeque::new(),
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        }
+    }
+
+    /// The underlying connection
+    pub fn connection(&self) -> &NetConnection {
+        &self.connection
+    }
+
+    /// Take back the underlying connection, e.g. to close it with a reason
+    pub fn into_inner(self) -> NetConnection {
+        self.connection
+    }
+
+    /// Change the size of the largest datagram that may be sent
+    ///
+    /// Defaults to a conservative 1200 bytes. Larger messages are fragmented, and lost entirely
+    /// if any fragment is lost.
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+}
+
+impl Transport for SteamTransport {
+    type Error = SteamError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), SteamError> {
+        match self
+            .connection
+            .send_message(datagram, SendFlags::UNRELIABLE_NO_NAGLE)
+        {
+            Ok(_)
+            | Err(steamworks::SteamError::LimitExceeded)
+            | Err(steamworks::SteamError::Ignored) => Ok(()),
+            Err(e) => Err(SteamError::Send(e)),
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, SteamError> {
+        if self.received.is_empty() {
+            let messages = self
+                .connection
+                .receive_messages(RECV_BATCH)
+                .map_err(|_| SteamError::InvalidConnection)?;
+            self.received
+                .extend(messages.iter().map(|message| message.data().to_vec()));
+        }
+        Ok(self.received.pop_front())
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
+
+impl fmt::Debug for SteamTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SteamTransport")
+            .field("received", &self.received.len())
+            .field("max_datagram_size", &self.max_datagram_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Errors produced by [`SteamTransport`]
+#[derive(Debug, Copy, Clone)]
+pub enum SteamError {
+    Send(steamworks::SteamError),
+    /// The connection was closed, or never existed
+    InvalidConnection,
+}
+
+impl fmt::Display for SteamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SteamError::Send(ref e) => write!(f, "sending message failed: {e}"),
+            SteamError::InvalidConnection => f.write_str("invalid connection"),
+        }
+    }
+}
+
+impl std::error::Error for SteamError {}
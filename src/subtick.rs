@@ -0,0 +1,196 @@
+use core::time::Duration;
+
+use crate::{BitReader, BitWriter, DecodeError, Delta};
+
+/// A moment within a simulation tick
+///
+/// Tick-granularity timestamps can't resolve the order of actions that occur within the same tick,
+/// such as two players firing at each other. Sub-tick timestamps record the moment at which an
+/// input was sampled, typically the client's interpolated view time, to a precision of 1/65536 of a
+/// tick. Ordered chronologically.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SubTick {
+    pub tick: u64,
+    /// Progress through `tick`, in units of 1/65536 of a tick
+    pub fraction: u16,
+}
+
+impl SubTick {
+    /// The start of `tick`
+    pub fn new(tick: u64) -> Self {
+        Self { tick, fraction: 0 }
+    }
+
+    /// The moment `fraction` of the way through `tick`, where `fraction` is in [0, 1)
+    pub fn with_fraction(tick: u64, fraction: f32) -> Self {
+        Self {
+            tick,
+            fraction: (fraction.clamp(0.0, 1.0) * 65536.0).min(u16::MAX.into()) as u16,
+        }
+    }
+
+    /// The moment `time` after the start of tick 0, for ticks `tick_interval` apart
+    pub fn from_duration(time: Duration, tick_interval: Duration) -> Self {
+        let interval = tick_interval.as_nanos().max(1);
+        let nanos = time.as_nanos();
+        Self {
+            tick: (nanos / interval) as u64,
+            fraction: ((nanos % interval) * 65536 / interval) as u16,
+        }
+    }
+
+    /// Time since the start of tick 0, for ticks `tick_interval` apart
+    ///
+    /// Suitable for use as a view time with a [`LagCompensator`](crate::LagCompensator).
+    pub fn to_duration(self, tick_interval: Duration) -> Duration {
+        let nanos = tick_interval.as_nanos()
+            * (u128::from(self.tick) * 65536 + u128::from(self.fraction))
+            / 65536;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+
+    /// The moment `time` seconds after the start of tick 0, for ticks `tick_interval` seconds
+    /// apart
+    ///
+    /// Negative times saturate to the start of tick 0.
+    pub fn from_secs_f64(time: f64, tick_interval: f64) -> Self {
+        let ticks = (time / tick_interval).max(0.0);
+        let tick = ticks as u64;
+        Self {
+            tick,
+            fraction: ((ticks - tick as f64) * 65536.0).min(u16::MAX.into()) as u16,
+        }
+    }
+
+    /// Seconds since the start of tick 0, for ticks `tick_interval` seconds apart
+    pub fn to_secs_f64(self, tick_interval: f64) -> f64 {
+        (self.tick as f64 + f64::from(self.fraction) / 65536.0) * tick_interval
+    }
+
+    /// Progress through `tick`, in [0, 1)
+    pub fn fraction(self) -> f32 {
+        f32::from(self.fraction) / 65536.0
+    }
+}
+
+impl Delta for SubTick {
+    fn encode(&self, w: &mut BitWriter) {
+        w.write_varint(self.tick);
+        w.write_bits(self.fraction.into(), 16);
+    }
+
+    fn decode(r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            tick: r.read_varint()?,
+            fraction: r.read_bits(16)? as u16,
+        })
+    }
+
+    fn encode_delta(&self, baseline: &Self, w: &mut BitWriter) {
+        self.tick.encode_delta(&baseline.tick, w);
+        w.write_bits(self.fraction.into(), 16);
+    }
+
+    fn decode_delta(baseline: &Self, r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            tick: u64::decode_delta(&baseline.tick, r)?,
+            fraction: r.read_bits(16)? as u16,
+        })
+    }
+}
+
+/// A value tagged with the moment it was sampled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Stamped<T> {
+    pub time: SubTick,
+    pub value: T,
+}
+
+impl<T: Delta> Delta for Stamped<T> {
+    fn encode(&self, w: &mut BitWriter) {
+        self.time.encode(w);
+        self.value.encode(w);
+    }
+
+    fn decode(r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            time: SubTick::decode(r)?,
+            value: T::decode(r)?,
+        })
+    }
+
+    fn encode_delta(&self, baseline: &Self, w: &mut BitWriter) {
+        self.time.encode_delta(&baseline.time, w);
+        self.value.encode_delta(&baseline.value, w);
+    }
+
+    fn decode_delta(baseline: &Self, r: &mut BitReader<'_>) -> Result<Self, DecodeError> {
+        Ok(Self {
+            time: SubTick::decode_delta(&baseline.time, r)?,
+            value: T::decode_delta(&baseline.value, r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration() {
+        let interval = Duration::from_millis(16);
+        let time = SubTick::from_duration(Duration::from_millis(40), interval);
+        assert_eq!(time.tick, 2);
+        assert_eq!(time.fraction(), 0.5);
+        assert_eq!(time.to_duration(interval), Duration::from_millis(40));
+        assert!(time < SubTick::with_fraction(2, 0.75));
+        assert_eq!(SubTick::with_fraction(0, 1.0).fraction, u16::MAX);
+
+        let time = SubTick::from_secs_f64(0.0390625, 1.0 / 64.0);
+        assert_eq!(time, SubTick::with_fraction(2, 0.5));
+        assert_eq!(time.to_secs_f64(1.0 / 64.0), 0.0390625);
+        assert_eq!(SubTick::from_secs_f64(-1.0, 0.1), SubTick::new(0));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let baseline = Stamped {
+            time: SubTick::with_fraction(100, 0.5),
+            value: 7u8,
+        };
+        let x = Stamped {
+            time: SubTick::with_fraction(101, 0.25),
+            value: 9u8,
+        };
+        let mut w = BitWriter::new();
+        x.encode_delta(&baseline, &mut w);
+        x.encode(&mut w);
+        let buf = w.finish();
+        let mut r = BitReader::new(&buf);
+        assert_eq!(Stamped::decode_delta(&baseline, &mut r), Ok(x));
+        assert_eq!(Stamped::decode(&mut r), Ok(x));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn queues() {
+        use crate::Instant;
+        use crate::{InputQueue, PredictionQueue};
+
+        let mut prediction = PredictionQueue::new(0);
+        prediction.record_at('a', SubTick::with_fraction(10, 0.5));
+        prediction.record_at('b', SubTick::with_fraction(12, 0.5));
+        let now = Instant::now();
+        let mut queue = InputQueue::new();
+        for &input in &prediction {
+            queue.push(8, input, now);
+        }
+        let first = queue.take_stamped(now, Duration::ZERO, 11).unwrap();
+        assert_eq!(first.time, SubTick::with_fraction(10, 0.5));
+        let second = queue.take_stamped(now, Duration::ZERO, 11).unwrap();
+        assert_eq!(second.time.tick, 11, "can't act in the future");
+    }
+}
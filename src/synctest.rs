@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use crate::{Desync, RollbackCallbacks, rollback::StatePool};
+
+/// Offline session that rolls back and resimulates every frame to flush out nondeterminism
+///
+/// Equivalent to GGPO's sync test. Each frame, the state is saved and checksummed before
+/// simulating. The session then loads the state from `check_distance` frames ago and resimulates
+/// up to the present, verifying that every intermediate state has the same checksum as the first
+/// time around. Any difference indicates a determinism bug in the simulation or in its
+/// save/load callbacks, which would cause a [`RollbackSession`](crate::RollbackSession) to
+/// desync.
+///
+/// Runs without a network: the caller supplies every player's inputs directly. Varying inputs
+/// frequently exercises more of the simulation.
+#[derive(Debug, Clone)]
+pub struct SyncTestSession<I, S> {
+    check_distance: u32,
+    /// The next frame to simulate
+    frame: u32,
+    /// Saved state, its checksum, and inputs for each recent frame
+    history: VecDeque<Record<I, S>>,
+    pool: StatePool<S>,
+}
+
+#[derive(Debug, Clone)]
+struct Record<I, S> {
+    frame: u32,
+    state: S,
+    checksum: u64,
+    inputs: Vec<I>,
+}
+
+impl<I, S: Default> SyncTestSession<I, S> {
+    /// Resimulate the last `check_distance` frames after each frame
+    pub fn new(check_distance: u32) -> Self {
+        Self {
+            check_distance: check_distance.max(1),
+            frame: 0,
+            history: VecDeque::new(),
+            pool: StatePool::new(check_distance.max(1) as usize + 1),
+        }
+    }
+
+    /// Simulate the next frame with each player's input, then roll back and resimulate
+    ///
+    /// Returns the first frame whose state differed on resimulation, with its original checksum as
+    /// [`Desync::local`] and the resimulated checksum as [`Desync::remote`].
+    pub fn advance_frame(
+        &mut self,
+        inputs: Vec<I>,
+        game: &mut impl RollbackCallbacks<I, S>,
+    ) -> Option<Desync> {
+        let frame = self.frame;
+        let mut state = self.pool.take();
+        game.save(frame, &mut state);
+        let checksum = game.checksum(frame, &state);
+        game.advance(frame, &inputs);
+        self.history.push_back(Record {
+            frame,
+            state,
+            checksum,
+            inputs,
+        });
+        if self.history.len() > self.check_distance as usize {
+            let record = self.history.pop_front().unwrap();
+            self.pool.put(record.state);
+        }
+        self.frame += 1;
+
+        // Roll back to the oldest saved state and resimulate, re-saving each frame as a real
+        // session would
+        let first = self.history.front()?;
+        game.load(first.frame, &first.state);
+        let mut desync = None;
+        let mut state = self.pool.take();
+        for record in &mut self.history {
+            game.save(record.frame, &mut state);
+            let checksum = game.checksum(record.frame, &state);
+            if checksum != record.checksum && desync.is_none() {
+                desync = Some(Desync {
+                    tick: record.frame.into(),
+                    local: record.checksum,
+                    remote: checksum,
+                });
+            }
+            std::mem::swap(&mut record.state, &mut state);
+            game.advance(record.frame, &record.inputs);
+        }
+        self.pool.put(state);
+        desync
+    }
+}
+
+impl<I, S> SyncTestSession<I, S> {
+    /// The next frame to be simulated
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Game {
+        state: u64,
+        /// Nondeterministic state that isn't saved
+        hidden: u64,
+    }
+
+    impl RollbackCallbacks<u8, u64> for Game {
+        fn save(&mut self, _: u32, state: &mut u64) {
+            *state = self.state;
+        }
+
+        fn load(&mut self, _: u32, state: &u64) {
+            self.state = *state;
+        }
+
+        fn advance(&mut self, frame: u32, inputs: &[u8]) {
+            self.state = self.state * 3 + u64::from(inputs[0]);
+            if frame >= 10 {
+                self.hidden += 1;
+                self.state += self.hidden;
+            }
+        }
+
+        fn checksum(&mut self, _: u32, state: &u64) -> u64 {
+            *state
+        }
+    }
+
+    #[test]
+    fn detect() {
+        let mut session = SyncTestSession::new(4);
+        let mut game = Game {
+            state: 0,
+            hidden: 0,
+        };
+        for frame in 0..10 {
+            assert_eq!(session.advance_frame(vec![frame as u8], &mut game), None);
+        }
+        assert_eq!(session.advance_frame(vec![0], &mut game), None);
+        let desync = session.advance_frame(vec![0], &mut game).unwrap();
+        assert_eq!(desync.tick, 11);
+    }
+}
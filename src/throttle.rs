@@ -1,4 +1,4 @@
-use std::time::Duration;
+use core::time::Duration;
 
 /// Compute the amount of time to advance a simulation after `real_time` has passed, given
 /// `buffer_remaining` simulation time until data is exhausted
@@ -22,22 +22,107 @@ pub fn throttle(
     min_latency: Duration,
     hysteresis: Duration,
 ) -> Duration {
-    let scaled = if let Some(error) = min_latency.checked_sub(buffer_remaining) {
-        // We're about to run out of data; slow down
-        #[allow(clippy::manual_clamp)] // NaN handling in case `min_latency` is too close to zero
-        let scale = 1.0 - f32::min(1.0, f32::max(0.0, error.div_duration_f32(min_latency)));
-        real_time.mul_f32(scale)
-    } else if let Some(error) = buffer_remaining.checked_sub(min_latency + hysteresis) {
-        // We've fallen too far behind; speed up. 1 second behind = 2x speed
-        let scale = error.as_secs_f32();
-        // We know we're at least `error` behind where we should be, but not necessarily any
-        // further. If we overshoot we'll underrun in the future.
-        real_time + Ord::min(real_time.mul_f32(scale), error)
-    } else {
-        real_time
+    let regime = ThrottleRegime::new(buffer_remaining, min_latency, hysteresis);
+    let scaled = match regime {
+        ThrottleRegime::Slow => {
+            // We're about to run out of data; slow down
+            let error = min_latency - buffer_remaining;
+            #[allow(clippy::manual_clamp)]
+            // NaN handling in case `min_latency` is too close to zero
+            let scale = 1.0 - f32::min(1.0, f32::max(0.0, error.div_duration_f32(min_latency)));
+            real_time.mul_f32(scale)
+        }
+        ThrottleRegime::Fast => {
+            // We've fallen too far behind; speed up. 1 second behind = 2x speed
+            let error = buffer_remaining - (min_latency + hysteresis);
+            let scale = error.as_secs_f32();
+            // We know we're at least `error` behind where we should be, but not necessarily any
+            // further. If we overshoot we'll underrun in the future.
+            real_time + Ord::min(real_time.mul_f32(scale), error)
+        }
+        ThrottleRegime::Normal => real_time,
     };
     // If `real_time` is large, we might overshoot the entire buffer.
-    Ord::min(scaled, buffer_remaining)
+    let result = Ord::min(scaled, buffer_remaining);
+    #[cfg(feature = "tracing")]
+    {
+        tracing::trace!(
+            target: "nettish::throttle",
+            ?regime,
+            ?real_time,
+            ?buffer_remaining,
+            advance = ?result,
+            "throttle"
+        );
+        if buffer_remaining.is_zero() && !real_time.is_zero() {
+            tracing::debug!(target: "nettish::throttle", ?real_time, "snapshot buffer underrun");
+        }
+    }
+    result
+}
+
+/// [`throttle`] for times measured in seconds
+///
+/// Computed directly in `f64`, for engines that represent time that way throughout.
+pub fn throttle_secs_f64(
+    real_time: f64,
+    buffer_remaining: f64,
+    min_latency: f64,
+    hysteresis: f64,
+) -> f64 {
+    let regime = ThrottleRegime::from_secs_f64(buffer_remaining, min_latency, hysteresis);
+    let scaled = match regime {
+        ThrottleRegime::Slow => {
+            let error = min_latency - buffer_remaining;
+            #[allow(clippy::manual_clamp)]
+            let scale = 1.0 - f64::min(1.0, f64::max(0.0, error / min_latency));
+            real_time * scale
+        }
+        ThrottleRegime::Fast => {
+            let error = buffer_remaining - (min_latency + hysteresis);
+            real_time + f64::min(real_time * error, error)
+        }
+        ThrottleRegime::Normal => real_time,
+    };
+    f64::min(scaled, buffer_remaining)
+}
+
+/// How [`throttle`] is adjusting the flow of time
+///
+/// Frequent changes may indicate that `min_latency` or `hysteresis` are too small for the
+/// connection's jitter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ThrottleRegime {
+    /// Too little data is buffered; time is slowed
+    Slow,
+    /// Time flows at the real rate
+    Normal,
+    /// Too much data is buffered; time is sped up
+    Fast,
+}
+
+impl ThrottleRegime {
+    /// The regime [`throttle`] applies for the given parameters
+    pub fn new(buffer_remaining: Duration, min_latency: Duration, hysteresis: Duration) -> Self {
+        if buffer_remaining < min_latency {
+            ThrottleRegime::Slow
+        } else if buffer_remaining > min_latency + hysteresis {
+            ThrottleRegime::Fast
+        } else {
+            ThrottleRegime::Normal
+        }
+    }
+
+    /// The regime [`throttle_secs_f64`] applies for the given parameters
+    pub fn from_secs_f64(buffer_remaining: f64, min_latency: f64, hysteresis: f64) -> Self {
+        if buffer_remaining < min_latency {
+            ThrottleRegime::Slow
+        } else if buffer_remaining > min_latency + hysteresis {
+            ThrottleRegime::Fast
+        } else {
+            ThrottleRegime::Normal
+        }
+    }
 }
 
 #[cfg(test)]
@@ -76,6 +161,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn regime() {
+        let ms = Duration::from_millis;
+        assert_eq!(
+            ThrottleRegime::new(ms(40), ms(50), ms(200)),
+            ThrottleRegime::Slow
+        );
+        assert_eq!(
+            ThrottleRegime::new(ms(100), ms(50), ms(200)),
+            ThrottleRegime::Normal
+        );
+        assert_eq!(
+            ThrottleRegime::new(ms(1000), ms(50), ms(200)),
+            ThrottleRegime::Fast
+        );
+    }
+
+    #[test]
+    fn secs_f64() {
+        let ms = Duration::from_millis;
+        for buffer in [0, 5, 50, 100, 120, 500, 3000] {
+            let expected = throttle(ms(16), ms(buffer), ms(100), ms(50));
+            let actual = throttle_secs_f64(0.016, buffer as f64 / 1000.0, 0.1, 0.05);
+            assert!(
+                (actual - expected.as_secs_f64()).abs() < 1e-6,
+                "{buffer}ms: {actual} != {expected:?}"
+            );
+        }
+    }
+
     #[test]
     fn large_step() {
         assert_eq!(
@@ -0,0 +1,435 @@
+use alloc::{vec, vec::Vec};
+use core::{ops::Range, time::Duration};
+
+use crate::{FixedTime, SubTick, throttle};
+
+/// The rate at which a server simulates, shared by every time-dependent component
+///
+/// Durations that should scale with the tickrate, such as the hysteresis passed to [`throttle`]
+/// or the delay of an [`InputQueue`](crate::InputQueue), should be derived from a `TickConfig`
+/// rather than hardcoded, so that they remain appropriate when the rate changes. A
+/// [`TickTimeline`] tracks the rate in effect at each tick.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TickConfig {
+    /// Ticks per second
+    pub rate: u32,
+}
+
+impl TickConfig {
+    pub fn new(rate: u32) -> Self {
+        Self { rate }
+    }
+
+    /// Time between ticks
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(1) / self.rate.max(1)
+    }
+
+    /// Duration of `ticks` ticks, without accumulating rounding error
+    pub fn duration(&self, ticks: u64) -> Duration {
+        self.subtick_duration(SubTick::new(ticks))
+    }
+
+    /// Time since the start of tick 0 at this rate, without accumulating rounding error
+    pub fn subtick_duration(&self, time: SubTick) -> Duration {
+        let nanos = (u128::from(time.tick) * 65536 + u128::from(time.fraction)) * 1_000_000_000
+            / (u128::from(self.rate.max(1)) * 65536);
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+
+    /// Time since the start of tick 0 to the start of `tick`, for deterministic simulations
+    ///
+    /// Computed directly rather than by summing intervals, so no rounding error accumulates.
+    pub fn fixed_time(&self, tick: u64) -> FixedTime {
+        FixedTime::from_ratio(tick as i64, self.rate.max(1).into())
+    }
+
+    /// Length of `tick`, for deterministic simulations
+    ///
+    /// Varies by at most [`FixedTime::EPSILON`] from tick to tick, such that the lengths of a
+    /// sequence of ticks always sum to exactly the [`fixed_time`](Self::fixed_time) elapsed.
+    pub fn fixed_step(&self, tick: u64) -> FixedTime {
+        self.fixed_time(tick + 1) - self.fixed_time(tick)
+    }
+
+    /// Delay to pass to [`InputQueue::take`](crate::InputQueue::take) to buffer `ticks` ticks of
+    /// input
+    pub fn input_delay(&self, ticks: u32) -> Duration {
+        self.duration(ticks.into())
+    }
+
+    /// The moment `time` after the start of tick 0 at this rate
+    pub fn subtick(&self, time: Duration) -> SubTick {
+        let scaled = time.as_nanos() * u128::from(self.rate.max(1));
+        SubTick {
+            tick: (scaled / 1_000_000_000) as u64,
+            fraction: ((scaled % 1_000_000_000) * 65536 / 1_000_000_000) as u16,
+        }
+    }
+
+    /// [`throttle`] a client receiving one snapshot per tick
+    ///
+    /// The hysteresis window is one tick interval, so that time flows uniformly when snapshots
+    /// arrive on schedule.
+    pub fn throttle(
+        &self,
+        real_time: Duration,
+        buffer_remaining: Duration,
+        min_latency: Duration,
+    ) -> Duration {
+        throttle(real_time, buffer_remaining, min_latency, self.interval())
+    }
+}
+
+impl Default for TickConfig {
+    fn default() -> Self {
+        Self { rate: 60 }
+    }
+}
+
+/// Converts between the ticks of a client and server running at different rates
+///
+/// For example, a client might sample input at 120Hz, its render rate, while the server
+/// simulates at 30Hz. Each server tick then corresponds to four client ticks, whose inputs must
+/// be coalesced into one. Rates needn't divide evenly: at 144Hz and 64Hz, each server tick spans
+/// two or three client ticks. Both ticks numbered 0 are taken to begin at the same moment.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TickConversion {
+    pub client: TickConfig,
+    pub server: TickConfig,
+}
+
+impl TickConversion {
+    pub fn new(client: TickConfig, server: TickConfig) -> Self {
+        Self { client, server }
+    }
+
+    /// The moment on the server's timeline at which `client_tick` begins
+    pub fn to_server(&self, client_tick: SubTick) -> SubTick {
+        rescale(client_tick, self.client.rate, self.server.rate)
+    }
+
+    /// The moment on the client's timeline at which `server_tick` begins
+    pub fn to_client(&self, server_tick: SubTick) -> SubTick {
+        rescale(server_tick, self.server.rate, self.client.rate)
+    }
+
+    /// The server tick in progress when `client_tick` begins
+    pub fn server_tick(&self, client_tick: u64) -> u64 {
+        self.to_server(SubTick::new(client_tick)).tick
+    }
+
+    /// The client ticks beginning during `server_tick`, whose inputs map to it
+    pub fn client_ticks(&self, server_tick: u64) -> Range<u64> {
+        self.first_client_tick(server_tick)..self.first_client_tick(server_tick + 1)
+    }
+
+    /// Number of client ticks beginning during `server_tick`
+    pub fn samples_per_tick(&self, server_tick: u64) -> u64 {
+        let range = self.client_ticks(server_tick);
+        range.end - range.start
+    }
+
+    /// Number of client ticks spanning `server_ticks` server ticks, rounded down
+    pub fn client_tick_count(&self, server_ticks: u64) -> u64 {
+        (u128::from(server_ticks) * u128::from(self.client.rate)
+            / u128::from(self.server.rate.max(1))) as u64
+    }
+
+    /// Number of server ticks spanning `client_ticks` client ticks, rounded down
+    pub fn server_tick_count(&self, client_ticks: u64) -> u64 {
+        (u128::from(client_ticks) * u128::from(self.server.rate)
+            / u128::from(self.client.rate.max(1))) as u64
+    }
+
+    /// The earliest client tick beginning at or after the start of `server_tick`
+    fn first_client_tick(&self, server_tick: u64) -> u64 {
+        u128::from(server_tick)
+            .saturating_mul(u128::from(self.client.rate))
+            .div_ceil(u128::from(self.server.rate.max(1))) as u64
+    }
+}
+
+/// Convert `time` from ticks at rate `from` to ticks at rate `to`, rounding down
+fn rescale(time: SubTick, from: u32, to: u32) -> SubTick {
+    let units = (u128::from(time.tick) << 16 | u128::from(time.fraction)) * u128::from(to)
+        / u128::from(from.max(1));
+    SubTick {
+        tick: (units >> 16) as u64,
+        fraction: units as u16,
+    }
+}
+
+/// A message agreeing on the tickrate between client and server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TickRateMessage {
+    /// Sent by a client on connecting: the rates it supports
+    Supported { rates: Vec<u32> },
+    /// Sent by a server: `config` takes effect from `tick` onwards, superseding changes with lower
+    /// revisions
+    Change {
+        revision: u32,
+        tick: u64,
+        config: TickConfig,
+    },
+}
+
+/// Select the first of a server's `preferred` rates that a client also supports
+pub fn choose_tick_rate(preferred: &[u32], supported: &[u32]) -> Option<TickConfig> {
+    preferred
+        .iter()
+        .find(|x| supported.contains(x))
+        .map(|&rate| TickConfig::new(rate))
+}
+
+/// The tickrate in effect at each tick of a session, allowing it to change mid-session
+///
+/// The server schedules a [`change`](Self::change) for a future tick and sends the resulting
+/// message to every client, which [`handle`](Self::handle) it to schedule the same change. Because
+/// the change is tied to a tick rather than a moment of wall-clock time, both sides switch rates
+/// at exactly the same point in the simulation, and conversions between ticks and simulation time
+/// agree across the transition. The server should schedule changes far enough ahead for the
+/// message to reach clients, e.g. several round trips, and resend it until acknowledged.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TickTimeline {
+    /// Rates in effect, in order of the tick at which they began. Never empty.
+    segments: Vec<Segment>,
+    revision: u32,
+}
+
+impl TickTimeline {
+    /// Begin a timeline at tick 0 with `config`
+    pub fn new(config: TickConfig) -> Self {
+        Self {
+            segments: vec![Segment {
+                tick: 0,
+                time: Duration::ZERO,
+                config,
+            }],
+            revision: 0,
+        }
+    }
+
+    /// Schedule a change to `config` at `tick`, returning the message to send to clients
+    ///
+    /// Supersedes any previously scheduled changes at or after `tick`. Panics if `tick` precedes
+    /// the start of the rate currently in effect.
+    pub fn change(&mut self, tick: u64, config: TickConfig) -> TickRateMessage {
+        self.revision += 1;
+        self.schedule(tick, config);
+        TickRateMessage::Change {
+            revision: self.revision,
+            tick,
+            config,
+        }
+    }
+
+    /// Apply a [`TickRateMessage::Change`] received from the server
+    ///
+    /// Returns whether the message was applied. Other messages, stale revisions, and changes
+    /// before the earliest remembered tick are ignored.
+    pub fn handle(&mut self, message: &TickRateMessage) -> bool {
+        let TickRateMessage::Change {
+            revision,
+            tick,
+            config,
+        } = *message
+        else {
+            return false;
+        };
+        if revision <= self.revision || tick < self.segments[0].tick {
+            return false;
+        }
+        self.revision = revision;
+        self.schedule(tick, config);
+        true
+    }
+
+    /// The rate in effect during `tick`
+    pub fn config_at(&self, tick: u64) -> TickConfig {
+        self.segment_at_tick(tick).config
+    }
+
+    /// Simulation time at the start of `tick`
+    ///
+    /// Ticks before the earliest remembered change are clamped to it.
+    pub fn time_of(&self, tick: u64) -> Duration {
+        let segment = self.segment_at_tick(tick);
+        segment.time + segment.config.duration(tick.saturating_sub(segment.tick))
+    }
+
+    /// Convert a moment on a [`SubTick`] timeline to simulation time
+    ///
+    /// Moments before the earliest remembered change are clamped to it.
+    pub fn subtick_time(&self, time: SubTick) -> Duration {
+        let segment = self.segment_at_tick(time.tick);
+        if time.tick < segment.tick {
+            return segment.time;
+        }
+        segment.time
+            + segment.config.subtick_duration(SubTick {
+                tick: time.tick - segment.tick,
+                fraction: time.fraction,
+            })
+    }
+
+    /// The tick in progress at simulation time `time`
+    ///
+    /// Times before the earliest remembered change are clamped to it.
+    pub fn tick_at(&self, time: Duration) -> SubTick {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|x| x.time <= time)
+            .unwrap_or(&self.segments[0]);
+        let mut result = segment.config.subtick(time.saturating_sub(segment.time));
+        result.tick += segment.tick;
+        result
+    }
+
+    /// Forget changes that took effect before `tick`, which will no longer be queried
+    pub fn forget_before(&mut self, tick: u64) {
+        let keep = self.segments.partition_point(|x| x.tick <= tick).max(1) - 1;
+        self.segments.drain(..keep);
+    }
+
+    fn schedule(&mut self, tick: u64, config: TickConfig) {
+        assert!(
+            tick >= self.segments[0].tick,
+            "can't change tickrate before the earliest remembered tick"
+        );
+        let time = self.time_of(tick);
+        self.segments.retain(|x| x.tick < tick);
+        self.segments.push(Segment { tick, time, config });
+    }
+
+    fn segment_at_tick(&self, tick: u64) -> &Segment {
+        let i = self.segments.partition_point(|x| x.tick <= tick);
+        &self.segments[i.max(1) - 1]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Segment {
+    /// First tick at which `config` applies
+    tick: u64,
+    /// Simulation time at the start of `tick`
+    time: Duration,
+    config: TickConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose() {
+        assert_eq!(
+            choose_tick_rate(&[128, 60, 30], &[30, 60]),
+            Some(TickConfig::new(60))
+        );
+        assert_eq!(choose_tick_rate(&[128], &[30, 60]), None);
+    }
+
+    #[test]
+    fn fixed() {
+        let config = TickConfig::new(60);
+        assert_eq!(config.fixed_time(60), FixedTime::from_secs(1));
+        let steps = (0..60).map(|tick| config.fixed_step(tick));
+        assert_eq!(
+            steps.clone().fold(FixedTime::ZERO, |a, b| a + b),
+            FixedTime::from_secs(1)
+        );
+        assert!(steps.clone().max().unwrap() - steps.min().unwrap() <= FixedTime::EPSILON);
+    }
+
+    #[test]
+    fn conversion() {
+        let even = TickConversion::new(TickConfig::new(120), TickConfig::new(30));
+        assert_eq!(even.client_ticks(3), 12..16);
+        assert_eq!(even.server_tick(15), 3);
+        assert_eq!(even.server_tick(16), 4);
+        assert_eq!(
+            even.to_server(SubTick::new(14)),
+            SubTick::with_fraction(3, 0.5)
+        );
+        assert_eq!(even.to_client(SubTick::new(3)), SubTick::new(12));
+        assert_eq!(even.client_tick_count(2), 8);
+
+        let uneven = TickConversion::new(TickConfig::new(144), TickConfig::new(64));
+        let counts = (0..4)
+            .map(|x| uneven.samples_per_tick(x))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [3, 2, 2, 2]);
+        for client_tick in 0..100 {
+            let server_tick = uneven.server_tick(client_tick);
+            assert!(uneven.client_ticks(server_tick).contains(&client_tick));
+        }
+        assert_eq!(uneven.server_tick_count(9), 4);
+    }
+
+    #[test]
+    fn transition() {
+        let mut server = TickTimeline::new(TickConfig::new(60));
+        let mut client = server.clone();
+        let message = server.change(120, TickConfig::new(30));
+        assert!(client.handle(&message));
+        assert!(!client.handle(&message), "duplicate");
+
+        for timeline in [&server, &client] {
+            assert_eq!(timeline.config_at(119).rate, 60);
+            assert_eq!(timeline.config_at(120).rate, 30);
+            assert_eq!(timeline.time_of(120), Duration::from_secs(2));
+            assert_eq!(timeline.time_of(150), Duration::from_secs(3));
+            assert_eq!(timeline.tick_at(Duration::from_secs(3)), SubTick::new(150));
+            assert_eq!(timeline.tick_at(Duration::from_secs(1)), SubTick::new(60));
+            assert_eq!(
+                timeline.subtick_time(SubTick::with_fraction(130, 0.5)),
+                Duration::from_millis(2350)
+            );
+        }
+
+        // Superseded before taking effect
+        let message = server.change(100, TickConfig::new(128));
+        assert!(client.handle(&message));
+        assert_eq!(client.config_at(130).rate, 128);
+
+        client.forget_before(110);
+        assert_eq!(client.config_at(110).rate, 128);
+        assert_eq!(client.time_of(100), server.time_of(100));
+    }
+
+    #[test]
+    fn stale_tick() {
+        let mut server = TickTimeline::new(TickConfig::new(60));
+        let mut client = server.clone();
+        assert!(client.handle(&server.change(100, TickConfig::new(30))));
+        client.forget_before(110);
+
+        // A newer revision that changes a tick the client has forgotten
+        assert!(!client.handle(&server.change(50, TickConfig::new(20))));
+        assert_eq!(client.config_at(110).rate, 30);
+
+        // Queries before the earliest remembered change are clamped to it
+        let start = client.time_of(100);
+        assert_eq!(client.time_of(50), start);
+        assert_eq!(client.subtick_time(SubTick::with_fraction(50, 0.5)), start);
+        assert_eq!(client.tick_at(Duration::from_secs(1)), SubTick::new(100));
+    }
+
+    #[test]
+    fn input_delay() {
+        assert_eq!(
+            TickConfig::new(20).input_delay(3),
+            Duration::from_millis(150)
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use crate::Instant;
+
+/// Rate limiter for outgoing data
+///
+/// Tokens accrue continuously at a fixed rate, up to a maximum of `burst`, and are spent by
+/// sending. Sending in bursts is therefore allowed so long as the long-run average stays within
+/// `rate`. Tokens are unitless, but are typically bytes: capping a connection to 256 kbps is a
+/// `rate` of 32 000.
+///
+/// `burst` must be at least as large as the largest single send, or that send will never be
+/// permitted.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    /// Tokens accrued per second
+    rate: u64,
+    burst: u64,
+    /// Fractional to avoid losing precision when refilled at very short intervals
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Construct a bucket that starts out full
+    pub fn new(rate: u64, burst: u64, now: Instant) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst as f64,
+            updated: now,
+        }
+    }
+
+    /// Tokens accrued per second
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Change the rate at which tokens accrue
+    ///
+    /// Tokens accrued before `now` are credited at the old rate.
+    pub fn set_rate(&mut self, rate: u64, now: Instant) {
+        self.refill(now);
+        self.rate = rate;
+    }
+
+    /// Maximum number of tokens that can be accrued
+    pub fn burst(&self) -> u64 {
+        self.burst
+    }
+
+    /// Change the maximum number of tokens that can be accrued
+    pub fn set_burst(&mut self, burst: u64, now: Instant) {
+        self.refill(now);
+        self.burst = burst;
+        self.tokens = self.tokens.min(burst as f64);
+    }
+
+    /// Number of whole tokens available at `now`
+    pub fn available(&mut self, now: Instant) -> u64 {
+        self.refill(now);
+        self.tokens as u64
+    }
+
+    /// Spend `cost` tokens if that many are available
+    ///
+    /// Returns whether the send is permitted.
+    pub fn try_consume(&mut self, cost: u64, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens < cost as f64 {
+            return false;
+        }
+        self.tokens -= cost as f64;
+        true
+    }
+
+    /// Spend `cost` tokens unconditionally, going into debt if too few are available
+    ///
+    /// A bucket in debt must accrue enough tokens to repay it before further sends are permitted,
+    /// which models a queue of sends waiting their turn.
+    pub(crate) fn consume(&mut self, cost: u64, now: Instant) {
+        self.refill(now);
+        self.tokens -= cost as f64;
+    }
+
+    /// Amount of time after `now` until `cost` tokens will be available
+    ///
+    /// Returns [`Duration::ZERO`] if a send may happen immediately, or `None` if `cost` exceeds
+    /// `burst` or `rate` is zero such that it will never be permitted.
+    pub fn delay(&mut self, cost: u64, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        let deficit = cost as f64 - self.tokens;
+        if deficit <= 0.0 {
+            return Some(Duration::ZERO);
+        }
+        if cost > self.burst || self.rate == 0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(deficit / self.rate as f64))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.updated = Ord::max(self.updated, now);
+        self.tokens = f64::min(
+            self.burst as f64,
+            self.tokens + elapsed.as_secs_f64() * self.rate as f64,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 100, start);
+        assert!(bucket.try_consume(60, start));
+        assert!(!bucket.try_consume(60, start), "insufficient tokens");
+        assert_eq!(bucket.delay(60, start), Some(Duration::from_millis(20)));
+        assert!(bucket.try_consume(60, start + Duration::from_millis(20)));
+        assert_eq!(
+            bucket.available(start + Duration::from_secs(10)),
+            100,
+            "tokens are capped at burst"
+        );
+    }
+
+    #[test]
+    fn oversized() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 100, start);
+        assert_eq!(bucket.delay(101, start), None);
+        assert!(!bucket.try_consume(101, start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn rate_change() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(1000, 100, start);
+        assert!(bucket.try_consume(100, start));
+        bucket.set_rate(0, start + Duration::from_millis(50));
+        assert_eq!(bucket.available(start + Duration::from_secs(10)), 50);
+        assert_eq!(bucket.delay(60, start + Duration::from_secs(10)), None);
+    }
+}
@@ -0,0 +1,246 @@
+use std::{collections::VecDeque, convert::Infallible, time::Duration};
+
+use crate::{Clock, DecodeError, Instant, Transport, read_varint, write_varint};
+
+/// Timestamped record of every datagram that passed through a [`Transport`]
+///
+/// Captured by a [`TracingTransport`], saved with [`encode`](Self::encode), and played back with
+/// a [`TraceReplay`] to reproduce a session's network activity exactly, e.g. to investigate a
+/// desync reported by a user.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    /// [`Transport::max_datagram_size`] when the trace was captured
+    pub max_datagram_size: usize,
+    /// Datagrams in the order they were sent or received
+    pub events: Vec<TraceEvent>,
+}
+
+/// A datagram recorded in a [`Trace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Time elapsed since the start of the trace
+    pub time: Duration,
+    /// Whether the datagram was received rather than sent
+    pub received: bool,
+    pub data: Vec<u8>,
+}
+
+impl Trace {
+    /// Serialize into a self-describing binary format suitable for saving to a file
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        write_varint(&mut buf, self.max_datagram_size as u64);
+        for event in &self.events {
+            write_varint(
+                &mut buf,
+                event.time.as_nanos().try_into().unwrap_or(u64::MAX),
+            );
+            buf.push(event.received.into());
+            write_varint(&mut buf, event.data.len() as u64);
+            buf.extend_from_slice(&event.data);
+        }
+        buf
+    }
+
+    /// Deserialize the output of [`encode`](Self::encode)
+    pub fn decode(mut data: &[u8]) -> Result<Self, DecodeError> {
+        data = data.strip_prefix(MAGIC).ok_or(DecodeError::Malformed)?;
+        let max_datagram_size = read_varint(&mut data)?
+            .try_into()
+            .map_err(|_| DecodeError::Overflow)?;
+        let mut events = Vec::new();
+        while !data.is_empty() {
+            let time = Duration::from_nanos(read_varint(&mut data)?);
+            let (&received, rest) = data.split_first().ok_or(DecodeError::Truncated)?;
+            data = rest;
+            let len =
+                usize::try_from(read_varint(&mut data)?).map_err(|_| DecodeError::Overflow)?;
+            if len > data.len() {
+                return Err(DecodeError::Truncated);
+            }
+            let (datagram, rest) = data.split_at(len);
+            data = rest;
+            events.push(TraceEvent {
+                time,
+                received: match received {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(DecodeError::Malformed),
+                },
+                data: datagram.to_vec(),
+            });
+        }
+        Ok(Self {
+            max_datagram_size,
+            events,
+        })
+    }
+}
+
+/// Identifies an encoded [`Trace`] and its format version
+const MAGIC: &[u8] = b"nettish-trace\x01";
+
+/// Wraps a [`Transport`], recording every datagram sent and received into a [`Trace`]
+#[derive(Debug, Clone)]
+pub struct TracingTransport<T, C> {
+    inner: T,
+    clock: C,
+    start: Instant,
+    trace: Trace,
+}
+
+impl<T: Transport, C: Clock> TracingTransport<T, C> {
+    /// Begin tracing `inner`, timestamping datagrams with `clock`
+    pub fn new(inner: T, clock: C) -> Self {
+        Self {
+            start: clock.now(),
+            trace: Trace {
+                max_datagram_size: inner.max_datagram_size(),
+                events: Vec::new(),
+            },
+            inner,
+            clock,
+        }
+    }
+
+    /// The datagrams recorded so far
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Stop tracing, returning the wrapped transport and the trace
+    pub fn into_inner(self) -> (T, Trace) {
+        (self.inner, self.trace)
+    }
+
+    fn record(&mut self, received: bool, data: &[u8]) {
+        self.trace.events.push(TraceEvent {
+            time: self.clock.now().saturating_duration_since(self.start),
+            received,
+            data: data.to_vec(),
+        });
+    }
+}
+
+impl<T: Transport, C: Clock> Transport for TracingTransport<T, C> {
+    type Error = T::Error;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), T::Error> {
+        self.inner.send(datagram)?;
+        self.record(false, datagram);
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, T::Error> {
+        let datagram = self.inner.try_recv()?;
+        if let Some(ref data) = datagram {
+            self.record(true, data);
+        }
+        Ok(datagram)
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.inner.max_datagram_size()
+    }
+}
+
+/// A [`Transport`] that delivers the datagrams received in a [`Trace`] at the times they were
+/// originally received
+///
+/// Intended to be driven by a [`VirtualClock`](crate::VirtualClock), advanced to each
+/// [`next_delivery`](Self::next_delivery) in turn, so that the code under test sees exactly the
+/// same sequence of datagrams as when the trace was captured. Datagrams sent during replay are
+/// recorded rather than delivered anywhere, for comparison with those sent originally.
+#[derive(Debug, Clone)]
+pub struct TraceReplay<C> {
+    clock: C,
+    start: Instant,
+    max_datagram_size: usize,
+    /// Received datagrams not yet delivered
+    pending: VecDeque<TraceEvent>,
+    sent: Vec<TraceEvent>,
+}
+
+impl<C: Clock> TraceReplay<C> {
+    /// Replay `trace`, treating the current time according to `clock` as its start
+    pub fn new(trace: Trace, clock: C) -> Self {
+        Self {
+            start: clock.now(),
+            clock,
+            max_datagram_size: trace.max_datagram_size,
+            pending: trace.events.into_iter().filter(|x| x.received).collect(),
+            sent: Vec::new(),
+        }
+    }
+
+    /// Time at which the next datagram becomes available, if any remain
+    pub fn next_delivery(&self) -> Option<Instant> {
+        Some(self.start + self.pending.front()?.time)
+    }
+
+    /// Datagrams sent during replay, timestamped relative to its start
+    pub fn sent(&self) -> &[TraceEvent] {
+        &self.sent
+    }
+}
+
+impl<C: Clock> Transport for TraceReplay<C> {
+    type Error = Infallible;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), Infallible> {
+        self.sent.push(TraceEvent {
+            time: self.clock.now().saturating_duration_since(self.start),
+            received: false,
+            data: datagram.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, Infallible> {
+        let now = self.clock.now().saturating_duration_since(self.start);
+        if self.pending.front().is_none_or(|x| x.time > now) {
+            return Ok(None);
+        }
+        Ok(self.pending.pop_front().map(|x| x.data))
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LoopbackTransport, VirtualClock};
+
+    #[test]
+    fn capture_replay() {
+        let clock = VirtualClock::new(Instant::now());
+        let (a, mut b) = LoopbackTransport::pair();
+        let mut a = TracingTransport::new(a, clock.clone());
+        a.send(&[1]).unwrap();
+        clock.advance(Duration::from_millis(10));
+        b.send(&[2, 3]).unwrap();
+        assert_eq!(a.try_recv(), Ok(Some(vec![2, 3])));
+        let (_, trace) = a.into_inner();
+        assert_eq!(trace.events.len(), 2);
+
+        let encoded = trace.encode();
+        assert_eq!(Trace::decode(&encoded), Ok(trace.clone()));
+        assert_eq!(
+            Trace::decode(&encoded[..encoded.len() - 1]),
+            Err(DecodeError::Truncated)
+        );
+
+        let clock = VirtualClock::new(Instant::now());
+        let mut replay = TraceReplay::new(trace, clock.clone());
+        replay.send(&[1]).unwrap();
+        assert_eq!(replay.try_recv(), Ok(None));
+        clock.advance_to(replay.next_delivery().unwrap());
+        assert_eq!(replay.try_recv(), Ok(Some(vec![2, 3])));
+        assert_eq!(replay.next_delivery(), None);
+        assert_eq!(replay.sent()[0].data, [1]);
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender, TryRecvError},
+    },
+};
+
+use crate::{Clock, NetworkSimulator, SimulatorConfig};
+
+/// An unreliable, unordered datagram link to a single peer
+///
+/// The rest of the crate is agnostic to how bytes reach their destination; this is the boundary
+/// at which they leave. Adapters for real networks are provided behind feature flags, and
+/// [`LoopbackTransport`] connects endpoints within a single process. Calls never block, so a
+/// transport can be polled from a game loop.
+pub trait Transport {
+    type Error;
+
+    /// Send `datagram` to the peer
+    ///
+    /// Success doesn't imply delivery: datagrams may be lost, duplicated, or reordered.
+    fn send(&mut self, datagram: &[u8]) -> Result<(), Self::Error>;
+    /// Take a datagram received from the peer, if any
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, Self::Error>;
+    /// Size of the largest datagram that can currently be sent
+    fn max_datagram_size(&self) -> usize;
+}
+
+/// One end of an in-process [`Transport`], connected to another
+///
+/// Useful for integration tests and for "listen servers" in which the host's own client runs in
+/// the same process as the server. Endpoints may be moved to different threads.
+pub struct LoopbackTransport {
+    link: Link,
+    max_datagram_size: usize,
+}
+
+enum Link {
+    Direct {
+        send: Sender<Vec<u8>>,
+        recv: Receiver<Vec<u8>>,
+    },
+    Simulated {
+        send: Arc<Mutex<NetworkSimulator>>,
+        recv: Arc<Mutex<NetworkSimulator>>,
+        clock: Arc<dyn Clock + Send + Sync>,
+    },
+}
+
+impl LoopbackTransport {
+    /// Construct a pair of endpoints that deliver datagrams to each other immediately and reliably
+    pub fn pair() -> (Self, Self) {
+        let (a_send, b_recv) = mpsc::channel();
+        let (b_send, a_recv) = mpsc::channel();
+        (
+            Self::new(Link::Direct {
+                send: a_send,
+                recv: a_recv,
+            }),
+            Self::new(Link::Direct {
+                send: b_send,
+                recv: b_recv,
+            }),
+        )
+    }
+
+    /// Construct a pair of endpoints connected by a [`NetworkSimulator`] in each direction
+    ///
+    /// `clock` supplies the current time, e.g. [`SystemClock`](crate::SystemClock). Tests that
+    /// need to be fast and deterministic should use a [`VirtualClock`](crate::VirtualClock).
+    pub fn simulated(
+        config: SimulatorConfig,
+        seed: u64,
+        clock: impl Clock + Send + Sync + 'static,
+    ) -> (Self, Self) {
+        let clock = Arc::new(clock) as Arc<dyn Clock + Send + Sync>;
+        let a_to_b = Arc::new(Mutex::new(NetworkSimulator::new(config, seed)));
+        let b_to_a = Arc::new(Mutex::new(NetworkSimulator::new(
+            config,
+            seed.wrapping_add(1),
+        )));
+        (
+            Self::new(Link::Simulated {
+                send: a_to_b.clone(),
+                recv: b_to_a.clone(),
+                clock: clock.clone(),
+            }),
+            Self::new(Link::Simulated {
+                send: b_to_a,
+                recv: a_to_b,
+                clock,
+            }),
+        )
+    }
+
+    fn new(link: Link) -> Self {
+        Self {
+            link,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        }
+    }
+
+    /// Change the size of the largest datagram that may be sent, e.g. to emulate a small MTU
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+
+    /// Change the impairments applied to datagrams sent from this endpoint
+    ///
+    /// Has no effect on endpoints constructed by [`pair`](Self::pair).
+    pub fn set_config(&mut self, config: SimulatorConfig) {
+        if let Link::Simulated { ref send, .. } = self.link {
+            send.lock().unwrap().set_config(config);
+        }
+    }
+}
+
+impl Transport for LoopbackTransport {
+    type Error = LoopbackError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), LoopbackError> {
+        if datagram.len() > self.max_datagram_size {
+            return Err(LoopbackError::TooLarge);
+        }
+        match self.link {
+            Link::Direct { ref send, .. } => send
+                .send(datagram.to_vec())
+                .map_err(|_| LoopbackError::Disconnected),
+            Link::Simulated {
+                ref send,
+                ref clock,
+                ..
+            } => {
+                if Arc::strong_count(send) == 1 {
+                    return Err(LoopbackError::Disconnected);
+                }
+                let now = clock.now();
+                send.lock()
+                    .unwrap()
+                    .send_sized(now, datagram.to_vec(), datagram.len());
+                Ok(())
+            }
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, LoopbackError> {
+        match self.link {
+            Link::Direct { ref recv, .. } => match recv.try_recv() {
+                Ok(datagram) => Ok(Some(datagram)),
+                Err(TryRecvError::Empty) => Ok(None),
+                Err(TryRecvError::Disconnected) => Err(LoopbackError::Disconnected),
+            },
+            Link::Simulated {
+                ref recv,
+                ref clock,
+                ..
+            } => {
+                let now = clock.now();
+                let mut sim = recv.lock().unwrap();
+                match sim.receive(now) {
+                    Some(datagram) => Ok(Some(datagram)),
+                    None if Arc::strong_count(recv) == 1 && sim.in_flight() == 0 => {
+                        Err(LoopbackError::Disconnected)
+                    }
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
+
+impl fmt::Debug for LoopbackTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoopbackTransport")
+            .field("simulated", &matches!(self.link, Link::Simulated { .. }))
+            .field("max_datagram_size", &self.max_datagram_size)
+            .finish()
+    }
+}
+
+/// Conservative datagram size that fits within the path MTU of virtually all real networks
+pub(crate) const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1200;
+
+/// Errors produced by [`LoopbackTransport`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoopbackError {
+    /// The other endpoint was dropped
+    Disconnected,
+    /// The datagram exceeded the maximum size
+    TooLarge,
+}
+
+impl fmt::Display for LoopbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LoopbackError::Disconnected => f.write_str("peer disconnected"),
+            LoopbackError::TooLarge => f.write_str("datagram too large"),
+        }
+    }
+}
+
+impl std::error::Error for LoopbackError {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::Instant;
+
+    use super::*;
+    use crate::VirtualClock;
+
+    #[test]
+    fn direct() {
+        let (mut a, mut b) = LoopbackTransport::pair();
+        a.send(&[1, 2, 3]).unwrap();
+        assert_eq!(b.try_recv(), Ok(Some(vec![1, 2, 3])));
+        assert_eq!(b.try_recv(), Ok(None));
+        assert_eq!(
+            a.send(&vec![0; a.max_datagram_size() + 1]),
+            Err(LoopbackError::TooLarge)
+        );
+        drop(b);
+        assert_eq!(a.send(&[]), Err(LoopbackError::Disconnected));
+        assert_eq!(a.try_recv(), Err(LoopbackError::Disconnected));
+    }
+
+    #[test]
+    fn simulated() {
+        let clock = VirtualClock::new(Instant::now());
+        let (mut a, mut b) =
+            LoopbackTransport::simulated(SimulatorConfig::default(), 0, clock.clone());
+        a.send(&[1]).unwrap();
+        assert_eq!(b.try_recv(), Ok(None));
+        clock.advance(SimulatorConfig::default().latency);
+        assert_eq!(b.try_recv(), Ok(Some(vec![1])));
+        b.send(&[2]).unwrap();
+        drop(b);
+        assert_eq!(a.send(&[3]), Err(LoopbackError::Disconnected));
+        assert_eq!(a.try_recv(), Ok(None), "still in flight");
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(a.try_recv(), Ok(Some(vec![2])));
+        assert_eq!(a.try_recv(), Err(LoopbackError::Disconnected));
+    }
+}
@@ -0,0 +1,223 @@
+use std::{io, net::SocketAddr, sync::Arc};
+
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{
+        self, Receiver, Sender,
+        error::{TryRecvError, TrySendError},
+    },
+    task::JoinHandle,
+};
+
+use crate::{Transport, transport::DEFAULT_MAX_DATAGRAM_SIZE};
+
+/// [`Transport`] exchanging UDP datagrams with a single peer using Tokio
+///
+/// Background tasks send and receive datagrams, so the [`Transport`] methods never block and may
+/// be called from a game loop outside the runtime. Datagrams passed to
+/// [`send`](Transport::send) between wakeups of the send task are written back to back, without
+/// yielding to the runtime until the socket's buffer fills, though still with one system call
+/// each. Datagrams received from addresses other than the peer are ignored. I/O errors
+/// encountered by either task are reported by [`try_recv`](Transport::try_recv).
+///
+/// At most [`QUEUE_CAPACITY`](Self::QUEUE_CAPACITY) datagrams are queued in each direction. If
+/// the socket can't keep up, [`send`](Transport::send) fails with
+/// [`io::ErrorKind::WouldBlock`]; if received datagrams aren't taken, further datagrams are left
+/// for the operating system to drop.
+#[derive(Debug)]
+pub struct TokioUdpTransport {
+    outgoing: Sender<Vec<u8>>,
+    incoming: Receiver<io::Result<Vec<u8>>>,
+    tasks: [JoinHandle<()>; 2],
+    max_datagram_size: usize,
+}
+
+impl TokioUdpTransport {
+    /// Number of datagrams queued in each direction
+    pub const QUEUE_CAPACITY: usize = 256;
+
+    /// Exchange datagrams with `peer` over `socket`
+    ///
+    /// Must be called from within a Tokio runtime, on which the background tasks are spawned.
+    pub fn new(socket: UdpSocket, peer: SocketAddr) -> Self {
+        let socket = Arc::new(socket);
+        let (outgoing, outgoing_recv) = mpsc::channel(Self::QUEUE_CAPACITY);
+        let (incoming_send, incoming) = mpsc::channel(Self::QUEUE_CAPACITY);
+        Self {
+            outgoing,
+            incoming,
+            tasks: [
+                tokio::spawn(send_task(
+                    socket.clone(),
+                    peer,
+                    outgoing_recv,
+                    incoming_send.clone(),
+                )),
+                tokio::spawn(recv_task(socket, peer, incoming_send)),
+            ],
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        }
+    }
+
+    /// Change the size of the largest datagram that may be sent
+    ///
+    /// Defaults to a conservative 1200 bytes. Larger datagrams may be fragmented or dropped by
+    /// the network.
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+}
+
+impl Transport for TokioUdpTransport {
+    type Error = io::Error;
+
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        if datagram.len() > self.max_datagram_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "datagram exceeds maximum size",
+            ));
+        }
+        self.outgoing
+            .try_send(datagram.to_vec())
+            .map_err(|e| match e {
+                TrySendError::Full(_) => io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "too many datagrams queued for sending",
+                ),
+                TrySendError::Closed(_) => stopped(),
+            })
+    }
+
+    fn try_recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.incoming.try_recv() {
+            Ok(result) => result.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(stopped()),
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
+
+impl Drop for TokioUdpTransport {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "transport task stopped")
+}
+
+/// Largest number of datagrams written per wakeup of the send task
+const MAX_BATCH: usize = 64;
+
+async fn send_task(
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    mut outgoing: Receiver<Vec<u8>>,
+    errors: Sender<io::Result<Vec<u8>>>,
+) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    while outgoing.recv_many(&mut batch, MAX_BATCH).await > 0 {
+        for datagram in batch.drain(..) {
+            let result = loop {
+                match socket.try_send_to(&datagram, peer) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        if let Err(e) = socket.writable().await {
+                            break Err(e);
+                        }
+                    }
+                    result => break result,
+                }
+            };
+            // If errors are already queued, the application has enough to act on
+            if let Err(e) = result
+                && let Err(TrySendError::Closed(_)) = errors.try_send(Err(e))
+            {
+                return;
+            }
+        }
+    }
+}
+
+async fn recv_task(
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    incoming: Sender<io::Result<Vec<u8>>>,
+) {
+    // Large enough for any UDP datagram
+    let mut buf = vec![0; 65536];
+    loop {
+        let result = match socket.recv_from(&mut buf).await {
+            Ok((_, from)) if from != peer => continue,
+            Ok((len, _)) => Ok(buf[..len].to_vec()),
+            Err(e) => Err(e),
+        };
+        if incoming.send(result).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchange() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+            let mut a = TokioUdpTransport::new(a, b_addr);
+            let mut b = TokioUdpTransport::new(b, a_addr);
+            for i in 0..10u8 {
+                a.send(&[i]).unwrap();
+            }
+            let mut received = Vec::new();
+            while received.len() < 10 {
+                match b.try_recv().unwrap() {
+                    Some(datagram) => received.extend(datagram),
+                    None => tokio::task::yield_now().await,
+                }
+            }
+            received.sort();
+            assert_eq!(received, (0..10).collect::<Vec<_>>());
+            assert_eq!(a.try_recv().unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn limits() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut a = TokioUdpTransport::new(a, b.local_addr().unwrap());
+            assert_eq!(
+                a.send(&vec![0; a.max_datagram_size() + 1])
+                    .unwrap_err()
+                    .kind(),
+                io::ErrorKind::InvalidInput
+            );
+            // The send task can't run until we yield
+            for _ in 0..TokioUdpTransport::QUEUE_CAPACITY {
+                a.send(&[0]).unwrap();
+            }
+            assert_eq!(a.send(&[0]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        });
+    }
+}
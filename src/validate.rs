@@ -0,0 +1,150 @@
+use crate::seq;
+
+/// Outcome of checking a value proposed by a client
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Validation<T> {
+    /// Accept the value, possibly adjusted, e.g. clamped to a legal range
+    Accept(T),
+    /// Discard the value, leaving the authoritative state unchanged
+    Reject,
+}
+
+/// Sanity check for a value proposed by a client
+///
+/// Implemented for closures taking the current authoritative value and the proposed value.
+pub trait Validator<T> {
+    fn validate(&mut self, current: &T, proposed: T) -> Validation<T>;
+}
+
+impl<T, F: FnMut(&T, T) -> Validation<T>> Validator<T> for F {
+    fn validate(&mut self, current: &T, proposed: T) -> Validation<T> {
+        self(current, proposed)
+    }
+}
+
+/// A [`Validator`] that limits values to an inclusive range
+///
+/// Values incomparable with the bounds, such as NaN, are rejected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Clamp<T> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: PartialOrd + Clone> Validator<T> for Clamp<T> {
+    fn validate(&mut self, _: &T, proposed: T) -> Validation<T> {
+        Validation::Accept(if proposed >= self.min && proposed <= self.max {
+            proposed
+        } else if proposed > self.max {
+            self.max.clone()
+        } else if proposed < self.min {
+            self.min.clone()
+        } else {
+            return Validation::Reject;
+        })
+    }
+}
+
+/// Server-side state for a field whose value is supplied by a client
+///
+/// Some state, such as a cosmetic aim direction, is cheaper and more responsive to accept from
+/// the client that controls it than to derive on the server. Since clients can't be trusted,
+/// each proposed value passes through a [`Validator`] before entering the authoritative state.
+/// Clients should send their latest value with a version number that increases with each change,
+/// e.g. using a [`PropertySender`](crate::PropertySender), so that reordered updates are ignored.
+#[derive(Debug, Clone)]
+pub struct ClientField<T, V> {
+    value: T,
+    version: Option<u16>,
+    validator: V,
+    rejected: u64,
+}
+
+impl<T, V: Validator<T>> ClientField<T, V> {
+    /// Construct a field with authoritative value `initial`, checking proposals with `validator`
+    pub fn new(initial: T, validator: V) -> Self {
+        Self {
+            value: initial,
+            version: None,
+            validator,
+            rejected: 0,
+        }
+    }
+
+    /// Handle a value proposed by the client
+    ///
+    /// Returns true if the authoritative value was updated, or false if `proposed` was stale or
+    /// rejected by the validator.
+    pub fn receive(&mut self, version: u16, proposed: T) -> bool {
+        if let Some(current) = self.version
+            && !seq::is_newer(version, current)
+        {
+            return false;
+        }
+        self.version = Some(version);
+        match self.validator.validate(&self.value, proposed) {
+            Validation::Accept(x) => {
+                self.value = x;
+                true
+            }
+            Validation::Reject => {
+                self.rejected += 1;
+                false
+            }
+        }
+    }
+
+    /// The authoritative value
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Override the authoritative value, e.g. on respawn
+    pub fn set(&mut self, value: T) {
+        self.value = value;
+    }
+
+    /// Number of proposals rejected by the validator, e.g. for cheat detection
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp() {
+        let mut field = ClientField::new(
+            0.0,
+            Clamp {
+                min: -1.0,
+                max: 1.0,
+            },
+        );
+        assert!(field.receive(0, 0.5));
+        assert_eq!(*field.get(), 0.5);
+        assert!(field.receive(2, 3.0));
+        assert_eq!(*field.get(), 1.0);
+        assert!(!field.receive(1, 0.0), "stale");
+        assert!(!field.receive(3, f32::NAN));
+        assert_eq!(*field.get(), 1.0);
+        assert_eq!(field.rejected(), 1);
+    }
+
+    #[test]
+    fn closure() {
+        // Limit how far the value may move per update
+        let mut field = ClientField::new(10i32, |current: &i32, proposed: i32| {
+            if (proposed - current).abs() <= 5 {
+                Validation::Accept(proposed)
+            } else {
+                Validation::Reject
+            }
+        });
+        assert!(field.receive(0, 14));
+        assert!(!field.receive(1, 100));
+        assert_eq!(*field.get(), 14);
+    }
+}
@@ -0,0 +1,158 @@
+use alloc::vec::Vec;
+
+use crate::{BitReader, BitWriter, DecodeError};
+
+/// Map signed integers onto unsigned integers such that small magnitudes stay small
+///
+/// 0, -1, 1, -2, 2, ... become 0, 1, 2, 3, 4, ..., so that deltas of either sign benefit from
+/// variable-length encoding.
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Invert [`zigzag_encode`]
+pub fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Append `value` to `buf` as an LEB128 variable-length integer
+///
+/// Values below 128 take one byte, below 16384 two bytes, and so on, up to ten bytes.
+pub fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Read an LEB128 variable-length integer from the start of `buf`, advancing past it
+pub fn read_varint(buf: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut value = 0u64;
+    for (i, &byte) in buf.iter().enumerate() {
+        let shift = 7 * i as u32;
+        let bits = u64::from(byte & 0x7f);
+        if shift >= 64 || (shift == 63 && bits > 1) {
+            return Err(DecodeError::Overflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            *buf = &buf[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::Truncated)
+}
+
+/// Number of bytes [`write_varint`] uses to encode `value`
+pub fn varint_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).div_ceil(7).max(1)
+}
+
+impl BitWriter {
+    /// Write `value` as a sequence of 7-bit groups, each preceded by a continuation flag
+    ///
+    /// Uses 8 bits per 7 significant bits of `value`, like [`write_varint`], but without
+    /// requiring byte alignment.
+    pub fn write_varint(&mut self, mut value: u64) {
+        loop {
+            let more = value >= 0x80;
+            self.write_bool(more);
+            self.write_bits(value & 0x7f, 7);
+            value >>= 7;
+            if !more {
+                break;
+            }
+        }
+    }
+
+    /// Write `value` with [`zigzag_encode`] and [`write_varint`](Self::write_varint)
+    pub fn write_signed_varint(&mut self, value: i64) {
+        self.write_varint(zigzag_encode(value));
+    }
+}
+
+impl BitReader<'_> {
+    /// Read a value written with [`BitWriter::write_varint`]
+    pub fn read_varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let more = self.read_bool()?;
+            let bits = self.read_bits(7)?;
+            if shift >= 64 || (shift == 63 && bits > 1) {
+                return Err(DecodeError::Overflow);
+            }
+            value |= bits << shift;
+            if !more {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Read a value written with [`BitWriter::write_signed_varint`]
+    pub fn read_signed_varint(&mut self) -> Result<i64, DecodeError> {
+        self.read_varint().map(zigzag_decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [u64; 8] = [0, 1, 127, 128, 300, 16383, 16384, u64::MAX];
+
+    #[test]
+    fn zigzag() {
+        for (value, encoded) in [(0, 0), (-1, 1), (1, 2), (-2, 3), (2, 4)] {
+            assert_eq!(zigzag_encode(value), encoded);
+            assert_eq!(zigzag_decode(encoded), value);
+        }
+        for value in [i64::MIN, i64::MAX] {
+            assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let mut buf = Vec::new();
+        for value in VALUES {
+            let before = buf.len();
+            write_varint(&mut buf, value);
+            assert_eq!(buf.len() - before, varint_len(value));
+        }
+        assert_eq!(&buf[..5], &[0, 1, 127, 0x80, 1]);
+        let mut cursor = &buf[..];
+        for value in VALUES {
+            assert_eq!(read_varint(&mut cursor), Ok(value));
+        }
+        assert!(cursor.is_empty());
+        assert_eq!(read_varint(&mut &[0x80][..]), Err(DecodeError::Truncated));
+        assert_eq!(
+            read_varint(&mut &[0xff; 10][..]),
+            Err(DecodeError::Overflow)
+        );
+    }
+
+    #[test]
+    fn bits() {
+        let mut w = BitWriter::new();
+        w.write_bool(true);
+        for value in VALUES {
+            w.write_varint(value);
+        }
+        for value in [0, -1, 1, i64::MIN, i64::MAX] {
+            w.write_signed_varint(value);
+        }
+        let buf = w.finish();
+        let mut r = BitReader::new(&buf);
+        assert_eq!(r.read_bool(), Ok(true));
+        for value in VALUES {
+            assert_eq!(r.read_varint(), Ok(value));
+        }
+        for value in [0, -1, 1, i64::MIN, i64::MAX] {
+            assert_eq!(r.read_signed_varint(), Ok(value));
+        }
+    }
+}
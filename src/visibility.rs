@@ -0,0 +1,125 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+/// A change in whether an entity is relevant to a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VisibilityChange<E> {
+    /// The entity became relevant, and should be spawned on the client
+    Entered(E),
+    /// The entity is no longer relevant, and should be despawned on the client
+    Left(E),
+}
+
+/// Tracks which entities each client can see, reporting when that changes
+///
+/// Relevance usually depends on gameplay rules such as teams, fog of war, or stealth as well as
+/// distance. Each update, candidate entities, e.g. from [`InterestGrid`](crate::InterestGrid),
+/// are filtered by an arbitrary rule, and the result is compared with the previous update's.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        deserialize = "C: serde::Deserialize<'de> + Hash + Eq, E: serde::Deserialize<'de> + Hash + Eq"
+    ))
+)]
+pub struct VisibilityFilter<C, E> {
+    visible: HashMap<C, HashSet<E>>,
+}
+
+impl<C: Hash + Eq + Clone, E: Hash + Eq + Clone> VisibilityFilter<C, E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recompute the entities visible to `client`
+    ///
+    /// An entity is visible if it's among `candidates` and `rule` returns true for it. Returns the
+    /// entities that entered or left visibility since the last update.
+    pub fn update(
+        &mut self,
+        client: &C,
+        candidates: impl IntoIterator<Item = E>,
+        mut rule: impl FnMut(&C, &E) -> bool,
+    ) -> Vec<VisibilityChange<E>> {
+        let now = candidates
+            .into_iter()
+            .filter(|e| rule(client, e))
+            .collect::<HashSet<_>>();
+        let before = self.visible.entry(client.clone()).or_default();
+        let mut changes = before
+            .difference(&now)
+            .cloned()
+            .map(VisibilityChange::Left)
+            .collect::<Vec<_>>();
+        changes.extend(
+            now.difference(before)
+                .cloned()
+                .map(VisibilityChange::Entered),
+        );
+        *before = now;
+        changes
+    }
+
+    /// Whether `entity` was visible to `client` as of the last update
+    pub fn is_visible(&self, client: &C, entity: &E) -> bool {
+        self.visible.get(client).is_some_and(|x| x.contains(entity))
+    }
+
+    /// Entities visible to `client` as of the last update
+    pub fn visible(&self, client: &C) -> impl Iterator<Item = &E> + '_ {
+        self.visible.get(client).into_iter().flatten()
+    }
+
+    /// Forget `client`, e.g. on disconnect
+    pub fn remove_client(&mut self, client: &C) {
+        self.visible.remove(client);
+    }
+
+    /// Forget `entity`, e.g. when it's destroyed, returning the clients it was visible to
+    pub fn remove_entity(&mut self, entity: &E) -> Vec<C> {
+        self.visible
+            .iter_mut()
+            .filter_map(|(client, visible)| visible.remove(entity).then(|| client.clone()))
+            .collect()
+    }
+}
+
+impl<C, E> Default for VisibilityFilter<C, E> {
+    fn default() -> Self {
+        Self {
+            visible: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke() {
+        let mut filter = VisibilityFilter::new();
+        let stealthy = |_: &&str, e: &u32| *e != 2;
+        let mut changes = filter.update(&"alice", [1, 2, 3], stealthy);
+        changes.sort_by_key(|x| format!("{x:?}"));
+        assert_eq!(
+            changes,
+            [VisibilityChange::Entered(1), VisibilityChange::Entered(3)]
+        );
+        assert!(filter.is_visible(&"alice", &1));
+        assert!(!filter.is_visible(&"alice", &2));
+
+        let changes = filter.update(&"alice", [1, 2, 4], stealthy);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&VisibilityChange::Left(3)));
+        assert!(changes.contains(&VisibilityChange::Entered(4)));
+        assert!(filter.update(&"alice", [4, 1], stealthy).is_empty());
+
+        assert_eq!(filter.remove_entity(&4), ["alice"]);
+        assert_eq!(filter.visible(&"alice").collect::<Vec<_>>(), [&1]);
+    }
+}
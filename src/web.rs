@@ -0,0 +1,291 @@
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc};
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+use crate::{Transport, transport::DEFAULT_MAX_DATAGRAM_SIZE};
+
+/// Number of bytes a [`WebSocketTransport`] may have waiting to be sent before further sends are
+/// dropped
+const MAX_BUFFERED: u32 = 64 * 1024;
+
+/// Datagrams received by a browser API's callbacks, awaiting [`Transport::try_recv`]
+#[derive(Debug, Default)]
+struct Incoming {
+    queue: VecDeque<Vec<u8>>,
+    closed: bool,
+}
+
+impl Incoming {
+    fn pop(&mut self) -> Result<Option<Vec<u8>>, WebError> {
+        match self.queue.pop_front() {
+            None if self.closed => Err(WebError::Closed),
+            x => Ok(x),
+        }
+    }
+}
+
+/// [`Transport`] over a WebSocket, for browsers that lack WebTransport
+///
+/// WebSockets are reliable and ordered, so a lost packet stalls everything sent after it until
+/// it's retransmitted. To limit the resulting latency, datagrams sent while the connection is
+/// still being established or while the browser has more than 64KiB waiting to be sent are
+/// dropped, as they might be by a congested network. Each datagram is sent as a binary message.
+pub struct WebSocketTransport {
+    socket: WebSocket,
+    incoming: Rc<RefCell<Incoming>>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut()>,
+}
+
+impl WebSocketTransport {
+    /// Begin connecting to the WebSocket server at `url`
+    pub fn new(url: &str) -> Result<Self, WebError> {
+        let socket = WebSocket::new(url)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let incoming = Rc::new(RefCell::new(Incoming::default()));
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new({
+            let incoming = incoming.clone();
+            move |event: MessageEvent| {
+                // Text messages aren't produced by this transport, so are ignored
+                if let Ok(data) = event.data().dyn_into::<ArrayBuffer>() {
+                    let data = Uint8Array::new(&data).to_vec();
+                    incoming.borrow_mut().queue.push_back(data);
+                }
+            }
+        });
+        let on_close = Closure::<dyn FnMut()>::new({
+            let incoming = incoming.clone();
+            move || incoming.borrow_mut().closed = true
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        Ok(Self {
+            socket,
+            incoming,
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+
+    /// The underlying socket
+    pub fn socket(&self) -> &WebSocket {
+        &self.socket
+    }
+}
+
+impl Transport for WebSocketTransport {
+    type Error = WebError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), WebError> {
+        match self.socket.ready_state() {
+            WebSocket::CONNECTING => return Ok(()),
+            WebSocket::OPEN => {}
+            _ => return Err(WebError::Closed),
+        }
+        if self.socket.buffered_amount() > MAX_BUFFERED {
+            return Ok(());
+        }
+        self.socket.send_with_u8_array(datagram)?;
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, WebError> {
+        self.incoming.borrow_mut().pop()
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        DEFAULT_MAX_DATAGRAM_SIZE
+    }
+}
+
+impl Drop for WebSocketTransport {
+    fn drop(&mut self) {
+        // The handlers are freed along with `self`, so must not be invoked afterwards
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+impl fmt::Debug for WebSocketTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketTransport")
+            .field("url", &self.socket.url())
+            .field("ready_state", &self.socket.ready_state())
+            .finish_non_exhaustive()
+    }
+}
+
+/// [`Transport`] over the unreliable datagrams of a WebTransport session
+///
+/// WebTransport datagrams behave like UDP, making this the preferred transport wherever the
+/// browser supports it. Received datagrams are read by a task spawned on the page's event loop.
+#[cfg(feature = "webtransport")]
+#[derive(Debug)]
+pub struct WebTransportDatagrams {
+    transport: web_sys::WebTransport,
+    datagrams: web_sys::WebTransportDatagramDuplexStream,
+    writer: web_sys::WritableStreamDefaultWriter,
+    incoming: Rc<RefCell<Incoming>>,
+}
+
+#[cfg(feature = "webtransport")]
+impl WebTransportDatagrams {
+    /// Begin connecting to the WebTransport server at `url`
+    pub fn new(url: &str) -> Result<Self, WebError> {
+        let transport = web_sys::WebTransport::new(url)?;
+        let datagrams = transport.datagrams();
+        let writer = web_sys::WritableStreamDefaultWriter::new(&datagrams.writable())?;
+        let reader = web_sys::ReadableStreamDefaultReader::new(&datagrams.readable())?;
+        let incoming = Rc::new(RefCell::new(Incoming::default()));
+        wasm_bindgen_futures::spawn_local(read_datagrams(reader, incoming.clone()));
+        Ok(Self {
+            transport,
+            datagrams,
+            writer,
+            incoming,
+        })
+    }
+
+    /// The underlying session, e.g. to open streams
+    pub fn transport(&self) -> &web_sys::WebTransport {
+        &self.transport
+    }
+}
+
+#[cfg(feature = "webtransport")]
+impl Transport for WebTransportDatagrams {
+    type Error = WebError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), WebError> {
+        if self.incoming.borrow().closed {
+            return Err(WebError::Closed);
+        }
+        // A full send queue means the network is congested, so drop the datagram as a router
+        // would
+        if let Some(size) = self.writer.desired_size()?
+            && size <= 0.0
+        {
+            return Ok(());
+        }
+        // Failures are reported by the promise, but a datagram may be lost regardless
+        let _ = self.writer.write_with_chunk(&Uint8Array::from(datagram));
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, WebError> {
+        self.incoming.borrow_mut().pop()
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.datagrams.max_datagram_size() as usize
+    }
+}
+
+#[cfg(feature = "webtransport")]
+impl Drop for WebTransportDatagrams {
+    fn drop(&mut self) {
+        self.transport.close();
+    }
+}
+
+/// Move datagrams from `reader` into `incoming` until the session closes
+#[cfg(feature = "webtransport")]
+async fn read_datagrams(
+    reader: web_sys::ReadableStreamDefaultReader,
+    incoming: Rc<RefCell<Incoming>>,
+) {
+    use js_sys::Reflect;
+    use wasm_bindgen_futures::JsFuture;
+
+    while let Ok(result) = JsFuture::from(reader.read()).await {
+        let done = Reflect::get(&result, &"done".into()).map(|x| x.as_bool());
+        if done != Ok(Some(false)) {
+            break;
+        }
+        let Ok(value) = Reflect::get(&result, &"value".into()) else {
+            break;
+        };
+        let datagram = Uint8Array::new(&value).to_vec();
+        incoming.borrow_mut().queue.push_back(datagram);
+    }
+    incoming.borrow_mut().closed = true;
+}
+
+/// [`Transport`] using the best protocol supported by the browser
+///
+/// Browsers without WebTransport fall back to a WebSocket, with the caveats described in
+/// [`WebSocketTransport`].
+#[cfg(feature = "webtransport")]
+#[derive(Debug)]
+pub enum BrowserTransport {
+    WebTransport(WebTransportDatagrams),
+    WebSocket(WebSocketTransport),
+}
+
+#[cfg(feature = "webtransport")]
+impl BrowserTransport {
+    /// Connect to `webtransport_url` if WebTransport is supported, or `websocket_url` otherwise
+    pub fn connect(webtransport_url: &str, websocket_url: &str) -> Result<Self, WebError> {
+        let supported = js_sys::Reflect::has(&js_sys::global(), &"WebTransport".into())?;
+        Ok(if supported {
+            Self::WebTransport(WebTransportDatagrams::new(webtransport_url)?)
+        } else {
+            Self::WebSocket(WebSocketTransport::new(websocket_url)?)
+        })
+    }
+}
+
+#[cfg(feature = "webtransport")]
+impl Transport for BrowserTransport {
+    type Error = WebError;
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), WebError> {
+        match *self {
+            Self::WebTransport(ref mut x) => x.send(datagram),
+            Self::WebSocket(ref mut x) => x.send(datagram),
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, WebError> {
+        match *self {
+            Self::WebTransport(ref mut x) => x.try_recv(),
+            Self::WebSocket(ref mut x) => x.try_recv(),
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        match *self {
+            Self::WebTransport(ref x) => x.max_datagram_size(),
+            Self::WebSocket(ref x) => x.max_datagram_size(),
+        }
+    }
+}
+
+/// Errors produced by browser transports
+#[derive(Debug, Clone)]
+pub enum WebError {
+    /// The connection was closed
+    Closed,
+    /// A browser API threw an exception
+    Js(String),
+}
+
+impl From<JsValue> for WebError {
+    fn from(value: JsValue) -> Self {
+        WebError::Js(value.as_string().unwrap_or_else(|| format!("{value:?}")))
+    }
+}
+
+impl fmt::Display for WebError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WebError::Closed => f.write_str("connection closed"),
+            WebError::Js(ref e) => write!(f, "browser error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for WebError {}